@@ -26,6 +26,8 @@ pub fn ax_exit(_exit_code: i32) -> ! {
 cfg_task! {
     use core::time::Duration;
 
+    pub use axtask::{AxCpuMask, DeadlineParams as AxDeadlineParams};
+
     /// A handle to a task.
     pub struct AxTaskHandle {
         inner: axtask::AxTaskRef,
@@ -67,10 +69,74 @@ cfg_task! {
         }
     }
 
+    pub fn ax_spawn_on<F>(
+        f: F,
+        name: alloc::string::String,
+        stack_size: usize,
+        cpumask: AxCpuMask,
+    ) -> crate::AxResult<AxTaskHandle>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut task = axtask::TaskInner::new(f, name, stack_size);
+        if !task.init_cpu_mask(cpumask) {
+            return axerrno::ax_err!(InvalidInput, "ax_spawn_on: the CPU mask is empty");
+        }
+        let inner = axtask::spawn_task(task);
+        Ok(AxTaskHandle {
+            id: inner.id().as_u64(),
+            inner,
+        })
+    }
+
+    pub fn ax_spawn_deadline<F>(
+        f: F,
+        name: alloc::string::String,
+        stack_size: usize,
+        cpumask: AxCpuMask,
+        params: AxDeadlineParams,
+    ) -> crate::AxResult<AxTaskHandle>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        if !params.is_valid() {
+            return axerrno::ax_err!(
+                InvalidInput,
+                "ax_spawn_deadline: invalid deadline parameters"
+            );
+        }
+        let mut task = axtask::TaskInner::new(f, name, stack_size);
+        if !task.init_cpu_mask(cpumask) {
+            return axerrno::ax_err!(InvalidInput, "ax_spawn_deadline: the CPU mask is empty");
+        }
+        match axtask::spawn_task_deadline(task, params) {
+            Some(inner) => Ok(AxTaskHandle {
+                id: inner.id().as_u64(),
+                inner,
+            }),
+            None => axerrno::ax_err!(
+                ResourceBusy,
+                "ax_spawn_deadline: failed to reserve the CPU bandwidth"
+            ),
+        }
+    }
+
     pub fn ax_wait_for_exit(task: AxTaskHandle) -> Option<i32> {
         task.inner.join()
     }
 
+    pub fn ax_wait_for_exit_timeout(task: &AxTaskHandle, timeout: Duration) -> Option<i32> {
+        task.inner.join_timeout(timeout)
+    }
+
+    pub fn ax_try_wait_for_exit(task: &AxTaskHandle) -> Option<i32> {
+        task.inner.try_join()
+    }
+
+    pub fn ax_detach(task: AxTaskHandle) {
+        task.inner.detach();
+    }
+
     pub fn ax_set_current_priority(prio: isize) -> crate::AxResult {
         if axtask::set_priority(prio) {
             Ok(())
@@ -82,6 +148,34 @@ cfg_task! {
         }
     }
 
+    pub fn ax_set_current_affinity(cpumask: AxCpuMask) -> crate::AxResult {
+        if axtask::set_current_affinity(cpumask) {
+            Ok(())
+        } else {
+            axerrno::ax_err!(
+                InvalidInput,
+                "ax_set_current_affinity: the CPU mask is empty"
+            )
+        }
+    }
+
+    pub fn ax_set_current_deadline(params: Option<AxDeadlineParams>) -> crate::AxResult {
+        if params.is_some_and(|p| !p.is_valid()) {
+            return axerrno::ax_err!(
+                InvalidInput,
+                "ax_set_current_deadline: invalid deadline parameters"
+            );
+        }
+        if axtask::set_deadline(params) {
+            Ok(())
+        } else {
+            axerrno::ax_err!(
+                ResourceBusy,
+                "ax_set_current_deadline: failed to reserve the CPU bandwidth"
+            )
+        }
+    }
+
     pub fn ax_wait_queue_wait(
         wq: &AxWaitQueueHandle,
         until_condition: impl Fn() -> bool,
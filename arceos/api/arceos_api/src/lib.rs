@@ -121,6 +121,8 @@ pub mod task {
         @cfg "multitask";
         pub type AxTaskHandle;
         pub type AxWaitQueueHandle;
+        pub type AxCpuMask;
+        pub type AxDeadlineParams;
     }
 
     define_api! {
@@ -150,11 +152,46 @@ pub mod task {
             name: alloc::string::String,
             stack_size: usize
         ) -> AxTaskHandle;
+        /// Spawns a new task that is only allowed to run on the CPUs in
+        /// `cpumask`.
+        pub fn ax_spawn_on(
+            f: impl FnOnce() + Send + 'static,
+            name: alloc::string::String,
+            stack_size: usize,
+            cpumask: AxCpuMask,
+        ) -> crate::AxResult<AxTaskHandle>;
+        /// Spawns a new real-time task with the given deadline parameters,
+        /// which is pinned to the first CPU in `cpumask` that can reserve its
+        /// bandwidth.
+        pub fn ax_spawn_deadline(
+            f: impl FnOnce() + Send + 'static,
+            name: alloc::string::String,
+            stack_size: usize,
+            cpumask: AxCpuMask,
+            params: AxDeadlineParams,
+        ) -> crate::AxResult<AxTaskHandle>;
         /// Waits for the given task to exit, and returns its exit code (the
         /// argument of [`ax_exit`]).
         pub fn ax_wait_for_exit(task: AxTaskHandle) -> Option<i32>;
+        /// Waits for the given task to exit for at most `timeout`, and returns
+        /// its exit code, or `None` if it's still running.
+        pub fn ax_wait_for_exit_timeout(task: &AxTaskHandle, timeout: core::time::Duration) -> Option<i32>;
+        /// Returns the exit code of the given task if it has exited, or `None`
+        /// if it's still running, without blocking.
+        pub fn ax_try_wait_for_exit(task: &AxTaskHandle) -> Option<i32>;
+        /// Detaches the given task, so that it's reclaimed as soon as it exits
+        /// without being waited for.
+        pub fn ax_detach(task: AxTaskHandle);
         /// Sets the priority of the current task.
         pub fn ax_set_current_priority(prio: isize) -> crate::AxResult;
+        /// Sets the CPUs that the current task is allowed to run on.
+        ///
+        /// The current task is migrated immediately if the current CPU is not
+        /// in `cpumask`.
+        pub fn ax_set_current_affinity(cpumask: AxCpuMask) -> crate::AxResult;
+        /// Sets the deadline parameters of the current task, making it a
+        /// real-time task under the EDF scheduler (or a normal task if `None`).
+        pub fn ax_set_current_deadline(params: Option<AxDeadlineParams>) -> crate::AxResult;
 
         /// Blocks the current task and put it into the wait queue, until the
         /// given condition becomes true, or the the given duration has elapsed
@@ -388,6 +425,8 @@ pub mod modules {
 
     #[cfg(feature = "alloc")]
     pub use axalloc;
+    #[cfg(feature = "async")]
+    pub use axasync;
     #[cfg(feature = "display")]
     pub use axdisplay;
     #[cfg(feature = "dma")]
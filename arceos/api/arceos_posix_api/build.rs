@@ -1,17 +1,29 @@
 fn main() {
     use std::io::Write;
 
-    fn gen_pthread_mutex(out_file: &str) -> std::io::Result<()> {
-        // TODO: generate size and initial content automatically.
-        let (mutex_size, mutex_init) = if cfg!(feature = "multitask") {
-            if cfg!(feature = "smp") {
-                (6, "{0, 0, 8, 0, 0, 0}") // core::mem::transmute::<_, [usize; 6]>(axsync::Mutex::new(()))
+    fn gen_pthread_types(out_file: &str) -> std::io::Result<()> {
+        // TODO: generate the sizes automatically.
+        // Sizes (in `long`) of mutex, cond, rwlock, barrier and semaphore. The
+        // first three are created on first use if they are all zeros (see
+        // `imp::pthread::LazyLock`), so their static initializers are zeros
+        // whatever the layouts of the locks are.
+        let (mutex_size, cond_size, rwlock_size, barrier_size, sem_size) =
+            if cfg!(feature = "multitask") {
+                // Under `lockdep`, every tracked lock embeds a lock class. The
+                // feature must be enabled through this crate, otherwise the
+                // size assertions in `imp::pthread` fail.
+                if cfg!(feature = "lockdep") && cfg!(feature = "smp") {
+                    (12, 8, 9, 11, 7)
+                } else if cfg!(feature = "lockdep") {
+                    (10, 7, 8, 9, 6)
+                } else if cfg!(feature = "smp") {
+                    (9, 7, 7, 9, 6)
+                } else {
+                    (7, 6, 6, 7, 5)
+                }
             } else {
-                (5, "{0, 8, 0, 0, 0}") // core::mem::transmute::<_, [usize; 5]>(axsync::Mutex::new(()))
-            }
-        } else {
-            (1, "{0}")
-        };
+                (1, 1, 1, 1, 1)
+            };
 
         let mut output = Vec::new();
         writeln!(
@@ -21,11 +33,34 @@ fn main() {
         writeln!(
             output,
             r#"
+#ifndef _AX_PTHREAD_TYPES_H
+#define _AX_PTHREAD_TYPES_H
+
 typedef struct {{
     long __l[{mutex_size}];
 }} pthread_mutex_t;
 
-#define PTHREAD_MUTEX_INITIALIZER {{ .__l = {mutex_init}}}
+typedef struct {{
+    long __l[{cond_size}];
+}} pthread_cond_t;
+
+typedef struct {{
+    long __l[{rwlock_size}];
+}} pthread_rwlock_t;
+
+typedef struct {{
+    long __l[{barrier_size}];
+}} pthread_barrier_t;
+
+typedef struct {{
+    long __l[{sem_size}];
+}} sem_t;
+
+#define PTHREAD_MUTEX_INITIALIZER {{ .__l = {{0}}}}
+#define PTHREAD_COND_INITIALIZER {{ .__l = {{0}}}}
+#define PTHREAD_RWLOCK_INITIALIZER {{ .__l = {{0}}}}
+
+#endif // _AX_PTHREAD_TYPES_H
 "#
         )?;
         std::fs::write(out_file, output)?;
@@ -48,11 +83,26 @@ typedef struct {{
             "pthread_attr_t",
             "pthread_mutex_t",
             "pthread_mutexattr_t",
+            "pthread_cond_t",
+            "pthread_condattr_t",
+            "pthread_rwlock_t",
+            "pthread_rwlockattr_t",
+            "pthread_barrier_t",
+            "pthread_barrierattr_t",
+            "sem_t",
+            "pid_t",
+            "cpu_set_t",
+            "sched_attr",
             "epoll_event",
             "iovec",
             "clockid_t",
             "rlimit",
+            "rusage",
+            "tms",
             "aibuf",
+            "key_t",
+            "ipc_perm",
+            "shmid_ds",
         ];
         let allow_vars = [
             "CLOCK_.*",
@@ -66,8 +116,18 @@ typedef struct {{
             "EPOLL_CTL_.*",
             "EPOLL.*",
             "RLIMIT_.*",
+            "RUSAGE_.*",
+            "SCHED_.*",
+            "PTHREAD_CANCEL_.*",
+            "PTHREAD_BARRIER_.*",
+            "SEM_VALUE_MAX",
             "EAI_.*",
             "MAXADDRS",
+            "PROT_.*",
+            "MAP_.*",
+            "MS_.*",
+            "IPC_.*",
+            "SHM.*",
         ];
 
         #[derive(Debug)]
@@ -75,7 +135,7 @@ typedef struct {{
 
         impl bindgen::callbacks::ParseCallbacks for MyCallbacks {
             fn include_file(&self, fname: &str) {
-                if !fname.contains("ax_pthread_mutex.h") {
+                if !fname.contains("ax_pthread_types.h") {
                     println!("cargo:rerun-if-changed={}", fname);
                 }
             }
@@ -102,6 +162,6 @@ typedef struct {{
             .expect("Couldn't write bindings!");
     }
 
-    gen_pthread_mutex("../../ulib/axlibc/include/ax_pthread_mutex.h").unwrap();
+    gen_pthread_types("../../ulib/axlibc/include/ax_pthread_types.h").unwrap();
     gen_c_to_rust_bindings("ctypes.h", "src/ctypes_gen.rs");
 }
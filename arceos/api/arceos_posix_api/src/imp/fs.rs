@@ -24,7 +24,7 @@ impl File {
         super::fd_ops::add_file_like(Arc::new(self))
     }
 
-    fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
+    pub(crate) fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
         let f = super::fd_ops::get_file_like(fd)?;
         f.into_any()
             .downcast::<Self>()
@@ -75,6 +75,21 @@ impl FileLike for File {
     }
 }
 
+#[cfg(feature = "mmap")]
+impl axmm::MmapFile for File {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> axerrno::AxResult<usize> {
+        self.inner.lock().read_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> axerrno::AxResult<usize> {
+        self.inner.lock().write_at(offset, buf)
+    }
+
+    fn size(&self) -> axerrno::AxResult<u64> {
+        Ok(self.inner.lock().get_attr()?.size())
+    }
+}
+
 /// Convert open flags to [`OpenOptions`].
 fn flags_to_options(flags: c_int, _mode: ctypes::mode_t) -> OpenOptions {
     let flags = flags as u32;
@@ -133,6 +148,25 @@ pub fn sys_lseek(fd: c_int, offset: ctypes::off_t, whence: c_int) -> ctypes::off
     })
 }
 
+/// Truncate or extend the file indicated by `fd` to `length` bytes.
+///
+/// It also resizes the POSIX shared memory objects opened by `shm_open`.
+pub fn sys_ftruncate(fd: c_int, length: ctypes::off_t) -> c_int {
+    debug!("sys_ftruncate <= {} {}", fd, length);
+    syscall_body!(sys_ftruncate, {
+        if length < 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        #[cfg(feature = "mmap")]
+        if let Ok(file) = super::shm::ShmFile::from_fd(fd) {
+            file.truncate(length as usize)?;
+            return Ok(0);
+        }
+        File::from_fd(fd)?.inner.lock().truncate(length as u64)?;
+        Ok(0)
+    })
+}
+
 /// Get the file metadata by `path` and write into `buf`.
 ///
 /// Return 0 if success.
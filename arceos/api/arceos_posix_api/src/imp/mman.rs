@@ -0,0 +1,239 @@
+use alloc::sync::Arc;
+use core::ffi::{c_int, c_void};
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::paging::MappingFlags;
+use axmm::{LoadedFilePages, MmapFile, PendingWriteBack, SharedMemory};
+use memory_addr::{align_up_4k, is_aligned_4k, MemoryAddr, VirtAddr, VirtAddrRange};
+
+use super::fs::File;
+use super::shm::get_shm_file;
+use crate::ctypes;
+
+/// What a mapping created by [`sys_mmap`] is backed by.
+enum MmapSource {
+    /// Anonymous memory, which is shared memory if `MAP_SHARED`.
+    Anonymous,
+    /// A file, with the pages loaded in advance.
+    File(Arc<dyn MmapFile>, LoadedFilePages),
+    /// A POSIX shared memory object opened by `shm_open`.
+    Shm(Arc<SharedMemory>),
+}
+
+/// Convert `PROT_*` flags to [`MappingFlags`].
+fn prot_to_flags(prot: u32) -> MappingFlags {
+    let mut flags = MappingFlags::empty();
+    if prot & ctypes::PROT_READ != 0 {
+        flags |= MappingFlags::READ;
+    }
+    if prot & ctypes::PROT_WRITE != 0 {
+        flags |= MappingFlags::WRITE;
+    }
+    if prot & ctypes::PROT_EXEC != 0 {
+        flags |= MappingFlags::EXECUTE;
+    }
+    flags
+}
+
+/// Get the file indicated by `fd` to be mapped by `mmap`.
+///
+/// The file must be readable, and also writable if `writable` is true, i.e.,
+/// for shared and writable mappings. Return `EACCES` otherwise.
+pub fn get_mmap_file(fd: c_int, writable: bool) -> LinuxResult<Arc<dyn MmapFile>> {
+    let file = File::from_fd(fd).map_err(|e| match e {
+        LinuxError::EINVAL => LinuxError::EACCES, // not a regular file
+        e => e,
+    })?;
+    // empty reads and writes only check the permissions.
+    file.read_at(0, &mut [])?;
+    if writable {
+        file.write_at(0, &[])?;
+    }
+    Ok(file)
+}
+
+/// Map a file or anonymous memory into the address space.
+///
+/// Only the kernel address space exists without user processes, where the
+/// pages are populated when mapped. The mappings are placed in
+/// [`axmm::kernel_mmap_region`], `addr` is used as a hint unless `MAP_FIXED`
+/// is specified. Shared anonymous mappings are backed by [`SharedMemory`].
+///
+/// The kernel address space is locked by a spinlock with IRQs disabled, so
+/// the files are read before locking it, and the changes of the replaced
+/// shared file mappings are written back after unlocking it.
+///
+/// There is no page cache: a shared file mapping has its own copy of the file
+/// data, which is not updated by the writes to the file or other mappings,
+/// and all its writable pages are written back by `msync` and `munmap`. The
+/// POSIX shared memory objects opened by `shm_open` are mapped directly, but
+/// only with `MAP_SHARED`.
+///
+/// Return the start address of the mapping.
+pub fn sys_mmap(
+    addr: *mut c_void,
+    len: ctypes::size_t,
+    prot: c_int,
+    flags: c_int,
+    fd: c_int,
+    off: ctypes::off_t,
+) -> isize {
+    debug!(
+        "sys_mmap <= {:#x} {:#x} {:#x} {:#x} {} {}",
+        addr as usize, len, prot, flags, fd, off
+    );
+    syscall_body!(sys_mmap, {
+        let (prot, flags) = (prot as u32, flags as u32);
+        if len == 0 || off < 0 || !is_aligned_4k(off as usize) {
+            return Err(LinuxError::EINVAL);
+        }
+        let len = align_up_4k(len as usize);
+        let shared = match flags & ctypes::MAP_TYPE {
+            ctypes::MAP_SHARED | ctypes::MAP_SHARED_VALIDATE => true,
+            ctypes::MAP_PRIVATE => false,
+            _ => return Err(LinuxError::EINVAL),
+        };
+        let source = if flags & ctypes::MAP_ANONYMOUS != 0 {
+            MmapSource::Anonymous
+        } else {
+            let writable = shared && prot & ctypes::PROT_WRITE != 0;
+            match get_shm_file(fd, writable)? {
+                Some(_) if !shared => return Err(LinuxError::EINVAL),
+                Some(shm) => MmapSource::Shm(shm),
+                None => {
+                    let file = get_mmap_file(fd, writable)?;
+                    let pages = LoadedFilePages::load(file.clone(), off as u64, len)?;
+                    MmapSource::File(file, pages)
+                }
+            }
+        };
+
+        let mut replaced = PendingWriteBack::default();
+        let res = mmap_kernel(
+            addr,
+            len,
+            prot_to_flags(prot),
+            flags,
+            shared,
+            source,
+            off,
+            &mut replaced,
+        );
+        // the replaced mappings are gone even if this fails, like `munmap`.
+        replaced.write_back().ok();
+        res
+    })
+}
+
+/// Add the mapping of [`sys_mmap`] to the kernel address space, with the file
+/// pages loaded already if it's a file mapping.
+///
+/// The changes of the shared file mappings replaced by `MAP_FIXED` are added
+/// to `replaced`.
+#[allow(clippy::too_many_arguments)]
+fn mmap_kernel(
+    addr: *mut c_void,
+    len: usize,
+    mapping_flags: MappingFlags,
+    flags: u32,
+    shared: bool,
+    source: MmapSource,
+    off: ctypes::off_t,
+    replaced: &mut PendingWriteBack,
+) -> LinuxResult<usize> {
+    let region = axmm::kernel_mmap_region();
+    let mut aspace = axmm::kernel_aspace().lock();
+    let start = if flags & ctypes::MAP_FIXED != 0 {
+        let start = VirtAddr::from(addr as usize);
+        if !start.is_aligned_4k()
+            || !region.contains_range(VirtAddrRange::from_start_size(start, len))
+        {
+            return Err(LinuxError::EINVAL);
+        }
+        *replaced = aspace.unmap_deferred(start, len)?;
+        start
+    } else {
+        let hint = VirtAddr::from(addr as usize).align_down_4k();
+        aspace
+            .find_free_area(hint, len, region)
+            .ok_or(LinuxError::ENOMEM)?
+    };
+
+    let pages = match source {
+        MmapSource::File(file, pages) => {
+            aspace.map_file(start, len, mapping_flags, file, off as u64, shared)?;
+            Some(pages)
+        }
+        MmapSource::Shm(shm) => {
+            aspace.map_shared(start, len, mapping_flags, shm, off as usize)?;
+            None
+        }
+        MmapSource::Anonymous if shared => {
+            let shm = SharedMemory::new(len)?;
+            aspace.map_shared(start, len, mapping_flags, shm, 0)?;
+            None
+        }
+        MmapSource::Anonymous => {
+            aspace.map_alloc(start, len, mapping_flags, true)?;
+            None
+        }
+    };
+    // the kernel address space does not handle page faults.
+    let populated = match pages {
+        Some(pages) => aspace.populate_loaded(start, len, pages),
+        None => Ok(()),
+    }
+    .and_then(|_| aspace.populate(start, len));
+    if let Err(e) = populated {
+        // the new mapping has no changes to write back.
+        drop(aspace.unmap_deferred(start, len)?);
+        return Err(e.into());
+    }
+    Ok(start.as_usize())
+}
+
+/// Remove the mappings in the specified address range created by
+/// [`sys_mmap`].
+///
+/// The changes of shared file mappings are written back to the files, after
+/// the kernel address space is unlocked.
+pub fn sys_munmap(addr: *mut c_void, len: ctypes::size_t) -> c_int {
+    debug!("sys_munmap <= {:#x} {:#x}", addr as usize, len);
+    syscall_body!(sys_munmap, {
+        let start = VirtAddr::from(addr as usize);
+        let len = align_up_4k(len as usize);
+        let region = axmm::kernel_mmap_region();
+        if !start.is_aligned_4k()
+            || len == 0
+            || !region.contains_range(VirtAddrRange::from_start_size(start, len))
+        {
+            return Err(LinuxError::EINVAL);
+        }
+        let pending = axmm::kernel_aspace().lock().unmap_deferred(start, len)?;
+        // like `munmap` on Linux, the write back errors are not reported.
+        pending.write_back().ok();
+        Ok(0)
+    })
+}
+
+/// Write the changes of the shared file mappings in the specified address
+/// range back to the files.
+///
+/// The write back is always synchronous, so `flags` is ignored. It's done
+/// after the kernel address space is unlocked.
+pub fn sys_msync(addr: *mut c_void, len: ctypes::size_t, flags: c_int) -> c_int {
+    debug!("sys_msync <= {:#x} {:#x} {:#x}", addr as usize, len, flags);
+    syscall_body!(sys_msync, {
+        let start = VirtAddr::from(addr as usize);
+        if !start.is_aligned_4k() {
+            return Err(LinuxError::EINVAL);
+        }
+        let len = align_up_4k(len as usize);
+        if !axmm::kernel_mmap_region().contains_range(VirtAddrRange::from_start_size(start, len)) {
+            return Err(LinuxError::ENOMEM);
+        }
+        let pending = axmm::kernel_aspace().lock().msync_deferred(start, len)?;
+        pending.write_back()?;
+        Ok(0)
+    })
+}
@@ -0,0 +1,154 @@
+use crate::{ctypes, utils::check_null_mut_ptr};
+
+use axerrno::{LinuxError, LinuxResult};
+use axsync::Condvar;
+
+use core::ffi::c_int;
+use core::mem::{size_of, ManuallyDrop};
+
+use super::{mutex::PthreadMutex, LazyLock};
+
+static_assertions::const_assert_eq!(
+    size_of::<ctypes::pthread_cond_t>(),
+    size_of::<PthreadCond>()
+);
+
+#[repr(C)]
+pub struct PthreadCond(LazyLock<Condvar>);
+
+impl PthreadCond {
+    const fn new() -> Self {
+        Self(LazyLock::new(Condvar::new()))
+    }
+
+    /// Returns the inner condition variable, which is created here if it's
+    /// statically initialized.
+    fn inner(&self) -> &Condvar {
+        self.0.get(Condvar::new)
+    }
+
+    fn wait(&self, mutex: &PthreadMutex) -> LinuxResult {
+        // the mutex is locked by the caller, and stays locked after returning.
+        let guard = unsafe { mutex.inner().make_guard_unchecked() };
+        let _guard = ManuallyDrop::new(self.inner().wait(guard));
+        Ok(())
+    }
+
+    fn timed_wait(&self, mutex: &PthreadMutex, abstime: &ctypes::timespec) -> LinuxResult {
+        let timeout = super::timeout_until(abstime)?;
+        let guard = unsafe { mutex.inner().make_guard_unchecked() };
+        #[cfg(feature = "irq")]
+        {
+            let (guard, res) = self.inner().wait_timeout(guard, timeout);
+            let _guard = ManuallyDrop::new(guard);
+            if res.timed_out() {
+                return Err(LinuxError::ETIMEDOUT);
+            }
+        }
+        #[cfg(not(feature = "irq"))]
+        {
+            warn!(
+                "pthread_cond_timedwait: the timeout {:?} is ignored without the `irq` feature",
+                timeout
+            );
+            let _guard = ManuallyDrop::new(self.inner().wait(guard));
+        }
+        Ok(())
+    }
+}
+
+/// Initialize a condition variable.
+pub fn sys_pthread_cond_init(
+    cond: *mut ctypes::pthread_cond_t,
+    _attr: *const ctypes::pthread_condattr_t,
+) -> c_int {
+    debug!("sys_pthread_cond_init <= {:#x}", cond as usize);
+    syscall_body!(sys_pthread_cond_init, {
+        check_null_mut_ptr(cond)?;
+        unsafe {
+            cond.cast::<PthreadCond>().write(PthreadCond::new());
+        }
+        Ok(0)
+    })
+}
+
+/// Destroy a condition variable.
+pub fn sys_pthread_cond_destroy(cond: *mut ctypes::pthread_cond_t) -> c_int {
+    debug!("sys_pthread_cond_destroy <= {:#x}", cond as usize);
+    syscall_body!(sys_pthread_cond_destroy, {
+        check_null_mut_ptr(cond)?;
+        unsafe {
+            cond.cast::<PthreadCond>().drop_in_place();
+        }
+        Ok(0)
+    })
+}
+
+/// Wait on the condition variable, with the given mutex locked.
+pub fn sys_pthread_cond_wait(
+    cond: *mut ctypes::pthread_cond_t,
+    mutex: *mut ctypes::pthread_mutex_t,
+) -> c_int {
+    debug!(
+        "sys_pthread_cond_wait <= {:#x} {:#x}",
+        cond as usize, mutex as usize
+    );
+    syscall_body!(sys_pthread_cond_wait, {
+        check_null_mut_ptr(cond)?;
+        check_null_mut_ptr(mutex)?;
+        unsafe {
+            (*cond.cast::<PthreadCond>()).wait(&*mutex.cast::<PthreadMutex>())?;
+        }
+        Ok(0)
+    })
+}
+
+/// Wait on the condition variable, with the given mutex locked, until the
+/// absolute time `abstime`.
+///
+/// Returns `ETIMEDOUT` if the time has passed.
+pub unsafe fn sys_pthread_cond_timedwait(
+    cond: *mut ctypes::pthread_cond_t,
+    mutex: *mut ctypes::pthread_mutex_t,
+    abstime: *const ctypes::timespec,
+) -> c_int {
+    debug!(
+        "sys_pthread_cond_timedwait <= {:#x} {:#x} {:#x}",
+        cond as usize, mutex as usize, abstime as usize
+    );
+    syscall_body!(sys_pthread_cond_timedwait, {
+        check_null_mut_ptr(cond)?;
+        check_null_mut_ptr(mutex)?;
+        if abstime.is_null() {
+            return Err(LinuxError::EINVAL);
+        }
+        unsafe {
+            (*cond.cast::<PthreadCond>()).timed_wait(&*mutex.cast::<PthreadMutex>(), &*abstime)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Wake up one task waiting on the condition variable.
+pub fn sys_pthread_cond_signal(cond: *mut ctypes::pthread_cond_t) -> c_int {
+    debug!("sys_pthread_cond_signal <= {:#x}", cond as usize);
+    syscall_body!(sys_pthread_cond_signal, {
+        check_null_mut_ptr(cond)?;
+        unsafe {
+            (*cond.cast::<PthreadCond>()).inner().notify_one();
+        }
+        Ok(0)
+    })
+}
+
+/// Wake up all tasks waiting on the condition variable.
+pub fn sys_pthread_cond_broadcast(cond: *mut ctypes::pthread_cond_t) -> c_int {
+    debug!("sys_pthread_cond_broadcast <= {:#x}", cond as usize);
+    syscall_body!(sys_pthread_cond_broadcast, {
+        check_null_mut_ptr(cond)?;
+        unsafe {
+            (*cond.cast::<PthreadCond>()).inner().notify_all();
+        }
+        Ok(0)
+    })
+}
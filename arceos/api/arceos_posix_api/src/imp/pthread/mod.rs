@@ -1,6 +1,9 @@
 use alloc::{boxed::Box, collections::BTreeMap, sync::Arc};
 use core::cell::UnsafeCell;
 use core::ffi::{c_int, c_void};
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
+use core::time::Duration;
 
 use axerrno::{LinuxError, LinuxResult};
 use axtask::AxTaskRef;
@@ -8,19 +11,77 @@ use spin::RwLock;
 
 use crate::ctypes;
 
+pub mod barrier;
+pub mod cond;
 pub mod mutex;
+pub mod rwlock;
+pub mod semaphore;
+
+/// The return value of a cancelled thread, `PTHREAD_CANCELED` in C.
+const PTHREAD_CANCELED: *mut c_void = -1isize as *mut c_void;
+
+/// A lock that is created on first use if it's all zeros, as given by the
+/// static initializers in C (e.g., `PTHREAD_MUTEX_INITIALIZER`), so that they
+/// don't depend on the layout of `T`, which varies with the features.
+#[repr(C)]
+struct LazyLock<T> {
+    state: AtomicU8,
+    inner: UnsafeCell<MaybeUninit<T>>,
+}
+
+impl<T> LazyLock<T> {
+    /// The lock is not created yet, i.e., all zeros.
+    const UNINIT: u8 = 0;
+    const CREATING: u8 = 1;
+    const READY: u8 = 2;
+
+    const fn new(inner: T) -> Self {
+        Self {
+            state: AtomicU8::new(Self::READY),
+            inner: UnsafeCell::new(MaybeUninit::new(inner)),
+        }
+    }
+
+    /// Returns the lock, which is created by `new` if it's not created yet.
+    fn get(&self, new: impl FnOnce() -> T) -> &T {
+        loop {
+            match self.state.compare_exchange(
+                Self::UNINIT,
+                Self::CREATING,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    let inner = unsafe { (*self.inner.get()).write(new()) };
+                    self.state.store(Self::READY, Ordering::Release);
+                    return inner;
+                }
+                Err(Self::READY) => return unsafe { (*self.inner.get()).assume_init_ref() },
+                Err(_) => core::hint::spin_loop(),
+            }
+        }
+    }
+}
+
+impl<T> Drop for LazyLock<T> {
+    fn drop(&mut self) {
+        if *self.state.get_mut() == Self::READY {
+            unsafe { self.inner.get_mut().assume_init_drop() };
+        }
+    }
+}
 
 lazy_static::lazy_static! {
     static ref TID_TO_PTHREAD: RwLock<BTreeMap<u64, ForceSendSync<ctypes::pthread_t>>> = {
         let mut map = BTreeMap::new();
         let main_task = axtask::current();
         let main_tid = main_task.id().as_u64();
-        let main_thread = Pthread {
-            inner: main_task.as_task_ref().clone(),
-            retval: Arc::new(Packet {
+        let main_thread = Pthread::new(
+            main_task.as_task_ref().clone(),
+            Arc::new(Packet {
                 result: UnsafeCell::new(core::ptr::null_mut()),
             }),
-        };
+        );
         let ptr = Box::into_raw(Box::new(main_thread)) as *mut c_void;
         map.insert(main_tid, ForceSendSync(ptr));
         RwLock::new(map)
@@ -37,9 +98,28 @@ unsafe impl<T> Sync for Packet<T> {}
 pub struct Pthread {
     inner: AxTaskRef,
     retval: Arc<Packet<*mut c_void>>,
+    cancel_state: AtomicU32,
+    cancel_type: AtomicU32,
+    /// A cancellation request not yet delivered to the task, as the
+    /// cancellation is disabled.
+    cancel_pending: AtomicBool,
+    /// Set by whichever of `pthread_detach` and the thread exit comes first,
+    /// so that the other one frees a detached thread.
+    detach_or_exit: AtomicBool,
 }
 
 impl Pthread {
+    fn new(inner: AxTaskRef, retval: Arc<Packet<*mut c_void>>) -> Self {
+        Self {
+            inner,
+            retval,
+            cancel_state: AtomicU32::new(ctypes::PTHREAD_CANCEL_ENABLE),
+            cancel_type: AtomicU32::new(ctypes::PTHREAD_CANCEL_DEFERRED),
+            cancel_pending: AtomicBool::new(false),
+            detach_or_exit: AtomicBool::new(false),
+        }
+    }
+
     fn create(
         _attr: *const ctypes::pthread_attr_t,
         start_routine: extern "C" fn(arg: *mut c_void) -> *mut c_void,
@@ -57,14 +137,12 @@ impl Pthread {
             let ret = start_routine(arg.0);
             unsafe { *their_packet.result.get() = ret };
             drop(their_packet);
+            Pthread::release_current();
         };
 
         let task_inner = axtask::spawn(main);
         let tid = task_inner.id().as_u64();
-        let thread = Pthread {
-            inner: task_inner,
-            retval: my_packet,
-        };
+        let thread = Pthread::new(task_inner, my_packet);
         let ptr = Box::into_raw(Box::new(thread)) as *mut c_void;
         TID_TO_PTHREAD.write().insert(tid, ForceSendSync(ptr));
         Ok(ptr)
@@ -85,16 +163,76 @@ impl Pthread {
     fn exit_current(retval: *mut c_void) -> ! {
         let thread = Self::current().expect("fail to get current thread");
         unsafe { *thread.retval.result.get() = retval };
+        Self::release_current();
         axtask::exit(0);
     }
 
-    fn join(ptr: ctypes::pthread_t) -> LinuxResult<*mut c_void> {
+    /// Frees the `Pthread` of the current thread on exit if it's detached.
+    fn release_current() {
+        let ptr = Self::current_ptr();
+        if !ptr.is_null() && unsafe { &*ptr }.detach_or_exit.swap(true, Ordering::AcqRel) {
+            unsafe { Self::free(ptr as _) };
+        }
+    }
+
+    fn detach(ptr: ctypes::pthread_t) {
+        let thread = unsafe { &*(ptr as *const Pthread) };
+        thread.inner.detach();
+        if thread.detach_or_exit.swap(true, Ordering::AcqRel) {
+            // already exited.
+            unsafe { Self::free(ptr) };
+        }
+    }
+
+    /// Removes the thread from the table and frees it.
+    unsafe fn free(ptr: ctypes::pthread_t) {
+        let thread = unsafe { Box::from_raw(ptr as *mut Pthread) };
+        TID_TO_PTHREAD.write().remove(&thread.inner.id().as_u64());
+    }
+
+    fn cancel(&self) {
+        self.cancel_pending.store(true, Ordering::SeqCst);
+        self.deliver_cancel();
+    }
+
+    /// Cancels the task if there is a pending request and the cancellation
+    /// is enabled.
+    fn deliver_cancel(&self) {
+        if self.cancel_state.load(Ordering::SeqCst) == ctypes::PTHREAD_CANCEL_ENABLE
+            && self.cancel_pending.swap(false, Ordering::SeqCst)
+        {
+            self.inner.cancel();
+        }
+    }
+
+    fn set_cancel_state(&self, state: u32) -> u32 {
+        let old = self.cancel_state.swap(state, Ordering::SeqCst);
+        self.deliver_cancel();
+        old
+    }
+
+    fn test_cancel(&self) {
+        if self.cancel_state.load(Ordering::SeqCst) == ctypes::PTHREAD_CANCEL_ENABLE
+            && self.inner.is_cancelled()
+        {
+            Self::exit_current(PTHREAD_CANCELED);
+        }
+    }
+
+    fn join(ptr: ctypes::pthread_t, timeout: Option<Duration>) -> LinuxResult<*mut c_void> {
         if core::ptr::eq(ptr, Self::current_ptr() as _) {
             return Err(LinuxError::EDEADLK);
         }
 
+        let task = unsafe { &*(ptr as *const Pthread) }.inner.clone();
+        let exited = match timeout {
+            Some(dur) => task.join_timeout(dur),
+            None => task.join(),
+        };
+        if exited.is_none() {
+            return Err(LinuxError::ETIMEDOUT);
+        }
         let thread = unsafe { Box::from_raw(ptr as *mut Pthread) };
-        thread.inner.join();
         let tid = thread.inner.id().as_u64();
         let retval = unsafe { *thread.retval.result.get() };
         TID_TO_PTHREAD.write().remove(&tid);
@@ -139,7 +277,35 @@ pub fn sys_pthread_exit(retval: *mut c_void) -> ! {
 pub unsafe fn sys_pthread_join(thread: ctypes::pthread_t, retval: *mut *mut c_void) -> c_int {
     debug!("sys_pthread_join <= {:#x}", retval as usize);
     syscall_body!(sys_pthread_join, {
-        let ret = Pthread::join(thread)?;
+        let ret = Pthread::join(thread, None)?;
+        if !retval.is_null() {
+            unsafe { core::ptr::write(retval, ret) };
+        }
+        Ok(0)
+    })
+}
+
+/// Waits for the given thread to exit until the absolute time `abstime`
+/// (measured by `CLOCK_REALTIME`), and stores the return value in `retval`.
+///
+/// Returns `ETIMEDOUT` if the thread is still running at `abstime`. It waits
+/// without a timeout if `abstime` is null.
+pub unsafe fn sys_pthread_timedjoin_np(
+    thread: ctypes::pthread_t,
+    retval: *mut *mut c_void,
+    abstime: *const ctypes::timespec,
+) -> c_int {
+    debug!(
+        "sys_pthread_timedjoin_np <= {:#x} {:#x}",
+        thread as usize, abstime as usize
+    );
+    syscall_body!(sys_pthread_timedjoin_np, {
+        let timeout = if abstime.is_null() {
+            None
+        } else {
+            Some(timeout_until(unsafe { &*abstime })?)
+        };
+        let ret = Pthread::join(thread, timeout)?;
         if !retval.is_null() {
             unsafe { core::ptr::write(retval, ret) };
         }
@@ -147,6 +313,96 @@ pub unsafe fn sys_pthread_join(thread: ctypes::pthread_t, retval: *mut *mut c_vo
     })
 }
 
+/// Detaches the given thread, whose resources are released as soon as it
+/// exits, without being joined.
+pub unsafe fn sys_pthread_detach(thread: ctypes::pthread_t) -> c_int {
+    debug!("sys_pthread_detach <= {:#x}", thread as usize);
+    syscall_body!(sys_pthread_detach, {
+        if thread.is_null() {
+            return Err(LinuxError::ESRCH);
+        }
+        Pthread::detach(thread);
+        Ok(0)
+    })
+}
+
+/// Requests the given thread to be cancelled.
+///
+/// The thread exits with `PTHREAD_CANCELED` when it reaches a cancellation
+/// point, i.e., [`sys_pthread_testcancel`], or a blocking call interrupted by
+/// the cancellation such as `nanosleep`. Asynchronous cancellation is not
+/// supported, so a thread that never reaches a cancellation point keeps
+/// running.
+pub unsafe fn sys_pthread_cancel(thread: ctypes::pthread_t) -> c_int {
+    debug!("sys_pthread_cancel <= {:#x}", thread as usize);
+    syscall_body!(sys_pthread_cancel, {
+        if thread.is_null() {
+            return Err(LinuxError::ESRCH);
+        }
+        unsafe { &*(thread as *const Pthread) }.cancel();
+        Ok(0)
+    })
+}
+
+/// Enables or disables the cancellation of the current thread, and stores
+/// the old state in `old` if it's not null.
+///
+/// A request received while the cancellation is disabled is delivered when
+/// it's enabled again.
+pub unsafe fn sys_pthread_setcancelstate(new: c_int, old: *mut c_int) -> c_int {
+    debug!("sys_pthread_setcancelstate <= {}", new);
+    syscall_body!(sys_pthread_setcancelstate, {
+        match new as u32 {
+            ctypes::PTHREAD_CANCEL_ENABLE | ctypes::PTHREAD_CANCEL_DISABLE => {}
+            _ => return Err(LinuxError::EINVAL),
+        }
+        let thread = Pthread::current().ok_or(LinuxError::ESRCH)?;
+        let old_state = thread.set_cancel_state(new as u32);
+        if !old.is_null() {
+            unsafe { *old = old_state as c_int };
+        }
+        Ok(0)
+    })
+}
+
+/// Sets the cancellation type of the current thread, and stores the old type
+/// in `old` if it's not null.
+///
+/// `PTHREAD_CANCEL_ASYNCHRONOUS` is accepted but works the same as
+/// `PTHREAD_CANCEL_DEFERRED`.
+pub unsafe fn sys_pthread_setcanceltype(new: c_int, old: *mut c_int) -> c_int {
+    debug!("sys_pthread_setcanceltype <= {}", new);
+    syscall_body!(sys_pthread_setcanceltype, {
+        match new as u32 {
+            ctypes::PTHREAD_CANCEL_DEFERRED | ctypes::PTHREAD_CANCEL_ASYNCHRONOUS => {}
+            _ => return Err(LinuxError::EINVAL),
+        }
+        let thread = Pthread::current().ok_or(LinuxError::ESRCH)?;
+        let old_type = thread.cancel_type.swap(new as u32, Ordering::SeqCst);
+        if !old.is_null() {
+            unsafe { *old = old_type as c_int };
+        }
+        Ok(0)
+    })
+}
+
+/// Exits the current thread with `PTHREAD_CANCELED` if it has been cancelled
+/// and the cancellation is enabled.
+pub fn sys_pthread_testcancel() {
+    if let Some(thread) = Pthread::current() {
+        thread.test_cancel();
+    }
+}
+
+/// Returns the duration from now until the absolute time `abstime`, which is
+/// measured against `CLOCK_REALTIME`.
+fn timeout_until(abstime: &ctypes::timespec) -> LinuxResult<Duration> {
+    if abstime.tv_nsec < 0 || abstime.tv_nsec > 999_999_999 {
+        return Err(LinuxError::EINVAL);
+    }
+    Ok(Duration::from(*abstime).saturating_sub(axhal::time::wall_time()))
+}
+
 #[derive(Clone, Copy)]
 struct ForceSendSync<T>(T);
 
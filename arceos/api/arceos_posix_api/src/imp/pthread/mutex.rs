@@ -6,26 +6,34 @@ use axsync::Mutex;
 use core::ffi::c_int;
 use core::mem::{size_of, ManuallyDrop};
 
+use super::LazyLock;
+
 static_assertions::const_assert_eq!(
     size_of::<ctypes::pthread_mutex_t>(),
     size_of::<PthreadMutex>()
 );
 
 #[repr(C)]
-pub struct PthreadMutex(Mutex<()>);
+pub struct PthreadMutex(LazyLock<Mutex<()>>);
 
 impl PthreadMutex {
     const fn new() -> Self {
-        Self(Mutex::new(()))
+        Self(LazyLock::new(Mutex::new(())))
+    }
+
+    /// Returns the inner mutex, which is created here if the mutex is
+    /// statically initialized.
+    pub(super) fn inner(&self) -> &Mutex<()> {
+        self.0.get(|| Mutex::new(()))
     }
 
     fn lock(&self) -> LinuxResult {
-        let _guard = ManuallyDrop::new(self.0.lock());
+        let _guard = ManuallyDrop::new(self.inner().lock());
         Ok(())
     }
 
     fn unlock(&self) -> LinuxResult {
-        unsafe { self.0.force_unlock() };
+        unsafe { self.inner().force_unlock() };
         Ok(())
     }
 }
@@ -0,0 +1,151 @@
+use crate::{ctypes, utils::check_null_mut_ptr};
+
+use axerrno::{LinuxError, LinuxResult};
+use axsync::RwLock;
+
+use core::ffi::c_int;
+use core::mem::{size_of, ManuallyDrop};
+
+use super::LazyLock;
+
+static_assertions::const_assert_eq!(
+    size_of::<ctypes::pthread_rwlock_t>(),
+    size_of::<PthreadRwLock>()
+);
+
+#[repr(C)]
+pub struct PthreadRwLock(LazyLock<RwLock<()>>);
+
+impl PthreadRwLock {
+    const fn new() -> Self {
+        Self(LazyLock::new(RwLock::new(())))
+    }
+
+    /// Returns the inner readers-writer lock, which is created here if it's
+    /// statically initialized.
+    fn inner(&self) -> &RwLock<()> {
+        self.0.get(|| RwLock::new(()))
+    }
+
+    fn read(&self) -> LinuxResult {
+        let _guard = ManuallyDrop::new(self.inner().read());
+        Ok(())
+    }
+
+    fn try_read(&self) -> LinuxResult {
+        let guard = self.inner().try_read().ok_or(LinuxError::EBUSY)?;
+        let _guard = ManuallyDrop::new(guard);
+        Ok(())
+    }
+
+    fn write(&self) -> LinuxResult {
+        let _guard = ManuallyDrop::new(self.inner().write());
+        Ok(())
+    }
+
+    fn try_write(&self) -> LinuxResult {
+        let guard = self.inner().try_write().ok_or(LinuxError::EBUSY)?;
+        let _guard = ManuallyDrop::new(guard);
+        Ok(())
+    }
+
+    fn unlock(&self) -> LinuxResult {
+        // a write-locked lock cannot be held by readers, so the caller must be
+        // the writer.
+        if self.inner().is_write_locked() {
+            unsafe { self.inner().force_write_unlock() };
+        } else {
+            unsafe { self.inner().force_read_decrement() };
+        }
+        Ok(())
+    }
+}
+
+/// Initialize a readers-writer lock.
+pub fn sys_pthread_rwlock_init(
+    rwlock: *mut ctypes::pthread_rwlock_t,
+    _attr: *const ctypes::pthread_rwlockattr_t,
+) -> c_int {
+    debug!("sys_pthread_rwlock_init <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_init, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe {
+            rwlock.cast::<PthreadRwLock>().write(PthreadRwLock::new());
+        }
+        Ok(0)
+    })
+}
+
+/// Destroy a readers-writer lock.
+pub fn sys_pthread_rwlock_destroy(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_destroy <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_destroy, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe {
+            rwlock.cast::<PthreadRwLock>().drop_in_place();
+        }
+        Ok(0)
+    })
+}
+
+/// Lock the given readers-writer lock for reading.
+pub fn sys_pthread_rwlock_rdlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_rdlock <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_rdlock, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe {
+            (*rwlock.cast::<PthreadRwLock>()).read()?;
+        }
+        Ok(0)
+    })
+}
+
+/// Try to lock the given readers-writer lock for reading, returns `EBUSY` if
+/// it's held by a writer.
+pub fn sys_pthread_rwlock_tryrdlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_tryrdlock <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_tryrdlock, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe {
+            (*rwlock.cast::<PthreadRwLock>()).try_read()?;
+        }
+        Ok(0)
+    })
+}
+
+/// Lock the given readers-writer lock for writing.
+pub fn sys_pthread_rwlock_wrlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_wrlock <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_wrlock, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe {
+            (*rwlock.cast::<PthreadRwLock>()).write()?;
+        }
+        Ok(0)
+    })
+}
+
+/// Try to lock the given readers-writer lock for writing, returns `EBUSY` if
+/// it's held by others.
+pub fn sys_pthread_rwlock_trywrlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_trywrlock <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_trywrlock, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe {
+            (*rwlock.cast::<PthreadRwLock>()).try_write()?;
+        }
+        Ok(0)
+    })
+}
+
+/// Unlock the given readers-writer lock.
+pub fn sys_pthread_rwlock_unlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_unlock <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_unlock, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe {
+            (*rwlock.cast::<PthreadRwLock>()).unlock()?;
+        }
+        Ok(0)
+    })
+}
@@ -0,0 +1,133 @@
+use crate::{ctypes, utils::check_null_mut_ptr};
+
+use axerrno::{LinuxError, LinuxResult};
+use axsync::Semaphore;
+
+use core::ffi::{c_int, c_uint};
+use core::mem::size_of;
+
+static_assertions::const_assert_eq!(size_of::<ctypes::sem_t>(), size_of::<PosixSemaphore>());
+
+#[repr(C)]
+pub struct PosixSemaphore(Semaphore);
+
+impl PosixSemaphore {
+    fn timed_wait(&self, abstime: &ctypes::timespec) -> LinuxResult {
+        let timeout = super::timeout_until(abstime)?;
+        #[cfg(feature = "irq")]
+        if !self.0.acquire_timeout(timeout) {
+            return Err(LinuxError::ETIMEDOUT);
+        }
+        #[cfg(not(feature = "irq"))]
+        {
+            warn!(
+                "sem_timedwait: the timeout {:?} is ignored without the `irq` feature",
+                timeout
+            );
+            self.0.acquire();
+        }
+        Ok(())
+    }
+}
+
+/// Initialize an unnamed semaphore with the given value.
+///
+/// The semaphore is always shared between all threads, the `pshared`
+/// argument is ignored.
+pub fn sys_sem_init(sem: *mut ctypes::sem_t, _pshared: c_int, value: c_uint) -> c_int {
+    debug!("sys_sem_init <= {:#x} {}", sem as usize, value);
+    syscall_body!(sys_sem_init, {
+        check_null_mut_ptr(sem)?;
+        if value > ctypes::SEM_VALUE_MAX {
+            return Err(LinuxError::EINVAL);
+        }
+        unsafe {
+            sem.cast::<PosixSemaphore>()
+                .write(PosixSemaphore(Semaphore::new(value as usize)));
+        }
+        Ok(0)
+    })
+}
+
+/// Destroy an unnamed semaphore.
+pub fn sys_sem_destroy(sem: *mut ctypes::sem_t) -> c_int {
+    debug!("sys_sem_destroy <= {:#x}", sem as usize);
+    syscall_body!(sys_sem_destroy, {
+        check_null_mut_ptr(sem)?;
+        unsafe {
+            sem.cast::<PosixSemaphore>().drop_in_place();
+        }
+        Ok(0)
+    })
+}
+
+/// Decrement the semaphore, blocking until its value is greater than zero.
+pub fn sys_sem_wait(sem: *mut ctypes::sem_t) -> c_int {
+    debug!("sys_sem_wait <= {:#x}", sem as usize);
+    syscall_body!(sys_sem_wait, {
+        check_null_mut_ptr(sem)?;
+        unsafe { (*sem.cast::<PosixSemaphore>()).0.acquire() };
+        Ok(0)
+    })
+}
+
+/// Decrement the semaphore if its value is greater than zero, returns
+/// `EAGAIN` otherwise.
+pub fn sys_sem_trywait(sem: *mut ctypes::sem_t) -> c_int {
+    debug!("sys_sem_trywait <= {:#x}", sem as usize);
+    syscall_body!(sys_sem_trywait, {
+        check_null_mut_ptr(sem)?;
+        if unsafe { (*sem.cast::<PosixSemaphore>()).0.try_acquire() } {
+            Ok(0)
+        } else {
+            Err(LinuxError::EAGAIN)
+        }
+    })
+}
+
+/// Decrement the semaphore, blocking until its value is greater than zero or
+/// the absolute time `abstime` has passed.
+///
+/// Returns `ETIMEDOUT` if the time has passed.
+pub unsafe fn sys_sem_timedwait(
+    sem: *mut ctypes::sem_t,
+    abstime: *const ctypes::timespec,
+) -> c_int {
+    debug!(
+        "sys_sem_timedwait <= {:#x} {:#x}",
+        sem as usize, abstime as usize
+    );
+    syscall_body!(sys_sem_timedwait, {
+        check_null_mut_ptr(sem)?;
+        if abstime.is_null() {
+            return Err(LinuxError::EINVAL);
+        }
+        unsafe { (*sem.cast::<PosixSemaphore>()).timed_wait(&*abstime)? };
+        Ok(0)
+    })
+}
+
+/// Increment the semaphore, waking up a thread blocked on it if any.
+pub fn sys_sem_post(sem: *mut ctypes::sem_t) -> c_int {
+    debug!("sys_sem_post <= {:#x}", sem as usize);
+    syscall_body!(sys_sem_post, {
+        check_null_mut_ptr(sem)?;
+        let sem = unsafe { &(*sem.cast::<PosixSemaphore>()).0 };
+        if sem.available_permits() >= ctypes::SEM_VALUE_MAX as usize {
+            return Err(LinuxError::EOVERFLOW);
+        }
+        sem.release();
+        Ok(0)
+    })
+}
+
+/// Get the current value of the semaphore.
+pub unsafe fn sys_sem_getvalue(sem: *mut ctypes::sem_t, sval: *mut c_int) -> c_int {
+    debug!("sys_sem_getvalue <= {:#x}", sem as usize);
+    syscall_body!(sys_sem_getvalue, {
+        check_null_mut_ptr(sem)?;
+        check_null_mut_ptr(sval)?;
+        unsafe { *sval = (*sem.cast::<PosixSemaphore>()).0.available_permits() as c_int };
+        Ok(0)
+    })
+}
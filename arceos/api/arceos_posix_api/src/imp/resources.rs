@@ -1,6 +1,7 @@
 use crate::ctypes;
 use axerrno::LinuxError;
 use core::ffi::c_int;
+use core::time::Duration;
 
 /// Get resource limitations
 ///
@@ -49,3 +50,49 @@ pub unsafe fn sys_setrlimit(resource: c_int, rlimits: *mut crate::ctypes::rlimit
         Ok(0)
     })
 }
+
+/// Get resource usage
+///
+/// Only the CPU time and context switches are reported. `RUSAGE_SELF` counts
+/// all tasks except the idle tasks, and `RUSAGE_THREAD` counts the current
+/// task only.
+pub unsafe fn sys_getrusage(who: c_int, usage: *mut ctypes::rusage) -> c_int {
+    debug!("sys_getrusage <= {} {:#x}", who, usage as usize);
+    syscall_body!(sys_getrusage, {
+        if usage.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        const RUSAGE_SELF: c_int = ctypes::RUSAGE_SELF as _;
+        const RUSAGE_THREAD: c_int = ctypes::RUSAGE_THREAD as _;
+        let (utime, stime, nvcsw, nivcsw) = match who {
+            #[cfg(feature = "multitask")]
+            RUSAGE_SELF | RUSAGE_THREAD => {
+                let cpu = if who == RUSAGE_SELF {
+                    axtask::total_cpu_usage()
+                } else {
+                    axtask::current().cpu_usage()
+                };
+                (
+                    cpu.utime,
+                    cpu.stime,
+                    cpu.voluntary_switches,
+                    cpu.involuntary_switches,
+                )
+            }
+            // the only task runs all the time.
+            #[cfg(not(feature = "multitask"))]
+            RUSAGE_SELF | RUSAGE_THREAD => (axhal::time::monotonic_time(), Duration::ZERO, 0, 0),
+            // no child processes.
+            ctypes::RUSAGE_CHILDREN => (Duration::ZERO, Duration::ZERO, 0, 0),
+            _ => return Err(LinuxError::EINVAL),
+        };
+        unsafe {
+            *usage = core::mem::zeroed();
+            (*usage).ru_utime = utime.into();
+            (*usage).ru_stime = stime.into();
+            (*usage).ru_nvcsw = nvcsw as _;
+            (*usage).ru_nivcsw = nivcsw as _;
+        }
+        Ok(0)
+    })
+}
@@ -0,0 +1,434 @@
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::ffi::{c_char, c_int, c_void};
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::paging::MappingFlags;
+use axio::PollState;
+use axmm::SharedMemory;
+use axsync::Mutex;
+use memory_addr::{align_down, align_up_4k, MemoryAddr, VirtAddr, VirtAddrRange, PAGE_SIZE_4K};
+
+use super::fd_ops::{add_file_like, get_file_like, FileLike};
+use crate::{ctypes, utils::char_ptr_to_str};
+
+/// Checks the permissions `requested` (in the lower 3 bits, i.e., `r`, `w`
+/// and `x`) against `mode`.
+///
+/// There are no users, so the owner permissions of `mode` are checked.
+fn check_perm(mode: ctypes::mode_t, requested: u32) -> LinuxResult {
+    if requested & !(mode >> 6) & 0o7 != 0 {
+        Err(LinuxError::EACCES)
+    } else {
+        Ok(())
+    }
+}
+
+/// A System V shared memory segment.
+struct ShmSegment {
+    key: ctypes::key_t,
+    mode: ctypes::mode_t,
+    shm: Arc<SharedMemory>,
+}
+
+struct ShmTable {
+    segments: BTreeMap<c_int, ShmSegment>,
+    next_id: c_int,
+    /// The memory of all the segments, including those removed by `IPC_RMID`
+    /// but still attached. The weak references also keep the addresses from
+    /// being reused by other shared memory.
+    memories: Vec<Weak<SharedMemory>>,
+}
+
+static SHM_TABLE: Mutex<ShmTable> = Mutex::new(ShmTable {
+    segments: BTreeMap::new(),
+    next_id: 0,
+    memories: Vec::new(),
+});
+
+/// Get the shared memory of the segment `shmid` created by [`sys_shmget`] to
+/// attach it with `shmflg`, e.g., to a user address space.
+///
+/// Return `EACCES` if the mode of the segment does not allow reading, or
+/// writing unless `SHM_RDONLY` is specified.
+pub fn get_shm(shmid: c_int, shmflg: c_int) -> LinuxResult<Arc<SharedMemory>> {
+    let table = SHM_TABLE.lock();
+    let seg = table.segments.get(&shmid).ok_or(LinuxError::EINVAL)?;
+    let requested = if shmflg as u32 & ctypes::SHM_RDONLY != 0 {
+        0o4
+    } else {
+        0o6
+    };
+    check_perm(seg.mode, requested)?;
+    Ok(seg.shm.clone())
+}
+
+/// Check if `shm` is the memory of a segment created by [`sys_shmget`], i.e.,
+/// a mapping of it is attached by `shmat` and can be detached by `shmdt`.
+pub fn is_shm_segment(shm: &Arc<SharedMemory>) -> bool {
+    SHM_TABLE
+        .lock()
+        .memories
+        .iter()
+        .any(|mem| core::ptr::eq(mem.as_ptr(), Arc::as_ptr(shm)))
+}
+
+/// Get the shared memory segment associated with `key`, or create a new one
+/// of `size` bytes if `key` is `IPC_PRIVATE` or `IPC_CREAT` is specified.
+///
+/// Return the segment identifier.
+pub fn sys_shmget(key: ctypes::key_t, size: ctypes::size_t, shmflg: c_int) -> c_int {
+    debug!("sys_shmget <= {} {:#x} {:#o}", key, size, shmflg);
+    syscall_body!(sys_shmget, {
+        let shmflg = shmflg as u32;
+        let mut table = SHM_TABLE.lock();
+        if key != 0 {
+            // not `IPC_PRIVATE`
+            if let Some((&id, seg)) = table.segments.iter().find(|(_, seg)| seg.key == key) {
+                if shmflg & ctypes::IPC_CREAT != 0 && shmflg & ctypes::IPC_EXCL != 0 {
+                    return Err(LinuxError::EEXIST);
+                }
+                // the permissions requested by the mode bits of `shmflg`.
+                let requested = (shmflg >> 6 | shmflg >> 3 | shmflg) & 0o7;
+                check_perm(seg.mode, requested)?;
+                if size as usize > seg.shm.size() {
+                    return Err(LinuxError::EINVAL);
+                }
+                return Ok(id);
+            }
+            if shmflg & ctypes::IPC_CREAT == 0 {
+                return Err(LinuxError::ENOENT);
+            }
+        }
+        if size == 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let shm = SharedMemory::new(align_up_4k(size as usize))?;
+        let id = table.next_id;
+        table.next_id += 1;
+        table.memories.retain(|mem| mem.strong_count() > 0);
+        table.memories.push(Arc::downgrade(&shm));
+        table.segments.insert(
+            id,
+            ShmSegment {
+                key,
+                mode: shmflg & 0o777,
+                shm,
+            },
+        );
+        Ok(id)
+    })
+}
+
+/// Attach the shared memory segment `shmid` to the address space.
+///
+/// The segment is attached at `shmaddr` if it's not null, otherwise in
+/// [`axmm::kernel_mmap_region`].
+///
+/// Return `EACCES` if the mode of the segment does not allow the access.
+///
+/// Return the address of the attached segment.
+pub fn sys_shmat(shmid: c_int, shmaddr: *const c_void, shmflg: c_int) -> isize {
+    debug!(
+        "sys_shmat <= {} {:#x} {:#o}",
+        shmid, shmaddr as usize, shmflg
+    );
+    syscall_body!(sys_shmat, {
+        let shmflg = shmflg as u32;
+        let shm = get_shm(shmid, shmflg as c_int)?;
+        let size = shm.size();
+        let flags = if shmflg & ctypes::SHM_RDONLY != 0 {
+            MappingFlags::READ
+        } else {
+            MappingFlags::READ | MappingFlags::WRITE
+        };
+
+        let region = axmm::kernel_mmap_region();
+        let mut aspace = axmm::kernel_aspace().lock();
+        let start = if shmaddr.is_null() {
+            aspace
+                .find_free_area(region.start, size, region)
+                .ok_or(LinuxError::ENOMEM)?
+        } else {
+            let mut start = shmaddr as usize;
+            if shmflg & ctypes::SHM_RND != 0 {
+                start = align_down(start, ctypes::SHMLBA as usize);
+            }
+            let start = VirtAddr::from(start);
+            if !start.is_aligned_4k()
+                || !region.contains_range(VirtAddrRange::from_start_size(start, size))
+            {
+                return Err(LinuxError::EINVAL);
+            }
+            start
+        };
+        aspace.map_shared(start, size, flags, shm, 0)?;
+        Ok(start.as_usize())
+    })
+}
+
+/// Detach the shared memory segment attached at `shmaddr` by [`sys_shmat`].
+///
+/// Return `EINVAL` if there is no segment attached at `shmaddr`, e.g., it's a
+/// shared memory mapping created by `mmap`.
+pub fn sys_shmdt(shmaddr: *const c_void) -> c_int {
+    debug!("sys_shmdt <= {:#x}", shmaddr as usize);
+    syscall_body!(sys_shmdt, {
+        let start = VirtAddr::from(shmaddr as usize);
+        let mut aspace = axmm::kernel_aspace().lock();
+        match aspace.shared_memory_at(start) {
+            Some((shm, range)) if range.start == start && is_shm_segment(&shm) => {
+                aspace.unmap(range.start, range.size())?;
+                Ok(0)
+            }
+            _ => Err(LinuxError::EINVAL),
+        }
+    })
+}
+
+/// Control the shared memory segment `shmid`.
+///
+/// Only `IPC_STAT` and `IPC_RMID` are supported. The segment is removed
+/// immediately by `IPC_RMID`, but its memory is freed after it's detached
+/// from all the address spaces.
+pub unsafe fn sys_shmctl(shmid: c_int, cmd: c_int, buf: *mut ctypes::shmid_ds) -> c_int {
+    debug!("sys_shmctl <= {} {} {:#x}", shmid, cmd, buf as usize);
+    syscall_body!(sys_shmctl, {
+        let mut table = SHM_TABLE.lock();
+        match cmd as u32 {
+            ctypes::IPC_STAT => {
+                if buf.is_null() {
+                    return Err(LinuxError::EFAULT);
+                }
+                let seg = table.segments.get(&shmid).ok_or(LinuxError::EINVAL)?;
+                let mut ds = ctypes::shmid_ds {
+                    shm_segsz: seg.shm.size() as _,
+                    // one reference is held by the table, the others by the mappings.
+                    shm_nattch: (Arc::strong_count(&seg.shm) - 1) as _,
+                    ..Default::default()
+                };
+                ds.shm_perm.__key = seg.key;
+                ds.shm_perm.mode = seg.mode;
+                unsafe { *buf = ds };
+                Ok(0)
+            }
+            ctypes::IPC_RMID => {
+                table.segments.remove(&shmid).ok_or(LinuxError::EINVAL)?;
+                Ok(0)
+            }
+            _ => Err(LinuxError::EINVAL),
+        }
+    })
+}
+
+/// A POSIX shared memory object created by [`sys_shm_open`].
+struct ShmObject {
+    mode: ctypes::mode_t,
+    /// The memory and the size in bytes, which is replaced on resizing.
+    inner: Mutex<(Arc<SharedMemory>, usize)>,
+}
+
+/// The POSIX shared memory objects by names, until unlinked.
+static SHM_OBJECTS: Mutex<BTreeMap<String, Arc<ShmObject>>> = Mutex::new(BTreeMap::new());
+
+impl ShmObject {
+    /// Change the size of the object to `size` bytes, keeping the contents.
+    ///
+    /// The memory is replaced by a new one, so the existing mappings of the
+    /// object still see the old memory.
+    fn resize(&self, size: usize) -> LinuxResult {
+        let mut inner = self.inner.lock();
+        if inner.0.size() != align_up_4k(size) {
+            let new = SharedMemory::new(align_up_4k(size))?;
+            let mut buf = [0; PAGE_SIZE_4K];
+            for offset in (0..new.size().min(inner.0.size())).step_by(PAGE_SIZE_4K) {
+                inner.0.read_at(offset, &mut buf);
+                new.write_at(offset, &buf);
+            }
+            inner.0 = new;
+        }
+        if size < inner.1 {
+            // the bytes beyond the end read as zeros if it's extended again.
+            let zeros = [0; PAGE_SIZE_4K];
+            let end = inner.1.min(inner.0.size());
+            let mut offset = size;
+            while offset < end {
+                offset += inner
+                    .0
+                    .write_at(offset, &zeros[..(end - offset).min(PAGE_SIZE_4K)]);
+            }
+        }
+        inner.1 = size;
+        Ok(())
+    }
+}
+
+/// A file descriptor of a POSIX shared memory object, opened by
+/// [`sys_shm_open`].
+pub struct ShmFile {
+    obj: Arc<ShmObject>,
+    writable: bool,
+    pos: Mutex<usize>,
+}
+
+impl ShmFile {
+    pub(crate) fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
+        get_file_like(fd)?
+            .into_any()
+            .downcast::<Self>()
+            .map_err(|_| LinuxError::EINVAL)
+    }
+
+    /// Truncate or extend the object to `size` bytes, like `ftruncate`.
+    pub(crate) fn truncate(&self, size: usize) -> LinuxResult {
+        if !self.writable {
+            return Err(LinuxError::EINVAL);
+        }
+        self.obj.resize(size)
+    }
+}
+
+impl FileLike for ShmFile {
+    fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        let inner = self.obj.inner.lock();
+        let mut pos = self.pos.lock();
+        let len = buf.len().min(inner.1.saturating_sub(*pos));
+        let read = inner.0.read_at(*pos, &mut buf[..len]);
+        *pos += read;
+        Ok(read)
+    }
+
+    fn write(&self, buf: &[u8]) -> LinuxResult<usize> {
+        if !self.writable {
+            return Err(LinuxError::EBADF);
+        }
+        // the object is not extended by writes, like a mapping of it.
+        let inner = self.obj.inner.lock();
+        let mut pos = self.pos.lock();
+        let len = buf.len().min(inner.1.saturating_sub(*pos));
+        let written = inner.0.write_at(*pos, &buf[..len]);
+        *pos += written;
+        Ok(written)
+    }
+
+    fn stat(&self) -> LinuxResult<ctypes::stat> {
+        let inner = self.obj.inner.lock();
+        Ok(ctypes::stat {
+            st_ino: 1,
+            st_nlink: 1,
+            st_mode: 0o100000 | self.obj.mode, // S_IFREG
+            st_uid: 1000,
+            st_gid: 1000,
+            st_size: inner.1 as _,
+            st_blocks: (inner.0.size() / 512) as _,
+            st_blksize: PAGE_SIZE_4K as _,
+            ..Default::default()
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        Ok(PollState {
+            readable: true,
+            writable: self.writable,
+        })
+    }
+
+    fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
+        Ok(())
+    }
+}
+
+/// Get the shared memory of the POSIX shared memory object `fd` to be mapped
+/// by `mmap` with `MAP_SHARED`, or `None` if `fd` is not such an object.
+///
+/// The object must be opened for writing if `writable` is true. Return
+/// `EACCES` otherwise.
+pub fn get_shm_file(fd: c_int, writable: bool) -> LinuxResult<Option<Arc<SharedMemory>>> {
+    let Ok(file) = ShmFile::from_fd(fd) else {
+        return Ok(None);
+    };
+    if writable && !file.writable {
+        return Err(LinuxError::EACCES);
+    }
+    let shm = file.obj.inner.lock().0.clone();
+    Ok(Some(shm))
+}
+
+/// Check the name of a POSIX shared memory object, which is like `/name`.
+fn shm_name(name: *const c_char) -> LinuxResult<&'static str> {
+    let name = char_ptr_to_str(name)?;
+    match name.strip_prefix('/') {
+        Some(rest) if !rest.is_empty() && !rest.contains('/') => Ok(name),
+        _ => Err(LinuxError::EINVAL),
+    }
+}
+
+/// Open the POSIX shared memory object `name`, or create it with `mode` if
+/// `O_CREAT` is specified. A new object is empty until resized by
+/// `ftruncate`.
+///
+/// The object can be mapped by `mmap` with `MAP_SHARED` (but not
+/// `MAP_PRIVATE`), and the mappings see the changes of each other. Resizing
+/// replaces the memory of the object, so the existing mappings are not
+/// changed.
+///
+/// Return the file descriptor.
+pub fn sys_shm_open(name: *const c_char, oflag: c_int, mode: ctypes::mode_t) -> c_int {
+    let name = shm_name(name);
+    debug!("sys_shm_open <= {:?} {:#o} {:#o}", name, oflag, mode);
+    syscall_body!(sys_shm_open, {
+        let name = name?;
+        let oflag = oflag as u32;
+        let writable = match oflag & 0b11 {
+            ctypes::O_RDONLY => false,
+            ctypes::O_RDWR => true,
+            _ => return Err(LinuxError::EINVAL),
+        };
+        let mut objects = SHM_OBJECTS.lock();
+        let obj = match objects.get(name) {
+            Some(_) if oflag & ctypes::O_CREAT != 0 && oflag & ctypes::O_EXCL != 0 => {
+                return Err(LinuxError::EEXIST);
+            }
+            Some(obj) => {
+                check_perm(obj.mode, if writable { 0o6 } else { 0o4 })?;
+                if oflag & ctypes::O_TRUNC != 0 && writable {
+                    obj.resize(0)?;
+                }
+                obj.clone()
+            }
+            None if oflag & ctypes::O_CREAT != 0 => {
+                let obj = Arc::new(ShmObject {
+                    mode: mode & 0o777,
+                    inner: Mutex::new((SharedMemory::new(0)?, 0)),
+                });
+                objects.insert(name.to_string(), obj.clone());
+                obj
+            }
+            None => return Err(LinuxError::ENOENT),
+        };
+        drop(objects);
+        add_file_like(Arc::new(ShmFile {
+            obj,
+            writable,
+            pos: Mutex::new(0),
+        }))
+    })
+}
+
+/// Remove the POSIX shared memory object `name` created by [`sys_shm_open`].
+///
+/// The object is still usable by the opened file descriptors and mappings.
+pub fn sys_shm_unlink(name: *const c_char) -> c_int {
+    let name = shm_name(name);
+    debug!("sys_shm_unlink <= {:?}", name);
+    syscall_body!(sys_shm_unlink, {
+        SHM_OBJECTS.lock().remove(name?).ok_or(LinuxError::ENOENT)?;
+        Ok(0)
+    })
+}
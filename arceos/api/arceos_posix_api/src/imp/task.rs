@@ -1,5 +1,9 @@
 use core::ffi::c_int;
 
+use axerrno::{LinuxError, LinuxResult};
+
+use crate::ctypes;
+
 /// Relinquish the CPU, and switches to another task.
 ///
 /// For single-threaded configuration (`multitask` feature is disabled), we just
@@ -38,3 +42,151 @@ pub fn sys_exit(exit_code: c_int) -> ! {
     #[cfg(not(feature = "multitask"))]
     axhal::misc::terminate();
 }
+
+/// Set the CPU affinity mask of the thread `pid` (0 means the calling thread).
+///
+/// Only the calling thread is supported currently.
+pub unsafe fn sys_sched_setaffinity(
+    pid: ctypes::pid_t,
+    cpusetsize: usize,
+    mask: *const ctypes::cpu_set_t,
+) -> c_int {
+    debug!(
+        "sys_sched_setaffinity <= {} {} {:#x}",
+        pid, cpusetsize, mask as usize
+    );
+    syscall_body!(sys_sched_setaffinity, {
+        check_affinity_args(pid, cpusetsize, mask)?;
+        let words = cpusetsize.min(core::mem::size_of::<ctypes::cpu_set_t>()) / 8;
+        let mut bits = [0u64; 16];
+        for (i, bit) in bits.iter_mut().enumerate().take(words) {
+            *bit = unsafe { (*mask).__bits[i] } as u64;
+        }
+        #[cfg(feature = "multitask")]
+        {
+            let cpumask = axtask::AxCpuMask::from_raw_bits(&bits);
+            if !axtask::set_current_affinity(cpumask) {
+                return Err(LinuxError::EINVAL);
+            }
+        }
+        #[cfg(not(feature = "multitask"))]
+        if bits[0] & 1 == 0 {
+            // only the primary CPU can run the single task.
+            return Err(LinuxError::EINVAL);
+        }
+        Ok(0)
+    })
+}
+
+/// Get the CPU affinity mask of the thread `pid` (0 means the calling thread).
+///
+/// Only the calling thread is supported currently. Returns the size of the
+/// mask copied into `mask` on success.
+pub unsafe fn sys_sched_getaffinity(
+    pid: ctypes::pid_t,
+    cpusetsize: usize,
+    mask: *mut ctypes::cpu_set_t,
+) -> c_int {
+    debug!(
+        "sys_sched_getaffinity <= {} {} {:#x}",
+        pid, cpusetsize, mask as usize
+    );
+    syscall_body!(sys_sched_getaffinity, {
+        check_affinity_args(pid, cpusetsize, mask)?;
+        #[cfg(feature = "multitask")]
+        let cpumask = axtask::current().cpu_mask();
+        let size = cpusetsize.min(core::mem::size_of::<ctypes::cpu_set_t>());
+        unsafe {
+            core::ptr::write_bytes(mask as *mut u8, 0, size);
+            #[cfg(feature = "multitask")]
+            for (i, &bits) in cpumask.as_raw_bits().iter().enumerate().take(size / 8) {
+                (*mask).__bits[i] = bits as _;
+            }
+            #[cfg(not(feature = "multitask"))]
+            {
+                (*mask).__bits[0] = 1;
+            }
+        }
+        Ok(size)
+    })
+}
+
+/// Set the scheduling policy and attributes of the thread `pid` (0 means the
+/// calling thread).
+///
+/// Only the calling thread is supported currently. `SCHED_DEADLINE` requires
+/// the `sched_edf` feature, and fails with `EBUSY` if the CPU bandwidth cannot
+/// be reserved.
+pub unsafe fn sys_sched_setattr(
+    pid: ctypes::pid_t,
+    attr: *const ctypes::sched_attr,
+    flags: core::ffi::c_uint,
+) -> c_int {
+    debug!(
+        "sys_sched_setattr <= {} {:#x} {}",
+        pid, attr as usize, flags
+    );
+    syscall_body!(sys_sched_setattr, {
+        if attr.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        if flags != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        if pid != 0 && pid != sys_getpid() {
+            return Err(LinuxError::ESRCH);
+        }
+        let attr = unsafe { &*attr };
+        #[cfg(feature = "multitask")]
+        match attr.sched_policy {
+            ctypes::SCHED_DEADLINE => {
+                use core::time::Duration;
+                // the policy is not supported by the scheduler.
+                if !axtask::deadline_supported() {
+                    return Err(LinuxError::EINVAL);
+                }
+                let params = axtask::DeadlineParams {
+                    runtime: Duration::from_nanos(attr.sched_runtime),
+                    deadline: Duration::from_nanos(attr.sched_deadline),
+                    // a zero period means the same as the deadline.
+                    period: Duration::from_nanos(if attr.sched_period == 0 {
+                        attr.sched_deadline
+                    } else {
+                        attr.sched_period
+                    }),
+                };
+                if !params.is_valid() {
+                    return Err(LinuxError::EINVAL);
+                }
+                if !axtask::set_deadline(Some(params)) {
+                    return Err(LinuxError::EBUSY);
+                }
+            }
+            ctypes::SCHED_OTHER | ctypes::SCHED_BATCH | ctypes::SCHED_IDLE => {
+                axtask::set_deadline(None);
+                if attr.sched_nice != 0 && !axtask::set_priority(attr.sched_nice as isize) {
+                    return Err(LinuxError::EINVAL);
+                }
+            }
+            _ => return Err(LinuxError::EINVAL),
+        }
+        #[cfg(not(feature = "multitask"))]
+        if attr.sched_policy != ctypes::SCHED_OTHER {
+            return Err(LinuxError::EINVAL);
+        }
+        Ok(0)
+    })
+}
+
+fn check_affinity_args<T>(pid: ctypes::pid_t, cpusetsize: usize, mask: *const T) -> LinuxResult {
+    if mask.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    if cpusetsize < 8 {
+        return Err(LinuxError::EINVAL);
+    }
+    if pid != 0 && pid != sys_getpid() {
+        return Err(LinuxError::ESRCH);
+    }
+    Ok(())
+}
@@ -5,6 +5,11 @@ use core::time::Duration;
 use crate::ctypes;
 use crate::ctypes::{CLOCK_MONOTONIC, CLOCK_REALTIME};
 
+fn duration_to_ticks(d: Duration) -> ctypes::clock_t {
+    use axhal::time::{NANOS_PER_SEC, USER_HZ};
+    (d.as_nanos() * USER_HZ as u128 / NANOS_PER_SEC as u128) as _
+}
+
 impl From<ctypes::timespec> for Duration {
     fn from(ts: ctypes::timespec) -> Self {
         Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
@@ -74,7 +79,10 @@ pub unsafe fn sys_nanosleep(req: *const ctypes::timespec, rem: *mut ctypes::time
         let now = axhal::time::monotonic_time();
 
         #[cfg(feature = "multitask")]
-        axtask::sleep(dur);
+        if axtask::sleep_interruptible(dur).is_err() {
+            // `nanosleep` is a cancellation point.
+            super::pthread::sys_pthread_testcancel();
+        }
         #[cfg(not(feature = "multitask"))]
         axhal::time::busy_wait(dur);
 
@@ -90,3 +98,29 @@ pub unsafe fn sys_nanosleep(req: *const ctypes::timespec, rem: *mut ctypes::time
         Ok(0)
     })
 }
+
+/// Get process times in clock ticks
+///
+/// Returns the number of clock ticks elapsed since booting.
+pub unsafe fn sys_times(buf: *mut ctypes::tms) -> ctypes::clock_t {
+    syscall_body!(sys_times, {
+        if !buf.is_null() {
+            #[cfg(feature = "multitask")]
+            let (utime, stime) = {
+                let usage = axtask::total_cpu_usage();
+                (usage.utime, usage.stime)
+            };
+            #[cfg(not(feature = "multitask"))]
+            let (utime, stime) = (axhal::time::monotonic_time(), Duration::ZERO);
+            unsafe {
+                *buf = ctypes::tms {
+                    tms_utime: duration_to_ticks(utime),
+                    tms_stime: duration_to_ticks(stime),
+                    tms_cutime: 0,
+                    tms_cstime: 0,
+                };
+            }
+        }
+        Ok(duration_to_ticks(axhal::time::monotonic_time()))
+    })
+}
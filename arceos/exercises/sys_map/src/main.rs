@@ -16,6 +16,8 @@ use axstd::io;
 use axhal::paging::MappingFlags;
 use axhal::arch::UspaceContext;
 use axhal::mem::VirtAddr;
+use axhal::trap::{register_trap_handler, PAGE_FAULT};
+use axtask::TaskExtRef;
 use axsync::Mutex;
 use alloc::sync::Arc;
 use alloc::string::String;
@@ -24,10 +26,13 @@ use axmm::AddrSpace;
 use loader::load_user_app;
 
 const USER_STACK_SIZE: usize = 0x10000;
+const USER_STACK_MAX_SIZE: usize = 0x80_0000; // 8 MiB, the default RLIMIT_STACK
 const KERNEL_STACK_SIZE: usize = 0x40000; // 256 KiB
 
 #[cfg_attr(feature = "axstd", no_mangle)]
 fn main() {
+    axfs::set_proc_memory(&task::UserProcMemory);
+
     // A new address space for user app.
     let mut uspace = axmm::new_user_aspace().unwrap();
 
@@ -60,9 +65,10 @@ fn init_user_stack(uspace: &mut AddrSpace, populating: bool) -> io::Result<VirtA
         "Mapping user stack: {:#x?} -> {:#x?}",
         ustack_vaddr, ustack_top
     );
-    uspace.map_alloc(
-        ustack_vaddr,
+    uspace.map_stack(
+        ustack_top,
         crate::USER_STACK_SIZE,
+        crate::USER_STACK_MAX_SIZE,
         MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER,
         populating,
     ).unwrap();
@@ -80,3 +86,21 @@ fn init_user_stack(uspace: &mut AddrSpace, populating: bool) -> io::Result<VirtA
 
     Ok(ustack_pointer.into())
 }
+
+#[register_trap_handler(PAGE_FAULT)]
+fn handle_page_fault(vaddr: VirtAddr, access_flags: MappingFlags, is_user: bool) -> bool {
+    if is_user {
+        if !axtask::current()
+            .task_ext()
+            .aspace
+            .lock()
+            .handle_page_fault(vaddr, access_flags)
+        {
+            ax_println!("{}: segmentation fault, exit!", axtask::current().id_name());
+            axtask::exit(-1);
+        }
+        true
+    } else {
+        false
+    }
+}
@@ -7,6 +7,9 @@ use axerrno::LinuxError;
 use axtask::current;
 use axtask::TaskExtRef;
 use axhal::paging::MappingFlags;
+use axhal::mem::{MemoryAddr, VirtAddr, PAGE_SIZE_4K};
+use memory_addr::{align_up_4k, is_aligned_4k, VirtAddrRange};
+use axmm::SharedMemory;
 use arceos_posix_api as api;
 
 const SYS_IOCTL: usize = 29;
@@ -18,6 +21,10 @@ const SYS_WRITEV: usize = 66;
 const SYS_EXIT: usize = 93;
 const SYS_EXIT_GROUP: usize = 94;
 const SYS_SET_TID_ADDRESS: usize = 96;
+const SYS_SHMGET: usize = 194;
+const SYS_SHMAT: usize = 196;
+const SYS_SHMDT: usize = 197;
+const SYS_MUNMAP: usize = 215;
 const SYS_MMAP: usize = 222;
 
 const AT_FDCWD: i32 = -100;
@@ -123,6 +130,10 @@ fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
             tf.arg4() as _,
             tf.arg5() as _,
         ),
+        SYS_MUNMAP => sys_munmap(tf.arg0() as _, tf.arg1() as _),
+        SYS_SHMGET => sys_shmget(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _),
+        SYS_SHMAT => sys_shmat(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _),
+        SYS_SHMDT => sys_shmdt(tf.arg0() as _),
         _ => {
             ax_println!("Unimplemented syscall: {}", syscall_num);
             -LinuxError::ENOSYS.code() as _
@@ -131,16 +142,116 @@ fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
     ret
 }
 
-#[allow(unused_variables)]
 fn sys_mmap(
     addr: *mut usize,
     length: usize,
     prot: i32,
     flags: i32,
     fd: i32,
-    _offset: isize,
+    offset: isize,
 ) -> isize {
-    unimplemented!("no sys_mmap!");
+    syscall_body!(sys_mmap, {
+        let prot = MmapProt::from_bits(prot).ok_or(LinuxError::EINVAL)?;
+        let flags = MmapFlags::from_bits_truncate(flags);
+        if length == 0 || offset < 0 || !is_aligned_4k(offset as usize) {
+            return Err(LinuxError::EINVAL);
+        }
+        let shared = flags.contains(MmapFlags::MAP_SHARED);
+        if shared == flags.contains(MmapFlags::MAP_PRIVATE) {
+            return Err(LinuxError::EINVAL);
+        }
+        let length = align_up_4k(length);
+        let writable = shared && prot.contains(MmapProt::PROT_WRITE);
+        let mapping_flags = MappingFlags::from(prot);
+
+        let curr = current();
+        let mut aspace = curr.task_ext().aspace.lock();
+        let start = if flags.contains(MmapFlags::MAP_FIXED) {
+            let start = VirtAddr::from(addr as usize);
+            if !start.is_aligned_4k() {
+                return Err(LinuxError::EINVAL);
+            }
+            aspace.unmap(start, length)?;
+            start
+        } else {
+            // never map the first page, so that null pointers are invalid.
+            let hint = VirtAddr::from((addr as usize).max(PAGE_SIZE_4K)).align_down_4k();
+            let limit = VirtAddrRange::new(aspace.base(), aspace.end());
+            aspace
+                .find_free_area(hint, length, limit)
+                .ok_or(LinuxError::ENOMEM)?
+        };
+
+        if flags.contains(MmapFlags::MAP_ANONYMOUS) {
+            if shared {
+                // still shared with the children after `clone_cow`.
+                let shm = SharedMemory::new(length)?;
+                aspace.map_shared(start, length, mapping_flags, shm, 0)?;
+            } else {
+                aspace.map_alloc(start, length, mapping_flags, false)?;
+            }
+        } else {
+            let file = api::get_mmap_file(fd, writable)?;
+            aspace.map_file(start, length, mapping_flags, file, offset as u64, shared)?;
+        }
+        Ok(start.as_usize())
+    })
+}
+
+fn sys_munmap(addr: *mut usize, length: usize) -> isize {
+    syscall_body!(sys_munmap, {
+        let start = VirtAddr::from(addr as usize);
+        if !start.is_aligned_4k() || length == 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let curr = current();
+        curr.task_ext().aspace.lock().unmap(start, align_up_4k(length))?;
+        Ok(0)
+    })
+}
+
+fn sys_shmget(key: i32, size: usize, shmflg: i32) -> isize {
+    api::sys_shmget(key, size as _, shmflg) as isize
+}
+
+fn sys_shmat(shmid: i32, shmaddr: *const c_void, shmflg: i32) -> isize {
+    syscall_body!(sys_shmat, {
+        let shm = api::get_shm(shmid, shmflg)?;
+        let size = shm.size();
+        let mut flags = MappingFlags::READ | MappingFlags::USER;
+        if shmflg as u32 & api::ctypes::SHM_RDONLY == 0 {
+            flags |= MappingFlags::WRITE;
+        }
+
+        let curr = current();
+        let mut aspace = curr.task_ext().aspace.lock();
+        let start = if shmaddr.is_null() {
+            let limit = VirtAddrRange::new(aspace.base(), aspace.end());
+            aspace
+                .find_free_area(VirtAddr::from(PAGE_SIZE_4K), size, limit)
+                .ok_or(LinuxError::ENOMEM)?
+        } else {
+            VirtAddr::from(shmaddr as usize)
+        };
+        aspace.map_shared(start, size, flags, shm, 0)?;
+        Ok(start.as_usize())
+    })
+}
+
+fn sys_shmdt(shmaddr: *const c_void) -> isize {
+    syscall_body!(sys_shmdt, {
+        let start = VirtAddr::from(shmaddr as usize);
+        let curr = current();
+        let mut aspace = curr.task_ext().aspace.lock();
+        // shared anonymous mappings are not attached by `shmat`.
+        match aspace.shared_memory_at(start) {
+            Some((shm, range)) if range.start == start && api::is_shm_segment(&shm) => {
+                aspace.unmap(range.start, range.size())?;
+                Ok(0)
+            }
+            _ => Err(LinuxError::EINVAL),
+        }
+    })
 }
 
 fn sys_openat(dfd: c_int, fname: *const c_char, flags: c_int, mode: api::ctypes::mode_t) -> isize {
@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 
+use core::fmt::{self, Write};
 use core::sync::atomic::AtomicU64;
 
 use alloc::sync::Arc;
@@ -8,6 +9,7 @@ use axhal::arch::UspaceContext;
 use axmm::AddrSpace;
 use axsync::Mutex;
 use axtask::{AxTaskRef, TaskExtRef, TaskInner};
+use lazyinit::LazyInit;
 
 /// Task extended data for the monolithic kernel.
 pub struct TaskExt {
@@ -48,6 +50,32 @@ impl TaskExt {
 
 axtask::def_task_ext!(TaskExt);
 
+axtask::def_task_ext_slot! {
+    /// The address space of the user task, which is also available to the
+    /// other tasks, e.g., reading `/proc/<tid>/maps`.
+    static TASK_ASPACE: LazyInit<Arc<Mutex<AddrSpace>>> = LazyInit::new();
+}
+
+/// Shows the memory areas and usage of the user tasks in
+/// `/proc/<tid>/{maps,status}`.
+pub struct UserProcMemory;
+
+impl axfs::ProcMemory for UserProcMemory {
+    fn usage(&self, task: &AxTaskRef) -> Option<(usize, usize)> {
+        let aspace = TASK_ASPACE.try_get(task)?.get()?.lock();
+        Some((aspace.vsz(), aspace.rss()))
+    }
+
+    fn write_maps(&self, task: &AxTaskRef, out: &mut dyn Write) -> fmt::Result {
+        if let Some(aspace) = TASK_ASPACE.try_get(task).and_then(|a| a.get()) {
+            for area in aspace.lock().areas() {
+                writeln!(out, "{}", area)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 pub fn spawn_user_task(aspace: Arc<Mutex<AddrSpace>>, uctx: UspaceContext) -> AxTaskRef {
     let mut task = TaskInner::new(
         || {
@@ -66,6 +94,7 @@ pub fn spawn_user_task(aspace: Arc<Mutex<AddrSpace>>, uctx: UspaceContext) -> Ax
     );
     task.ctx_mut()
         .set_page_table_root(aspace.lock().page_table_root());
+    TASK_ASPACE.get(&task).init_once(aspace.clone());
     task.init_task_ext(TaskExt::new(uctx, aspace));
     axtask::spawn_task(task)
 }
@@ -0,0 +1,137 @@
+//! Running blocking functions on a pool of dedicated tasks.
+
+use alloc::{boxed::Box, collections::VecDeque, string::String, sync::Arc};
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+
+use axtask::WaitQueue;
+use kspin::SpinNoIrq;
+
+/// The maximum number of tasks that run blocking functions.
+///
+/// Functions submitted when all of them are busy wait in a queue.
+pub const MAX_BLOCKING_WORKERS: usize = 8;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+struct BlockingPool {
+    jobs: VecDeque<Job>,
+    num_workers: usize,
+    num_idle: usize,
+}
+
+static POOL: SpinNoIrq<BlockingPool> = SpinNoIrq::new(BlockingPool {
+    jobs: VecDeque::new(),
+    num_workers: 0,
+    num_idle: 0,
+});
+
+/// Idle workers wait here for new jobs.
+static POOL_WQ: WaitQueue = WaitQueue::new();
+
+fn blocking_worker() {
+    loop {
+        let job = {
+            let mut pool = POOL.lock();
+            let job = pool.jobs.pop_front();
+            if job.is_none() {
+                pool.num_idle += 1;
+            }
+            job
+        };
+        match job {
+            Some(job) => job(),
+            None => {
+                POOL_WQ.wait_idle_until(|| !POOL.lock().jobs.is_empty());
+                POOL.lock().num_idle -= 1;
+            }
+        }
+    }
+}
+
+/// Queues the job, and starts a new worker if the idle ones are not enough.
+fn submit(job: Job) {
+    let spawn_worker = {
+        let mut pool = POOL.lock();
+        pool.jobs.push_back(job);
+        let spawn = pool.jobs.len() > pool.num_idle && pool.num_workers < MAX_BLOCKING_WORKERS;
+        if spawn {
+            pool.num_workers += 1;
+        }
+        spawn
+    };
+    if spawn_worker {
+        axtask::spawn_raw(
+            blocking_worker,
+            String::from("async-blocking"),
+            axconfig::TASK_STACK_SIZE,
+        );
+    } else {
+        POOL_WQ.notify_one(true);
+    }
+}
+
+/// Returns the number of worker tasks in the blocking pool.
+pub fn num_blocking_workers() -> usize {
+    POOL.lock().num_workers
+}
+
+struct BlockingState<T> {
+    result: SpinNoIrq<Option<T>>,
+    waker: SpinNoIrq<Option<Waker>>,
+    finished: AtomicBool,
+}
+
+/// A future that resolves to the result of a function run by
+/// [`spawn_blocking`].
+pub struct BlockingHandle<T> {
+    state: Arc<BlockingState<T>>,
+}
+
+impl<T> Future for BlockingHandle<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let state = &self.state;
+        if !state.finished.load(Ordering::Acquire) {
+            *state.waker.lock() = Some(cx.waker().clone());
+            if !state.finished.load(Ordering::Acquire) {
+                return Poll::Pending;
+            }
+        }
+        match state.result.lock().take() {
+            Some(value) => Poll::Ready(value),
+            None => panic!("`BlockingHandle` polled after completion"),
+        }
+    }
+}
+
+/// Runs a blocking function on the blocking pool, and returns a future that
+/// resolves to its result.
+///
+/// It's used to call blocking APIs (e.g., file operations) in asynchronous
+/// code without blocking the executor. At most [`MAX_BLOCKING_WORKERS`] tasks
+/// are spawned, they are kept to run the later functions.
+pub fn spawn_blocking<F, T>(f: F) -> BlockingHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let state = Arc::new(BlockingState {
+        result: SpinNoIrq::new(None),
+        waker: SpinNoIrq::new(None),
+        finished: AtomicBool::new(false),
+    });
+    let their_state = state.clone();
+    submit(Box::new(move || {
+        let value = f();
+        *their_state.result.lock() = Some(value);
+        their_state.finished.store(true, Ordering::Release);
+        if let Some(waker) = their_state.waker.lock().take() {
+            waker.wake();
+        }
+    }));
+    BlockingHandle { state }
+}
@@ -0,0 +1,347 @@
+//! Task-backed executors.
+
+use alloc::{boxed::Box, collections::VecDeque, format, sync::Arc, task::Wake, vec::Vec};
+use core::cell::UnsafeCell;
+use core::future::Future;
+use core::pin::{pin, Pin};
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use core::task::{Context, Poll, Waker};
+
+use axtask::{AxTaskRef, WaitQueue};
+use kspin::SpinNoIrq;
+
+use crate::waker::Signal;
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+// State transitions of a spawned future:
+//
+// IDLE -(wake)-> SCHEDULED -(pop)-> RUNNING -(pending)-> IDLE
+//                                      |  \-(ready)-> COMPLETED
+//                                      |
+//                                      \-(wake)-> NOTIFIED -(pending)-> SCHEDULED
+const STATE_IDLE: u8 = 0;
+const STATE_SCHEDULED: u8 = 1;
+const STATE_RUNNING: u8 = 2;
+const STATE_NOTIFIED: u8 = 3;
+const STATE_COMPLETED: u8 = 4;
+
+/// States shared by an executor and its worker tasks.
+struct Shared {
+    queue: SpinNoIrq<VecDeque<Arc<AsyncTask>>>,
+    wq: WaitQueue,
+    shutdown: AtomicBool,
+}
+
+impl Shared {
+    fn schedule(&self, task: Arc<AsyncTask>) {
+        if self.shutdown.load(Ordering::Acquire) {
+            return; // the executor has been dropped, drop the task as well.
+        }
+        self.queue.lock().push_back(task);
+        self.wq.notify_one(true);
+    }
+
+    fn pop(&self) -> Option<Arc<AsyncTask>> {
+        self.queue.lock().pop_front()
+    }
+
+    fn has_work(&self) -> bool {
+        !self.queue.lock().is_empty()
+    }
+}
+
+/// A future spawned on an [`Executor`].
+struct AsyncTask {
+    state: AtomicU8,
+    // Only accessed in the `RUNNING` state, which is exclusive.
+    future: UnsafeCell<Option<BoxFuture>>,
+    shared: Arc<Shared>,
+}
+
+unsafe impl Sync for AsyncTask {}
+
+impl AsyncTask {
+    /// Polls the future once, it must be popped from the queue of the executor.
+    fn run(self: Arc<Self>) {
+        if self
+            .state
+            .compare_exchange(
+                STATE_SCHEDULED,
+                STATE_RUNNING,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_err()
+        {
+            return;
+        }
+
+        let waker = Waker::from(self.clone());
+        let mut cx = Context::from_waker(&waker);
+        // SAFETY: we are the only one in the `RUNNING` state.
+        let future = unsafe { &mut *self.future.get() };
+        let ready = future
+            .as_mut()
+            .map_or(true, |f| f.as_mut().poll(&mut cx).is_ready());
+        if ready {
+            *future = None;
+            self.state.store(STATE_COMPLETED, Ordering::Release);
+        } else if self
+            .state
+            .compare_exchange(
+                STATE_RUNNING,
+                STATE_IDLE,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_err()
+        {
+            // woken up while it was running.
+            self.state.store(STATE_SCHEDULED, Ordering::Release);
+            self.shared.schedule(self.clone());
+        }
+    }
+}
+
+impl Wake for AsyncTask {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let mut state = self.state.load(Ordering::Acquire);
+        loop {
+            let new_state = match state {
+                STATE_IDLE => STATE_SCHEDULED,
+                STATE_RUNNING => STATE_NOTIFIED,
+                _ => return,
+            };
+            match self.state.compare_exchange_weak(
+                state,
+                new_state,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => break,
+                Err(s) => state = s,
+            }
+        }
+        if state == STATE_IDLE {
+            self.shared.schedule(self.clone());
+        }
+    }
+}
+
+/// The result of a spawned future, and the waker of its [`JoinHandle`].
+struct JoinState<T> {
+    result: SpinNoIrq<Option<T>>,
+    waker: SpinNoIrq<Option<Waker>>,
+    finished: AtomicBool,
+}
+
+impl<T> JoinState<T> {
+    fn complete(&self, value: T) {
+        *self.result.lock() = Some(value);
+        self.finished.store(true, Ordering::Release);
+        if let Some(waker) = self.waker.lock().take() {
+            waker.wake();
+        }
+    }
+}
+
+/// An owned permission to await the result of a spawned future.
+///
+/// Dropping the handle does not cancel the future, it keeps running in the
+/// background.
+pub struct JoinHandle<T> {
+    state: Arc<JoinState<T>>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Whether the spawned future has completed.
+    pub fn is_finished(&self) -> bool {
+        self.state.finished.load(Ordering::Acquire)
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        if !self.is_finished() {
+            *self.state.waker.lock() = Some(cx.waker().clone());
+            // check again in case it completed before the waker is stored.
+            if !self.is_finished() {
+                return Poll::Pending;
+            }
+        }
+        match self.state.result.lock().take() {
+            Some(value) => Poll::Ready(value),
+            None => panic!("`JoinHandle` polled after completion"),
+        }
+    }
+}
+
+/// An executor that runs futures on [`axtask`] tasks.
+///
+/// A single-threaded executor (created by [`Executor::new`]) runs spawned
+/// futures only inside [`Executor::block_on`], on the calling task. A
+/// multi-threaded executor (created by [`Executor::with_workers`]) also runs
+/// them on its worker tasks.
+///
+/// When dropped, the worker tasks exit, and the futures that have not
+/// completed are dropped.
+pub struct Executor {
+    shared: Arc<Shared>,
+    workers: Vec<AxTaskRef>,
+}
+
+impl Executor {
+    /// Creates a new single-threaded executor.
+    pub fn new() -> Self {
+        Self::with_workers(0)
+    }
+
+    /// Creates a new multi-threaded executor with `num_workers` worker tasks.
+    pub fn with_workers(num_workers: usize) -> Self {
+        let shared = Arc::new(Shared {
+            queue: SpinNoIrq::new(VecDeque::new()),
+            wq: WaitQueue::new(),
+            shutdown: AtomicBool::new(false),
+        });
+        let workers = (0..num_workers)
+            .map(|i| {
+                let shared = shared.clone();
+                axtask::spawn_raw(
+                    move || worker_loop(&shared),
+                    format!("async-worker-{}", i),
+                    axconfig::TASK_STACK_SIZE,
+                )
+            })
+            .collect();
+        Self { shared, workers }
+    }
+
+    /// Spawns a future onto the executor, and returns a [`JoinHandle`] to
+    /// await its result.
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let state = Arc::new(JoinState {
+            result: SpinNoIrq::new(None),
+            waker: SpinNoIrq::new(None),
+            finished: AtomicBool::new(false),
+        });
+        let their_state = state.clone();
+        let task = Arc::new(AsyncTask {
+            state: AtomicU8::new(STATE_SCHEDULED),
+            future: UnsafeCell::new(Some(Box::pin(async move {
+                their_state.complete(future.await);
+            }))),
+            shared: self.shared.clone(),
+        });
+        self.shared.schedule(task);
+        JoinHandle { state }
+    }
+
+    /// Runs a future to completion on the current task, and returns its
+    /// output.
+    ///
+    /// Spawned futures are also run on the current task while the given
+    /// future is pending.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let mut future = pin!(future);
+        let main = Arc::new(MainWaker {
+            notified: AtomicBool::new(true),
+            shared: self.shared.clone(),
+        });
+        let waker = Waker::from(main.clone());
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if main.notified.swap(false, Ordering::AcqRel) {
+                if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                    return output;
+                }
+            }
+            if let Some(task) = self.shared.pop() {
+                task.run();
+                continue;
+            }
+            self.shared.wq.wait_idle_until(|| {
+                main.notified.load(Ordering::Acquire) || self.shared.has_work()
+            });
+        }
+    }
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Executor {
+    fn drop(&mut self) {
+        self.shared.shutdown.store(true, Ordering::Release);
+        self.shared.wq.notify_all(true);
+        for worker in self.workers.drain(..) {
+            worker.join();
+        }
+        // break the reference cycles between tasks and the executor.
+        let tasks = core::mem::take(&mut *self.shared.queue.lock());
+        drop(tasks);
+    }
+}
+
+/// The waker of the future run by [`Executor::block_on`].
+struct MainWaker {
+    notified: AtomicBool,
+    shared: Arc<Shared>,
+}
+
+impl Wake for MainWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.notified.store(true, Ordering::Release);
+        // the blocked task may not be the first one in the wait queue.
+        self.shared.wq.notify_all(true);
+    }
+}
+
+fn worker_loop(shared: &Shared) {
+    loop {
+        if shared.shutdown.load(Ordering::Acquire) {
+            break;
+        } else if let Some(task) = shared.pop() {
+            task.run();
+        } else {
+            shared
+                .wq
+                .wait_idle_until(|| shared.has_work() || shared.shutdown.load(Ordering::Acquire));
+        }
+    }
+    debug!("{} exited", axtask::current().id_name());
+}
+
+/// Runs a future to completion on the current task, and returns its output.
+///
+/// The current task is blocked while the future is pending, until the future
+/// is woken up.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let signal = Arc::new(Signal::new());
+    let waker = Waker::from(signal.clone());
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        signal.wait();
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
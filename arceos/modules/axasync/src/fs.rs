@@ -0,0 +1,93 @@
+//! Asynchronous file operations.
+//!
+//! Filesystem operations are blocking, so they are run on separate tasks by
+//! [`spawn_blocking`], with owned buffers that are copied back on completion.
+
+use alloc::{string::String, sync::Arc, vec};
+
+use axerrno::AxResult;
+use axfs::fops::{self, FileAttr, OpenOptions};
+use axio::SeekFrom;
+use axsync::Mutex;
+
+use crate::spawn_blocking;
+
+/// An opened file that can be accessed asynchronously.
+///
+/// It's cheap to clone, and all clones share the same cursor.
+#[derive(Clone)]
+pub struct File {
+    inner: Arc<Mutex<fops::File>>,
+}
+
+impl File {
+    /// Opens a file at the path with the given options.
+    pub async fn open(path: &str, opts: &OpenOptions) -> AxResult<Self> {
+        let path = String::from(path);
+        let opts = opts.clone();
+        let file = spawn_blocking(move || fops::File::open(&path, &opts)).await?;
+        Ok(Self {
+            inner: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    /// Reads data from the current position, returns the number of bytes
+    /// read.
+    pub async fn read(&self, buf: &mut [u8]) -> AxResult<usize> {
+        let file = self.inner.clone();
+        let mut tmp = vec![0; buf.len()];
+        let (res, tmp) = spawn_blocking(move || (file.lock().read(&mut tmp), tmp)).await;
+        let n = res?;
+        buf[..n].copy_from_slice(&tmp[..n]);
+        Ok(n)
+    }
+
+    /// Reads data at the given offset, the cursor is not changed.
+    pub async fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize> {
+        let file = self.inner.clone();
+        let mut tmp = vec![0; buf.len()];
+        let (res, tmp) = spawn_blocking(move || (file.lock().read_at(offset, &mut tmp), tmp)).await;
+        let n = res?;
+        buf[..n].copy_from_slice(&tmp[..n]);
+        Ok(n)
+    }
+
+    /// Writes data to the current position, returns the number of bytes
+    /// written.
+    pub async fn write(&self, buf: &[u8]) -> AxResult<usize> {
+        let file = self.inner.clone();
+        let data = buf.to_vec();
+        spawn_blocking(move || file.lock().write(&data)).await
+    }
+
+    /// Writes data at the given offset, the cursor is not changed.
+    pub async fn write_at(&self, offset: u64, buf: &[u8]) -> AxResult<usize> {
+        let file = self.inner.clone();
+        let data = buf.to_vec();
+        spawn_blocking(move || file.lock().write_at(offset, &data)).await
+    }
+
+    /// Flushes the file, writes all buffered data to the storage.
+    pub async fn flush(&self) -> AxResult {
+        let file = self.inner.clone();
+        spawn_blocking(move || file.lock().flush()).await
+    }
+
+    /// Moves the cursor, returns the new position.
+    pub async fn seek(&self, pos: SeekFrom) -> AxResult<u64> {
+        let file = self.inner.clone();
+        spawn_blocking(move || file.lock().seek(pos)).await
+    }
+
+    /// Truncates the file to the given size.
+    pub async fn truncate(&self, size: u64) -> AxResult {
+        let file = self.inner.clone();
+        spawn_blocking(move || file.lock().truncate(size)).await
+    }
+
+    /// Returns the attributes of the file.
+    pub async fn get_attr(&self) -> AxResult<FileAttr> {
+        let file = self.inner.clone();
+        spawn_blocking(move || file.lock().get_attr()).await
+    }
+}
@@ -0,0 +1,45 @@
+//! [ArceOS](https://github.com/arceos-org/arceos) asynchronous runtime.
+//!
+//! It runs `async` code on top of [`axtask`] tasks:
+//!
+//! - [`Executor`]: runs spawned futures on the calling task (single-threaded)
+//!   or on a pool of worker tasks (multi-threaded).
+//! - [`block_on`]: runs a future to completion on the current task.
+//! - [`spawn_blocking`]: runs a blocking function on a bounded pool of tasks.
+//! - mod [`time`]: asynchronous timers built on the kernel timers of [`axtask`].
+//! - mod [`net`]: asynchronous TCP and UDP sockets.
+//! - mod [`fs`]: asynchronous file operations.
+//!
+//! All [`Waker`](core::task::Waker)s wake the blocked tasks through
+//! [`WaitQueue`](axtask::WaitQueue)s.
+//!
+//! # Cargo Features
+//!
+//! - `net`: Enable asynchronous wrappers of [`axnet`] sockets.
+//! - `fs`: Enable asynchronous wrappers of [`axfs`] files.
+
+#![no_std]
+#![feature(doc_auto_cfg)]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+mod blocking;
+mod executor;
+mod waker;
+
+pub mod time;
+
+#[cfg(feature = "fs")]
+pub mod fs;
+#[cfg(feature = "net")]
+pub mod net;
+
+#[cfg(test)]
+mod tests;
+
+pub use self::blocking::{
+    num_blocking_workers, spawn_blocking, BlockingHandle, MAX_BLOCKING_WORKERS,
+};
+pub use self::executor::{block_on, Executor, JoinHandle};
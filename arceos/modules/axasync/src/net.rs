@@ -0,0 +1,268 @@
+//! Asynchronous TCP and UDP sockets.
+//!
+//! A pending operation registers the waker of the future on the socket, and
+//! the network stack wakes it up when the socket becomes ready. As the network
+//! devices are polled rather than interrupt-driven, a single poller task polls
+//! the interfaces every [`POLL_INTERVAL`], only while some operations are
+//! pending.
+
+use alloc::string::String;
+use core::future::poll_fn;
+use core::net::SocketAddr;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::task::{Poll, Waker};
+use core::time::Duration;
+
+use axerrno::{ax_err, AxError, AxResult};
+use axnet::TcpSocket;
+use axtask::WaitQueue;
+
+/// The interval at which the poller task polls the interfaces, while some
+/// socket operations are pending.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The number of pending socket operations.
+static NUM_PENDING: AtomicUsize = AtomicUsize::new(0);
+static POLLER_STARTED: AtomicBool = AtomicBool::new(false);
+/// The poller task waits here when no operation is pending.
+static POLLER_WQ: WaitQueue = WaitQueue::new();
+
+fn net_poller() {
+    loop {
+        POLLER_WQ.wait_idle_until(|| NUM_PENDING.load(Ordering::Acquire) > 0);
+        axnet::poll_interfaces();
+        axtask::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Keeps the poller task running while the operation is pending.
+struct PendingGuard(bool);
+
+impl PendingGuard {
+    fn set_pending(&mut self) {
+        if self.0 {
+            return;
+        }
+        self.0 = true;
+        if !POLLER_STARTED.swap(true, Ordering::AcqRel) {
+            axtask::spawn_raw(
+                net_poller,
+                String::from("async-net-poller"),
+                axconfig::TASK_STACK_SIZE,
+            );
+        }
+        if NUM_PENDING.fetch_add(1, Ordering::AcqRel) == 0 {
+            POLLER_WQ.notify_one(false);
+        }
+    }
+}
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        if self.0 {
+            NUM_PENDING.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+}
+
+/// Runs the nonblocking operation `f` until it does not block.
+///
+/// The waker is registered by `register` before each try, so that a socket
+/// that becomes ready right after the try still wakes up the future.
+async fn wait_io<T>(register: impl Fn(&Waker), mut f: impl FnMut() -> AxResult<T>) -> AxResult<T> {
+    let mut pending = PendingGuard(false);
+    poll_fn(|cx| {
+        axnet::poll_interfaces();
+        register(cx.waker());
+        match f() {
+            Err(AxError::WouldBlock) => {
+                pending.set_pending();
+                Poll::Pending
+            }
+            res => Poll::Ready(res),
+        }
+    })
+    .await
+}
+
+/// An asynchronous TCP stream between a local and a remote socket.
+pub struct TcpStream {
+    socket: TcpSocket,
+}
+
+impl TcpStream {
+    /// Opens a TCP connection to a remote host.
+    pub async fn connect(addr: SocketAddr) -> AxResult<Self> {
+        let socket = TcpSocket::new();
+        socket.set_nonblocking(true);
+        match socket.connect(addr) {
+            Ok(()) => {}
+            Err(AxError::WouldBlock) => {
+                wait_io(
+                    |waker| socket.register_send_waker(waker),
+                    || {
+                        if socket.poll()?.writable {
+                            Ok(())
+                        } else {
+                            Err(AxError::WouldBlock)
+                        }
+                    },
+                )
+                .await?;
+                if socket.peer_addr().is_err() {
+                    return ax_err!(ConnectionRefused, "socket connect() failed");
+                }
+            }
+            Err(e) => return Err(e),
+        }
+        Ok(Self { socket })
+    }
+
+    /// Returns the local address that this stream is bound to.
+    pub fn local_addr(&self) -> AxResult<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Returns the remote address that this stream is connected to.
+    pub fn peer_addr(&self) -> AxResult<SocketAddr> {
+        self.socket.peer_addr()
+    }
+
+    /// Receives data from the stream, returns the number of bytes read.
+    ///
+    /// Returns `Ok(0)` if the connection is closed by the remote.
+    pub async fn read(&self, buf: &mut [u8]) -> AxResult<usize> {
+        wait_io(
+            |waker| self.socket.register_recv_waker(waker),
+            || self.socket.recv(buf),
+        )
+        .await
+    }
+
+    /// Sends data to the stream, returns the number of bytes written.
+    pub async fn write(&self, buf: &[u8]) -> AxResult<usize> {
+        wait_io(
+            |waker| self.socket.register_send_waker(waker),
+            || self.socket.send(buf),
+        )
+        .await
+    }
+
+    /// Sends all data in `buf` to the stream.
+    pub async fn write_all(&self, mut buf: &[u8]) -> AxResult {
+        while !buf.is_empty() {
+            match self.write(buf).await? {
+                0 => return ax_err!(WriteZero, "failed to write whole buffer"),
+                n => buf = &buf[n..],
+            }
+        }
+        Ok(())
+    }
+
+    /// Shuts down the connection.
+    pub fn shutdown(&self) -> AxResult {
+        self.socket.shutdown()
+    }
+}
+
+/// An asynchronous TCP socket server, listening for connections.
+pub struct TcpListener {
+    socket: TcpSocket,
+}
+
+impl TcpListener {
+    /// Creates a new listener bound to the given address.
+    pub fn bind(addr: SocketAddr) -> AxResult<Self> {
+        let socket = TcpSocket::new();
+        socket.set_nonblocking(true);
+        socket.bind(addr)?;
+        socket.listen()?;
+        Ok(Self { socket })
+    }
+
+    /// Returns the local address that this listener is bound to.
+    pub fn local_addr(&self) -> AxResult<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Accepts a new incoming connection, returns the connected stream and
+    /// the remote address.
+    pub async fn accept(&self) -> AxResult<(TcpStream, SocketAddr)> {
+        let socket = wait_io(
+            |waker| self.socket.register_recv_waker(waker),
+            || self.socket.accept(),
+        )
+        .await?;
+        socket.set_nonblocking(true);
+        let addr = socket.peer_addr()?;
+        Ok((TcpStream { socket }, addr))
+    }
+}
+
+/// An asynchronous UDP socket.
+pub struct UdpSocket {
+    socket: axnet::UdpSocket,
+}
+
+impl UdpSocket {
+    /// Creates a new UDP socket bound to the given address.
+    pub fn bind(addr: SocketAddr) -> AxResult<Self> {
+        let socket = axnet::UdpSocket::new();
+        socket.set_nonblocking(true);
+        socket.bind(addr)?;
+        Ok(Self { socket })
+    }
+
+    /// Returns the local address that this socket is bound to.
+    pub fn local_addr(&self) -> AxResult<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Returns the remote address that this socket is connected to.
+    pub fn peer_addr(&self) -> AxResult<SocketAddr> {
+        self.socket.peer_addr()
+    }
+
+    /// Connects this socket to a remote address, so that [`send`](Self::send)
+    /// and [`recv`](Self::recv) can be used.
+    pub fn connect(&self, addr: SocketAddr) -> AxResult {
+        self.socket.connect(addr)
+    }
+
+    /// Sends data to the given address, returns the number of bytes written.
+    pub async fn send_to(&self, buf: &[u8], addr: SocketAddr) -> AxResult<usize> {
+        wait_io(
+            |waker| self.socket.register_send_waker(waker),
+            || self.socket.send_to(buf, addr),
+        )
+        .await
+    }
+
+    /// Receives a single datagram, returns the number of bytes read and the
+    /// address it comes from.
+    pub async fn recv_from(&self, buf: &mut [u8]) -> AxResult<(usize, SocketAddr)> {
+        wait_io(
+            |waker| self.socket.register_recv_waker(waker),
+            || self.socket.recv_from(buf),
+        )
+        .await
+    }
+
+    /// Sends data to the connected remote address.
+    pub async fn send(&self, buf: &[u8]) -> AxResult<usize> {
+        wait_io(
+            |waker| self.socket.register_send_waker(waker),
+            || self.socket.send(buf),
+        )
+        .await
+    }
+
+    /// Receives a single datagram from the connected remote address.
+    pub async fn recv(&self, buf: &mut [u8]) -> AxResult<usize> {
+        wait_io(
+            |waker| self.socket.register_recv_waker(waker),
+            || self.socket.recv(buf),
+        )
+        .await
+    }
+}
@@ -0,0 +1,126 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Once};
+use std::time::Duration;
+
+use axerrno::AxError;
+use axhal::time::wall_time;
+
+use crate::time::{sleep, sleep_until, timeout};
+use crate::{block_on, num_blocking_workers, spawn_blocking, Executor, MAX_BLOCKING_WORKERS};
+
+static INIT: Once = Once::new();
+static SERIAL: Mutex<()> = Mutex::new(());
+
+/// A future that is pending for the first `n` polls, and wakes itself up
+/// each time.
+async fn yield_times(n: usize) {
+    let mut left = n;
+    core::future::poll_fn(|cx| {
+        if left == 0 {
+            core::task::Poll::Ready(())
+        } else {
+            left -= 1;
+            cx.waker().wake_by_ref();
+            core::task::Poll::Pending
+        }
+    })
+    .await
+}
+
+#[test]
+fn test_block_on() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    assert_eq!(block_on(async { 42 }), 42);
+    assert_eq!(
+        block_on(async {
+            yield_times(10).await;
+            7
+        }),
+        7
+    );
+}
+
+#[test]
+fn test_executor() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    const NUM_FUTURES: usize = 32;
+    for num_workers in [0, 1, 4] {
+        let executor = Executor::with_workers(num_workers);
+        let counter = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (0..NUM_FUTURES)
+            .map(|i| {
+                let counter = counter.clone();
+                executor.spawn(async move {
+                    yield_times(i % 5).await;
+                    counter.fetch_add(1, Ordering::Relaxed);
+                    i * 2
+                })
+            })
+            .collect();
+        let sum = executor.block_on(async move {
+            let mut sum = 0;
+            for handle in handles {
+                sum += handle.await;
+            }
+            sum
+        });
+        assert_eq!(sum, (0..NUM_FUTURES).map(|i| i * 2).sum::<usize>());
+        assert_eq!(counter.load(Ordering::Relaxed), NUM_FUTURES);
+    }
+}
+
+#[test]
+fn test_spawn_blocking() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    const NUM_JOBS: usize = MAX_BLOCKING_WORKERS * 4;
+    static RUNNING: AtomicUsize = AtomicUsize::new(0);
+    static MAX_RUNNING: AtomicUsize = AtomicUsize::new(0);
+
+    let handles: Vec<_> = (0..NUM_JOBS)
+        .map(|i| {
+            spawn_blocking(move || {
+                let running = RUNNING.fetch_add(1, Ordering::AcqRel) + 1;
+                MAX_RUNNING.fetch_max(running, Ordering::AcqRel);
+                for _ in 0..10 {
+                    axtask::yield_now();
+                }
+                RUNNING.fetch_sub(1, Ordering::AcqRel);
+                i
+            })
+        })
+        .collect();
+    let results: Vec<_> = handles.into_iter().map(block_on).collect();
+    assert_eq!(results, (0..NUM_JOBS).collect::<Vec<_>>());
+    assert!(MAX_RUNNING.load(Ordering::Acquire) <= MAX_BLOCKING_WORKERS);
+
+    // the workers are reused by later jobs.
+    let num_workers = num_blocking_workers();
+    assert!(num_workers > 0 && num_workers <= MAX_BLOCKING_WORKERS);
+    assert_eq!(block_on(spawn_blocking(|| 1)), 1);
+    assert_eq!(num_blocking_workers(), num_workers);
+}
+
+#[test]
+fn test_sleep_and_timeout() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    // deadlines that have passed complete immediately.
+    block_on(sleep_until(wall_time()));
+    block_on(sleep(Duration::ZERO));
+
+    assert_eq!(
+        block_on(timeout(Duration::from_secs(1), async { 5 })),
+        Ok(5)
+    );
+    assert_eq!(
+        block_on(timeout(Duration::ZERO, core::future::pending::<()>())),
+        Err(AxError::TimedOut)
+    );
+}
@@ -0,0 +1,163 @@
+//! Asynchronous timers.
+//!
+//! They are built on the kernel timers of [`axtask`], wakers are called in the
+//! timer interrupt handler when the deadlines are reached. The timers are
+//! cancelled as soon as the futures complete or are dropped.
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+
+use axerrno::{AxError, AxResult};
+use axhal::time::{wall_time, TimeValue};
+use axtask::timer::TimerHandle;
+
+/// A future that completes at a specific time.
+///
+/// It's created by [`sleep`] or [`sleep_until`].
+pub struct Sleep {
+    deadline: TimeValue,
+    /// The waker registered in the timer list, and the timer to wake it.
+    timer: Option<(Waker, TimerHandle)>,
+}
+
+impl Sleep {
+    /// Returns the time at which the future will complete.
+    pub fn deadline(&self) -> TimeValue {
+        self.deadline
+    }
+
+    /// Resets the deadline of the future.
+    pub fn reset(&mut self, deadline: TimeValue) {
+        self.deadline = deadline;
+        self.cancel_timer();
+    }
+
+    fn cancel_timer(&mut self) {
+        if let Some((_, timer)) = self.timer.take() {
+            timer.cancel();
+        }
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        self.cancel_timer();
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if wall_time() >= self.deadline {
+            self.cancel_timer();
+            return Poll::Ready(());
+        }
+        // set the timer again only if the waker changes.
+        if !self
+            .timer
+            .as_ref()
+            .is_some_and(|(w, _)| w.will_wake(cx.waker()))
+        {
+            self.cancel_timer();
+            let waker = cx.waker().clone();
+            let timer = axtask::timer::at(self.deadline, {
+                let waker = waker.clone();
+                move || waker.wake()
+            });
+            self.timer = Some((waker, timer));
+        }
+        Poll::Pending
+    }
+}
+
+/// Waits until `dur` has elapsed.
+pub fn sleep(dur: Duration) -> Sleep {
+    sleep_until(wall_time() + dur)
+}
+
+/// Waits until the wall time reaches `deadline`.
+pub fn sleep_until(deadline: TimeValue) -> Sleep {
+    Sleep {
+        deadline,
+        timer: None,
+    }
+}
+
+/// A future returned by [`timeout`].
+pub struct Timeout<F> {
+    future: F,
+    sleep: Sleep,
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = AxResult<F::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `future` is never moved, and `sleep` is `Unpin`.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        if let Poll::Ready(output) = future.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        match Pin::new(&mut this.sleep).poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(AxError::TimedOut)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Requires a future to complete within `dur`.
+///
+/// Returns [`Err(TimedOut)`](AxError::TimedOut) if the future does not
+/// complete in time, and the future is dropped.
+pub fn timeout<F: Future>(dur: Duration, future: F) -> Timeout<F> {
+    Timeout {
+        future,
+        sleep: sleep(dur),
+    }
+}
+
+/// A stream of periodic ticks, created by [`interval`].
+pub struct Interval {
+    period: Duration,
+    sleep: Sleep,
+}
+
+impl Interval {
+    /// Waits until the next tick.
+    ///
+    /// The first tick completes immediately. If some ticks are missed, the
+    /// next tick is scheduled one period after the current time.
+    pub async fn tick(&mut self) -> TimeValue {
+        (&mut self.sleep).await;
+        let deadline = self.sleep.deadline();
+        let mut next = deadline + self.period;
+        let now = wall_time();
+        if next <= now {
+            next = now + self.period;
+        }
+        self.sleep.reset(next);
+        deadline
+    }
+
+    /// Returns the period of the interval.
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+}
+
+/// Creates an [`Interval`] that ticks every `period`, starting from now.
+///
+/// # Panics
+///
+/// Panics if `period` is zero.
+pub fn interval(period: Duration) -> Interval {
+    assert!(!period.is_zero(), "`interval` period must be non-zero");
+    Interval {
+        period,
+        sleep: sleep_until(wall_time()),
+    }
+}
@@ -0,0 +1,49 @@
+//! Wakers that wake up blocked tasks through wait queues.
+
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axtask::WaitQueue;
+
+/// A notification flag that a task can block on.
+///
+/// It's used as the [`Waker`](core::task::Waker) of futures that are polled
+/// directly by a task, e.g., in [`block_on`](crate::block_on).
+pub(crate) struct Signal {
+    notified: AtomicBool,
+    wq: WaitQueue,
+}
+
+impl Signal {
+    /// Creates a new signal, which is notified initially so that the first
+    /// [`wait`](Self::wait) returns immediately.
+    pub fn new() -> Self {
+        Self {
+            notified: AtomicBool::new(true),
+            wq: WaitQueue::new(),
+        }
+    }
+
+    /// Blocks the current task until the signal is notified, and resets it.
+    pub fn wait(&self) {
+        self.wq
+            .wait_idle_until(|| self.notified.swap(false, Ordering::AcqRel));
+    }
+
+    /// Notifies the signal, and wakes up the waiting task.
+    pub fn notify(&self) {
+        self.notified.store(true, Ordering::Release);
+        self.wq.notify_one(true);
+    }
+}
+
+impl Wake for Signal {
+    fn wake(self: Arc<Self>) {
+        self.notify();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.notify();
+    }
+}
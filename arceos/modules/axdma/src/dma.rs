@@ -3,13 +3,14 @@ use core::{alloc::Layout, ptr::NonNull};
 use allocator::{AllocError, AllocResult, BaseAllocator, ByteAllocator};
 use axalloc::{global_allocator, DefaultByteAllocator};
 use axhal::{mem::virt_to_phys, paging::MappingFlags};
-use kspin::SpinNoIrq;
+use axmm::TlbSpinLock;
 use log::{debug, error};
 use memory_addr::{va, VirtAddr, PAGE_SIZE_4K};
 
 use crate::{phys_to_bus, BusAddr, DMAInfo};
 
-pub(crate) static ALLOCATOR: SpinNoIrq<DmaAllocator> = SpinNoIrq::new(DmaAllocator::new());
+// held while the kernel page table is updated, which shoots down the TLBs.
+pub(crate) static ALLOCATOR: TlbSpinLock<DmaAllocator> = TlbSpinLock::new(DmaAllocator::new());
 
 pub(crate) struct DmaAllocator {
     alloc: DefaultByteAllocator,
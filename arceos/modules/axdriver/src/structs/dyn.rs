@@ -59,6 +59,16 @@ impl<D> AxDeviceContainer<D> {
         }
     }
 
+    /// Takes the device at `index` out of the container (will remove it from
+    /// the container).
+    pub fn take_nth(&mut self, index: usize) -> Option<D> {
+        if index < self.len() {
+            Some(self.0.remove(index))
+        } else {
+            None
+        }
+    }
+
     /// Constructs the container from one device.
     pub fn from_one(dev: D) -> Self {
         Self(vec![dev])
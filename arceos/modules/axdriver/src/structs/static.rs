@@ -51,6 +51,16 @@ impl<D> AxDeviceContainer<D> {
         self.0.take()
     }
 
+    /// Takes the device at `index` out of the container (will remove it from
+    /// the container).
+    pub fn take_nth(&mut self, index: usize) -> Option<D> {
+        if index == 0 {
+            self.0.take()
+        } else {
+            None
+        }
+    }
+
     /// Constructs the container from one device.
     pub const fn from_one(dev: D) -> Self {
         Self(Some(dev))
@@ -0,0 +1,373 @@
+//! The proc filesystem.
+//!
+//! Static entries are stored in a [`RamFileSystem`], and a `<tid>` directory
+//! with `stat` and `status` entries is generated on access for every live task
+//! if the `multitask` feature is enabled, where `self` refers to the current
+//! task. `<tid>/maps` and the memory usage in `<tid>/status` are provided by
+//! the monolithic kernel through [`ProcMemory`], which are empty for kernel
+//! tasks. If the `sched_trace` feature is enabled, `sched_trace` contains the
+//! recorded scheduler events in the Chrome trace event format.
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+use axfs_ramfs::RamFileSystem;
+use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps, VfsResult};
+
+#[cfg(feature = "multitask")]
+pub use self::task::{set_proc_memory, ProcMemory};
+
+/// The proc filesystem mounted at `/proc`.
+pub struct ProcFileSystem {
+    inner: RamFileSystem,
+    root: Arc<ProcRootDir>,
+}
+
+impl ProcFileSystem {
+    /// Creates a new proc filesystem with no static entries.
+    pub fn new() -> Self {
+        let inner = RamFileSystem::new();
+        let root = Arc::new(ProcRootDir {
+            inner: inner.root_dir(),
+        });
+        Self { inner, root }
+    }
+
+    /// Returns the root directory of the static entries.
+    pub fn static_root(&self) -> VfsNodeRef {
+        self.inner.root_dir()
+    }
+}
+
+impl Default for ProcFileSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VfsOps for ProcFileSystem {
+    fn mount(&self, path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        self.inner.mount(path, mount_point)
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+/// The root directory, which combines the static entries with the task
+/// directories.
+struct ProcRootDir {
+    inner: VfsNodeRef,
+}
+
+impl ProcRootDir {
+    /// Returns the IDs of all live tasks.
+    fn task_ids() -> Vec<u64> {
+        #[cfg(feature = "multitask")]
+        return axtask::tasks().map(|t| t.id().as_u64()).collect();
+        #[cfg(not(feature = "multitask"))]
+        Vec::new()
+    }
+}
+
+impl VfsNodeOps for ProcRootDir {
+    axfs_vfs::impl_vfs_dir_default! {}
+
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        self.inner.get_attr()
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        self.inner.parent()
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let path = path.trim_matches('/');
+        if path.is_empty() || path == "." {
+            return Ok(self.clone());
+        }
+        if let Some(rest) = path.strip_prefix("./") {
+            return self.lookup(rest);
+        }
+
+        #[cfg(feature = "sched_trace")]
+        if path == "sched_trace" {
+            return Ok(Arc::new(SchedTraceFile::new()));
+        }
+        #[cfg(feature = "multitask")]
+        {
+            let (name, rest) = path.split_once('/').unwrap_or((path, ""));
+            let tid = if name == "self" {
+                Some(axtask::current().id().as_u64())
+            } else {
+                name.parse()
+                    .ok()
+                    .and_then(axtask::find_task)
+                    .map(|t| t.id().as_u64())
+            };
+            if let Some(tid) = tid {
+                let dir: VfsNodeRef = Arc::new(task::TaskDir::new(tid));
+                return dir.lookup(rest);
+            }
+        }
+        self.inner.clone().lookup(path)
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        self.inner.create(path, ty)
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        self.inner.remove(path)
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        // collect all static entries, followed by the task directories.
+        let mut entries: Vec<(String, VfsNodeType)> = Vec::new();
+        const EMPTY: VfsDirEntry = VfsDirEntry::default();
+        let mut buf = [EMPTY; 16];
+        loop {
+            let n = self.inner.read_dir(entries.len(), &mut buf)?;
+            for entry in &buf[..n] {
+                let name = String::from_utf8_lossy(entry.name_as_bytes()).into_owned();
+                entries.push((name, entry.entry_type()));
+            }
+            if n < buf.len() {
+                break;
+            }
+        }
+        #[cfg(feature = "sched_trace")]
+        entries.push((String::from("sched_trace"), VfsNodeType::File));
+        for tid in Self::task_ids() {
+            entries.push((alloc::format!("{}", tid), VfsNodeType::Dir));
+        }
+
+        let mut count = 0;
+        for ((name, ty), out_entry) in entries.iter().skip(start_idx).zip(dirents.iter_mut()) {
+            *out_entry = VfsDirEntry::new(name, *ty);
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        self.inner.rename(src_path, dst_path)
+    }
+}
+
+/// The `/proc/sched_trace` file, with the events recorded when it's opened.
+#[cfg(feature = "sched_trace")]
+struct SchedTraceFile {
+    content: String,
+}
+
+#[cfg(feature = "sched_trace")]
+impl SchedTraceFile {
+    fn new() -> Self {
+        let mut content = String::new();
+        axtask::trace::dump(&mut content).ok();
+        Self { content }
+    }
+}
+
+#[cfg(feature = "sched_trace")]
+impl VfsNodeOps for SchedTraceFile {
+    axfs_vfs::impl_vfs_non_dir_default! {}
+
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            axfs_vfs::VfsNodePerm::from_bits_truncate(0o444),
+            VfsNodeType::File,
+            self.content.len() as u64,
+            0,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let start = self.content.len().min(offset as usize);
+        let len = buf.len().min(self.content.len() - start);
+        buf[..len].copy_from_slice(&self.content.as_bytes()[start..start + len]);
+        Ok(len)
+    }
+}
+
+#[cfg(feature = "multitask")]
+mod task {
+    use alloc::{string::String, sync::Arc};
+    use axfs_vfs::{VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm};
+    use axfs_vfs::{VfsNodeRef, VfsNodeType, VfsResult};
+    use axtask::AxTaskRef;
+    use core::fmt::{self, Write};
+    use lazyinit::LazyInit;
+
+    /// The memory of the user processes shown in `/proc/<tid>/maps` and
+    /// `/proc/<tid>/status`, provided by the monolithic kernel that manages
+    /// their address spaces.
+    pub trait ProcMemory: Send + Sync {
+        /// Returns the virtual memory size (VSZ) and the resident set size
+        /// (RSS) in bytes of the process of `task`, or [`None`] if the task
+        /// has no user address space.
+        fn usage(&self, task: &AxTaskRef) -> Option<(usize, usize)>;
+
+        /// Writes the memory areas of the process of `task` in the format of
+        /// Linux `/proc/<pid>/maps`, or nothing if the task has no user
+        /// address space.
+        fn write_maps(&self, task: &AxTaskRef, out: &mut dyn Write) -> fmt::Result;
+    }
+
+    static PROC_MEMORY: LazyInit<&'static dyn ProcMemory> = LazyInit::new();
+
+    /// Sets the provider of the memory of the user processes in the proc
+    /// filesystem. It can be set only once.
+    pub fn set_proc_memory(memory: &'static dyn ProcMemory) {
+        PROC_MEMORY.init_once(memory);
+    }
+
+    /// The `/proc/<tid>` directory.
+    pub(super) struct TaskDir {
+        tid: u64,
+    }
+
+    /// A file in the `/proc/<tid>` directory, whose content is generated on
+    /// every access.
+    struct TaskFile {
+        tid: u64,
+        content: fn(&AxTaskRef) -> String,
+    }
+
+    impl TaskDir {
+        pub const fn new(tid: u64) -> Self {
+            Self { tid }
+        }
+
+        /// The entries in the directory and their generators.
+        const FILES: [(&'static str, fn(&AxTaskRef) -> String); 3] =
+            [("maps", maps), ("stat", stat), ("status", status)];
+    }
+
+    impl VfsNodeOps for TaskDir {
+        axfs_vfs::impl_vfs_dir_default! {}
+
+        fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+            Ok(VfsNodeAttr::new(
+                VfsNodePerm::from_bits_truncate(0o555),
+                VfsNodeType::Dir,
+                0,
+                0,
+            ))
+        }
+
+        fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+            match path.trim_matches('/') {
+                "" | "." => Ok(self.clone()),
+                name => match Self::FILES.iter().find(|(n, _)| *n == name) {
+                    Some(&(_, content)) => Ok(Arc::new(TaskFile {
+                        tid: self.tid,
+                        content,
+                    })),
+                    None => Err(VfsError::NotFound),
+                },
+            }
+        }
+
+        fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+            let entries = [(".", VfsNodeType::Dir), ("..", VfsNodeType::Dir)]
+                .into_iter()
+                .chain(
+                    Self::FILES
+                        .iter()
+                        .map(|&(name, _)| (name, VfsNodeType::File)),
+                );
+            let mut count = 0;
+            for ((name, ty), out_entry) in entries.skip(start_idx).zip(dirents.iter_mut()) {
+                *out_entry = VfsDirEntry::new(name, ty);
+                count += 1;
+            }
+            Ok(count)
+        }
+    }
+
+    /// Generates the content in the format of Linux `/proc/<pid>/stat`, fields
+    /// that ArceOS doesn't track are filled with zeros.
+    fn stat(task: &AxTaskRef) -> String {
+        let usage = task.cpu_usage();
+        let ticks = |d: core::time::Duration| {
+            d.as_nanos() * axhal::time::USER_HZ as u128 / axhal::time::NANOS_PER_SEC as u128
+        };
+        let nice = task.priority();
+        alloc::format!(
+            "{} ({}) {} 0 0 0 0 -1 0 0 0 0 0 {} {} 0 0 {} {} 1 {}{}\n",
+            task.id().as_u64(),
+            task.name(),
+            task.state().as_char(),
+            ticks(usage.utime),
+            ticks(usage.stime),
+            nice + 20,
+            nice,
+            // fields from `itrealvalue` to `exit_signal`
+            "0 ".repeat(18),
+            task.cpu_id(),
+        )
+    }
+
+    /// Generates the content in the format of Linux `/proc/<pid>/status`, with
+    /// the fields that ArceOS tracks only.
+    fn status(task: &AxTaskRef) -> String {
+        let state = match task.state().as_char() {
+            'R' => "R (running)",
+            'S' => "S (sleeping)",
+            _ => "Z (zombie)",
+        };
+        let tid = task.id().as_u64();
+        let mut s = alloc::format!(
+            "Name:\t{}\nState:\t{}\nTgid:\t{}\nPid:\t{}\nPPid:\t0\n",
+            task.name(),
+            state,
+            tid,
+            tid,
+        );
+        if let Some((vsz, rss)) = PROC_MEMORY.get().and_then(|m| m.usage(task)) {
+            writeln!(s, "VmSize:\t{:8} kB", vsz / 1024).ok();
+            writeln!(s, "VmRSS:\t{:8} kB", rss / 1024).ok();
+        }
+        writeln!(s, "Threads:\t1").ok();
+        s
+    }
+
+    /// Generates the content in the format of Linux `/proc/<pid>/maps`, which
+    /// is empty if the task has no user address space.
+    fn maps(task: &AxTaskRef) -> String {
+        let mut s = String::new();
+        if let Some(memory) = PROC_MEMORY.get() {
+            memory.write_maps(task, &mut s).ok();
+        }
+        s
+    }
+
+    impl TaskFile {
+        fn content(&self) -> VfsResult<String> {
+            let task = axtask::find_task(self.tid).ok_or(VfsError::NotFound)?;
+            Ok((self.content)(&task))
+        }
+    }
+
+    impl VfsNodeOps for TaskFile {
+        axfs_vfs::impl_vfs_non_dir_default! {}
+
+        fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+            Ok(VfsNodeAttr::new(
+                VfsNodePerm::from_bits_truncate(0o444),
+                VfsNodeType::File,
+                self.content()?.len() as u64,
+                0,
+            ))
+        }
+
+        fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+            let content = self.content()?;
+            let start = content.len().min(offset as usize);
+            let len = buf.len().min(content.len() - start);
+            buf[..len].copy_from_slice(&content.as_bytes()[start..start + len]);
+            Ok(len)
+        }
+    }
+}
@@ -10,6 +10,13 @@
 //!    **enabled** by default.
 //! - `ramfs`: Mount [`axfs_ramfs::RamFileSystem`] on `/tmp`. This feature is
 //!    **enabled** by default.
+//! - `procfs`: Mount the proc filesystem on `/proc`. This feature is **enabled**
+//!    by default.
+//! - `multitask`: Provide `/proc/<tid>/stat`, `/proc/<tid>/status` and
+//!    `/proc/<tid>/maps` for every live task in the proc filesystem, where the
+//!    memory of the user processes is provided by [`set_proc_memory`].
+//! - `sched_trace`: Provide `/proc/sched_trace` with the recorded scheduler
+//!    events in the Chrome trace event format.
 //! - `myfs`: Allow users to define their custom filesystems to override the
 //!    default. In this case, [`MyFileSystemIf`] is required to be implemented
 //!    to create and initialize other filesystems. This feature is **disabled** by
@@ -34,6 +41,9 @@ mod root;
 pub mod api;
 pub mod fops;
 
+#[cfg(all(feature = "procfs", feature = "multitask"))]
+pub use self::fs::procfs::{set_proc_memory, ProcMemory};
+
 use axdriver::{prelude::*, AxDeviceContainer};
 
 /// Initializes filesystems by block devices.
@@ -22,9 +22,9 @@ pub(crate) fn ramfs() -> Arc<fs::ramfs::RamFileSystem> {
 }
 
 #[cfg(feature = "procfs")]
-pub(crate) fn procfs() -> VfsResult<Arc<fs::ramfs::RamFileSystem>> {
-    let procfs = fs::ramfs::RamFileSystem::new();
-    let proc_root = procfs.root_dir();
+pub(crate) fn procfs() -> VfsResult<Arc<fs::procfs::ProcFileSystem>> {
+    let procfs = fs::procfs::ProcFileSystem::new();
+    let proc_root = procfs.static_root();
 
     // Create /proc/sys/net/core/somaxconn
     proc_root.create("sys", VfsNodeType::Dir)?;
@@ -5,7 +5,7 @@
 use alloc::{string::String, sync::Arc, vec::Vec};
 use axerrno::{ax_err, AxError, AxResult};
 use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps, VfsResult};
-use axsync::Mutex;
+use axsync::{Mutex, Rcu};
 use lazyinit::LazyInit;
 
 use crate::{api::FileType, fs, mounts};
@@ -20,7 +20,10 @@ struct MountPoint {
 
 struct RootDirectory {
     main_fs: Arc<dyn VfsOps>,
-    mounts: Vec<MountPoint>,
+    /// Read on every path lookup but rarely updated, so it's protected by RCU.
+    mounts: Rcu<Vec<Arc<MountPoint>>>,
+    /// Serializes the updates of `mounts`.
+    mount_lock: Mutex<()>,
 }
 
 static ROOT_DIR: LazyInit<Arc<RootDirectory>> = LazyInit::new();
@@ -38,36 +41,44 @@ impl Drop for MountPoint {
 }
 
 impl RootDirectory {
-    pub const fn new(main_fs: Arc<dyn VfsOps>) -> Self {
+    pub fn new(main_fs: Arc<dyn VfsOps>) -> Self {
         Self {
             main_fs,
-            mounts: Vec::new(),
+            mounts: Rcu::new(Vec::new()),
+            mount_lock: Mutex::new(()),
         }
     }
 
-    pub fn mount(&mut self, path: &'static str, fs: Arc<dyn VfsOps>) -> AxResult {
+    pub fn mount(&self, path: &'static str, fs: Arc<dyn VfsOps>) -> AxResult {
         if path == "/" {
             return ax_err!(InvalidInput, "cannot mount root filesystem");
         }
         if !path.starts_with('/') {
             return ax_err!(InvalidInput, "mount path must start with '/'");
         }
-        if self.mounts.iter().any(|mp| mp.path == path) {
+        let _lock = self.mount_lock.lock();
+        if self.contains(path) {
             return ax_err!(InvalidInput, "mount point already exists");
         }
         // create the mount point in the main filesystem if it does not exist
         self.main_fs.root_dir().create(path, FileType::Dir)?;
         fs.mount(path, self.main_fs.root_dir().lookup(path)?)?;
-        self.mounts.push(MountPoint::new(path, fs));
+        let mut mounts = self.mounts.read().clone();
+        mounts.push(Arc::new(MountPoint::new(path, fs)));
+        self.mounts.replace(mounts);
         Ok(())
     }
 
-    pub fn _umount(&mut self, path: &str) {
-        self.mounts.retain(|mp| mp.path != path);
+    pub fn _umount(&self, path: &str) {
+        let _lock = self.mount_lock.lock();
+        let mut mounts = self.mounts.read().clone();
+        mounts.retain(|mp| mp.path != path);
+        // the filesystem is unmounted when the last lookup using it finishes.
+        self.mounts.replace(mounts);
     }
 
     pub fn contains(&self, path: &str) -> bool {
-        self.mounts.iter().any(|mp| mp.path == path)
+        self.mounts.read().iter().any(|mp| mp.path == path)
     }
 
     fn lookup_mounted_fs<F, T>(&self, path: &str, f: F) -> AxResult<T>
@@ -80,24 +91,25 @@ impl RootDirectory {
             return self.lookup_mounted_fs(rest, f);
         }
 
-        let mut idx = 0;
+        let mut fs = &self.main_fs; // not matched any mount point
         let mut max_len = 0;
 
-        // Find the filesystem that has the longest mounted path match
+        // Find the filesystem that has the longest mounted path match.
+        // Operations on the filesystem may block, so only the lookup is done
+        // in the RCU read-side critical section.
         // TODO: more efficient, e.g. trie
-        for (i, mp) in self.mounts.iter().enumerate() {
+        let mounts = self.mounts.read();
+        for mp in mounts.iter() {
             // skip the first '/'
             if path.starts_with(&mp.path[1..]) && mp.path.len() - 1 > max_len {
                 max_len = mp.path.len() - 1;
-                idx = i;
+                fs = &mp.fs;
             }
         }
+        let fs = fs.clone();
+        drop(mounts);
 
-        if max_len == 0 {
-            f(self.main_fs.clone(), path) // not matched any mount point
-        } else {
-            f(self.mounts[idx].fs.clone(), &path[max_len..]) // matched at `idx`
-        }
+        f(fs, &path[max_len..])
     }
 }
 
@@ -155,7 +167,7 @@ pub(crate) fn init_rootfs(disk: crate::dev::Disk) {
         }
     }
 
-    let mut root_dir = RootDirectory::new(main_fs);
+    let root_dir = RootDirectory::new(main_fs);
 
     #[cfg(feature = "devfs")]
     root_dir
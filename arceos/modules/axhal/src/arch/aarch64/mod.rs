@@ -35,6 +35,20 @@ pub fn wait_for_irqs() {
     aarch64_cpu::asm::wfi();
 }
 
+/// Enables interrupts and waits for one atomically, so that an interrupt
+/// arriving after the caller has disabled interrupts (e.g., to check whether
+/// there is work to do) still wakes up the CPU.
+///
+/// It must be called with interrupts disabled. The interrupt has been handled
+/// when it returns, with interrupts enabled.
+#[inline]
+pub fn enable_irqs_and_wait() {
+    // `wfi` returns once an interrupt is pending even if it's masked, which
+    // is then taken after unmasking interrupts.
+    aarch64_cpu::asm::wfi();
+    enable_irqs();
+}
+
 /// Halt the current CPU.
 #[inline]
 pub fn halt() {
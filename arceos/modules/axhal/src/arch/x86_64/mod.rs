@@ -13,7 +13,7 @@ use x86_64::instructions::interrupts;
 
 pub use self::context::{ExtendedState, FxsaveArea, TaskContext, TrapFrame};
 pub use self::gdt::GdtStruct;
-pub use self::idt::IdtStruct;
+pub use self::idt::{IdtStruct, DOUBLE_FAULT_IST_INDEX};
 pub use x86_64::structures::tss::TaskStateSegment;
 
 /// Allows the current CPU to respond to interrupts.
@@ -48,6 +48,22 @@ pub fn wait_for_irqs() {
     }
 }
 
+/// Enables interrupts and waits for one atomically, so that an interrupt
+/// arriving after the caller has disabled interrupts (e.g., to check whether
+/// there is work to do) still wakes up the CPU.
+///
+/// It must be called with interrupts disabled. The interrupt has been handled
+/// when it returns, with interrupts enabled.
+#[inline]
+pub fn enable_irqs_and_wait() {
+    if cfg!(target_os = "none") {
+        // no interrupt is taken between `sti` and the next instruction.
+        unsafe { asm!("sti; hlt") }
+    } else {
+        core::hint::spin_loop()
+    }
+}
+
 /// Halt the current CPU.
 #[inline]
 pub fn halt() {
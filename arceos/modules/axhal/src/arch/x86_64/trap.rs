@@ -26,10 +26,36 @@ fn handle_page_fault(tf: &TrapFrame) {
     }
 }
 
+/// Size of the frame that the CPU pushes onto the stack on a page fault (SS,
+/// RSP, RFLAGS, CS, RIP and the error code).
+const PAGE_FAULT_FRAME_SIZE: usize = 6 * 8;
+
+fn handle_double_fault(tf: &TrapFrame) -> ! {
+    // A kernel stack overflow causes a page fault on the guard page, which
+    // cannot be delivered on the same stack and results in a double fault.
+    // `CR2` may not describe the failed delivery, so check whether the stack
+    // pointer or the page fault frame below it hits a guard page instead.
+    let sp = tf.rsp as usize;
+    if !tf.is_user() {
+        let guard_owner =
+            |vaddr: usize| crate::trap::STACK_GUARD.iter().find_map(|f| f(va!(vaddr)));
+        if let Some((id, name)) =
+            guard_owner(sp).or_else(|| guard_owner(sp.wrapping_sub(PAGE_FAULT_FRAME_SIZE)))
+        {
+            panic!(
+                "stack overflow in Task({}, {:?}) @ {:#x}, rsp={:#x}:\n{:#x?}",
+                id, name, tf.rip, sp, tf
+            );
+        }
+    }
+    panic!("#DF @ {:#x}, rsp={:#x}:\n{:#x?}", tf.rip, sp, tf);
+}
+
 #[no_mangle]
 fn x86_trap_handler(tf: &TrapFrame) {
     match tf.vector as u8 {
         PAGE_FAULT_VECTOR => handle_page_fault(tf),
+        DOUBLE_FAULT_VECTOR => handle_double_fault(tf),
         BREAKPOINT_VECTOR => debug!("#BP @ {:#x} ", tf.rip),
         GENERAL_PROTECTION_FAULT_VECTOR => {
             panic!(
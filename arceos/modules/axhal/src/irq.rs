@@ -3,7 +3,7 @@
 use handler_table::HandlerTable;
 
 use crate::platform::irq::{dispatch_irq, MAX_IRQ_COUNT};
-use crate::trap::{register_trap_handler, IRQ};
+use crate::trap::{register_trap_handler, IRQ, IRQ_ENTER, IRQ_EXIT};
 
 pub use crate::platform::irq::{register_handler, set_enable};
 
@@ -38,7 +38,9 @@ pub(crate) fn register_handler_common(irq_num: usize, handler: IrqHandler) -> bo
 #[register_trap_handler(IRQ)]
 fn handler_irq(irq_num: usize) -> bool {
     let guard = kernel_guard::NoPreempt::new();
+    IRQ_ENTER.iter().for_each(|hook| hook(irq_num));
     dispatch_irq(irq_num);
+    IRQ_EXIT.iter().for_each(|hook| hook(irq_num));
     drop(guard); // rescheduling may occur when preemption is re-enabled.
     true
 }
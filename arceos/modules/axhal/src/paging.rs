@@ -6,6 +6,8 @@ use page_table_multiarch::PagingHandler;
 
 use crate::mem::{phys_to_virt, virt_to_phys, MemRegionFlags, PhysAddr, VirtAddr, PAGE_SIZE_4K};
 
+#[doc(no_inline)]
+pub use page_table_entry::GenericPTE;
 #[doc(no_inline)]
 pub use page_table_multiarch::{MappingFlags, PageSize, PagingError, PagingResult};
 
@@ -57,12 +59,33 @@ cfg_if::cfg_if! {
     if #[cfg(target_arch = "x86_64")] {
         /// The architecture-specific page table.
         pub type PageTable = page_table_multiarch::x86_64::X64PageTable<PagingHandlerImpl>;
+        /// The architecture-specific page table entry.
+        pub type PageTableEntry = page_table_entry::x86_64::X64PTE;
+        /// The number of levels of the architecture-specific page table.
+        pub const PAGE_TABLE_LEVELS: usize = 4;
+        /// The accessed bit of [`PageTableEntry`], which is set by the CPU when
+        /// the page is accessed.
+        pub const PTE_ACCESSED: u64 = 1 << 5;
     } else if #[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))] {
         /// The architecture-specific page table.
         pub type PageTable = page_table_multiarch::riscv::Sv39PageTable<PagingHandlerImpl>;
+        /// The architecture-specific page table entry.
+        pub type PageTableEntry = page_table_entry::riscv::Rv64PTE;
+        /// The number of levels of the architecture-specific page table.
+        pub const PAGE_TABLE_LEVELS: usize = 3;
+        /// The accessed bit of [`PageTableEntry`]. Depending on the CPU, an access
+        /// to the page sets it, or causes a page fault when it's clear.
+        pub const PTE_ACCESSED: u64 = 1 << 6;
     } else if #[cfg(target_arch = "aarch64")]{
         /// The architecture-specific page table.
         pub type PageTable = page_table_multiarch::aarch64::A64PageTable<PagingHandlerImpl>;
+        /// The architecture-specific page table entry.
+        pub type PageTableEntry = page_table_entry::aarch64::A64PTE;
+        /// The number of levels of the architecture-specific page table.
+        pub const PAGE_TABLE_LEVELS: usize = 4;
+        /// The access flag of [`PageTableEntry`]. An access to the page causes
+        /// an access flag fault when it's clear, unless the CPU updates it.
+        pub const PTE_ACCESSED: u64 = 1 << 10;
     }
 }
 
@@ -1,7 +1,11 @@
 //! Description tables (per-CPU GDT, per-CPU ISS, IDT)
 
-use crate::arch::{GdtStruct, IdtStruct, TaskStateSegment};
+use crate::arch::{GdtStruct, IdtStruct, TaskStateSegment, DOUBLE_FAULT_IST_INDEX};
 use lazyinit::LazyInit;
+use x86_64::VirtAddr;
+
+/// Size of the per-CPU stack for handling double faults.
+const DOUBLE_FAULT_STACK_SIZE: usize = 0x4000;
 
 static IDT: LazyInit<IdtStruct> = LazyInit::new();
 
@@ -11,12 +15,19 @@ static TSS: LazyInit<TaskStateSegment> = LazyInit::new();
 #[percpu::def_percpu]
 static GDT: LazyInit<GdtStruct> = LazyInit::new();
 
+#[percpu::def_percpu]
+static DOUBLE_FAULT_STACK: [u8; DOUBLE_FAULT_STACK_SIZE] = [0; DOUBLE_FAULT_STACK_SIZE];
+
 fn init_percpu() {
     unsafe {
         IDT.load();
         let tss = TSS.current_ref_mut_raw();
         let gdt = GDT.current_ref_mut_raw();
-        tss.init_once(TaskStateSegment::new());
+        let mut new_tss = TaskStateSegment::new();
+        let df_stack_top = DOUBLE_FAULT_STACK.current_ptr() as usize + DOUBLE_FAULT_STACK_SIZE;
+        new_tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] =
+            VirtAddr::new(df_stack_top as u64);
+        tss.init_once(new_tss);
         gdt.init_once(GdtStruct::new(tss));
         gdt.load();
         gdt.load_tss();
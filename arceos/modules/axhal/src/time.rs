@@ -24,6 +24,9 @@ pub const NANOS_PER_SEC: u64 = 1_000_000_000;
 pub const NANOS_PER_MILLIS: u64 = 1_000_000;
 /// Number of nanoseconds in a microsecond.
 pub const NANOS_PER_MICROS: u64 = 1_000;
+/// Clock ticks per second in which CPU times are reported to user space
+/// (`sysconf(_SC_CLK_TCK)`), independent of the timer frequency.
+pub const USER_HZ: u64 = 100;
 
 /// Returns nanoseconds elapsed since system boot.
 pub fn monotonic_time_nanos() -> u64 {
@@ -13,10 +13,24 @@ pub use linkme::distributed_slice as register_trap_handler;
 #[def_trap_handler]
 pub static IRQ: [fn(usize) -> bool];
 
+/// A slice of functions called before an IRQ is handled, with the IRQ number.
+#[def_trap_handler]
+pub static IRQ_ENTER: [fn(usize)];
+
+/// A slice of functions called after an IRQ is handled, with the IRQ number.
+#[def_trap_handler]
+pub static IRQ_EXIT: [fn(usize)];
+
 /// A slice of page fault handler functions.
 #[def_trap_handler]
 pub static PAGE_FAULT: [fn(VirtAddr, MappingFlags, bool) -> bool];
 
+/// A slice of functions that return the ID and name of the task whose kernel
+/// stack has the given address in its guard page, used to report kernel stack
+/// overflows.
+#[def_trap_handler]
+pub static STACK_GUARD: [fn(VirtAddr) -> Option<(u64, &'static str)>];
+
 /// A slice of syscall handler functions.
 #[cfg(feature = "uspace")]
 #[def_trap_handler]
@@ -1,5 +1,16 @@
 use core::fmt;
 
+use crate::backend::{resident_frame, unshare_page, AreaPageTable, Backend, MmapFile};
+use crate::frame::{frame_ref_count, share_frame};
+use crate::huge::{map_region_huge, split_huge_pages_at, split_to_4k};
+use crate::mapping_err_to_ax_err;
+use crate::paging_err_to_ax_err;
+#[cfg(feature = "swap")]
+use crate::swap::{self, share_slot, swap_slot};
+use crate::tlb::{flush_local, shootdown};
+use crate::{AreaInfo, LoadedFilePages, PendingWriteBack, SharedMemory};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
 use axerrno::{ax_err, AxError, AxResult};
 use axhal::{
     mem::phys_to_virt,
@@ -9,16 +20,42 @@ use memory_addr::{
     is_aligned_4k, pa, MemoryAddr, PageIter4K, PhysAddr, VirtAddr, VirtAddrRange, PAGE_SIZE_4K,
 };
 use memory_set::{MemoryArea, MemorySet};
-use crate::backend::Backend;
-use crate::paging_err_to_ax_err;
-use crate::mapping_err_to_ax_err;
-use alloc::vec::Vec;
+
+/// The minimum gap between a growing stack and the mapping below it, which is
+/// never mapped by the stack.
+pub const STACK_GUARD_GAP: usize = 256 * PAGE_SIZE_4K;
+
+/// A stack that grows down on page faults, see [`AddrSpace::map_stack`].
+#[derive(Clone, Copy)]
+struct GrowDownStack {
+    /// The end address (exclusive) of the stack.
+    top: VirtAddr,
+    /// The start address of the lowest area of the stack.
+    bottom: VirtAddr,
+    /// The lowest address the stack can grow down to.
+    limit: VirtAddr,
+    /// The mapping flags of the stack.
+    flags: MappingFlags,
+}
+
+impl GrowDownStack {
+    /// Returns the start of the range reserved for the stack, including the
+    /// guard gap.
+    fn reserved_start(&self) -> usize {
+        self.limit.as_usize().saturating_sub(STACK_GUARD_GAP)
+    }
+}
 
 /// The virtual memory address space.
 pub struct AddrSpace {
     va_range: VirtAddrRange,
     areas: MemorySet<Backend>,
-    pt: PageTable,
+    pt: AreaPageTable,
+    /// The stack added by [`map_stack`](Self::map_stack).
+    stack: Option<GrowDownStack>,
+    /// Where the next page reclaim starts.
+    #[cfg(feature = "swap")]
+    swap_hand: VirtAddr,
 }
 
 impl AddrSpace {
@@ -38,12 +75,12 @@ impl AddrSpace {
     }
 
     /// Returns the reference to the inner page table.
-    pub const fn page_table(&self) -> &PageTable {
+    pub fn page_table(&self) -> &PageTable {
         &self.pt
     }
 
     /// Returns the root physical address of the inner page table.
-    pub const fn page_table_root(&self) -> PhysAddr {
+    pub fn page_table_root(&self) -> PhysAddr {
         self.pt.root_paddr()
     }
 
@@ -58,10 +95,72 @@ impl AddrSpace {
         Ok(Self {
             va_range: VirtAddrRange::from_start_size(base, size),
             areas: MemorySet::new(),
-            pt: PageTable::try_new().map_err(|_| AxError::NoMemory)?,
+            pt: AreaPageTable::try_new()?,
+            stack: None,
+            #[cfg(feature = "swap")]
+            swap_hand: base,
         })
     }
 
+    /// Returns an iterator over the information of all memory areas, in the
+    /// ascending order of their addresses.
+    ///
+    /// The resident pages of the lazy mappings are looked up in the page
+    /// table (like Linux `/proc/<pid>/smaps`), so it's much slower than
+    /// [`rss`](Self::rss).
+    pub fn areas(&self) -> impl Iterator<Item = AreaInfo> + '_ {
+        self.areas.iter().map(|area| {
+            let (shared, offset) = match *area.backend() {
+                Backend::File {
+                    shared,
+                    start,
+                    offset,
+                    ..
+                } => (
+                    shared,
+                    offset + (area.start().as_usize() - start.as_usize()) as u64,
+                ),
+                Backend::Shared { .. } => (true, 0),
+                _ => (false, 0),
+            };
+            AreaInfo {
+                start: area.start(),
+                end: area.end(),
+                flags: area.flags(),
+                kind: area.backend().kind(),
+                shared,
+                offset,
+                resident_pages: area
+                    .backend()
+                    .resident_pages(area.start(), area.end(), &self.pt),
+            }
+        })
+    }
+
+    /// Returns the virtual memory size (VSZ) in bytes, i.e., the total size of
+    /// all memory areas.
+    pub fn vsz(&self) -> usize {
+        self.areas.iter().map(|area| area.size()).sum()
+    }
+
+    /// Returns the resident set size (RSS) in bytes, i.e., the size of the
+    /// pages in the memory areas that frames are mapped to, even if they are
+    /// inaccessible. It's kept by the backends as they map and unmap frames.
+    ///
+    /// Pages mapped by [`map_linear`] or [`copy_mappings_from`] outside the
+    /// areas are not counted.
+    ///
+    /// [`map_linear`]: Self::map_linear
+    /// [`copy_mappings_from`]: Self::copy_mappings_from
+    pub fn rss(&self) -> usize {
+        self.pt.resident_pages() * PAGE_SIZE_4K
+    }
+
+    /// Checks if the page at `vaddr` is resident, i.e., a frame is mapped.
+    fn is_resident(&self, vaddr: VirtAddr) -> bool {
+        resident_frame(&self.pt, vaddr).is_some()
+    }
+
     /// Copies page table mappings from another address space.
     ///
     /// It copies the page table entries only rather than the memory regions,
@@ -77,6 +176,102 @@ impl AddrSpace {
         Ok(())
     }
 
+    /// Creates a copy-on-write clone of this address space, e.g., for `fork`.
+    ///
+    /// The frames of the allocation and private file mappings (see
+    /// [`Backend`]) are shared by the two address spaces and mapped read-only
+    /// in both, until either of them writes to a page, which is copied in
+    /// [`handle_page_fault`] then. Linear mappings of the areas are mapped to
+    /// the same physical memory, and so are the shared file mappings and the
+    /// shared memory mappings.
+    ///
+    /// Only the mappings of the areas are cloned, other page table mappings
+    /// (e.g., by [`copy_mappings_from`] or [`map_linear`]) are not.
+    ///
+    /// [`handle_page_fault`]: Self::handle_page_fault
+    /// [`copy_mappings_from`]: Self::copy_mappings_from
+    /// [`map_linear`]: Self::map_linear
+    pub fn clone_cow(&mut self) -> AxResult<Self> {
+        let mut new = Self::new_empty(self.base(), self.size())?;
+        for area in self.areas.iter() {
+            let (start, size, flags) = (area.start(), area.size(), area.flags());
+            match *area.backend() {
+                Backend::Linear { .. } | Backend::Shared { .. } => {
+                    let new_area = MemoryArea::new(start, size, flags, area.backend().clone());
+                    new.areas
+                        .map(new_area, &mut new.pt, false)
+                        .map_err(mapping_err_to_ax_err)?;
+                }
+                Backend::Alloc { .. } | Backend::File { .. } => {
+                    // Create empty entries in the new page table, the present
+                    // pages are filled in below, and the others are allocated
+                    // (or loaded from the file) on demand as usual.
+                    let backend = match area.backend() {
+                        Backend::Alloc { .. } => Backend::new_alloc(false),
+                        backend => backend.clone(),
+                    };
+                    // The frames of shared file mappings are still shared
+                    // after cloning, rather than copied on write.
+                    let cow = !matches!(area.backend(), Backend::File { shared: true, .. });
+                    let new_area = MemoryArea::new(start, size, flags, backend);
+                    new.areas
+                        .map(new_area, &mut new.pt, false)
+                        .map_err(mapping_err_to_ax_err)?;
+                    for vaddr in PageIter4K::new(start, start + size).unwrap() {
+                        let Ok((frame, page_flags, page_size)) = self.pt.query(vaddr) else {
+                            continue;
+                        };
+                        #[cfg(feature = "swap")]
+                        if let Some(slot) = swap_slot(frame, page_flags) {
+                            // the swapped-out page is shared like the frames.
+                            new.pt
+                                .remap(vaddr, frame, page_flags)
+                                .map_err(paging_err_to_ax_err)?
+                                .1
+                                .ignore();
+                            share_slot(slot);
+                            continue;
+                        }
+                        // inaccessible pages are shared as well.
+                        if resident_frame(&self.pt, vaddr).is_none() {
+                            continue;
+                        }
+                        // The frames are shared page by page.
+                        let frame = if page_size.is_huge() {
+                            split_to_4k(&mut self.pt, vaddr).map_err(paging_err_to_ax_err)?;
+                            self.pt.query(vaddr).map_err(paging_err_to_ax_err)?.0
+                        } else {
+                            frame
+                        };
+                        let new_flags = if cow {
+                            let cow_flags = page_flags - MappingFlags::WRITE;
+                            self.pt
+                                .protect(vaddr, cow_flags)
+                                .map_err(paging_err_to_ax_err)?
+                                .1
+                                .ignore();
+                            cow_flags
+                        } else {
+                            page_flags
+                        };
+                        new.pt
+                            .remap(vaddr, frame, new_flags)
+                            .map_err(paging_err_to_ax_err)?
+                            .1
+                            .ignore();
+                        share_frame(frame);
+                        new.pt.add_resident(1);
+                    }
+                }
+            }
+        }
+        new.stack = self.stack;
+        // the pages of this address space may be cached as writable by any
+        // CPU running it.
+        shootdown();
+        Ok(new)
+    }
+
     /// Finds a free area that can accommodate the given size.
     ///
     /// The search starts from the given hint address, and the area should be within the given limit range.
@@ -88,7 +283,27 @@ impl AddrSpace {
         size: usize,
         limit: VirtAddrRange,
     ) -> Option<VirtAddr> {
-        self.areas.find_free_area(hint, size, limit)
+        let start = self.areas.find_free_area(hint, size, limit)?;
+        let Some(stack) = self.stack else {
+            return Some(start);
+        };
+        if start.as_usize() + size <= stack.reserved_start() || start >= stack.bottom {
+            return Some(start);
+        }
+        // skip the range reserved for the stack to grow into, by searching
+        // below it, then above the stack. The results are checked again, as
+        // the free area found before an existing area may exceed the limit.
+        let below_end = stack.reserved_start().min(limit.end.as_usize());
+        if below_end > limit.start.as_usize() {
+            let below = VirtAddrRange::new(limit.start, below_end.into());
+            if let Some(start) = self.areas.find_free_area(limit.start, size, below) {
+                if start.as_usize() + size <= below_end {
+                    return Some(start);
+                }
+            }
+        }
+        let start = self.areas.find_free_area(stack.top, size, limit)?;
+        (start.as_usize() + size <= limit.end.as_usize()).then_some(start)
     }
 
     /// Add a new linear mapping.
@@ -98,6 +313,8 @@ impl AddrSpace {
     ///
     /// The `flags` parameter indicates the mapping permissions and attributes.
     ///
+    /// Huge pages are used if the alignment and size allow.
+    ///
     /// Returns an error if the address range is out of the address space or not
     /// aligned.
     pub fn map_linear(
@@ -115,17 +332,15 @@ impl AddrSpace {
         }
 
         let offset = start_vaddr.as_usize() - start_paddr.as_usize();
-        self.pt
-            .map_region(
-                start_vaddr,
-                |va| pa!(va.as_usize() - offset),
-                size,
-                flags,
-                false, // allow_huge
-                false, // flush_tlb_by_page
-            )
-            .map_err(paging_err_to_ax_err)?
-            .flush_all();
+        map_region_huge(
+            &mut self.pt,
+            start_vaddr,
+            size,
+            |va| pa!(va.as_usize() - offset),
+            flags,
+        )
+        .map_err(paging_err_to_ax_err)?;
+        flush_local(None);
         Ok(())
     }
 
@@ -158,11 +373,341 @@ impl AddrSpace {
         Ok(())
     }
 
+    /// Add a new stack mapping of `size` bytes below `top`, which is an
+    /// allocation mapping that grows down on page faults below it, until it
+    /// reaches `max_size` bytes (i.e., `RLIMIT_STACK`).
+    ///
+    /// The range the stack may grow into and [`STACK_GUARD_GAP`] bytes below
+    /// it are reserved, i.e., skipped by [`find_free_area`]. Mappings may
+    /// still be added there explicitly, but the stack never grows to within
+    /// the guard gap above them, and such page faults fail instead.
+    ///
+    /// Returns an error if there is a stack already, `size` is larger than
+    /// `max_size`, or the address range of the maximum stack is out of the
+    /// address space or not aligned.
+    ///
+    /// [`find_free_area`]: Self::find_free_area
+    pub fn map_stack(
+        &mut self,
+        top: VirtAddr,
+        size: usize,
+        max_size: usize,
+        flags: MappingFlags,
+        populate: bool,
+    ) -> AxResult {
+        if self.stack.is_some() {
+            return ax_err!(AlreadyExists, "stack already mapped");
+        }
+        if size > max_size {
+            return ax_err!(InvalidInput, "stack size exceeds the limit");
+        }
+        if top.as_usize() < max_size || !self.contains_range(top - max_size, max_size) {
+            return ax_err!(InvalidInput, "address out of range");
+        }
+        if !top.is_aligned_4k() || !is_aligned_4k(size) || !is_aligned_4k(max_size) {
+            return ax_err!(InvalidInput, "address not aligned");
+        }
+
+        self.map_alloc(top - size, size, flags, populate)?;
+        self.stack = Some(GrowDownStack {
+            top,
+            bottom: top - size,
+            limit: top - max_size,
+            flags,
+        });
+        Ok(())
+    }
+
+    /// Changes the maximum size of the stack added by
+    /// [`map_stack`](Self::map_stack), like setting `RLIMIT_STACK`.
+    ///
+    /// Returns an error if there is no stack, the stack is already larger, or
+    /// the address range of the maximum stack is out of the address space or
+    /// not aligned.
+    pub fn set_stack_limit(&mut self, max_size: usize) -> AxResult {
+        let Some(stack) = self.stack else {
+            return ax_err!(BadState, "no stack mapped");
+        };
+        if max_size < stack.top.as_usize() - stack.bottom.as_usize() {
+            return ax_err!(InvalidInput, "stack size exceeds the limit");
+        }
+        if stack.top.as_usize() < max_size || !self.contains_range(stack.top - max_size, max_size) {
+            return ax_err!(InvalidInput, "address out of range");
+        }
+        if !is_aligned_4k(max_size) {
+            return ax_err!(InvalidInput, "address not aligned");
+        }
+        self.stack = Some(GrowDownStack {
+            limit: stack.top - max_size,
+            ..stack
+        });
+        Ok(())
+    }
+
+    /// Grows the stack down to the page at `vaddr`, if it's within the stack
+    /// limit and not within the guard gap above other mappings.
+    ///
+    /// Returns `true` if the stack is grown.
+    fn grow_stack(&mut self, vaddr: VirtAddr) -> bool {
+        let Some(stack) = self.stack else {
+            return false;
+        };
+        // the stack is not growable once its lowest area is unmapped.
+        if vaddr < stack.limit || vaddr >= stack.bottom || self.areas.find(stack.bottom).is_none() {
+            return false;
+        }
+        let start = vaddr.align_down_4k();
+        let gap_start = start
+            .as_usize()
+            .saturating_sub(STACK_GUARD_GAP)
+            .max(self.base().as_usize());
+        if self
+            .areas
+            .overlaps(VirtAddrRange::new(gap_start.into(), stack.bottom))
+        {
+            warn!("stack guard gap hit at {:#x}", vaddr);
+            return false;
+        }
+        let size = stack.bottom.as_usize() - start.as_usize();
+        let area = MemoryArea::new(start, size, stack.flags, Backend::new_alloc(false));
+        if self.areas.map(area, &mut self.pt, false).is_err() {
+            return false;
+        }
+        self.stack = Some(GrowDownStack {
+            bottom: start,
+            ..stack
+        });
+        true
+    }
+
+    /// Add a new file mapping, where `start` is mapped to `offset` of the
+    /// file.
+    ///
+    /// If `shared` is `true`, the changes are written back to the file by
+    /// [`msync`](Self::msync) or when unmapped. Otherwise, the mapping is
+    /// private and the changes are never written to the file. There is no page
+    /// cache, see [`Backend::File`] for the limitations.
+    ///
+    /// The pages are loaded on demand by [`handle_page_fault`], or in advance
+    /// by [`populate`].
+    ///
+    /// Returns an error if the address range is out of the address space or not
+    /// aligned.
+    ///
+    /// [`handle_page_fault`]: Self::handle_page_fault
+    /// [`populate`]: Self::populate
+    pub fn map_file(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        file: Arc<dyn MmapFile>,
+        offset: u64,
+        shared: bool,
+    ) -> AxResult {
+        if !self.contains_range(start, size) {
+            return ax_err!(InvalidInput, "address out of range");
+        }
+        if !start.is_aligned_4k() || !is_aligned_4k(size) || !is_aligned_4k(offset as usize) {
+            return ax_err!(InvalidInput, "address not aligned");
+        }
+
+        let area = MemoryArea::new(
+            start,
+            size,
+            flags,
+            Backend::new_file(file, start, offset, shared),
+        );
+        self.areas
+            .map(area, &mut self.pt, false)
+            .map_err(mapping_err_to_ax_err)?;
+        Ok(())
+    }
+
+    /// Add a new shared memory mapping, where `start` is mapped to `offset` of
+    /// the shared memory.
+    ///
+    /// The same shared memory can be mapped in multiple address spaces, or
+    /// multiple times in one address space.
+    ///
+    /// Returns an error if the address range is out of the address space or not
+    /// aligned, or exceeds the shared memory.
+    pub fn map_shared(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        shm: Arc<SharedMemory>,
+        offset: usize,
+    ) -> AxResult {
+        if !self.contains_range(start, size) {
+            return ax_err!(InvalidInput, "address out of range");
+        }
+        if !start.is_aligned_4k() || !is_aligned_4k(size) || !is_aligned_4k(offset) {
+            return ax_err!(InvalidInput, "address not aligned");
+        }
+        if offset
+            .checked_add(size)
+            .map_or(true, |end| end > shm.size())
+        {
+            return ax_err!(InvalidInput, "out of the shared memory");
+        }
+
+        let area = MemoryArea::new(start, size, flags, Backend::new_shared(shm, start, offset));
+        self.areas
+            .map(area, &mut self.pt, false)
+            .map_err(mapping_err_to_ax_err)?;
+        Ok(())
+    }
+
+    /// Returns the shared memory mapped at `vaddr` along with the address
+    /// range of the mapping, if it's mapped by [`map_shared`].
+    ///
+    /// [`map_shared`]: Self::map_shared
+    pub fn shared_memory_at(&self, vaddr: VirtAddr) -> Option<(Arc<SharedMemory>, VirtAddrRange)> {
+        let area = self.areas.find(vaddr)?;
+        match area.backend() {
+            Backend::Shared { shm, .. } => {
+                Some((shm.clone(), VirtAddrRange::new(area.start(), area.end())))
+            }
+            _ => None,
+        }
+    }
+
+    /// Maps all the pages that are not mapped yet in the specified range of
+    /// the on-demand mappings, just like they are accessed. The pages without
+    /// any access permission are skipped.
+    ///
+    /// Returns an error if a page in the range is not in any area or cannot
+    /// be mapped.
+    pub fn populate(&mut self, start: VirtAddr, size: usize) -> AxResult {
+        if !self.contains_range(start, size) {
+            return ax_err!(InvalidInput, "address out of range");
+        }
+        if !start.is_aligned_4k() || !is_aligned_4k(size) {
+            return ax_err!(InvalidInput, "address not aligned");
+        }
+
+        for vaddr in PageIter4K::new(start, start + size).unwrap() {
+            let area = self.areas.find(vaddr).ok_or(AxError::BadAddress)?;
+            // inaccessible pages are never faulted in.
+            if area.flags().is_empty() || self.is_resident(vaddr) {
+                continue;
+            }
+            if !area
+                .backend()
+                .handle_page_fault(vaddr, area.flags(), &mut self.pt)
+            {
+                return Err(AxError::NoMemory);
+            }
+        }
+        Ok(())
+    }
+
+    /// Maps the pages loaded in advance by [`LoadedFilePages::load`] in the
+    /// specified range of the file mappings, unless they are mapped already,
+    /// so that [`populate`](Self::populate) needn't load them from the files
+    /// with the address space locked. The pages without any access permission
+    /// are skipped.
+    ///
+    /// Returns an error if a page in the range is not in any area or cannot
+    /// be mapped.
+    pub fn populate_loaded(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        mut pages: LoadedFilePages,
+    ) -> AxResult {
+        if !self.contains_range(start, size) {
+            return ax_err!(InvalidInput, "address out of range");
+        }
+        if !start.is_aligned_4k() || !is_aligned_4k(size) {
+            return ax_err!(InvalidInput, "address not aligned");
+        }
+
+        for vaddr in PageIter4K::new(start, start + size).unwrap() {
+            let area = self.areas.find(vaddr).ok_or(AxError::BadAddress)?;
+            if area.flags().is_empty()
+                || !matches!(area.backend(), Backend::File { .. })
+                || self.is_resident(vaddr)
+            {
+                continue;
+            }
+            let (file, offset) = area.backend().file_page(vaddr);
+            let Some(frame) = pages.take(file, offset) else {
+                continue;
+            };
+            if !area
+                .backend()
+                .map_loaded_page(vaddr, frame, area.flags(), &mut self.pt)
+            {
+                return Err(AxError::NoMemory);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the changes of the shared file mappings within the specified
+    /// virtual address range back to the files.
+    ///
+    /// Returns an error if the address range is out of the address space or not
+    /// aligned, or any page cannot be written.
+    pub fn msync(&self, start: VirtAddr, size: usize) -> AxResult {
+        self.msync_deferred(start, size)?.write_back()
+    }
+
+    /// Like [`msync`](Self::msync), but returns the changes to be written back
+    /// later, e.g., after the address space is unlocked.
+    pub fn msync_deferred(&self, start: VirtAddr, size: usize) -> AxResult<PendingWriteBack> {
+        if !self.contains_range(start, size) {
+            return ax_err!(InvalidInput, "address out of range");
+        }
+        if !start.is_aligned_4k() || !is_aligned_4k(size) {
+            return ax_err!(InvalidInput, "address not aligned");
+        }
+        Ok(self.collect_write_back(start, size))
+    }
+
+    /// Collects the changes of the shared file mappings within the specified
+    /// range to be written back.
+    fn collect_write_back(&self, start: VirtAddr, size: usize) -> PendingWriteBack {
+        let end = start + size;
+        let mut pending = PendingWriteBack::default();
+        for area in self.areas.iter() {
+            if !matches!(area.backend(), Backend::File { shared: true, .. }) {
+                continue;
+            }
+            let sync_start = area.start().max(start);
+            let sync_end = area.end().min(end);
+            if sync_start < sync_end {
+                area.backend().collect_write_back(
+                    sync_start,
+                    sync_end - sync_start,
+                    &self.pt,
+                    &mut pending,
+                );
+            }
+        }
+        pending
+    }
+
     /// Removes mappings within the specified virtual address range.
     ///
+    /// The changes of the shared file mappings are written back to the files,
+    /// but the mappings are removed even if that fails.
+    ///
     /// Returns an error if the address range is out of the address space or not
     /// aligned.
     pub fn unmap(&mut self, start: VirtAddr, size: usize) -> AxResult {
+        // the errors have been logged.
+        self.unmap_deferred(start, size)?.write_back().ok();
+        Ok(())
+    }
+
+    /// Like [`unmap`](Self::unmap), but returns the changes of the shared file
+    /// mappings to be written back later, e.g., after the address space is
+    /// unlocked.
+    pub fn unmap_deferred(&mut self, start: VirtAddr, size: usize) -> AxResult<PendingWriteBack> {
         if !self.contains_range(start, size) {
             return ax_err!(InvalidInput, "address out of range");
         }
@@ -170,13 +715,126 @@ impl AddrSpace {
             return ax_err!(InvalidInput, "address not aligned");
         }
 
-        self.pt
-            .unmap_region(start, size, true)
-            .map_err(paging_err_to_ax_err)?
-            .ignore();
+        if self
+            .areas
+            .overlaps(VirtAddrRange::from_start_size(start, size))
+        {
+            // mappings added by `map_alloc` or `map_file`, the frames are
+            // deallocated as well.
+            let pending = self.collect_write_back(start, size);
+            self.areas
+                .unmap(start, size, &mut self.pt)
+                .map_err(mapping_err_to_ax_err)?;
+            shootdown();
+            Ok(pending)
+        } else {
+            self.split_huge_pages(start, size)?;
+            self.pt
+                .unmap_region(start, size, false)
+                .map_err(paging_err_to_ax_err)?
+                .ignore();
+            shootdown();
+            Ok(PendingWriteBack::default())
+        }
+    }
+
+    /// Splits the huge pages across the boundaries of the given range, so that
+    /// the pages in the range can be changed separately.
+    fn split_huge_pages(&mut self, start: VirtAddr, size: usize) -> AxResult {
+        split_huge_pages_at(&mut self.pt, start).map_err(paging_err_to_ax_err)?;
+        split_huge_pages_at(&mut self.pt, start + size).map_err(paging_err_to_ax_err)
+    }
+
+    /// Swaps in the swapped-out pages in the specified range, so that they
+    /// can be accessed or changed by the kernel.
+    #[cfg(feature = "swap")]
+    fn swap_in_range(&mut self, start: VirtAddr, end: VirtAddr) -> AxResult {
+        for vaddr in PageIter4K::new(start.align_down_4k(), end.align_up_4k()).unwrap() {
+            let Ok((paddr, flags, _)) = self.pt.query(vaddr) else {
+                continue;
+            };
+            if swap_slot(paddr, flags).is_none() {
+                continue;
+            }
+            let area = self.areas.find(vaddr).ok_or(AxError::BadAddress)?;
+            if !area
+                .backend()
+                .handle_page_fault(vaddr, area.flags(), &mut self.pt)
+            {
+                return Err(AxError::NoMemory);
+            }
+        }
         Ok(())
     }
 
+    /// Swaps out at most `max_pages` resident pages of the lazy allocation
+    /// mappings, and returns the number of pages swapped out.
+    ///
+    /// It's a clock algorithm: the pages are scanned in address order from
+    /// where the last reclaim stopped, wrapping around at the end of the
+    /// address space. A page that has been accessed since the hand passed it
+    /// has its accessed bit cleared and is kept, otherwise it's swapped out.
+    /// The pages are scanned at most twice, so the pages kept in the first
+    /// round can be swapped out in the second. Huge pages and the pages shared
+    /// by [`clone_cow`](Self::clone_cow) are skipped.
+    ///
+    /// The chosen pages are made inaccessible and the TLBs of all CPUs are
+    /// shot down once, before the pages are written to the swap area.
+    #[cfg(feature = "swap")]
+    pub fn reclaim(&mut self, max_pages: usize) -> usize {
+        let max_pages = max_pages.min(swap::swap_pages().map_or(0, |(_, free)| free));
+        let ranges: Vec<_> = self
+            .areas
+            .iter()
+            .filter(|area| {
+                !area.flags().is_empty()
+                    && matches!(area.backend(), Backend::Alloc { populate: false })
+            })
+            .map(|area| (area.start(), area.end()))
+            .collect();
+        // the ranges after the hand go first, then those before it.
+        let hand = self.swap_hand;
+        let after = ranges
+            .iter()
+            .filter(|&&(_, end)| end > hand)
+            .map(|&(start, end)| (start.max(hand), end));
+        let before = ranges
+            .iter()
+            .filter(|&&(start, _)| start < hand)
+            .map(|&(start, end)| (start, end.min(hand)));
+        let ranges: Vec<_> = after.chain(before).collect();
+
+        let mut isolated = Vec::new();
+        self.swap_hand = self.base();
+        'scan: for _ in 0..2 {
+            for &(start, end) in &ranges {
+                for vaddr in PageIter4K::new(start, end).unwrap() {
+                    if isolated.len() == max_pages {
+                        self.swap_hand = vaddr;
+                        break 'scan;
+                    }
+                    if let Some((frame, flags)) = crate::backend::isolate_page(vaddr, &mut self.pt)
+                    {
+                        isolated.push((vaddr, frame, flags));
+                    }
+                }
+            }
+        }
+        if isolated.is_empty() {
+            return 0;
+        }
+        // other CPUs may still write to the pages through their TLBs, which
+        // must be flushed before the frames are swapped out and freed.
+        shootdown();
+        let mut reclaimed = 0;
+        for (vaddr, frame, flags) in isolated {
+            if crate::backend::swap_out_page(vaddr, frame, flags, &mut self.pt) {
+                reclaimed += 1;
+            }
+        }
+        reclaimed
+    }
+
     /// To process data in this area with the given function.
     ///
     /// Now it supports reading and writing data in the given interval.
@@ -193,7 +851,11 @@ impl AddrSpace {
         for vaddr in PageIter4K::new(start.align_down_4k(), end_align_up)
             .expect("Failed to create page iterator")
         {
-            let (mut paddr, _, _) = self.pt.query(vaddr).map_err(|_| AxError::BadAddress)?;
+            let (mut paddr, _flags, _) = self.pt.query(vaddr).map_err(|_| AxError::BadAddress)?;
+            #[cfg(feature = "swap")]
+            if swap_slot(paddr, _flags).is_some() {
+                return Err(AxError::BadAddress);
+            }
 
             let mut copy_size = (size - cnt).min(PAGE_SIZE_4K);
 
@@ -213,11 +875,19 @@ impl AddrSpace {
 
     /// To read data from the address space.
     ///
+    /// The swapped-out pages are swapped in first, that's why it takes
+    /// `&mut self`.
+    ///
     /// # Arguments
     ///
     /// * `start` - The start virtual address to read.
     /// * `buf` - The buffer to store the data.
-    pub fn read(&self, start: VirtAddr, buf: &mut [u8]) -> AxResult {
+    pub fn read(&mut self, start: VirtAddr, buf: &mut [u8]) -> AxResult {
+        if !self.contains_range(start, buf.len()) {
+            return ax_err!(InvalidInput, "address out of range");
+        }
+        #[cfg(feature = "swap")]
+        self.swap_in_range(start, start + buf.len())?;
         self.process_area_data(start, buf.len(), |src, offset, read_size| unsafe {
             core::ptr::copy_nonoverlapping(src.as_ptr(), buf.as_mut_ptr().add(offset), read_size);
         })
@@ -225,11 +895,39 @@ impl AddrSpace {
 
     /// To write data to the address space.
     ///
+    /// Pages shared by [`clone_cow`](Self::clone_cow) are copied before
+    /// writing, except those of shared file mappings. The swapped-out pages
+    /// are swapped in first, like [`read`](Self::read).
+    ///
     /// # Arguments
     ///
     /// * `start_vaddr` - The start virtual address to write.
     /// * `buf` - The buffer to write to the address space.
-    pub fn write(&self, start: VirtAddr, buf: &[u8]) -> AxResult {
+    pub fn write(&mut self, start: VirtAddr, buf: &[u8]) -> AxResult {
+        if !self.contains_range(start, buf.len()) {
+            return ax_err!(InvalidInput, "address out of range");
+        }
+        let end = (start + buf.len()).align_up_4k();
+        #[cfg(feature = "swap")]
+        self.swap_in_range(start, end)?;
+        let mut unshared = false;
+        for vaddr in PageIter4K::new(start.align_down_4k(), end).unwrap() {
+            if let Ok((frame, flags, _)) = self.pt.query(vaddr) {
+                let cow = self.areas.find(vaddr).map_or(true, |area| {
+                    !matches!(area.backend(), Backend::File { shared: true, .. })
+                });
+                if cow && frame_ref_count(frame) > 1 {
+                    if !unshare_page(vaddr, flags, &mut self.pt) {
+                        return Err(AxError::NoMemory);
+                    }
+                    unshared = true;
+                }
+            }
+        }
+        if unshared {
+            // other CPUs may still cache the shared frames.
+            shootdown();
+        }
         self.process_area_data(start, buf.len(), |dst, offset, write_size| unsafe {
             core::ptr::copy_nonoverlapping(buf.as_ptr().add(offset), dst.as_mut_ptr(), write_size);
         })
@@ -247,10 +945,30 @@ impl AddrSpace {
             return ax_err!(InvalidInput, "address not aligned");
         }
 
+        // swap entries must not be changed into mappings.
+        #[cfg(feature = "swap")]
+        self.swap_in_range(start, start + size)?;
+        self.split_huge_pages(start, size)?;
         self.pt
-            .protect_region(start, size, flags, true)
+            .protect_region(start, size, flags, false)
             .map_err(paging_err_to_ax_err)?
             .ignore();
+        if flags.contains(MappingFlags::WRITE) {
+            // keep the pages shared by `clone_cow` read-only.
+            for vaddr in PageIter4K::new(start, start + size).unwrap() {
+                if let Ok((frame, _, _)) = self.pt.query(vaddr) {
+                    if frame_ref_count(frame) > 1 {
+                        self.pt
+                            .protect(vaddr, flags - MappingFlags::WRITE)
+                            .map_err(paging_err_to_ax_err)?
+                            .1
+                            .ignore();
+                    }
+                }
+            }
+        }
+        // the permissions may be reduced, which must be seen by all CPUs.
+        shootdown();
         Ok(())
     }
 
@@ -264,12 +982,40 @@ impl AddrSpace {
         if !self.va_range.contains(vaddr) {
             return false;
         }
+        // some CPUs fault on the accessed bits cleared by `reclaim`, instead
+        // of setting them.
+        #[cfg(feature = "swap")]
+        if swap::set_accessed(&mut self.pt, vaddr) {
+            return true;
+        }
+        #[cfg(feature = "swap")]
+        if swap::should_reclaim() {
+            self.reclaim(swap::RECLAIM_BATCH_PAGES);
+        }
+        // faults below the stack grow it down.
+        if self.areas.find(vaddr).is_none() && !self.grow_stack(vaddr) {
+            return false;
+        }
         if let Some(area) = self.areas.find(vaddr) {
             let orig_flags = area.flags();
             if orig_flags.contains(access_flags) {
-                return area
-                    .backend()
-                    .handle_page_fault(vaddr, orig_flags, &mut self.pt);
+                let was_resident = self.is_resident(vaddr);
+                let backend = area.backend().clone();
+                let handled = backend.handle_page_fault(vaddr, orig_flags, &mut self.pt);
+                // retry once if the memory runs out.
+                #[cfg(feature = "swap")]
+                let handled = handled
+                    || (self.reclaim(swap::RECLAIM_BATCH_PAGES) > 0
+                        && backend.handle_page_fault(vaddr, orig_flags, &mut self.pt));
+                if !handled {
+                    return false;
+                }
+                if was_resident {
+                    // a page shared by copy-on-write is replaced, which other
+                    // CPUs may still cache.
+                    shootdown();
+                }
+                return true;
             }
         }
         false
@@ -324,6 +1070,19 @@ impl AddrSpace {
     }
 }
 
+impl Drop for AddrSpace {
+    /// Unmaps all the areas, so that their frames (and swap slots) are
+    /// deallocated, before the page table itself is dropped. The changes of
+    /// the shared file mappings are written back.
+    fn drop(&mut self) {
+        let pending = self.collect_write_back(self.base(), self.size());
+        if let Err(e) = self.areas.clear(&mut self.pt) {
+            warn!("failed to unmap the areas of {:?}: {:?}", self, e);
+        }
+        pending.write_back().ok();
+    }
+}
+
 impl fmt::Debug for AddrSpace {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("AddrSpace")
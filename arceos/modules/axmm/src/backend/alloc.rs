@@ -1,22 +1,140 @@
-use axalloc::global_allocator;
-use axhal::mem::{phys_to_virt, virt_to_phys};
-use axhal::paging::{MappingFlags, PageSize, PageTable};
-use memory_addr::{PageIter4K, PhysAddr, VirtAddr, PAGE_SIZE_4K};
+use axhal::paging::{MappingFlags, PageTable};
+use memory_addr::{PhysAddr, VirtAddr, PAGE_SIZE_4K};
 
-use super::Backend;
+use super::{resident_frame, AreaPageTable, Backend};
+use crate::frame::{
+    alloc_frame, alloc_page_frames, copy_frame, dealloc_frame, dealloc_page_frames, frame_ref_count,
+};
+use crate::huge::{max_page_size, smaller_page_size};
+#[cfg(feature = "swap")]
+use crate::swap::{
+    free_slot, swap_entry, swap_in_frame, swap_out_frame, swap_slot, test_and_clear_accessed,
+};
+use crate::tlb::flush_local;
 
-fn alloc_frame(zeroed: bool) -> Option<PhysAddr> {
-    let vaddr = VirtAddr::from(global_allocator().alloc_pages(1, PAGE_SIZE_4K).ok()?);
-    if zeroed {
-        unsafe { core::ptr::write_bytes(vaddr.as_mut_ptr(), 0, PAGE_SIZE_4K) };
+/// Makes the page at `vaddr`, which may be shared with other address spaces
+/// by copy-on-write, private to this page table, and remaps it with `flags`.
+///
+/// The frame is copied if it's still shared, otherwise it's reused.
+pub(crate) fn unshare_page(vaddr: VirtAddr, flags: MappingFlags, pt: &mut PageTable) -> bool {
+    let Ok((frame, _, page_size)) = pt.query(vaddr) else {
+        return false;
+    };
+    if page_size.is_huge() {
+        return false;
     }
-    let paddr = virt_to_phys(vaddr);
-    Some(paddr)
+    let new_frame = if frame_ref_count(frame) > 1 {
+        let Some(new_frame) = copy_frame(frame) else {
+            return false;
+        };
+        // drop the reference to the shared frame.
+        dealloc_frame(frame);
+        new_frame
+    } else {
+        frame
+    };
+    pt.remap(vaddr, new_frame, flags)
+        .map(|(_, tlb)| {
+            tlb.ignore();
+            flush_local(Some(vaddr));
+        })
+        .is_ok()
 }
 
-fn dealloc_frame(frame: PhysAddr) {
-    let vaddr = phys_to_virt(frame);
-    global_allocator().dealloc_pages(vaddr.as_usize(), 1);
+/// Makes the page at `vaddr` inaccessible before it's swapped out, so that it
+/// cannot be changed in the meantime, and returns its frame and flags.
+///
+/// It must be a present 4K page of a lazy allocation mapping, and not shared
+/// with other address spaces. If the page has been accessed since the last
+/// call, its accessed bit is cleared instead, and it's kept (a second chance).
+///
+/// Other CPUs may still cache the page, so the TLBs must be shot down (which
+/// waits for all CPUs to flush) before [`swap_out_page`] frees the frame.
+#[cfg(feature = "swap")]
+pub(crate) fn isolate_page(
+    vaddr: VirtAddr,
+    pt: &mut AreaPageTable,
+) -> Option<(PhysAddr, MappingFlags)> {
+    let (frame, flags, page_size) = pt.query(vaddr).ok()?;
+    if flags.is_empty() || page_size.is_huge() || frame_ref_count(frame) > 1 {
+        return None;
+    }
+    if test_and_clear_accessed(pt, vaddr) {
+        return None;
+    }
+    let (_, tlb) = pt.remap(vaddr, frame, MappingFlags::empty()).ok()?;
+    tlb.ignore();
+    flush_local(Some(vaddr));
+    Some((frame, flags))
+}
+
+/// Writes the page at `vaddr` isolated by [`isolate_page`] to the swap area,
+/// and records the slot in its page table entry.
+///
+/// The page is restored with `flags` on failure.
+#[cfg(feature = "swap")]
+pub(crate) fn swap_out_page(
+    vaddr: VirtAddr,
+    frame: PhysAddr,
+    flags: MappingFlags,
+    pt: &mut AreaPageTable,
+) -> bool {
+    // the entry is not present before and after, so the TLB needn't be
+    // flushed.
+    match swap_out_frame(frame) {
+        Ok(slot) => {
+            if let Ok((_, tlb)) = pt.remap(vaddr, swap_entry(slot), MappingFlags::empty()) {
+                tlb.ignore();
+            }
+            dealloc_frame(frame);
+            pt.sub_resident(1);
+            true
+        }
+        Err(e) => {
+            warn!("failed to swap out page at {:#x}: {:?}", vaddr, e);
+            if let Ok((_, tlb)) = pt.remap(vaddr, frame, flags) {
+                tlb.ignore();
+            }
+            false
+        }
+    }
+}
+
+/// Reads the page swapped out to `slot` into a new frame, and maps it at
+/// `vaddr` with `flags`.
+#[cfg(feature = "swap")]
+fn swap_in_page(vaddr: VirtAddr, slot: usize, flags: MappingFlags, pt: &mut AreaPageTable) -> bool {
+    let Some(frame) = alloc_frame(false) else {
+        return false;
+    };
+    if let Err(e) = swap_in_frame(slot, frame) {
+        warn!("failed to swap in page at {:#x}: {:?}", vaddr, e);
+        dealloc_frame(frame);
+        return false;
+    }
+    map_new_page(vaddr, frame, flags, pt)
+}
+
+/// Maps the newly allocated `frame` to the page at `vaddr`, which is not
+/// resident yet, with `flags`. The frame is deallocated on failure.
+pub(super) fn map_new_page(
+    vaddr: VirtAddr,
+    frame: PhysAddr,
+    flags: MappingFlags,
+    pt: &mut AreaPageTable,
+) -> bool {
+    match pt.remap(vaddr, frame, flags) {
+        Ok((_, tlb)) => {
+            tlb.ignore();
+            flush_local(Some(vaddr));
+            pt.add_resident(1);
+            true
+        }
+        Err(_) => {
+            dealloc_frame(frame);
+            false
+        }
+    }
 }
 
 impl Backend {
@@ -30,7 +148,7 @@ impl Backend {
         start: VirtAddr,
         size: usize,
         flags: MappingFlags,
-        pt: &mut PageTable,
+        pt: &mut AreaPageTable,
         populate: bool,
     ) -> bool {
         debug!(
@@ -41,15 +159,30 @@ impl Backend {
             populate
         );
         if populate {
-            // allocate all possible physical frames for populated mapping.
-            for addr in PageIter4K::new(start, start + size).unwrap() {
-                if let Some(frame) = alloc_frame(true) {
-                    if let Ok(tlb) = pt.map(addr, frame, PageSize::Size4K, flags) {
-                        tlb.ignore(); // TLB flush on map is unnecessary, as there are no outdated mappings.
-                    } else {
-                        return false;
+            // allocate all physical frames for populated mapping, using huge
+            // pages if the alignment and size allow, and falling back to
+            // smaller pages if they cannot be allocated or mapped.
+            let end = start + size;
+            let mut addr = start;
+            while addr < end {
+                let mut page_size = max_page_size(addr, end);
+                loop {
+                    if let Some(frame) = alloc_page_frames(page_size, true) {
+                        if let Ok(tlb) = pt.map(addr, frame, page_size, flags) {
+                            tlb.ignore(); // TLB flush on map is unnecessary, as there are no outdated mappings.
+                            pt.add_resident(page_size as usize / PAGE_SIZE_4K);
+                            break;
+                        }
+                        // A huge page cannot be mapped if there is a page
+                        // table left by previous mappings.
+                        dealloc_page_frames(frame, page_size);
+                    }
+                    match smaller_page_size(page_size) {
+                        Some(smaller) => page_size = smaller,
+                        None => return false,
                     }
                 }
+                addr += page_size as usize;
             }
             true
         } else {
@@ -65,21 +198,39 @@ impl Backend {
         &self,
         start: VirtAddr,
         size: usize,
-        pt: &mut PageTable,
+        pt: &mut AreaPageTable,
         _populate: bool,
     ) -> bool {
         debug!("unmap_alloc: [{:#x}, {:#x})", start, start + size);
-        for addr in PageIter4K::new(start, start + size).unwrap() {
-            if let Ok((frame, page_size, tlb)) = pt.unmap(addr) {
+        let end = start + size;
+        let mut addr = start;
+        while addr < end {
+            #[cfg(feature = "swap")]
+            if let Ok((paddr, flags, _)) = pt.query(addr) {
+                if let Some(slot) = swap_slot(paddr, flags) {
+                    // The page is swapped out, drop the reference to the slot.
+                    pt.unmap(addr).ok();
+                    free_slot(slot);
+                    addr += PAGE_SIZE_4K;
+                    continue;
+                }
+            }
+            if let Some((frame, _, page_size)) = resident_frame(pt, addr) {
                 // Deallocate the physical frame if there is a mapping in the
-                // page table.
-                if page_size.is_huge() {
-                    return false;
+                // page table. Huge pages across the range boundaries have
+                // been split before.
+                if let Ok((_, _, tlb)) = pt.unmap(addr) {
+                    tlb.ignore();
+                    flush_local(Some(addr));
                 }
-                tlb.flush();
-                dealloc_frame(frame);
+                dealloc_page_frames(frame, page_size);
+                pt.sub_resident(page_size as usize / PAGE_SIZE_4K);
+                addr += page_size as usize;
             } else {
-                // Deallocation is needn't if the page is not mapped.
+                // Deallocation is needn't if the page is not mapped, but the
+                // empty entry is removed.
+                pt.unmap(addr).ok();
+                addr += PAGE_SIZE_4K;
             }
         }
         true
@@ -89,18 +240,31 @@ impl Backend {
         &self,
         vaddr: VirtAddr,
         orig_flags: MappingFlags,
-        pt: &mut PageTable,
+        pt: &mut AreaPageTable,
         populate: bool,
     ) -> bool {
+        if let Some((_, flags, _)) = resident_frame(pt, vaddr) {
+            // The page is resident, so it's a write to a page shared by
+            // copy-on-write, which is mapped read-only. An inaccessible page
+            // is not faulted in again.
+            return orig_flags.contains(MappingFlags::WRITE)
+                && !flags.is_empty()
+                && !flags.contains(MappingFlags::WRITE)
+                && unshare_page(vaddr, orig_flags, pt);
+        }
+        #[cfg(feature = "swap")]
+        if let Ok((paddr, flags, _)) = pt.query(vaddr) {
+            if let Some(slot) = swap_slot(paddr, flags) {
+                return swap_in_page(vaddr, slot, orig_flags, pt);
+            }
+        }
         if populate {
             false // Populated mappings should not trigger page faults.
         } else if let Some(frame) = alloc_frame(true) {
             // Allocate a physical frame lazily and map it to the fault address.
             // `vaddr` does not need to be aligned. It will be automatically
             // aligned during `pt.remap` regardless of the page size.
-            pt.remap(vaddr, frame, orig_flags)
-                .map(|(_, tlb)| tlb.flush())
-                .is_ok()
+            map_new_page(vaddr, frame, orig_flags, pt)
         } else {
             false
         }
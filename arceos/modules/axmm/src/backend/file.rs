@@ -0,0 +1,315 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use axerrno::{AxError, AxResult};
+use axhal::mem::phys_to_virt;
+use axhal::paging::{MappingFlags, PageTable};
+use memory_addr::{MemoryAddr, PageIter4K, PhysAddr, VirtAddr, PAGE_SIZE_4K};
+
+use super::alloc::map_new_page;
+use super::{resident_frame, unshare_page, AreaPageTable, Backend};
+use crate::frame::{alloc_frame, dealloc_frame, share_frame};
+use crate::tlb::flush_local;
+
+/// A file that can be mapped into an address space by [`Backend::File`].
+///
+/// The methods may be called with the address space locked, e.g., in the
+/// page fault handler, so they must not access the same address space.
+pub trait MmapFile: Send + Sync {
+    /// Reads data at the given offset of the file into `buf`, returns the
+    /// number of bytes read, which is 0 at the end of the file.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize>;
+
+    /// Writes `buf` at the given offset of the file, returns the number of
+    /// bytes written.
+    fn write_at(&self, offset: u64, buf: &[u8]) -> AxResult<usize>;
+
+    /// Returns the current size of the file.
+    fn size(&self) -> AxResult<u64>;
+}
+
+fn page_slice(frame: PhysAddr) -> &'static mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut(phys_to_virt(frame).as_mut_ptr(), PAGE_SIZE_4K) }
+}
+
+/// Allocates a frame and fills it with the file data at `offset`. The part
+/// beyond the end of the file is zeroed.
+fn load_page(file: &dyn MmapFile, offset: u64) -> AxResult<PhysAddr> {
+    let frame = alloc_frame(true).ok_or(AxError::NoMemory)?;
+    let buf = page_slice(frame);
+    let mut read = 0;
+    while read < PAGE_SIZE_4K {
+        match file.read_at(offset + read as u64, &mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) => {
+                warn!("failed to load file page at offset {:#x}: {:?}", offset, e);
+                dealloc_frame(frame);
+                return Err(e);
+            }
+        }
+    }
+    Ok(frame)
+}
+
+/// Writes the contents of the frame back to the file at `offset`, without
+/// extending the file.
+fn write_back_page(file: &dyn MmapFile, offset: u64, frame: PhysAddr) -> AxResult {
+    let file_size = file.size()?;
+    if offset >= file_size {
+        return Ok(());
+    }
+    let len = PAGE_SIZE_4K.min((file_size - offset) as usize);
+    let buf = &page_slice(frame)[..len];
+    let mut written = 0;
+    while written < len {
+        match file.write_at(offset + written as u64, &buf[written..])? {
+            0 => break,
+            n => written += n,
+        }
+    }
+    Ok(())
+}
+
+/// The pages of a file loaded in advance by [`load`](Self::load), which are
+/// mapped by [`AddrSpace::populate_loaded`] without any file I/O, e.g., when
+/// the address space is locked by a spinlock.
+///
+/// The pages that are not mapped are deallocated on drop.
+///
+/// [`AddrSpace::populate_loaded`]: crate::AddrSpace::populate_loaded
+pub struct LoadedFilePages {
+    file: Arc<dyn MmapFile>,
+    offset: u64,
+    frames: Vec<Option<PhysAddr>>,
+}
+
+impl LoadedFilePages {
+    /// Loads `size` bytes of the file at `offset`. The part beyond the end of
+    /// the file is zeroed.
+    pub fn load(file: Arc<dyn MmapFile>, offset: u64, size: usize) -> AxResult<Self> {
+        let mut pages = Self {
+            file,
+            offset,
+            frames: Vec::with_capacity(size.div_ceil(PAGE_SIZE_4K)),
+        };
+        for off in (0..size).step_by(PAGE_SIZE_4K) {
+            let frame = load_page(pages.file.as_ref(), offset + off as u64)?;
+            pages.frames.push(Some(frame));
+        }
+        Ok(pages)
+    }
+
+    /// Takes the page loaded from `offset` of `file`, if any.
+    pub(crate) fn take(&mut self, file: &dyn MmapFile, offset: u64) -> Option<PhysAddr> {
+        if !core::ptr::addr_eq(Arc::as_ptr(&self.file), file) || offset < self.offset {
+            return None;
+        }
+        let index = ((offset - self.offset) / PAGE_SIZE_4K as u64) as usize;
+        self.frames.get_mut(index)?.take()
+    }
+}
+
+impl Drop for LoadedFilePages {
+    fn drop(&mut self) {
+        for frame in self.frames.iter().flatten() {
+            dealloc_frame(*frame);
+        }
+    }
+}
+
+/// The pages of shared file mappings to be written back to the files by
+/// [`write_back`](Self::write_back), which can be done after the address
+/// space is unlocked, as the file I/O may block.
+///
+/// The pages are kept allocated until they are written back or dropped, even
+/// if they are unmapped in the meantime.
+#[derive(Default)]
+pub struct PendingWriteBack {
+    pages: Vec<(Arc<dyn MmapFile>, u64, PhysAddr)>,
+}
+
+impl PendingWriteBack {
+    fn push(&mut self, file: Arc<dyn MmapFile>, offset: u64, frame: PhysAddr) {
+        share_frame(frame);
+        self.pages.push((file, offset, frame));
+    }
+
+    /// Writes the pages back to the files.
+    ///
+    /// All the pages are written even if some of them fail, and the last
+    /// error is returned then.
+    pub fn write_back(mut self) -> AxResult {
+        let mut res = Ok(());
+        for (file, offset, frame) in self.pages.drain(..) {
+            if let Err(e) = write_back_page(file.as_ref(), offset, frame) {
+                warn!(
+                    "failed to write back file page at offset {:#x}: {:?}",
+                    offset, e
+                );
+                res = Err(e);
+            }
+            dealloc_frame(frame);
+        }
+        res
+    }
+}
+
+impl Drop for PendingWriteBack {
+    fn drop(&mut self) {
+        for &(_, _, frame) in self.pages.iter() {
+            dealloc_frame(frame);
+        }
+    }
+}
+
+impl Backend {
+    /// Creates a new file mapping backend, where the virtual address `start`
+    /// is mapped to `offset` of the file.
+    pub fn new_file(file: Arc<dyn MmapFile>, start: VirtAddr, offset: u64, shared: bool) -> Self {
+        Self::File {
+            file,
+            start,
+            offset,
+            shared,
+        }
+    }
+
+    /// Returns the mapped file and the file offset of the page at `vaddr`.
+    pub(crate) fn file_page(&self, vaddr: VirtAddr) -> (&dyn MmapFile, u64) {
+        match self {
+            Self::File {
+                file,
+                start,
+                offset,
+                ..
+            } => (
+                file.as_ref(),
+                offset + (vaddr.as_usize() - start.as_usize()) as u64,
+            ),
+            _ => unreachable!(),
+        }
+    }
+
+    pub(crate) fn map_file(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        pt: &mut PageTable,
+    ) -> bool {
+        debug!(
+            "map_file: [{:#x}, {:#x}) {:?} (offset={:#x})",
+            start,
+            start + size,
+            flags,
+            self.file_page(start).1
+        );
+        // The pages are always loaded on demand (by handling page faults), or
+        // by `AddrSpace::populate`.
+        pt.map_region(
+            start,
+            |_| 0.into(),
+            size,
+            MappingFlags::empty(),
+            false,
+            false,
+        )
+        .map(|tlb| tlb.ignore())
+        .is_ok()
+    }
+
+    pub(crate) fn unmap_file(&self, start: VirtAddr, size: usize, pt: &mut AreaPageTable) -> bool {
+        debug!("unmap_file: [{:#x}, {:#x})", start, start + size);
+        // the changes of shared mappings have been collected by
+        // `AddrSpace`, to be written back after unmapping.
+        for addr in PageIter4K::new(start, start + size).unwrap() {
+            let resident = resident_frame(pt, addr);
+            if let Ok((_, _, tlb)) = pt.unmap(addr) {
+                tlb.ignore();
+                flush_local(Some(addr));
+            }
+            if let Some((frame, _, _)) = resident {
+                dealloc_frame(frame);
+                pt.sub_resident(1);
+            }
+        }
+        true
+    }
+
+    /// Maps `frame` loaded in advance from the file to the page at `vaddr`,
+    /// which is not resident yet, with `flags`. The frame is deallocated on
+    /// failure.
+    pub(crate) fn map_loaded_page(
+        &self,
+        vaddr: VirtAddr,
+        frame: PhysAddr,
+        flags: MappingFlags,
+        pt: &mut AreaPageTable,
+    ) -> bool {
+        map_new_page(vaddr, frame, flags, pt)
+    }
+
+    /// Adds the present and writable pages of a shared file mapping in the
+    /// given range to `pending`, to be written back to the file.
+    ///
+    /// The page table cannot tell whether a page is dirty, so all writable
+    /// pages are written.
+    pub(crate) fn collect_write_back(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        pt: &PageTable,
+        pending: &mut PendingWriteBack,
+    ) {
+        let Self::File { file, .. } = self else {
+            unreachable!()
+        };
+        for addr in PageIter4K::new(start, start + size).unwrap() {
+            let Ok((frame, flags, _)) = pt.query(addr) else {
+                continue;
+            };
+            if flags.contains(MappingFlags::WRITE) {
+                pending.push(file.clone(), self.file_page(addr).1, frame);
+            }
+        }
+    }
+
+    pub(crate) fn handle_page_fault_file(
+        &self,
+        vaddr: VirtAddr,
+        orig_flags: MappingFlags,
+        pt: &mut AreaPageTable,
+        shared: bool,
+    ) -> bool {
+        if let Some((_, flags, _)) = resident_frame(pt, vaddr) {
+            // An inaccessible page is not faulted in again.
+            if flags.is_empty()
+                || !orig_flags.contains(MappingFlags::WRITE)
+                || flags.contains(MappingFlags::WRITE)
+            {
+                return false;
+            }
+            // A write to a read-only page shared by `AddrSpace::clone_cow`.
+            // The frame of a shared mapping is still shared by both address
+            // spaces, while a private one is copied on write.
+            return if shared {
+                pt.protect(vaddr, orig_flags)
+                    .map(|(_, tlb)| {
+                        tlb.ignore();
+                        flush_local(Some(vaddr));
+                    })
+                    .is_ok()
+            } else {
+                unshare_page(vaddr, orig_flags, pt)
+            };
+        }
+        // Private mappings also load the file data into a new frame, so their
+        // writes never reach the file.
+        let (file, offset) = self.file_page(vaddr.align_down_4k());
+        match load_page(file, offset) {
+            Ok(frame) => map_new_page(vaddr, frame, orig_flags, pt),
+            Err(_) => false,
+        }
+    }
+}
@@ -1,7 +1,9 @@
-use axhal::paging::{MappingFlags, PageTable};
-use memory_addr::{PhysAddr, VirtAddr};
+use axhal::paging::MappingFlags;
+use memory_addr::{PhysAddr, VirtAddr, PAGE_SIZE_4K};
 
-use super::Backend;
+use super::{AreaPageTable, Backend};
+use crate::huge::map_region_huge;
+use crate::tlb::flush_local;
 
 impl Backend {
     /// Creates a new linear mapping backend.
@@ -14,7 +16,7 @@ impl Backend {
         start: VirtAddr,
         size: usize,
         flags: MappingFlags,
-        pt: &mut PageTable,
+        pt: &mut AreaPageTable,
         pa_va_offset: usize,
     ) -> bool {
         let va_to_pa = |va: VirtAddr| PhysAddr::from(va.as_usize() - pa_va_offset);
@@ -26,21 +28,31 @@ impl Backend {
             va_to_pa(start + size),
             flags
         );
-        pt.map_region(start, va_to_pa, size, flags, false, false)
-            .map(|tlb| tlb.ignore()) // TLB flush on map is unnecessary, as there are no outdated mappings.
-            .is_ok()
+        let mapped = map_region_huge(pt, start, size, va_to_pa, flags).is_ok();
+        if mapped {
+            pt.add_resident(size / PAGE_SIZE_4K);
+        }
+        mapped
     }
 
     pub(crate) fn unmap_linear(
         &self,
         start: VirtAddr,
         size: usize,
-        pt: &mut PageTable,
+        pt: &mut AreaPageTable,
         _pa_va_offset: usize,
     ) -> bool {
         debug!("unmap_linear: [{:#x}, {:#x})", start, start + size);
-        pt.unmap_region(start, size, true)
-            .map(|tlb| tlb.ignore()) // flush each page on unmap, do not flush the entire TLB.
-            .is_ok()
+        let unmapped = pt
+            .unmap_region(start, size, false)
+            .map(|tlb| {
+                tlb.ignore();
+                flush_local(None);
+            })
+            .is_ok();
+        if unmapped {
+            pt.sub_resident(size / PAGE_SIZE_4K);
+        }
+        unmapped
     }
 }
@@ -1,21 +1,46 @@
 //! Memory mapping backends.
 #![allow(dead_code)]
 
-use axhal::paging::{MappingFlags, PageTable};
-use memory_addr::VirtAddr;
+use ::alloc::sync::Arc;
+use core::ops::{Deref, DerefMut};
+
+use axerrno::{AxError, AxResult};
+use axhal::paging::{MappingFlags, PageSize, PageTable};
+use memory_addr::{MemoryAddr, PhysAddr, VirtAddr, PAGE_SIZE_4K};
 use memory_set::MappingBackend;
 
+use crate::huge::split_huge_pages_at;
+#[cfg(feature = "swap")]
+use crate::swap::swap_slot;
+use crate::tlb::flush_local;
+use crate::{BackendKind, SharedMemory};
+
 mod alloc;
+mod file;
 mod linear;
+mod shared;
+
+pub(crate) use self::alloc::unshare_page;
+#[cfg(feature = "swap")]
+pub(crate) use self::alloc::{isolate_page, swap_out_page};
+pub use self::file::{LoadedFilePages, MmapFile, PendingWriteBack};
 
 /// A unified enum type for different memory mapping backends.
 ///
-/// Currently, two backends are implemented:
+/// Currently, four backends are implemented:
 ///
 /// - **Linear**: used for linear mappings. The target physical frames are
 ///   contiguous and their addresses should be known when creating the mapping.
 /// - **Allocation**: used in general, or for lazy mappings. The target physical
 ///   frames are obtained from the global allocator.
+/// - **File**: used for file mappings. The physical frames are obtained from
+///   the global allocator and filled with the file data on demand.
+/// - **Shared**: used for shared memory. The target physical frames are owned
+///   by a [`SharedMemory`], which may be mapped in other address spaces.
+///
+/// Linear and populated allocation mappings use huge pages (2M, and 1G except
+/// on x86_64) when the alignment and size allow. The huge pages are split when
+/// a part of them is unmapped or protected.
 #[derive(Clone)]
 pub enum Backend {
     /// Linear mapping backend.
@@ -30,30 +55,157 @@ pub enum Backend {
     /// Allocation mapping backend.
     ///
     /// If `populate` is `true`, all physical frames are allocated when the
-    /// mapping is created, and no page faults are triggered during the memory
-    /// access. Otherwise, the physical frames are allocated on demand (by
+    /// mapping is created (as huge pages if possible), and no page faults are
+    /// triggered during the memory access. Otherwise, the physical frames are allocated on demand (by
     /// handling page faults).
     Alloc {
         /// Whether to populate the physical frames when creating the mapping.
         populate: bool,
     },
+    /// File mapping backend.
+    ///
+    /// The pages are loaded from the file on page faults. The virtual address
+    /// `start` is mapped to `offset` of the file, and so on, which still holds
+    /// after the area is split.
+    ///
+    /// If `shared` is `true` (`MAP_SHARED`), the writable pages are written
+    /// back to the file on [`AddrSpace::msync`](crate::AddrSpace::msync) and
+    /// unmapping. Otherwise (`MAP_PRIVATE`), the changes are never written to
+    /// the file.
+    ///
+    /// There is no page cache shared by the mappings: each mapping loads its
+    /// own copy of the pages, so the mappings of the same file (and the file
+    /// reads and writes) do not see the changes of each other until they are
+    /// written back. The page table has no dirty bits either, so all the
+    /// writable pages are written back, changed or not.
+    File {
+        /// The mapped file.
+        file: Arc<dyn MmapFile>,
+        /// The virtual address mapped to `offset`.
+        start: VirtAddr,
+        /// The file offset of `start`.
+        offset: u64,
+        /// Whether the changes are written back to the file.
+        shared: bool,
+    },
+    /// Shared memory mapping backend.
+    ///
+    /// The virtual address `start` is mapped to `offset` of the shared memory,
+    /// and so on. The frames are always mapped, and are deallocated when the
+    /// shared memory is dropped rather than unmapped.
+    Shared {
+        /// The mapped shared memory.
+        shm: Arc<SharedMemory>,
+        /// The virtual address mapped to `offset`.
+        start: VirtAddr,
+        /// The offset in the shared memory of `start`.
+        offset: usize,
+    },
+}
+
+/// The page table of the memory areas, along with the number of resident 4K
+/// pages in them, which is kept by the backends as they map and unmap frames.
+///
+/// A page is resident if a frame is mapped to it, even if it's inaccessible
+/// (e.g., `PROT_NONE`). The on-demand pages not mapped yet and the pages
+/// swapped out are not.
+pub struct AreaPageTable {
+    inner: PageTable,
+    resident: usize,
+}
+
+impl AreaPageTable {
+    /// Creates a new empty page table.
+    pub(crate) fn try_new() -> AxResult<Self> {
+        Ok(Self {
+            inner: PageTable::try_new().map_err(|_| AxError::NoMemory)?,
+            resident: 0,
+        })
+    }
+
+    /// Returns the number of resident 4K pages.
+    pub(crate) const fn resident_pages(&self) -> usize {
+        self.resident
+    }
+
+    /// Records that `pages` 4K pages become resident.
+    pub(crate) fn add_resident(&mut self, pages: usize) {
+        self.resident += pages;
+    }
+
+    /// Records that `pages` 4K pages are no longer resident.
+    pub(crate) fn sub_resident(&mut self, pages: usize) {
+        self.resident -= pages;
+    }
+}
+
+impl Deref for AreaPageTable {
+    type Target = PageTable;
+
+    fn deref(&self) -> &PageTable {
+        &self.inner
+    }
+}
+
+impl DerefMut for AreaPageTable {
+    fn deref_mut(&mut self) -> &mut PageTable {
+        &mut self.inner
+    }
+}
+
+/// Returns the frame mapped to the page at `vaddr`, along with the mapping
+/// flags and the page size, if the page is resident (see [`AreaPageTable`]).
+pub(crate) fn resident_frame(
+    pt: &PageTable,
+    vaddr: VirtAddr,
+) -> Option<(PhysAddr, MappingFlags, PageSize)> {
+    let (paddr, flags, page_size) = pt.query(vaddr).ok()?;
+    // the on-demand pages are mapped to empty entries.
+    if flags.is_empty() && paddr.align_down_4k().as_usize() == 0 {
+        return None;
+    }
+    #[cfg(feature = "swap")]
+    if swap_slot(paddr, flags).is_some() {
+        return None;
+    }
+    Some((paddr, flags, page_size))
+}
+
+/// Splits the huge pages across the boundaries of `[start, start + size)`, so
+/// that the range can be unmapped or protected without affecting the pages
+/// outside it, e.g., when only a part of an area is changed.
+fn split_at_boundaries(start: VirtAddr, size: usize, pt: &mut PageTable) -> bool {
+    split_huge_pages_at(pt, start).is_ok() && split_huge_pages_at(pt, start + size).is_ok()
 }
 
 impl MappingBackend for Backend {
     type Addr = VirtAddr;
     type Flags = MappingFlags;
-    type PageTable = PageTable;
-    fn map(&self, start: VirtAddr, size: usize, flags: MappingFlags, pt: &mut PageTable) -> bool {
+    type PageTable = AreaPageTable;
+    fn map(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        pt: &mut AreaPageTable,
+    ) -> bool {
         match *self {
             Self::Linear { pa_va_offset } => self.map_linear(start, size, flags, pt, pa_va_offset),
             Self::Alloc { populate } => self.map_alloc(start, size, flags, pt, populate),
+            Self::File { .. } => self.map_file(start, size, flags, pt),
+            Self::Shared { .. } => self.map_shared(start, size, flags, pt),
         }
     }
 
-    fn unmap(&self, start: VirtAddr, size: usize, pt: &mut PageTable) -> bool {
+    fn unmap(&self, start: VirtAddr, size: usize, pt: &mut AreaPageTable) -> bool {
+        if !split_at_boundaries(start, size, pt) {
+            return false;
+        }
         match *self {
             Self::Linear { pa_va_offset } => self.unmap_linear(start, size, pt, pa_va_offset),
             Self::Alloc { populate } => self.unmap_alloc(start, size, pt, populate),
+            Self::File { .. } => self.unmap_file(start, size, pt),
+            Self::Shared { .. } => self.unmap_shared(start, size, pt),
         }
     }
 
@@ -64,25 +216,72 @@ impl MappingBackend for Backend {
         new_flags: Self::Flags,
         page_table: &mut Self::PageTable,
     ) -> bool {
+        if !split_at_boundaries(start, size, page_table) {
+            return false;
+        }
         page_table
-            .protect_region(start, size, new_flags, true)
-            .map(|tlb| tlb.ignore())
+            .protect_region(start, size, new_flags, false)
+            .map(|tlb| {
+                tlb.ignore();
+                flush_local(None);
+            })
             .is_ok()
     }
 }
 
 impl Backend {
+    /// Returns the kind of the backend.
+    pub const fn kind(&self) -> BackendKind {
+        match self {
+            Self::Linear { .. } => BackendKind::Linear,
+            Self::Alloc { .. } => BackendKind::Alloc,
+            Self::File { .. } => BackendKind::File,
+            Self::Shared { .. } => BackendKind::Shared,
+        }
+    }
+
+    /// Returns the number of resident 4K pages in `[start, end)` of the area.
+    ///
+    /// The pages of linear and shared memory mappings are always resident,
+    /// while those of the others are looked up in the page table.
+    pub(crate) fn resident_pages(&self, start: VirtAddr, end: VirtAddr, pt: &PageTable) -> usize {
+        match *self {
+            Self::Linear { .. } | Self::Shared { .. } | Self::Alloc { populate: true } => {
+                (end.as_usize() - start.as_usize()) / PAGE_SIZE_4K
+            }
+            Self::Alloc { populate: false } | Self::File { .. } => {
+                let mut count = 0;
+                let mut vaddr = start;
+                while vaddr < end {
+                    match resident_frame(pt, vaddr) {
+                        Some((_, _, page_size)) => {
+                            let next = (vaddr.align_down(page_size) + page_size as usize).min(end);
+                            count += (next.as_usize() - vaddr.as_usize()) / PAGE_SIZE_4K;
+                            vaddr = next;
+                        }
+                        None => vaddr += PAGE_SIZE_4K,
+                    }
+                }
+                count
+            }
+        }
+    }
+
     pub(crate) fn handle_page_fault(
         &self,
         vaddr: VirtAddr,
         orig_flags: MappingFlags,
-        page_table: &mut PageTable,
+        page_table: &mut AreaPageTable,
     ) -> bool {
         match *self {
-            Self::Linear { .. } => false, // Linear mappings should not trigger page faults.
+            // Linear and shared mappings should not trigger page faults.
+            Self::Linear { .. } | Self::Shared { .. } => false,
             Self::Alloc { populate } => {
                 self.handle_page_fault_alloc(vaddr, orig_flags, page_table, populate)
             }
+            Self::File { shared, .. } => {
+                self.handle_page_fault_file(vaddr, orig_flags, page_table, shared)
+            }
         }
     }
 }
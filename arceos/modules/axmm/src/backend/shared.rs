@@ -0,0 +1,74 @@
+use alloc::sync::Arc;
+
+use axhal::paging::MappingFlags;
+use memory_addr::{VirtAddr, PAGE_SIZE_4K};
+
+use super::{AreaPageTable, Backend};
+use crate::tlb::flush_local;
+use crate::SharedMemory;
+
+impl Backend {
+    /// Creates a new shared memory mapping backend, where the virtual address
+    /// `start` is mapped to `offset` of the shared memory.
+    pub fn new_shared(shm: Arc<SharedMemory>, start: VirtAddr, offset: usize) -> Self {
+        Self::Shared { shm, start, offset }
+    }
+
+    pub(crate) fn map_shared(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        pt: &mut AreaPageTable,
+    ) -> bool {
+        let Self::Shared {
+            shm,
+            start: shm_start,
+            offset,
+        } = self
+        else {
+            unreachable!()
+        };
+        let va_to_pa = |va: VirtAddr| {
+            shm.frame_at(offset + (va.as_usize() - shm_start.as_usize()))
+                .expect("address out of the shared memory")
+        };
+        debug!(
+            "map_shared: [{:#x}, {:#x}) -> {:#x} {:?}",
+            start,
+            start + size,
+            va_to_pa(start),
+            flags
+        );
+        // the frames are not contiguous, so map them one by one.
+        let mapped = pt
+            .map_region(start, va_to_pa, size, flags, false, false)
+            .map(|tlb| tlb.ignore()) // TLB flush on map is unnecessary, as there are no outdated mappings.
+            .is_ok();
+        if mapped {
+            pt.add_resident(size / PAGE_SIZE_4K);
+        }
+        mapped
+    }
+
+    pub(crate) fn unmap_shared(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        pt: &mut AreaPageTable,
+    ) -> bool {
+        debug!("unmap_shared: [{:#x}, {:#x})", start, start + size);
+        // the frames are deallocated when the shared memory is dropped.
+        let unmapped = pt
+            .unmap_region(start, size, false)
+            .map(|tlb| {
+                tlb.ignore();
+                flush_local(None);
+            })
+            .is_ok();
+        if unmapped {
+            pt.sub_resident(size / PAGE_SIZE_4K);
+        }
+        unmapped
+    }
+}
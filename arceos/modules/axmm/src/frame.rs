@@ -0,0 +1,91 @@
+//! Physical frame allocation with reference counting.
+//!
+//! Frames allocated for [`Backend::Alloc`](crate::backend::Backend::Alloc)
+//! mappings may be shared by multiple address spaces after
+//! [`AddrSpace::clone_cow`](crate::AddrSpace::clone_cow). A frame is only
+//! deallocated when the last mapping to it is removed.
+//!
+//! Most frames are never shared, so only the reference counts greater than 1
+//! are recorded.
+
+use alloc::collections::BTreeMap;
+
+use axalloc::global_allocator;
+use axhal::mem::{phys_to_virt, virt_to_phys};
+use axhal::paging::PageSize;
+use kspin::SpinNoIrq;
+use memory_addr::{PhysAddr, VirtAddr, PAGE_SIZE_4K};
+
+/// Reference counts of the shared frames, which are always greater than 1.
+static SHARED_FRAMES: SpinNoIrq<BTreeMap<PhysAddr, usize>> = SpinNoIrq::new(BTreeMap::new());
+
+/// Allocates a 4K frame, with the reference count of 1.
+pub(crate) fn alloc_frame(zeroed: bool) -> Option<PhysAddr> {
+    alloc_page_frames(PageSize::Size4K, zeroed)
+}
+
+/// Allocates a frame of `page_size` (which may be a huge page) aligned to its
+/// size, with the reference count of 1.
+pub(crate) fn alloc_page_frames(page_size: PageSize, zeroed: bool) -> Option<PhysAddr> {
+    let size: usize = page_size.into();
+    let num_pages = size / PAGE_SIZE_4K;
+    let vaddr = VirtAddr::from(global_allocator().alloc_pages(num_pages, size).ok()?);
+    if zeroed {
+        unsafe { core::ptr::write_bytes(vaddr.as_mut_ptr(), 0, size) };
+    }
+    let paddr = virt_to_phys(vaddr);
+    Some(paddr)
+}
+
+/// Decreases the reference count of the frame, and deallocates it if there
+/// are no other references.
+pub(crate) fn dealloc_frame(frame: PhysAddr) {
+    let mut shared = SHARED_FRAMES.lock();
+    if let Some(count) = shared.get_mut(&frame) {
+        *count -= 1;
+        if *count == 1 {
+            shared.remove(&frame);
+        }
+        return;
+    }
+    drop(shared);
+    let vaddr = phys_to_virt(frame);
+    global_allocator().dealloc_pages(vaddr.as_usize(), 1);
+}
+
+/// Deallocates a frame of `page_size` mapped by a page table entry.
+///
+/// Huge page frames are never shared, as they are split into 4K pages before
+/// being shared by [`AddrSpace::clone_cow`](crate::AddrSpace::clone_cow).
+pub(crate) fn dealloc_page_frames(frame: PhysAddr, page_size: PageSize) {
+    if page_size.is_huge() {
+        let num_pages = usize::from(page_size) / PAGE_SIZE_4K;
+        global_allocator().dealloc_pages(phys_to_virt(frame).as_usize(), num_pages);
+    } else {
+        dealloc_frame(frame);
+    }
+}
+
+/// Increases the reference count of the frame, when it's mapped by another
+/// page table entry.
+pub(crate) fn share_frame(frame: PhysAddr) {
+    *SHARED_FRAMES.lock().entry(frame).or_insert(1) += 1;
+}
+
+/// Returns the reference count of the frame.
+pub fn frame_ref_count(frame: PhysAddr) -> usize {
+    SHARED_FRAMES.lock().get(&frame).copied().unwrap_or(1)
+}
+
+/// Copies the contents of the frame to a newly allocated frame.
+pub(crate) fn copy_frame(frame: PhysAddr) -> Option<PhysAddr> {
+    let new_frame = alloc_frame(false)?;
+    unsafe {
+        core::ptr::copy_nonoverlapping(
+            phys_to_virt(frame).as_ptr(),
+            phys_to_virt(new_frame).as_mut_ptr(),
+            PAGE_SIZE_4K,
+        );
+    }
+    Some(new_frame)
+}
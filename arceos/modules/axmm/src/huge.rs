@@ -0,0 +1,182 @@
+//! Helpers for huge page (2M and 1G) mappings.
+//!
+//! Huge pages are used when the alignment and size of a mapping allow, and
+//! are split into smaller pages when only a part of them is unmapped, has its
+//! permissions changed, or needs to be handled page by page (e.g., shared by
+//! copy-on-write).
+
+use axhal::mem::phys_to_virt;
+use axhal::paging::{
+    GenericPTE, MappingFlags, PageSize, PageTable, PageTableEntry, PagingError, PagingResult,
+    PAGE_TABLE_LEVELS,
+};
+use memory_addr::{MemoryAddr, PhysAddr, VirtAddr, PAGE_SIZE_4K};
+
+use crate::frame::alloc_frame;
+use crate::tlb::flush_local;
+
+/// Returns the smaller page size that a huge page of `page_size` is split
+/// into, or falls back to when it cannot be allocated or mapped.
+pub(crate) const fn smaller_page_size(page_size: PageSize) -> Option<PageSize> {
+    match page_size {
+        PageSize::Size1G => Some(PageSize::Size2M),
+        PageSize::Size2M => Some(PageSize::Size4K),
+        PageSize::Size4K => None,
+    }
+}
+
+/// The huge page sizes to use, from the largest.
+///
+/// 1G pages are not supported by all x86_64 CPUs (without `pdpe1gb`), so only
+/// 2M pages are used there.
+const HUGE_PAGE_SIZES: &[PageSize] = if cfg!(target_arch = "x86_64") {
+    &[PageSize::Size2M]
+} else {
+    &[PageSize::Size1G, PageSize::Size2M]
+};
+
+/// Returns the largest page size that `vaddr` is aligned to and fits in
+/// `[vaddr, end)`.
+pub(crate) fn max_page_size(vaddr: VirtAddr, end: VirtAddr) -> PageSize {
+    for &page_size in HUGE_PAGE_SIZES {
+        if vaddr.is_aligned(page_size) && end.as_usize() - vaddr.as_usize() >= page_size as usize {
+            return page_size;
+        }
+    }
+    PageSize::Size4K
+}
+
+/// Maps the region `[start, start + size)` to the physical memory given by
+/// `get_paddr`, using huge pages if the alignment of both the virtual and
+/// physical addresses allows.
+///
+/// The physical memory must be contiguous within each huge page.
+pub(crate) fn map_region_huge(
+    pt: &mut PageTable,
+    start: VirtAddr,
+    size: usize,
+    get_paddr: impl Fn(VirtAddr) -> PhysAddr,
+    flags: MappingFlags,
+) -> PagingResult {
+    let end = start + size;
+    let mut vaddr = start;
+    while vaddr < end {
+        let paddr = get_paddr(vaddr);
+        let mut page_size = max_page_size(vaddr, end);
+        loop {
+            if paddr.is_aligned(page_size) {
+                match pt.map(vaddr, paddr, page_size, flags) {
+                    // TLB flush on map is unnecessary, as there are no outdated mappings.
+                    Ok(tlb) => break tlb.ignore(),
+                    // A huge page cannot be mapped if there is a page table
+                    // left by previous mappings.
+                    Err(e) if !page_size.is_huge() => return Err(e),
+                    Err(_) => {}
+                }
+            }
+            page_size = smaller_page_size(page_size).unwrap();
+        }
+        vaddr += page_size as usize;
+    }
+    Ok(())
+}
+
+/// Number of entries in a page table of any level.
+const ENTRY_COUNT: usize = 512;
+
+/// Returns the page table at `paddr` as a slice of entries.
+///
+/// # Safety
+///
+/// `paddr` must be the physical address of a page table (or a frame that is
+/// becoming one) that is not referenced elsewhere for the returned lifetime.
+unsafe fn table_of_mut<'a>(paddr: PhysAddr) -> &'a mut [PageTableEntry] {
+    let ptr = phys_to_virt(paddr).as_mut_ptr() as *mut PageTableEntry;
+    core::slice::from_raw_parts_mut(ptr, ENTRY_COUNT)
+}
+
+/// Returns the entry of `pt` that maps the page of `page_size` containing
+/// `vaddr`.
+pub(crate) fn entry_mut(
+    pt: &mut PageTable,
+    vaddr: VirtAddr,
+    page_size: PageSize,
+) -> PagingResult<&mut PageTableEntry> {
+    let target_shift = usize::from(page_size).trailing_zeros() as usize;
+    let mut shift = PAGE_SIZE_4K.trailing_zeros() as usize + 9 * (PAGE_TABLE_LEVELS - 1);
+    let mut table_paddr = pt.root_paddr();
+    loop {
+        // SAFETY: the tables are owned by `pt`, which is borrowed mutably.
+        let table = unsafe { table_of_mut(table_paddr) };
+        let entry = &mut table[(vaddr.as_usize() >> shift) % ENTRY_COUNT];
+        if entry.is_unused() {
+            return Err(PagingError::NotMapped);
+        }
+        if shift == target_shift {
+            return Ok(entry);
+        }
+        if entry.is_huge() {
+            return Err(PagingError::MappedToHugePage);
+        }
+        table_paddr = entry.paddr();
+        shift -= 9;
+    }
+}
+
+/// Splits the huge page that contains `vaddr` into pages of the next smaller
+/// size, with the same flags and target physical memory.
+///
+/// Returns the page size of `vaddr` after splitting.
+///
+/// The next level table is filled before it replaces the huge page with a
+/// single entry write, so the memory stays mapped all the time, even if `pt`
+/// is in use (e.g., the kernel address space).
+fn split_page(pt: &mut PageTable, vaddr: VirtAddr) -> PagingResult<PageSize> {
+    let (paddr, flags, page_size) = pt.query(vaddr)?;
+    let Some(sub_size) = smaller_page_size(page_size) else {
+        return Ok(page_size);
+    };
+    let base = vaddr.align_down(page_size);
+    let base_paddr = paddr - (vaddr.as_usize() - base.as_usize());
+    let entry = entry_mut(pt, base, page_size)?;
+
+    // The frame is deallocated by `pt` like other page tables.
+    let table_paddr = alloc_frame(false).ok_or(PagingError::NoMemory)?;
+    // SAFETY: the frame is just allocated and not referenced elsewhere.
+    let table = unsafe { table_of_mut(table_paddr) };
+    let sub_size_bytes: usize = sub_size.into();
+    for (i, sub_entry) in table.iter_mut().enumerate() {
+        *sub_entry =
+            PageTableEntry::new_page(base_paddr + i * sub_size_bytes, flags, sub_size.is_huge());
+    }
+    *entry = PageTableEntry::new_table(table_paddr);
+    // The translation of the smaller pages is the same as the huge page, but
+    // the TLB entry of the huge page is dropped for consistency. Flushing any
+    // address in the huge page does that.
+    flush_local(Some(base));
+    Ok(sub_size)
+}
+
+/// Splits the huge pages that contain `vaddr` until `vaddr` is at the
+/// boundary of a page, so that the mappings on either side of it can be
+/// changed separately.
+pub(crate) fn split_huge_pages_at(pt: &mut PageTable, vaddr: VirtAddr) -> PagingResult {
+    while let Ok((_, _, page_size)) = pt.query(vaddr) {
+        if vaddr.is_aligned(page_size) {
+            break;
+        }
+        split_page(pt, vaddr)?;
+    }
+    Ok(())
+}
+
+/// Splits the huge page that contains `vaddr` into 4K pages.
+pub(crate) fn split_to_4k(pt: &mut PageTable, vaddr: VirtAddr) -> PagingResult {
+    while let Ok((_, _, page_size)) = pt.query(vaddr) {
+        if !page_size.is_huge() {
+            break;
+        }
+        split_page(pt, vaddr)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,72 @@
+//! Structured information of the memory areas in an address space.
+
+use core::fmt;
+
+use axhal::paging::MappingFlags;
+use memory_addr::VirtAddr;
+
+/// The kind of the backend of a memory area, see [`Backend`].
+///
+/// [`Backend`]: crate::backend::Backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendKind {
+    /// Linear mapping to contiguous physical memory.
+    Linear,
+    /// Frames allocated from the global allocator.
+    Alloc,
+    /// File mapping.
+    File,
+    /// Shared memory mapping.
+    Shared,
+}
+
+/// Information of a memory area in an address space, returned by
+/// [`AddrSpace::areas`].
+///
+/// It's displayed as a line of Linux `/proc/<pid>/maps`, without the device,
+/// inode and path name fields, which are always empty.
+///
+/// [`AddrSpace::areas`]: crate::AddrSpace::areas
+#[derive(Debug, Clone)]
+pub struct AreaInfo {
+    /// The start address of the area.
+    pub start: VirtAddr,
+    /// The end address (exclusive) of the area.
+    pub end: VirtAddr,
+    /// The mapping flags of the area.
+    pub flags: MappingFlags,
+    /// The kind of the backend.
+    pub kind: BackendKind,
+    /// Whether the changes are visible to other mappings of the same memory,
+    /// i.e., shared file mappings and shared memory mappings.
+    pub shared: bool,
+    /// The file offset of `start` for file mappings, or 0 for others.
+    pub offset: u64,
+    /// The number of resident 4K pages, i.e., mapped with any access
+    /// permission.
+    pub resident_pages: usize,
+}
+
+impl AreaInfo {
+    /// Returns the size of the area in bytes.
+    pub fn size(&self) -> usize {
+        self.end.as_usize() - self.start.as_usize()
+    }
+}
+
+impl fmt::Display for AreaInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let perm = |flag, c| if self.flags.contains(flag) { c } else { '-' };
+        write!(
+            f,
+            "{:08x}-{:08x} {}{}{}{} {:08x} 00:00 0",
+            self.start,
+            self.end,
+            perm(MappingFlags::READ, 'r'),
+            perm(MappingFlags::WRITE, 'w'),
+            perm(MappingFlags::EXECUTE, 'x'),
+            if self.shared { 's' } else { 'p' },
+            self.offset,
+        )
+    }
+}
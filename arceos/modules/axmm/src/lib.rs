@@ -1,6 +1,11 @@
 //! [ArceOS](https://github.com/arceos-org/arceos) memory management module.
+//!
+//! # Cargo Features
+//!
+//! - `swap`: Swap the pages of lazy allocation mappings out to a swap area
+//!   set by [`swap_on`] when the free memory runs low.
 
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
 #[macro_use]
 extern crate log;
@@ -8,21 +13,48 @@ extern crate alloc;
 
 mod aspace;
 mod backend;
-
-pub use self::aspace::AddrSpace;
+mod frame;
+mod huge;
+mod info;
+mod shm;
+#[cfg(feature = "swap")]
+mod swap;
+mod tlb;
+
+#[cfg(test)]
+mod tests;
+
+pub use self::aspace::{AddrSpace, STACK_GUARD_GAP};
+pub use self::backend::{LoadedFilePages, MmapFile, PendingWriteBack};
+pub use self::frame::frame_ref_count;
+pub use self::info::{AreaInfo, BackendKind};
+pub use self::shm::SharedMemory;
+#[cfg(feature = "swap")]
+pub use self::swap::{swap_off, swap_on, swap_pages, BlockSwap, SwapDevice, SwapFile};
+pub use self::tlb::{handle_tlb_shootdown, TlbSpinLock, TlbSpinLockGuard};
 
 use axerrno::{AxError, AxResult};
 use axhal::mem::phys_to_virt;
-use axhal::paging::PagingError;
-use kspin::SpinNoIrq;
+use axhal::paging::{MappingFlags, PagingError};
 use lazyinit::LazyInit;
-use memory_addr::{va, PhysAddr, VirtAddr};
+use memory_addr::{align_down, va, PhysAddr, VirtAddr, VirtAddrRange, PAGE_SIZE_4K};
 use memory_set::MappingError;
 
 const USER_ASPACE_BASE: usize = 0x0000;
 const USER_ASPACE_SIZE: usize = 0x40_0000_0000;
 
-static KERNEL_ASPACE: LazyInit<SpinNoIrq<AddrSpace>> = LazyInit::new();
+/// Size of the region for kernel stacks, near the top of the kernel address
+/// space.
+const KERNEL_STACK_REGION_SIZE: usize = 0x4000_0000; // 1G
+
+/// Size of the region for the mappings created by `mmap` in the kernel address
+/// space, right below the kernel stack region.
+const KERNEL_MMAP_REGION_SIZE: usize = 0x4000_0000; // 1G
+
+/// Size of the guard page below each kernel stack.
+pub const KERNEL_STACK_GUARD_SIZE: usize = PAGE_SIZE_4K;
+
+static KERNEL_ASPACE: LazyInit<TlbSpinLock<AddrSpace>> = LazyInit::new();
 
 fn mapping_err_to_ax_err(err: MappingError) -> AxError {
     warn!("Mapping error: {:?}", err);
@@ -60,11 +92,42 @@ pub fn new_kernel_aspace() -> AxResult<AddrSpace> {
     for r in axhal::mem::memory_regions() {
         aspace.map_linear(phys_to_virt(r.paddr), r.paddr, r.size, r.flags.into())?;
     }
+    // Reserve the first page of the kernel stack region, which also creates
+    // the page tables of the region in advance, so that the stacks mapped
+    // later are visible in the user address spaces.
+    let stack_region = kernel_stack_region();
+    aspace.map_alloc(
+        stack_region.start,
+        PAGE_SIZE_4K,
+        MappingFlags::empty(),
+        false,
+    )?;
     Ok(aspace)
 }
 
+/// Returns the region for kernel stacks, which is aligned to its size so that
+/// it is covered by a single top-level page table entry on all architectures.
+fn kernel_stack_region() -> VirtAddrRange {
+    let aspace_end = axconfig::KERNEL_ASPACE_BASE + axconfig::KERNEL_ASPACE_SIZE;
+    let start = align_down(
+        aspace_end - KERNEL_STACK_REGION_SIZE,
+        KERNEL_STACK_REGION_SIZE,
+    );
+    VirtAddrRange::from_start_size(va!(start), KERNEL_STACK_REGION_SIZE)
+}
+
+/// Returns the region for the mappings created by `mmap` in the kernel address
+/// space, e.g., by unikernel apps.
+///
+/// Unlike the user address spaces, the kernel address space does not handle
+/// page faults, so the mappings in it should be populated in advance.
+pub fn kernel_mmap_region() -> VirtAddrRange {
+    let start = kernel_stack_region().start - KERNEL_MMAP_REGION_SIZE;
+    VirtAddrRange::from_start_size(start, KERNEL_MMAP_REGION_SIZE)
+}
+
 /// Returns the globally unique kernel address space.
-pub fn kernel_aspace() -> &'static SpinNoIrq<AddrSpace> {
+pub fn kernel_aspace() -> &'static TlbSpinLock<AddrSpace> {
     &KERNEL_ASPACE
 }
 
@@ -73,6 +136,37 @@ pub fn kernel_page_table_root() -> PhysAddr {
     KERNEL_ASPACE.lock().page_table_root()
 }
 
+/// Allocates a kernel stack of `size` bytes, and maps it into the kernel
+/// address space with an unmapped guard page right below it.
+///
+/// Returns the bottom address of the stack. `size` must be 4K-aligned.
+pub fn alloc_kernel_stack(size: usize) -> AxResult<VirtAddr> {
+    let region = kernel_stack_region();
+    let mut aspace = KERNEL_ASPACE.lock();
+    let start = aspace
+        .find_free_area(region.start, KERNEL_STACK_GUARD_SIZE + size, region)
+        .ok_or(AxError::NoMemory)?;
+    // The guard page is mapped with no permissions, so that it won't be taken
+    // by other stacks and any access to it triggers a page fault.
+    aspace.map_alloc(start, KERNEL_STACK_GUARD_SIZE, MappingFlags::empty(), false)?;
+    let stack = start + KERNEL_STACK_GUARD_SIZE;
+    let flags = MappingFlags::READ | MappingFlags::WRITE;
+    if let Err(e) = aspace.map_alloc(stack, size, flags, true) {
+        aspace.unmap(start, KERNEL_STACK_GUARD_SIZE)?;
+        return Err(e);
+    }
+    Ok(stack)
+}
+
+/// Deallocates a kernel stack allocated by [`alloc_kernel_stack`], along with
+/// its guard page.
+pub fn dealloc_kernel_stack(stack: VirtAddr, size: usize) -> AxResult {
+    KERNEL_ASPACE.lock().unmap(
+        stack - KERNEL_STACK_GUARD_SIZE,
+        KERNEL_STACK_GUARD_SIZE + size,
+    )
+}
+
 /// Initializes virtual memory management.
 ///
 /// It mainly sets up the kernel virtual memory address space and recreate a
@@ -82,7 +176,7 @@ pub fn init_memory_management() {
 
     let kernel_aspace = new_kernel_aspace().expect("failed to initialize kernel address space");
     debug!("kernel address space init OK: {:#x?}", kernel_aspace);
-    KERNEL_ASPACE.init_once(SpinNoIrq::new(kernel_aspace));
+    KERNEL_ASPACE.init_once(TlbSpinLock::new(kernel_aspace));
     axhal::paging::set_kernel_page_table_root(kernel_page_table_root());
 }
 
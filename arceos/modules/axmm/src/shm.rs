@@ -0,0 +1,109 @@
+//! Shared memory that can be mapped into multiple address spaces.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use axerrno::{ax_err, AxError, AxResult};
+use axhal::mem::phys_to_virt;
+use memory_addr::{is_aligned_4k, PhysAddr, PAGE_SIZE_4K};
+
+use crate::frame::{alloc_frame, dealloc_frame};
+
+/// A region of physical memory that can be mapped into several address spaces
+/// at arbitrary addresses, by [`AddrSpace::map_shared`].
+///
+/// The frames are allocated when it's created, and deallocated when the last
+/// reference to it is dropped, i.e., it's not held by any other owner and is
+/// unmapped from all the address spaces.
+///
+/// [`AddrSpace::map_shared`]: crate::AddrSpace::map_shared
+pub struct SharedMemory {
+    frames: Vec<PhysAddr>,
+}
+
+impl SharedMemory {
+    /// Creates a new zeroed shared memory of `size` bytes.
+    ///
+    /// Returns an error if `size` is not 4K-aligned, or there is not enough
+    /// memory.
+    pub fn new(size: usize) -> AxResult<Arc<Self>> {
+        if !is_aligned_4k(size) {
+            return ax_err!(InvalidInput, "size not aligned");
+        }
+        let mut frames = Vec::with_capacity(size / PAGE_SIZE_4K);
+        for _ in 0..size / PAGE_SIZE_4K {
+            match alloc_frame(true) {
+                Some(frame) => frames.push(frame),
+                None => {
+                    frames.into_iter().for_each(dealloc_frame);
+                    return Err(AxError::NoMemory);
+                }
+            }
+        }
+        Ok(Arc::new(Self { frames }))
+    }
+
+    /// Returns the size of the shared memory in bytes.
+    pub fn size(&self) -> usize {
+        self.frames.len() * PAGE_SIZE_4K
+    }
+
+    /// Calls `f` with the pointer to each piece of `[offset, offset + len)` in
+    /// the frames, the offset of the piece in the range, and its length.
+    ///
+    /// The range is truncated at the end of the shared memory, and the
+    /// truncated length is returned.
+    fn for_each_piece(
+        &self,
+        offset: usize,
+        len: usize,
+        mut f: impl FnMut(*mut u8, usize, usize),
+    ) -> usize {
+        let len = len.min(self.size().saturating_sub(offset));
+        let mut done = 0;
+        while done < len {
+            let pos = offset + done;
+            let page_offset = pos % PAGE_SIZE_4K;
+            let piece = (PAGE_SIZE_4K - page_offset).min(len - done);
+            let frame = self.frames[pos / PAGE_SIZE_4K];
+            f(
+                unsafe { phys_to_virt(frame).as_mut_ptr().add(page_offset) },
+                done,
+                piece,
+            );
+            done += piece;
+        }
+        len
+    }
+
+    /// Reads the data at `offset` of the shared memory into `buf`, and returns
+    /// the number of bytes read, which is less than the length of `buf` at the
+    /// end of the shared memory.
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.for_each_piece(offset, buf.len(), |src, done, len| unsafe {
+            core::ptr::copy_nonoverlapping(src, buf.as_mut_ptr().add(done), len);
+        })
+    }
+
+    /// Writes `buf` at `offset` of the shared memory, and returns the number
+    /// of bytes written, which is less than the length of `buf` at the end of
+    /// the shared memory.
+    pub fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        self.for_each_piece(offset, buf.len(), |dst, done, len| unsafe {
+            core::ptr::copy_nonoverlapping(buf.as_ptr().add(done), dst, len);
+        })
+    }
+
+    /// Returns the frame at the given offset (in bytes) of the shared memory.
+    pub(crate) fn frame_at(&self, offset: usize) -> Option<PhysAddr> {
+        self.frames.get(offset / PAGE_SIZE_4K).copied()
+    }
+}
+
+impl Drop for SharedMemory {
+    fn drop(&mut self) {
+        for &frame in &self.frames {
+            dealloc_frame(frame);
+        }
+    }
+}
@@ -0,0 +1,401 @@
+//! Swapping anonymous pages out to a swap area.
+//!
+//! The pages of lazy allocation mappings (see [`Backend::Alloc`]) can be
+//! swapped out to the swap area set by [`swap_on`] when the free memory runs
+//! low, and are swapped in again on page faults. A swapped-out page is
+//! recorded in its non-present page table entry, whose physical address is
+//! tagged by [`SWAP_ENTRY_TAG`] and holds the slot number in the swap area.
+//!
+//! The swap area must be initialized by `mkswap`: its first page holds the
+//! header, which is checked by [`swap_on`], and is never used as a slot.
+//!
+//! Pages are reclaimed from the address space of the faulting task, by a
+//! clock algorithm on the accessed bits of the page table entries, see
+//! [`AddrSpace::reclaim`].
+//!
+//! A slot may be shared by several address spaces after
+//! [`AddrSpace::clone_cow`], so the slots are reference counted like the
+//! frames.
+//!
+//! [`Backend::Alloc`]: crate::backend::Backend::Alloc
+//! [`AddrSpace::clone_cow`]: crate::AddrSpace::clone_cow
+//! [`AddrSpace::reclaim`]: crate::AddrSpace::reclaim
+
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use axalloc::global_allocator;
+use axdriver::prelude::{AxBlockDevice, BlockDriverOps};
+use axerrno::{ax_err, AxError, AxResult};
+use axhal::mem::phys_to_virt;
+use axhal::paging::{GenericPTE, MappingFlags, PageSize, PageTable, PTE_ACCESSED};
+use kspin::{SpinNoIrq, SpinNoPreempt};
+use memory_addr::{PhysAddr, VirtAddr, PAGE_SIZE_4K};
+
+use crate::huge::entry_mut;
+use crate::tlb::flush_local;
+use crate::MmapFile;
+
+/// The bit of the physical address in the page table entries that marks them
+/// as swap entries, which is beyond the physical memory of all the supported
+/// platforms, and is within the address bits of their page table entries.
+const SWAP_ENTRY_TAG: usize = 1 << 46;
+
+/// Pages are reclaimed when the number of free pages is below this.
+const LOW_WATERMARK_PAGES: usize = 256;
+
+/// The maximum number of pages reclaimed at a time.
+pub(crate) const RECLAIM_BATCH_PAGES: usize = 32;
+
+/// The magic at the end of the header page written by `mkswap`.
+const SWAP_MAGIC: &[u8] = b"SWAPSPACE2";
+
+/// The supported version of the swap header.
+const SWAP_VERSION: u32 = 1;
+
+/// The offset of the version, the last page and the number of bad pages in
+/// the header page, which are followed by the UUID and the label.
+const SWAP_INFO_OFFSET: usize = 1024;
+
+/// The offset of the bad page list in the header page.
+const SWAP_BAD_PAGES_OFFSET: usize = SWAP_INFO_OFFSET + 512;
+
+/// The maximum number of bad pages in the header page.
+const MAX_SWAP_BAD_PAGES: usize = (PAGE_SIZE_4K - SWAP_MAGIC.len() - SWAP_BAD_PAGES_OFFSET) / 4;
+
+/// A swap area, i.e., a block device or a file that stores the swapped-out
+/// pages.
+pub trait SwapDevice: Send + Sync {
+    /// Returns the number of 4K pages that can be stored.
+    fn num_pages(&self) -> usize;
+
+    /// Reads the page at the given index into `buf`, which is 4K bytes.
+    fn read_page(&self, index: usize, buf: &mut [u8]) -> AxResult;
+
+    /// Writes the page at the given index from `buf`, which is 4K bytes.
+    fn write_page(&self, index: usize, buf: &[u8]) -> AxResult;
+}
+
+/// A swap area on a block device, which takes the whole device.
+///
+/// The device is locked by a spinlock that keeps IRQs enabled during the I/O,
+/// as the sleeping locks are not available to this module.
+pub struct BlockSwap {
+    dev: SpinNoPreempt<AxBlockDevice>,
+    blocks_per_page: usize,
+}
+
+impl BlockSwap {
+    /// Creates a swap area on the block device.
+    pub fn new(dev: AxBlockDevice) -> Self {
+        let blocks_per_page = PAGE_SIZE_4K / dev.block_size();
+        Self {
+            dev: SpinNoPreempt::new(dev),
+            blocks_per_page,
+        }
+    }
+}
+
+impl SwapDevice for BlockSwap {
+    fn num_pages(&self) -> usize {
+        self.dev.lock().num_blocks() as usize / self.blocks_per_page
+    }
+
+    fn read_page(&self, index: usize, buf: &mut [u8]) -> AxResult {
+        let mut dev = self.dev.lock();
+        let block_size = dev.block_size();
+        let first_block = (index * self.blocks_per_page) as u64;
+        for (i, block) in buf.chunks_exact_mut(block_size).enumerate() {
+            dev.read_block(first_block + i as u64, block)
+                .map_err(|_| AxError::Io)?;
+        }
+        Ok(())
+    }
+
+    fn write_page(&self, index: usize, buf: &[u8]) -> AxResult {
+        let mut dev = self.dev.lock();
+        let block_size = dev.block_size();
+        let first_block = (index * self.blocks_per_page) as u64;
+        for (i, block) in buf.chunks_exact(block_size).enumerate() {
+            dev.write_block(first_block + i as u64, block)
+                .map_err(|_| AxError::Io)?;
+        }
+        Ok(())
+    }
+}
+
+/// A swap area in a file, which takes the current size of the file.
+pub struct SwapFile {
+    file: Arc<dyn MmapFile>,
+    num_pages: usize,
+}
+
+impl SwapFile {
+    /// Creates a swap area in the file.
+    pub fn new(file: Arc<dyn MmapFile>) -> AxResult<Self> {
+        let num_pages = (file.size()? / PAGE_SIZE_4K as u64) as usize;
+        Ok(Self { file, num_pages })
+    }
+}
+
+impl SwapDevice for SwapFile {
+    fn num_pages(&self) -> usize {
+        self.num_pages
+    }
+
+    fn read_page(&self, index: usize, buf: &mut [u8]) -> AxResult {
+        let offset = (index * PAGE_SIZE_4K) as u64;
+        let mut read = 0;
+        while read < buf.len() {
+            match self.file.read_at(offset + read as u64, &mut buf[read..])? {
+                0 => return Err(AxError::Io),
+                n => read += n,
+            }
+        }
+        Ok(())
+    }
+
+    fn write_page(&self, index: usize, buf: &[u8]) -> AxResult {
+        let offset = (index * PAGE_SIZE_4K) as u64;
+        let mut written = 0;
+        while written < buf.len() {
+            match self
+                .file
+                .write_at(offset + written as u64, &buf[written..])?
+            {
+                0 => return Err(AxError::Io),
+                n => written += n,
+            }
+        }
+        Ok(())
+    }
+}
+
+struct SwapArea {
+    dev: Arc<dyn SwapDevice>,
+    /// The reference counts of the slots, 0 for free slots. The header page
+    /// and the bad pages are never freed.
+    counts: Vec<usize>,
+    /// Where to start searching for a free slot.
+    next: usize,
+    /// The number of usable slots.
+    total: usize,
+    /// The number of free slots.
+    free: usize,
+}
+
+static SWAP_AREA: SpinNoIrq<Option<SwapArea>> = SpinNoIrq::new(None);
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_ne_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+/// Checks the header page of the swap area, and returns the reference counts
+/// of the slots, in which the header page and the bad pages are in use.
+fn parse_header(header: &[u8], num_pages: usize) -> AxResult<Vec<usize>> {
+    if &header[PAGE_SIZE_4K - SWAP_MAGIC.len()..] != SWAP_MAGIC {
+        return ax_err!(InvalidData, "no swap header, not initialized by mkswap?");
+    }
+    let version = read_u32(header, SWAP_INFO_OFFSET);
+    if version != SWAP_VERSION {
+        return ax_err!(InvalidData, "unsupported swap header version");
+    }
+    let last_page = read_u32(header, SWAP_INFO_OFFSET + 4) as usize;
+    if last_page == 0 || last_page >= num_pages {
+        return ax_err!(InvalidData, "invalid size in the swap header");
+    }
+    let num_bad_pages = read_u32(header, SWAP_INFO_OFFSET + 8) as usize;
+    if num_bad_pages > MAX_SWAP_BAD_PAGES {
+        return ax_err!(InvalidData, "too many bad pages in the swap header");
+    }
+    let mut counts = vec![0; last_page + 1];
+    counts[0] = 1;
+    for i in 0..num_bad_pages {
+        let page = read_u32(header, SWAP_BAD_PAGES_OFFSET + i * 4) as usize;
+        if page == 0 || page > last_page {
+            return ax_err!(InvalidData, "invalid bad page in the swap header");
+        }
+        counts[page] = 1;
+    }
+    Ok(counts)
+}
+
+/// Enables swapping to the given swap area.
+///
+/// Returns an error if a swap area has been enabled, or the swap area has no
+/// valid header.
+pub fn swap_on(dev: Arc<dyn SwapDevice>) -> AxResult {
+    let mut header = vec![0; PAGE_SIZE_4K];
+    dev.read_page(0, &mut header)?;
+    let counts = parse_header(&header, dev.num_pages())?;
+    let total = counts.iter().filter(|&&count| count == 0).count();
+    if total == 0 {
+        return ax_err!(InvalidData, "no usable pages in the swap area");
+    }
+    let mut area = SWAP_AREA.lock();
+    if area.is_some() {
+        return ax_err!(AlreadyExists, "swap area already enabled");
+    }
+    info!("Enable swapping: {} pages", total);
+    *area = Some(SwapArea {
+        dev,
+        counts,
+        next: 1,
+        total,
+        free: total,
+    });
+    Ok(())
+}
+
+/// Disables swapping.
+///
+/// Returns an error if swapping is not enabled, or some pages are still
+/// swapped out.
+pub fn swap_off() -> AxResult {
+    let mut area = SWAP_AREA.lock();
+    match area.as_ref() {
+        None => ax_err!(BadState, "swapping not enabled"),
+        Some(a) if a.free != a.total => ax_err!(ResourceBusy, "swap area in use"),
+        Some(_) => {
+            info!("Disable swapping");
+            *area = None;
+            Ok(())
+        }
+    }
+}
+
+/// Returns the total and free number of pages in the swap area, or [`None`]
+/// if swapping is not enabled.
+pub fn swap_pages() -> Option<(usize, usize)> {
+    SWAP_AREA
+        .lock()
+        .as_ref()
+        .map(|area| (area.total, area.free))
+}
+
+/// Returns true if swapping is enabled and the free memory runs low.
+pub(crate) fn should_reclaim() -> bool {
+    global_allocator().available_pages() < LOW_WATERMARK_PAGES
+        && SWAP_AREA.lock().as_ref().is_some_and(|area| area.free > 0)
+}
+
+/// Returns the physical address in the swap entry of the slot.
+pub(crate) fn swap_entry(slot: usize) -> PhysAddr {
+    PhysAddr::from(SWAP_ENTRY_TAG | (slot * PAGE_SIZE_4K))
+}
+
+/// Returns the slot of the page table entry with the given physical address
+/// and flags, or [`None`] if it's not a swap entry.
+pub(crate) fn swap_slot(paddr: PhysAddr, flags: MappingFlags) -> Option<usize> {
+    let paddr = paddr.as_usize();
+    if flags.is_empty() && paddr & SWAP_ENTRY_TAG != 0 {
+        Some((paddr & !SWAP_ENTRY_TAG) / PAGE_SIZE_4K)
+    } else {
+        None
+    }
+}
+
+fn frame_slice(frame: PhysAddr) -> &'static mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut(phys_to_virt(frame).as_mut_ptr(), PAGE_SIZE_4K) }
+}
+
+/// Returns the entry of the resident 4K page at `vaddr` as an atomic, as the
+/// CPU may set its accessed bit at the same time.
+fn present_entry(pt: &mut PageTable, vaddr: VirtAddr) -> Option<&AtomicU64> {
+    let entry = entry_mut(pt, vaddr, PageSize::Size4K).ok()?;
+    if !entry.is_present() {
+        return None;
+    }
+    // SAFETY: the entries are `#[repr(transparent)]` wrappers of `u64`.
+    Some(unsafe { AtomicU64::from_ptr(entry as *mut _ as *mut u64) })
+}
+
+/// Clears the accessed bit of the resident 4K page at `vaddr`, and returns
+/// whether it was set.
+pub(crate) fn test_and_clear_accessed(pt: &mut PageTable, vaddr: VirtAddr) -> bool {
+    let Some(entry) = present_entry(pt, vaddr) else {
+        return false;
+    };
+    if entry.fetch_and(!PTE_ACCESSED, Ordering::Relaxed) & PTE_ACCESSED == 0 {
+        return false;
+    }
+    // other CPUs may still cache the entry with the bit set, which only makes
+    // the page look idle for longer.
+    flush_local(Some(vaddr));
+    true
+}
+
+/// Sets the accessed bit of the resident 4K page at `vaddr`, and returns
+/// whether it was clear, i.e., the page fault at `vaddr` may be caused by the
+/// cleared bit and is handled.
+pub(crate) fn set_accessed(pt: &mut PageTable, vaddr: VirtAddr) -> bool {
+    let Some(entry) = present_entry(pt, vaddr) else {
+        return false;
+    };
+    if entry.fetch_or(PTE_ACCESSED, Ordering::Relaxed) & PTE_ACCESSED != 0 {
+        return false;
+    }
+    flush_local(Some(vaddr));
+    true
+}
+
+/// Writes the contents of the frame to a free slot in the swap area, and
+/// returns the slot with the reference count of 1.
+pub(crate) fn swap_out_frame(frame: PhysAddr) -> AxResult<usize> {
+    let (dev, slot) = {
+        let mut guard = SWAP_AREA.lock();
+        let area = guard.as_mut().ok_or(AxError::Unsupported)?;
+        if area.free == 0 {
+            return Err(AxError::NoMemory);
+        }
+        let len = area.counts.len();
+        let slot = (0..len)
+            .map(|i| (area.next + i) % len)
+            .find(|&i| area.counts[i] == 0)
+            .unwrap();
+        area.counts[slot] = 1;
+        area.free -= 1;
+        area.next = (slot + 1) % len;
+        (area.dev.clone(), slot)
+    };
+    if let Err(e) = dev.write_page(slot, frame_slice(frame)) {
+        free_slot(slot);
+        return Err(e);
+    }
+    Ok(slot)
+}
+
+/// Reads the page in the slot into the frame, and drops the reference to the
+/// slot.
+pub(crate) fn swap_in_frame(slot: usize, frame: PhysAddr) -> AxResult {
+    let dev = SWAP_AREA
+        .lock()
+        .as_ref()
+        .ok_or(AxError::BadState)?
+        .dev
+        .clone();
+    dev.read_page(slot, frame_slice(frame))?;
+    free_slot(slot);
+    Ok(())
+}
+
+/// Increases the reference count of the slot, when it's recorded by another
+/// page table entry.
+pub(crate) fn share_slot(slot: usize) {
+    if let Some(area) = SWAP_AREA.lock().as_mut() {
+        area.counts[slot] += 1;
+    }
+}
+
+/// Decreases the reference count of the slot, and frees it if there are no
+/// other references.
+pub(crate) fn free_slot(slot: usize) {
+    if let Some(area) = SWAP_AREA.lock().as_mut() {
+        area.counts[slot] -= 1;
+        if area.counts[slot] == 0 {
+            area.free += 1;
+        }
+    }
+}
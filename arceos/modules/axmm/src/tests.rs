@@ -0,0 +1,818 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Once};
+use std::time::Duration;
+
+use axalloc::global_allocator;
+use axerrno::{AxError, AxResult};
+use axhal::paging::{MappingFlags, PageSize};
+use memory_addr::{va, VirtAddr, VirtAddrRange, PAGE_SIZE_4K};
+
+use crate::frame::{alloc_frame, dealloc_frame, share_frame};
+use crate::huge::max_page_size;
+use crate::tlb::Shootdowns;
+use crate::{
+    frame_ref_count, AddrSpace, AreaInfo, BackendKind, LoadedFilePages, MmapFile, SharedMemory,
+    STACK_GUARD_GAP,
+};
+
+/// Size of the memory given to the global allocator.
+const POOL_SIZE: usize = 64 * 1024 * 1024;
+
+const USER_FLAGS: MappingFlags = MappingFlags::READ
+    .union(MappingFlags::WRITE)
+    .union(MappingFlags::USER);
+
+static INIT: Once = Once::new();
+static SERIAL: Mutex<()> = Mutex::new(());
+
+/// Initializes the global allocator with memory from the host, whose
+/// addresses are also the physical addresses on the dummy platform.
+fn init_allocator() {
+    let layout = std::alloc::Layout::from_size_align(POOL_SIZE, 0x20_0000).unwrap();
+    let pool = unsafe { std::alloc::alloc(layout) };
+    assert!(!pool.is_null());
+    axalloc::global_init(pool as usize, POOL_SIZE);
+}
+
+fn new_aspace() -> AddrSpace {
+    AddrSpace::new_empty(va!(0x1000_0000), 0x1000_0000).unwrap()
+}
+
+/// A file in memory to be mapped.
+struct MemFile(Mutex<Vec<u8>>);
+
+impl MemFile {
+    fn new(data: Vec<u8>) -> Arc<Self> {
+        Arc::new(Self(Mutex::new(data)))
+    }
+
+    fn data(&self) -> Vec<u8> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl MmapFile for MemFile {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize> {
+        let data = self.0.lock().unwrap();
+        let start = (offset as usize).min(data.len());
+        let len = buf.len().min(data.len() - start);
+        buf[..len].copy_from_slice(&data[start..start + len]);
+        Ok(len)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> AxResult<usize> {
+        let mut data = self.0.lock().unwrap();
+        let end = offset as usize + buf.len();
+        if data.len() < end {
+            data.resize(end, 0);
+        }
+        data[offset as usize..end].copy_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn size(&self) -> AxResult<u64> {
+        Ok(self.0.lock().unwrap().len() as u64)
+    }
+}
+
+fn query(aspace: &AddrSpace, vaddr: VirtAddr) -> (usize, MappingFlags) {
+    let (paddr, flags, _) = aspace.page_table().query(vaddr).unwrap();
+    (paddr.as_usize(), flags)
+}
+
+fn page_size_of(aspace: &AddrSpace, vaddr: VirtAddr) -> PageSize {
+    aspace.page_table().query(vaddr).unwrap().2
+}
+
+const SIZE_2M: usize = 0x20_0000;
+
+#[test]
+fn test_frame_ref_count() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(init_allocator);
+
+    let used = global_allocator().used_pages();
+    let frame = alloc_frame(true).unwrap();
+    assert_eq!(frame_ref_count(frame), 1);
+    share_frame(frame);
+    share_frame(frame);
+    assert_eq!(frame_ref_count(frame), 3);
+
+    // the frame is deallocated when the last reference is dropped.
+    dealloc_frame(frame);
+    assert_eq!(frame_ref_count(frame), 2);
+    dealloc_frame(frame);
+    assert_eq!(frame_ref_count(frame), 1);
+    assert_eq!(global_allocator().used_pages(), used + 1);
+    dealloc_frame(frame);
+    assert_eq!(global_allocator().used_pages(), used);
+}
+
+#[test]
+fn test_clone_cow() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(init_allocator);
+
+    let used = global_allocator().used_pages();
+    let mut parent = new_aspace();
+    let start = parent.base();
+    let second = start + PAGE_SIZE_4K;
+    parent
+        .map_alloc(start, 2 * PAGE_SIZE_4K, USER_FLAGS, false)
+        .unwrap();
+    parent.populate(start, 2 * PAGE_SIZE_4K).unwrap();
+    parent.write(start, b"parent").unwrap();
+
+    // the frames are shared and write-protected in both address spaces.
+    let mut child = parent.clone_cow().unwrap();
+    let (frame, flags) = query(&parent, start);
+    assert_eq!(query(&child, start), (frame, flags));
+    assert!(!flags.contains(MappingFlags::WRITE));
+    assert_eq!(frame_ref_count(frame.into()), 2);
+    assert_eq!(child.rss(), parent.rss());
+
+    // the child writes to a copy of the frame.
+    assert!(child.handle_page_fault(start, MappingFlags::WRITE));
+    let (child_frame, child_flags) = query(&child, start);
+    assert_ne!(child_frame, frame);
+    assert_eq!(child_flags, USER_FLAGS);
+    assert_eq!(frame_ref_count(frame.into()), 1);
+    child.write(start, b"child!").unwrap();
+
+    // the parent owns the frame alone now, which is reused.
+    assert!(parent.handle_page_fault(start, MappingFlags::WRITE));
+    assert_eq!(query(&parent, start), (frame, USER_FLAGS));
+
+    // `write` copies the shared pages as well.
+    let (frame, _) = query(&parent, second);
+    child.write(second, b"child!").unwrap();
+    assert_ne!(query(&child, second).0, frame);
+    assert_eq!(frame_ref_count(frame.into()), 1);
+
+    let mut buf = [0; 6];
+    parent.read(start, &mut buf).unwrap();
+    assert_eq!(&buf, b"parent");
+    child.read(start, &mut buf).unwrap();
+    assert_eq!(&buf, b"child!");
+    parent.read(second, &mut buf).unwrap();
+    assert_eq!(buf, [0; 6]);
+    child.read(second, &mut buf).unwrap();
+    assert_eq!(&buf, b"child!");
+
+    // all the frames and page tables are deallocated on drop.
+    drop(parent);
+    drop(child);
+    assert_eq!(global_allocator().used_pages(), used);
+}
+
+#[test]
+fn test_private_file_mapping() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(init_allocator);
+
+    let used = global_allocator().used_pages();
+    let contents = vec![b'a'; PAGE_SIZE_4K + 16];
+    let file = MemFile::new(contents.clone());
+    let mut aspace = new_aspace();
+    let start = aspace.base();
+    aspace
+        .map_file(start, 2 * PAGE_SIZE_4K, USER_FLAGS, file.clone(), 0, false)
+        .unwrap();
+
+    // the pages are loaded on demand, and zeroed beyond the end of the file.
+    assert!(aspace.handle_page_fault(start + PAGE_SIZE_4K, MappingFlags::READ));
+    assert_eq!(aspace.rss(), PAGE_SIZE_4K);
+    let mut buf = [0; 32];
+    aspace.read(start + PAGE_SIZE_4K, &mut buf).unwrap();
+    assert_eq!(buf[..16], [b'a'; 16]);
+    assert_eq!(buf[16..], [0; 16]);
+
+    // the changes never reach the file.
+    aspace.populate(start, 2 * PAGE_SIZE_4K).unwrap();
+    aspace.write(start, b"private").unwrap();
+    aspace.msync(start, 2 * PAGE_SIZE_4K).unwrap();
+    aspace.unmap(start, 2 * PAGE_SIZE_4K).unwrap();
+    assert_eq!(file.data(), contents);
+    assert_eq!(aspace.rss(), 0);
+
+    drop(aspace);
+    assert_eq!(global_allocator().used_pages(), used);
+}
+
+#[test]
+fn test_shared_file_mapping() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(init_allocator);
+
+    let used = global_allocator().used_pages();
+    let mut contents = vec![b'a'; PAGE_SIZE_4K + 16];
+    let file = MemFile::new(contents.clone());
+    let mut aspace = new_aspace();
+    let start = aspace.base();
+    let size = 2 * PAGE_SIZE_4K;
+    aspace
+        .map_file(start, size, USER_FLAGS, file.clone(), 0, true)
+        .unwrap();
+
+    // the pages loaded in advance are mapped without reading the file.
+    let pages = LoadedFilePages::load(file.clone(), 0, size).unwrap();
+    file.write_at(0, b"changed").unwrap();
+    contents[..7].copy_from_slice(b"changed");
+    aspace.populate_loaded(start, size, pages).unwrap();
+    assert_eq!(aspace.rss(), size);
+    let mut buf = [0; 7];
+    aspace.read(start, &mut buf).unwrap();
+    assert_eq!(buf, [b'a'; 7]);
+
+    // the changes are written back when the pending write back is done,
+    // without extending the file. There is no page cache, so the writes to
+    // the file are overwritten by the writable pages.
+    aspace.write(start + PAGE_SIZE_4K, b"shared").unwrap();
+    aspace.write(start + PAGE_SIZE_4K + 32, b"beyond").unwrap();
+    let pending = aspace.msync_deferred(start, size).unwrap();
+    assert_eq!(file.data(), contents);
+    pending.write_back().unwrap();
+    contents[..7].copy_from_slice(&[b'a'; 7]);
+    contents[PAGE_SIZE_4K..PAGE_SIZE_4K + 6].copy_from_slice(b"shared");
+    assert_eq!(file.data(), contents);
+
+    // and when the mapping is unmapped, after which the pages are kept
+    // until written back.
+    aspace.write(start, b"unmap").unwrap();
+    let pending = aspace.unmap_deferred(start, size).unwrap();
+    assert_eq!(aspace.rss(), 0);
+    assert_eq!(file.data(), contents);
+    pending.write_back().unwrap();
+    contents[..5].copy_from_slice(b"unmap");
+    assert_eq!(file.data(), contents);
+
+    drop(aspace);
+    assert_eq!(global_allocator().used_pages(), used);
+}
+
+#[test]
+fn test_shared_memory() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(init_allocator);
+
+    let used = global_allocator().used_pages();
+    let shm = SharedMemory::new(2 * PAGE_SIZE_4K).unwrap();
+    assert!(SharedMemory::new(PAGE_SIZE_4K + 1).is_err());
+    // accesses across the pages, and truncated at the end.
+    assert_eq!(shm.write_at(PAGE_SIZE_4K - 2, b"span"), 4);
+    assert_eq!(shm.write_at(2 * PAGE_SIZE_4K - 2, b"end"), 2);
+    let mut buf = [0; 4];
+    assert_eq!(shm.read_at(PAGE_SIZE_4K - 2, &mut buf), 4);
+    assert_eq!(&buf, b"span");
+    assert_eq!(shm.read_at(2 * PAGE_SIZE_4K, &mut buf), 0);
+
+    // mapped at different addresses and offsets in two address spaces.
+    let mut a = new_aspace();
+    let mut b = new_aspace();
+    let start = a.base();
+    a.map_shared(start, 2 * PAGE_SIZE_4K, USER_FLAGS, shm.clone(), 0)
+        .unwrap();
+    let b_start = start + 4 * PAGE_SIZE_4K;
+    b.map_shared(b_start, PAGE_SIZE_4K, USER_FLAGS, shm.clone(), PAGE_SIZE_4K)
+        .unwrap();
+    assert!(b
+        .map_shared(
+            start,
+            2 * PAGE_SIZE_4K,
+            USER_FLAGS,
+            shm.clone(),
+            PAGE_SIZE_4K
+        )
+        .is_err());
+    assert_eq!(a.rss(), 2 * PAGE_SIZE_4K);
+    assert_eq!(b.rss(), PAGE_SIZE_4K);
+    assert!(a.shared_memory_at(start + PAGE_SIZE_4K).is_some());
+
+    a.write(start + PAGE_SIZE_4K, b"hello").unwrap();
+    let mut buf = [0; 5];
+    b.read(b_start, &mut buf).unwrap();
+    assert_eq!(&buf, b"hello");
+
+    // still shared rather than copied on write after cloning.
+    let mut child = a.clone_cow().unwrap();
+    child.write(start + PAGE_SIZE_4K, b"child").unwrap();
+    b.read(b_start, &mut buf).unwrap();
+    assert_eq!(&buf, b"child");
+
+    // the frames are deallocated after the last reference is dropped.
+    drop(a);
+    drop(b);
+    drop(child);
+    assert_eq!(global_allocator().used_pages(), used + 2);
+    drop(shm);
+    assert_eq!(global_allocator().used_pages(), used);
+}
+
+#[test]
+fn test_max_page_size() {
+    let end = va!(0x8000_0000);
+    // 1G pages are not used on x86_64.
+    let largest = if cfg!(target_arch = "x86_64") {
+        PageSize::Size2M
+    } else {
+        PageSize::Size1G
+    };
+    assert_eq!(max_page_size(va!(0x4000_0000), end), largest);
+    assert_eq!(max_page_size(va!(0x4020_0000), end), PageSize::Size2M);
+    assert_eq!(
+        max_page_size(va!(0x4020_0000), va!(0x403f_f000)),
+        PageSize::Size4K
+    );
+    assert_eq!(max_page_size(va!(0x4020_1000), end), PageSize::Size4K);
+}
+
+#[test]
+fn test_split_huge_page() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(init_allocator);
+
+    let used = global_allocator().used_pages();
+    let mut aspace = new_aspace();
+    let start = aspace.base();
+    aspace.map_alloc(start, SIZE_2M, USER_FLAGS, true).unwrap();
+    assert_eq!(page_size_of(&aspace, start), PageSize::Size2M);
+    let (frame, _) = query(&aspace, start);
+    aspace.write(start + PAGE_SIZE_4K, b"huge").unwrap();
+
+    // the huge page is split when a part of it is changed, with the same
+    // memory mapped.
+    let ro_flags = USER_FLAGS - MappingFlags::WRITE;
+    aspace
+        .protect(start + PAGE_SIZE_4K, PAGE_SIZE_4K, ro_flags)
+        .unwrap();
+    assert_eq!(page_size_of(&aspace, start), PageSize::Size4K);
+    assert_eq!(query(&aspace, start), (frame, USER_FLAGS));
+    assert_eq!(
+        query(&aspace, start + PAGE_SIZE_4K),
+        (frame + PAGE_SIZE_4K, ro_flags)
+    );
+    let last = start + SIZE_2M - PAGE_SIZE_4K;
+    assert_eq!(
+        query(&aspace, last),
+        (frame + SIZE_2M - PAGE_SIZE_4K, USER_FLAGS)
+    );
+    let mut buf = [0; 4];
+    aspace.read(start + PAGE_SIZE_4K, &mut buf).unwrap();
+    assert_eq!(&buf, b"huge");
+
+    aspace.unmap(start, PAGE_SIZE_4K).unwrap();
+    assert_eq!(aspace.rss(), SIZE_2M - PAGE_SIZE_4K);
+
+    // all the frames and page tables are deallocated on drop.
+    drop(aspace);
+    assert_eq!(global_allocator().used_pages(), used);
+}
+
+#[test]
+fn test_populate_fallback() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(init_allocator);
+
+    let used = global_allocator().used_pages();
+    let mut aspace = new_aspace();
+    let start = aspace.base();
+
+    // a huge page cannot be mapped where the page table of previous 4K
+    // mappings is left.
+    aspace
+        .map_alloc(start, PAGE_SIZE_4K, USER_FLAGS, true)
+        .unwrap();
+    aspace.unmap(start, PAGE_SIZE_4K).unwrap();
+    aspace.map_alloc(start, SIZE_2M, USER_FLAGS, true).unwrap();
+    assert_eq!(page_size_of(&aspace, start), PageSize::Size4K);
+    assert_eq!(page_size_of(&aspace, start + SIZE_2M - 1), PageSize::Size4K);
+
+    // and where the range is not aligned, only the aligned part is mapped by
+    // huge pages.
+    let second = start + 2 * SIZE_2M - PAGE_SIZE_4K;
+    aspace
+        .map_alloc(second, SIZE_2M + PAGE_SIZE_4K, USER_FLAGS, true)
+        .unwrap();
+    assert_eq!(page_size_of(&aspace, second), PageSize::Size4K);
+    assert_eq!(
+        page_size_of(&aspace, second + PAGE_SIZE_4K),
+        PageSize::Size2M
+    );
+    assert_eq!(aspace.rss(), 2 * SIZE_2M + PAGE_SIZE_4K);
+
+    drop(aspace);
+    assert_eq!(global_allocator().used_pages(), used);
+}
+
+#[test]
+fn test_rss() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(init_allocator);
+
+    let used = global_allocator().used_pages();
+    let mut aspace = new_aspace();
+    let start = aspace.base();
+    aspace
+        .map_alloc(start, 4 * PAGE_SIZE_4K, USER_FLAGS, false)
+        .unwrap();
+    assert_eq!(aspace.rss(), 0);
+    aspace.populate(start, 2 * PAGE_SIZE_4K).unwrap();
+    assert!(aspace.handle_page_fault(start + 3 * PAGE_SIZE_4K, MappingFlags::WRITE));
+    assert_eq!(aspace.rss(), 3 * PAGE_SIZE_4K);
+
+    // inaccessible pages are still resident, and never faulted in again.
+    aspace
+        .protect(start, 2 * PAGE_SIZE_4K, MappingFlags::empty())
+        .unwrap();
+    assert_eq!(aspace.rss(), 3 * PAGE_SIZE_4K);
+    assert!(!aspace.handle_page_fault(start, MappingFlags::READ));
+    assert_eq!(aspace.areas().next().unwrap().resident_pages, 3);
+
+    // including the copies.
+    let child = aspace.clone_cow().unwrap();
+    assert_eq!(child.rss(), 3 * PAGE_SIZE_4K);
+    drop(child);
+
+    aspace.unmap(start, PAGE_SIZE_4K).unwrap();
+    assert_eq!(aspace.rss(), 2 * PAGE_SIZE_4K);
+    aspace.unmap(start, 4 * PAGE_SIZE_4K).unwrap();
+    assert_eq!(aspace.rss(), 0);
+
+    drop(aspace);
+    assert_eq!(global_allocator().used_pages(), used);
+}
+
+#[test]
+fn test_area_info() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(init_allocator);
+
+    let info = AreaInfo {
+        start: va!(0x1000),
+        end: va!(0x3000),
+        flags: MappingFlags::READ | MappingFlags::EXECUTE,
+        kind: BackendKind::File,
+        shared: false,
+        offset: 0x2000,
+        resident_pages: 1,
+    };
+    assert_eq!(info.size(), 0x2000);
+    assert_eq!(info.to_string(), "00001000-00003000 r-xp 00002000 00:00 0");
+
+    let used = global_allocator().used_pages();
+    let mut aspace = new_aspace();
+    let start = aspace.base();
+    let file = MemFile::new(vec![0; 4 * PAGE_SIZE_4K]);
+    aspace
+        .map_alloc(start, 3 * PAGE_SIZE_4K, USER_FLAGS, true)
+        .unwrap();
+    let file_start = start + 4 * PAGE_SIZE_4K;
+    let file_flags = MappingFlags::READ | MappingFlags::USER;
+    aspace
+        .map_file(file_start, PAGE_SIZE_4K, file_flags, file, 0x2000, true)
+        .unwrap();
+    // split by unmapping the middle of the area.
+    aspace.unmap(start + PAGE_SIZE_4K, PAGE_SIZE_4K).unwrap();
+
+    let areas: Vec<_> = aspace.areas().collect();
+    assert_eq!(areas.len(), 3);
+    assert!(areas[..2]
+        .iter()
+        .all(|area| area.kind == BackendKind::Alloc && area.resident_pages == 1));
+    assert_eq!(areas[2].kind, BackendKind::File);
+    assert_eq!(areas[2].resident_pages, 0);
+    let lines: Vec<_> = areas.iter().map(|area| area.to_string()).collect();
+    assert_eq!(
+        lines,
+        [
+            "10000000-10001000 rw-p 00000000 00:00 0",
+            "10002000-10003000 rw-p 00000000 00:00 0",
+            "10004000-10005000 r--s 00002000 00:00 0",
+        ]
+    );
+    assert_eq!(aspace.vsz(), 3 * PAGE_SIZE_4K);
+    assert_eq!(aspace.rss(), 2 * PAGE_SIZE_4K);
+
+    drop(aspace);
+    assert_eq!(global_allocator().used_pages(), used);
+}
+
+#[test]
+fn test_stack_growth() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(init_allocator);
+
+    let used = global_allocator().used_pages();
+    let mut aspace = new_aspace();
+    let top = aspace.end();
+    aspace
+        .map_stack(top, 4 * PAGE_SIZE_4K, 16 * PAGE_SIZE_4K, USER_FLAGS, false)
+        .unwrap();
+    assert_eq!(aspace.vsz(), 4 * PAGE_SIZE_4K);
+    assert!(aspace.handle_page_fault(top - PAGE_SIZE_4K, MappingFlags::WRITE));
+
+    // faults below the stack grow it down to the faulting page.
+    assert!(aspace.handle_page_fault(top - 10 * PAGE_SIZE_4K + 8, MappingFlags::WRITE));
+    assert_eq!(aspace.vsz(), 10 * PAGE_SIZE_4K);
+    aspace.write(top - 10 * PAGE_SIZE_4K, b"grown").unwrap();
+
+    // but not below the limit.
+    let limit = top - 16 * PAGE_SIZE_4K;
+    assert!(!aspace.handle_page_fault(limit - 1, MappingFlags::WRITE));
+    assert!(aspace.handle_page_fault(limit, MappingFlags::WRITE));
+    assert_eq!(aspace.vsz(), 16 * PAGE_SIZE_4K);
+    assert_eq!(
+        aspace.set_stack_limit(8 * PAGE_SIZE_4K),
+        Err(AxError::InvalidInput)
+    );
+    aspace.set_stack_limit(1024 * PAGE_SIZE_4K).unwrap();
+
+    // a mapping added in the reserved range stops the stack at the guard gap
+    // above it.
+    let other_end = top - 512 * PAGE_SIZE_4K;
+    aspace
+        .map_alloc(other_end - PAGE_SIZE_4K, PAGE_SIZE_4K, USER_FLAGS, false)
+        .unwrap();
+    let gap_end = other_end + STACK_GUARD_GAP;
+    assert!(!aspace.handle_page_fault(gap_end - PAGE_SIZE_4K, MappingFlags::WRITE));
+    assert!(aspace.handle_page_fault(gap_end, MappingFlags::WRITE));
+    assert_eq!(aspace.vsz(), 257 * PAGE_SIZE_4K);
+
+    drop(aspace);
+    assert_eq!(global_allocator().used_pages(), used);
+}
+
+#[test]
+fn test_find_free_area_with_stack() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(init_allocator);
+
+    let used = global_allocator().used_pages();
+    let mut aspace = new_aspace();
+    let (base, top) = (aspace.base(), aspace.end());
+    let limit = VirtAddrRange::new(base, top);
+    aspace
+        .map_stack(top, 4 * PAGE_SIZE_4K, 256 * PAGE_SIZE_4K, USER_FLAGS, false)
+        .unwrap();
+    let reserved_start = top - 256 * PAGE_SIZE_4K - STACK_GUARD_GAP;
+    let size = 200 * PAGE_SIZE_4K;
+
+    // the free area at the hint overlaps the reserved range, so the one below
+    // it is found instead.
+    let hint = reserved_start - 100 * PAGE_SIZE_4K;
+    assert_eq!(aspace.find_free_area(hint, size, limit), Some(base));
+
+    // the free area left below the reserved range is too small, and there is
+    // nothing above the stack.
+    aspace
+        .map_alloc(base, hint.as_usize() - base.as_usize(), USER_FLAGS, false)
+        .unwrap();
+    assert_eq!(aspace.find_free_area(base, size, limit), None);
+    assert_eq!(
+        aspace.find_free_area(base, 100 * PAGE_SIZE_4K, limit),
+        Some(hint)
+    );
+
+    drop(aspace);
+    assert_eq!(global_allocator().used_pages(), used);
+}
+
+#[test]
+fn test_shootdown_skips_idle_cpus() {
+    static SHOOTDOWNS: Shootdowns<2> = Shootdowns::new();
+    // CPU 1 is idle, and flushes before running other tasks.
+    SHOOTDOWNS.shootdown(0);
+    SHOOTDOWNS.handle(1, true);
+    SHOOTDOWNS.shootdown(0);
+}
+
+#[test]
+fn test_shootdown_waits_with_irqs_disabled() {
+    static SHOOTDOWNS: Shootdowns<2> = Shootdowns::new();
+    static DONE: AtomicBool = AtomicBool::new(false);
+    SHOOTDOWNS.handle(1, false);
+    // CPU 0 never takes IRQs while shooting down, like in the page fault
+    // handler, and must not free the reclaimed frames before CPU 1 flushes.
+    let cpu0 = std::thread::spawn(|| {
+        SHOOTDOWNS.shootdown(0);
+        DONE.store(true, Ordering::SeqCst);
+    });
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(!DONE.load(Ordering::SeqCst));
+    // the timer tick on CPU 1.
+    SHOOTDOWNS.handle(1, false);
+    cpu0.join().unwrap();
+    assert!(DONE.load(Ordering::SeqCst));
+}
+
+#[test]
+fn test_concurrent_shootdowns() {
+    static SHOOTDOWNS: Shootdowns<2> = Shootdowns::new();
+    SHOOTDOWNS.handle(0, false);
+    SHOOTDOWNS.handle(1, false);
+    // each CPU flushes for the other one while waiting for it.
+    let cpus: Vec<_> = (0..2)
+        .map(|cpu_id| {
+            std::thread::spawn(move || {
+                for _ in 0..1000 {
+                    SHOOTDOWNS.shootdown(cpu_id);
+                }
+            })
+        })
+        .collect();
+    for cpu in cpus {
+        cpu.join().unwrap();
+    }
+}
+
+#[cfg(feature = "swap")]
+mod swap {
+    use axhal::mem::phys_to_virt;
+    use memory_addr::{pa, PhysAddr};
+
+    use super::*;
+    use crate::swap::{
+        free_slot, share_slot, swap_entry, swap_in_frame, swap_out_frame, swap_slot,
+    };
+    use crate::{swap_off, swap_on, swap_pages, SwapDevice};
+
+    /// A swap area in memory.
+    struct MemSwap(Mutex<Vec<u8>>);
+
+    impl MemSwap {
+        /// Creates a swap area of `num_pages`, with the header written by
+        /// `mkswap`.
+        fn new(num_pages: usize, bad_pages: &[u32]) -> Self {
+            let swap = Self(Mutex::new(vec![0; num_pages * PAGE_SIZE_4K]));
+            swap.set_u32(1024, 1);
+            swap.set_u32(1028, num_pages as u32 - 1);
+            swap.set_u32(1032, bad_pages.len() as u32);
+            for (i, &page) in bad_pages.iter().enumerate() {
+                swap.set_u32(1536 + i * 4, page);
+            }
+            swap.0.lock().unwrap()[PAGE_SIZE_4K - 10..PAGE_SIZE_4K].copy_from_slice(b"SWAPSPACE2");
+            swap
+        }
+
+        fn set_u32(&self, offset: usize, val: u32) {
+            self.0.lock().unwrap()[offset..offset + 4].copy_from_slice(&val.to_ne_bytes());
+        }
+    }
+
+    impl SwapDevice for MemSwap {
+        fn num_pages(&self) -> usize {
+            self.0.lock().unwrap().len() / PAGE_SIZE_4K
+        }
+
+        fn read_page(&self, index: usize, buf: &mut [u8]) -> AxResult {
+            let offset = index * PAGE_SIZE_4K;
+            buf.copy_from_slice(&self.0.lock().unwrap()[offset..offset + PAGE_SIZE_4K]);
+            Ok(())
+        }
+
+        fn write_page(&self, index: usize, buf: &[u8]) -> AxResult {
+            let offset = index * PAGE_SIZE_4K;
+            self.0.lock().unwrap()[offset..offset + PAGE_SIZE_4K].copy_from_slice(buf);
+            Ok(())
+        }
+    }
+
+    fn frame_data(frame: PhysAddr) -> &'static mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(phys_to_virt(frame).as_mut_ptr(), PAGE_SIZE_4K) }
+    }
+
+    fn is_swapped_out(aspace: &AddrSpace, vaddr: VirtAddr) -> bool {
+        let (paddr, flags) = query(aspace, vaddr);
+        swap_slot(pa!(paddr), flags).is_some()
+    }
+
+    #[test]
+    fn test_swap_entry() {
+        for slot in [0, 1, 0x1234_5678] {
+            let entry = swap_entry(slot);
+            assert_eq!(swap_slot(entry, MappingFlags::empty()), Some(slot));
+            // present entries are never swap entries.
+            assert_eq!(swap_slot(entry, USER_FLAGS), None);
+        }
+        assert_eq!(swap_slot(pa!(0), MappingFlags::empty()), None);
+        assert_eq!(swap_slot(pa!(0x8000_0000), MappingFlags::empty()), None);
+    }
+
+    #[test]
+    fn test_swap_header() {
+        let _lock = SERIAL.lock();
+
+        let zeroed = MemSwap(Mutex::new(vec![0; 16 * PAGE_SIZE_4K]));
+        assert_eq!(swap_on(Arc::new(zeroed)), Err(AxError::InvalidData));
+        let bad_version = MemSwap::new(16, &[]);
+        bad_version.set_u32(1024, 2);
+        assert_eq!(swap_on(Arc::new(bad_version)), Err(AxError::InvalidData));
+        let too_large = MemSwap::new(16, &[]);
+        too_large.set_u32(1028, 16);
+        assert_eq!(swap_on(Arc::new(too_large)), Err(AxError::InvalidData));
+        for bad_page in [0, 16] {
+            let swap = MemSwap::new(16, &[bad_page]);
+            assert_eq!(swap_on(Arc::new(swap)), Err(AxError::InvalidData));
+        }
+        assert_eq!(swap_pages(), None);
+
+        // the header page and the bad pages are not usable.
+        swap_on(Arc::new(MemSwap::new(16, &[3, 5]))).unwrap();
+        assert_eq!(swap_pages(), Some((13, 13)));
+        let swap = MemSwap::new(16, &[]);
+        assert_eq!(swap_on(Arc::new(swap)), Err(AxError::AlreadyExists));
+        swap_off().unwrap();
+        assert_eq!(swap_pages(), None);
+    }
+
+    #[test]
+    fn test_swap_slots() {
+        let _lock = SERIAL.lock();
+        INIT.call_once(init_allocator);
+
+        let used = global_allocator().used_pages();
+        swap_on(Arc::new(MemSwap::new(4, &[2]))).unwrap();
+        let frame = alloc_frame(true).unwrap();
+        frame_data(frame).fill(0x5a);
+
+        let slot = swap_out_frame(frame).unwrap();
+        assert_eq!(slot, 1);
+        assert_eq!(swap_pages(), Some((2, 1)));
+        // a shared slot is freed when the last reference is dropped.
+        share_slot(slot);
+        free_slot(slot);
+        assert_eq!(swap_pages(), Some((2, 1)));
+        // the bad page is skipped.
+        let other = swap_out_frame(frame).unwrap();
+        assert_eq!(other, 3);
+        assert_eq!(swap_out_frame(frame), Err(AxError::NoMemory));
+        assert_eq!(swap_off(), Err(AxError::ResourceBusy));
+
+        frame_data(frame).fill(0);
+        swap_in_frame(slot, frame).unwrap();
+        assert!(frame_data(frame).iter().all(|&b| b == 0x5a));
+        assert_eq!(swap_pages(), Some((2, 1)));
+        free_slot(other);
+        assert_eq!(swap_pages(), Some((2, 2)));
+
+        dealloc_frame(frame);
+        swap_off().unwrap();
+        assert_eq!(global_allocator().used_pages(), used);
+    }
+
+    #[test]
+    fn test_reclaim() {
+        let _lock = SERIAL.lock();
+        INIT.call_once(init_allocator);
+
+        let used = global_allocator().used_pages();
+        swap_on(Arc::new(MemSwap::new(16, &[]))).unwrap();
+        let mut aspace = new_aspace();
+        let start = aspace.base();
+        let pages: Vec<_> = (0..4).map(|i| start + i * PAGE_SIZE_4K).collect();
+        aspace
+            .map_alloc(start, 4 * PAGE_SIZE_4K, USER_FLAGS, false)
+            .unwrap();
+        for (i, &vaddr) in pages.iter().enumerate() {
+            assert!(aspace.handle_page_fault(vaddr, MappingFlags::WRITE));
+            aspace.write(vaddr, &[i as u8 + 1; 8]).unwrap();
+        }
+
+        // the CPU never sets the accessed bits on the host, so page 0 is the
+        // only one accessed, by faulting on its cleared bit.
+        assert!(aspace.handle_page_fault(pages[0], MappingFlags::READ));
+        assert_eq!(aspace.reclaim(1), 1);
+        assert!(!is_swapped_out(&aspace, pages[0]));
+        assert!(is_swapped_out(&aspace, pages[1]));
+        assert_eq!(aspace.rss(), 3 * PAGE_SIZE_4K);
+        assert_eq!(swap_pages(), Some((15, 14)));
+
+        // reading swaps the page in.
+        let mut buf = [0; 8];
+        aspace.read(pages[1], &mut buf).unwrap();
+        assert_eq!(buf, [2; 8]);
+        assert_eq!(aspace.rss(), 4 * PAGE_SIZE_4K);
+        assert_eq!(swap_pages(), Some((15, 15)));
+
+        // the hand goes on from page 2, and page 0 has lost its second chance.
+        assert_eq!(aspace.reclaim(3), 3);
+        for vaddr in [pages[0], pages[2], pages[3]] {
+            assert!(is_swapped_out(&aspace, vaddr));
+        }
+        assert_eq!(aspace.rss(), PAGE_SIZE_4K);
+
+        // the slots are shared by the child, and freed after both are dropped.
+        let mut child = aspace.clone_cow().unwrap();
+        drop(aspace);
+        assert_eq!(swap_pages(), Some((15, 12)));
+        for (i, &vaddr) in pages.iter().enumerate() {
+            child.read(vaddr, &mut buf).unwrap();
+            assert_eq!(buf, [i as u8 + 1; 8]);
+        }
+        assert_eq!(swap_pages(), Some((15, 15)));
+
+        drop(child);
+        swap_off().unwrap();
+        assert_eq!(global_allocator().used_pages(), used);
+    }
+}
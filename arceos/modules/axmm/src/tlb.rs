@@ -0,0 +1,204 @@
+//! TLB maintenance.
+//!
+//! There are no IPIs to flush the TLBs of other CPUs, so a shootdown bumps a
+//! global generation, and each CPU flushes its TLB when it sees a newer
+//! generation on its next reschedule or timer tick (see
+//! [`handle_tlb_shootdown`]), or while it's spinning on a [`TlbSpinLock`].
+//! The CPUs running the idle task are skipped, as they flush before switching
+//! to other tasks.
+//!
+//! The CPU issuing a shootdown waits until all the other CPUs have flushed,
+//! even if IRQs are disabled (e.g., in the page fault handler), as the other
+//! CPUs flush on their own. It flushes its own TLB meanwhile if other CPUs
+//! issue shootdowns, so the CPUs shooting down at the same time don't wait
+//! for each other forever. The waiting CPU must not hold any spinlock that
+//! other CPUs may spin on with IRQs disabled, except [`TlbSpinLock`]s.
+
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use kernel_guard::NoPreemptIrqSave;
+use memory_addr::VirtAddr;
+
+/// The TLB state of a CPU.
+pub(crate) struct CpuTlbState {
+    /// The latest shootdown generation the CPU has flushed its TLB for.
+    flushed_gen: AtomicUsize,
+    /// Whether the CPU is running the idle task, or not booted yet.
+    idle: AtomicBool,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const CPU_TLB_STATE_INIT: CpuTlbState = CpuTlbState {
+    flushed_gen: AtomicUsize::new(0),
+    idle: AtomicBool::new(true),
+};
+
+/// The shootdowns among `N` CPUs.
+pub(crate) struct Shootdowns<const N: usize> {
+    /// The generation of the latest shootdown.
+    gen: AtomicUsize,
+    cpus: [CpuTlbState; N],
+}
+
+impl<const N: usize> Shootdowns<N> {
+    pub(crate) const fn new() -> Self {
+        Self {
+            gen: AtomicUsize::new(0),
+            cpus: [CPU_TLB_STATE_INIT; N],
+        }
+    }
+
+    /// Flushes the TLB of CPU `cpu_id`, and waits until all the other
+    /// non-idle CPUs have flushed theirs.
+    pub(crate) fn shootdown(&self, cpu_id: usize) {
+        flush_local(None);
+        let gen = self.gen.fetch_add(1, Ordering::SeqCst) + 1;
+        self.cpus[cpu_id]
+            .flushed_gen
+            .fetch_max(gen, Ordering::Release);
+        for (id, state) in self.cpus.iter().enumerate() {
+            if id == cpu_id {
+                continue;
+            }
+            // pairs with the `SeqCst` accesses in `handle`, so the CPU either
+            // is seen non-idle here, or sees the new generation there.
+            while state.flushed_gen.load(Ordering::Acquire) < gen
+                && !state.idle.load(Ordering::SeqCst)
+            {
+                // the CPU may be waiting for this one in its own shootdown.
+                self.flush_pending(cpu_id);
+                core::hint::spin_loop();
+            }
+        }
+    }
+
+    /// Flushes the TLB of CPU `cpu_id` if there are shootdowns it has not
+    /// seen yet.
+    pub(crate) fn flush_pending(&self, cpu_id: usize) {
+        let state = &self.cpus[cpu_id];
+        let gen = self.gen.load(Ordering::SeqCst);
+        if state.flushed_gen.load(Ordering::Relaxed) < gen {
+            flush_local(None);
+            state.flushed_gen.fetch_max(gen, Ordering::Release);
+        }
+    }
+
+    /// Handles the pending shootdowns of CPU `cpu_id` on reschedule or timer
+    /// tick, see [`handle_tlb_shootdown`].
+    pub(crate) fn handle(&self, cpu_id: usize, idle: bool) {
+        let state = &self.cpus[cpu_id];
+        if !idle {
+            state.idle.store(false, Ordering::SeqCst);
+        }
+        self.flush_pending(cpu_id);
+        if idle {
+            state.idle.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+static SHOOTDOWNS: Shootdowns<{ axconfig::SMP }> = Shootdowns::new();
+
+/// Flushes the TLB entry of `vaddr`, or the entire TLB if `vaddr` is `None`,
+/// on the current CPU.
+pub(crate) fn flush_local(vaddr: Option<VirtAddr>) {
+    // the page tables are not in use on the host (in unit tests).
+    #[cfg(target_os = "none")]
+    axhal::arch::flush_tlb(vaddr);
+    #[cfg(not(target_os = "none"))]
+    let _ = vaddr;
+}
+
+/// Flushes the TLBs of all CPUs, after the permissions of some mappings that
+/// may be used by other CPUs are reduced, e.g., pages are write-protected,
+/// and waits until it's done, see the [module-level documentation](self).
+pub(crate) fn shootdown() {
+    if axconfig::SMP == 1 {
+        flush_local(None);
+        return;
+    }
+    SHOOTDOWNS.shootdown(axhal::cpu::this_cpu_id());
+}
+
+/// Flushes the TLB of the CPU if there are shootdowns it has not seen yet.
+///
+/// It should be called by the scheduler on every reschedule (with `idle`
+/// indicating whether the next task is the idle task) and timer tick, with
+/// IRQs disabled.
+pub fn handle_tlb_shootdown(cpu_id: usize, idle: bool) {
+    SHOOTDOWNS.handle(cpu_id, idle);
+}
+
+/// A spinlock that disables IRQs and preemption like `SpinNoIrq`, which can be
+/// held while shooting down TLBs, e.g., the lock of the kernel address space.
+///
+/// The CPUs spinning on it flush their TLBs for the pending shootdowns, so the
+/// holder can wait for its shootdowns without deadlocks.
+pub struct TlbSpinLock<T> {
+    locked: AtomicBool,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Send for TlbSpinLock<T> {}
+unsafe impl<T: Send> Sync for TlbSpinLock<T> {}
+
+impl<T> TlbSpinLock<T> {
+    /// Creates a new lock wrapping `data`.
+    pub const fn new(data: T) -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Locks it, and returns a guard that unlocks it when dropped.
+    pub fn lock(&self) -> TlbSpinLockGuard<'_, T> {
+        let irq_guard = NoPreemptIrqSave::new();
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // the holder may be waiting for this CPU in a shootdown.
+            if axconfig::SMP > 1 {
+                SHOOTDOWNS.flush_pending(axhal::cpu::this_cpu_id());
+            }
+            core::hint::spin_loop();
+        }
+        TlbSpinLockGuard {
+            lock: self,
+            _irq_guard: irq_guard,
+        }
+    }
+}
+
+/// A guard of [`TlbSpinLock`], which unlocks it when dropped.
+pub struct TlbSpinLockGuard<'a, T> {
+    lock: &'a TlbSpinLock<T>,
+    _irq_guard: NoPreemptIrqSave,
+}
+
+impl<T> Deref for TlbSpinLockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the lock is held.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for TlbSpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the lock is held.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T> Drop for TlbSpinLockGuard<'_, T> {
+    fn drop(&mut self) {
+        // IRQs are restored after unlocking, when `_irq_guard` is dropped.
+        self.lock.locked.store(false, Ordering::Release);
+    }
+}
@@ -68,6 +68,9 @@ impl DnsSocket {
                     }
                     return Ok(res);
                 }
+                Err(AxError::WouldBlock) if axtask::is_cancelled() => {
+                    return Err(AxError::Interrupted);
+                }
                 Err(AxError::WouldBlock) => axtask::yield_now(),
                 Err(e) => return Err(e),
             }
@@ -1,5 +1,6 @@
 use alloc::{boxed::Box, collections::VecDeque};
 use core::ops::{Deref, DerefMut};
+use core::task::Waker;
 
 use axerrno::{ax_err, AxError, AxResult};
 use axsync::Mutex;
@@ -14,6 +15,9 @@ const PORT_NUM: usize = 65536;
 struct ListenTableEntry {
     listen_endpoint: IpListenEndpoint,
     syn_queue: VecDeque<SocketHandle>,
+    /// The waker of a pending `accept`, which is also registered on the
+    /// sockets added to the SYN queue later.
+    waker: Option<Waker>,
 }
 
 impl ListenTableEntry {
@@ -21,6 +25,7 @@ impl ListenTableEntry {
         Self {
             listen_endpoint,
             syn_queue: VecDeque::with_capacity(LISTEN_QUEUE_SIZE),
+            waker: None,
         }
     }
 
@@ -110,6 +115,21 @@ impl ListenTable {
         }
     }
 
+    /// Registers a waker to be woken up when a connection in the SYN queue
+    /// of the port may be established.
+    pub fn register_waker(&self, port: u16, waker: &Waker) {
+        if let Some(entry) = self.tcp[port as usize].lock().deref_mut() {
+            for &handle in &entry.syn_queue {
+                SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+                    socket.register_recv_waker(waker)
+                });
+            }
+            entry.waker = Some(waker.clone());
+        } else {
+            waker.wake_by_ref();
+        }
+    }
+
     pub fn incoming_tcp_packet(
         &self,
         src: IpEndpoint,
@@ -127,6 +147,9 @@ impl ListenTable {
                 return;
             }
             let mut socket = SocketSetWrapper::new_tcp_socket();
+            if let Some(waker) = &entry.waker {
+                socket.register_recv_waker(waker);
+            }
             if socket.listen(entry.listen_endpoint).is_ok() {
                 let handle = sockets.add(socket);
                 debug!(
@@ -1,6 +1,7 @@
 use core::cell::UnsafeCell;
 use core::net::SocketAddr;
 use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use core::task::Waker;
 
 use axerrno::{ax_err, ax_err_type, AxError, AxResult};
 use axio::PollState;
@@ -332,6 +333,50 @@ impl TcpSocket {
         })
     }
 
+    /// Registers a waker to be woken up when the socket may become readable,
+    /// i.e., [`recv`](Self::recv) or [`accept`](Self::accept) may not block.
+    ///
+    /// The waker is woken up once, by the [`poll_interfaces`](crate::poll_interfaces)
+    /// that changes the socket, so it must be registered again before the
+    /// next wait.
+    pub fn register_recv_waker(&self, waker: &Waker) {
+        match self.get_state() {
+            STATE_LISTENING => {
+                // SAFETY: `self.local_addr` should be initialized in a listening socket.
+                let local_port = unsafe { self.local_addr.get().read().port };
+                LISTEN_TABLE.register_waker(local_port, waker);
+            }
+            STATE_CONNECTING | STATE_CONNECTED => {
+                // SAFETY: `self.handle` should be initialized in these states.
+                if let Some(handle) = unsafe { self.handle.get().read() } {
+                    SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+                        socket.register_recv_waker(waker)
+                    });
+                }
+            }
+            _ => waker.wake_by_ref(),
+        }
+    }
+
+    /// Registers a waker to be woken up when the socket may become writable,
+    /// i.e., [`send`](Self::send) may not block, or the connection is
+    /// established.
+    ///
+    /// The waker is woken up once, like [`register_recv_waker`](Self::register_recv_waker).
+    pub fn register_send_waker(&self, waker: &Waker) {
+        match self.get_state() {
+            STATE_CONNECTING | STATE_CONNECTED => {
+                // SAFETY: `self.handle` should be initialized in these states.
+                if let Some(handle) = unsafe { self.handle.get().read() } {
+                    SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+                        socket.register_send_waker(waker)
+                    });
+                }
+            }
+            _ => waker.wake_by_ref(),
+        }
+    }
+
     /// Whether the socket is readable or writable.
     pub fn poll(&self) -> AxResult<PollState> {
         match self.get_state() {
@@ -473,7 +518,8 @@ impl TcpSocket {
     ///
     /// If the socket is non-blocking, it calls the function once and returns
     /// immediately. Otherwise, it may call the function multiple times if it
-    /// returns [`Err(WouldBlock)`](AxError::WouldBlock).
+    /// returns [`Err(WouldBlock)`](AxError::WouldBlock), until the current
+    /// task is cancelled, which returns [`Err(Interrupted)`](AxError::Interrupted).
     fn block_on<F, T>(&self, mut f: F) -> AxResult<T>
     where
         F: FnMut() -> AxResult<T>,
@@ -485,6 +531,9 @@ impl TcpSocket {
                 SOCKET_SET.poll_interfaces();
                 match f() {
                     Ok(t) => return Ok(t),
+                    Err(AxError::WouldBlock) if axtask::is_cancelled() => {
+                        return Err(AxError::Interrupted);
+                    }
                     Err(AxError::WouldBlock) => axtask::yield_now(),
                     Err(e) => return Err(e),
                 }
@@ -1,5 +1,6 @@
 use core::net::SocketAddr;
 use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::Waker;
 
 use axerrno::{ax_err, ax_err_type, AxError, AxResult};
 use axio::PollState;
@@ -180,6 +181,27 @@ impl UdpSocket {
         Ok(())
     }
 
+    /// Registers a waker to be woken up when a datagram may be received.
+    ///
+    /// The waker is woken up once, by the [`poll_interfaces`](crate::poll_interfaces)
+    /// that changes the socket, so it must be registered again before the
+    /// next wait.
+    pub fn register_recv_waker(&self, waker: &Waker) {
+        SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
+            socket.register_recv_waker(waker)
+        });
+    }
+
+    /// Registers a waker to be woken up when the transmit buffer may have
+    /// free space.
+    ///
+    /// The waker is woken up once, like [`register_recv_waker`](Self::register_recv_waker).
+    pub fn register_send_waker(&self, waker: &Waker) {
+        SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
+            socket.register_send_waker(waker)
+        });
+    }
+
     /// Whether the socket is readable or writable.
     pub fn poll(&self) -> AxResult<PollState> {
         if self.local_addr.read().is_none() {
@@ -263,6 +285,9 @@ impl UdpSocket {
                 SOCKET_SET.poll_interfaces();
                 match f() {
                     Ok(t) => return Ok(t),
+                    Err(AxError::WouldBlock) if axtask::is_cancelled() => {
+                        return Err(AxError::Interrupted);
+                    }
                     Err(AxError::WouldBlock) => axtask::yield_now(),
                     Err(e) => return Err(e),
                 }
@@ -13,6 +13,8 @@
 //! - `fs`: Enable filesystem support.
 //! - `net`: Enable networking support.
 //! - `display`: Enable graphics support.
+//! - `swap`: Enable swapping to the swap area given by `AX_SWAP` at build
+//!   time: `blk<N>` for the N-th block device, or the path of a swap file.
 //!
 //! All the features are optional and disabled by default.
 
@@ -22,12 +24,18 @@
 #[macro_use]
 extern crate axlog;
 
+#[cfg(feature = "swap")]
+extern crate alloc;
+
 #[cfg(all(target_os = "none", not(test)))]
 mod lang_items;
 
 #[cfg(feature = "smp")]
 mod mp;
 
+#[cfg(feature = "swap")]
+mod swap;
+
 #[cfg(feature = "smp")]
 pub use self::mp::rust_main_secondary;
 
@@ -155,12 +163,18 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
 
     #[cfg(any(feature = "fs", feature = "net", feature = "display"))]
     {
-        #[allow(unused_variables)]
-        let all_devices = axdriver::init_drivers();
+        #[allow(unused_variables, unused_mut)]
+        let mut all_devices = axdriver::init_drivers();
+
+        #[cfg(feature = "swap")]
+        let swap_dev = self::swap::take_swap_device(&mut all_devices.block);
 
         #[cfg(feature = "fs")]
         axfs::init_filesystems(all_devices.block);
 
+        #[cfg(feature = "swap")]
+        self::swap::init_swap(swap_dev);
+
         #[cfg(feature = "net")]
         axnet::init_network(all_devices.net);
 
@@ -0,0 +1,71 @@
+//! Enables swapping to the swap area given at build time.
+
+use alloc::sync::Arc;
+
+use axdriver::{AxBlockDevice, AxDeviceContainer};
+use axerrno::AxResult;
+use axfs::fops::{File, OpenOptions};
+
+/// The swap area: `blk<N>` for the N-th block device (counting from 0), which
+/// is then not used by the filesystems, or the absolute path of a swap file.
+/// Swapping is disabled if it's empty.
+const SWAP: &str = match option_env!("AX_SWAP") {
+    Some(val) => val,
+    None => "",
+};
+
+/// A swap file in the filesystems.
+struct SwapFileImpl(File);
+
+impl axmm::MmapFile for SwapFileImpl {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize> {
+        self.0.read_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> AxResult<usize> {
+        self.0.write_at(offset, buf)
+    }
+
+    fn size(&self) -> AxResult<u64> {
+        Ok(self.0.get_attr()?.size())
+    }
+}
+
+/// Takes the swap device out of the block devices, before they are given to
+/// the filesystems.
+pub(crate) fn take_swap_device(
+    block: &mut AxDeviceContainer<AxBlockDevice>,
+) -> Option<AxBlockDevice> {
+    let index = SWAP.strip_prefix("blk")?;
+    let dev = index.parse().ok().and_then(|index| block.take_nth(index));
+    if dev.is_none() {
+        warn!("Swap device {:?} not found", SWAP);
+    }
+    dev
+}
+
+fn open_swap_file(path: &str) -> AxResult<axmm::SwapFile> {
+    let mut opts = OpenOptions::new();
+    opts.read(true);
+    opts.write(true);
+    let file = File::open(path, &opts)?;
+    axmm::SwapFile::new(Arc::new(SwapFileImpl(file)))
+}
+
+/// Enables swapping to the swap device taken by [`take_swap_device`], or to
+/// the swap file, after the filesystems are initialized.
+pub(crate) fn init_swap(dev: Option<AxBlockDevice>) {
+    let res = if let Some(dev) = dev {
+        axmm::swap_on(Arc::new(axmm::BlockSwap::new(dev)))
+    } else if SWAP.starts_with('/') {
+        open_swap_file(SWAP).and_then(|file| axmm::swap_on(Arc::new(file)))
+    } else {
+        if !SWAP.is_empty() && !SWAP.starts_with("blk") {
+            warn!("Invalid swap area {:?}", SWAP);
+        }
+        return;
+    };
+    if let Err(e) = res {
+        warn!("Failed to enable swapping on {:?}: {:?}", SWAP, e);
+    }
+}
@@ -2,7 +2,13 @@
 //!
 //! Currently supported primitives:
 //!
-//! - [`Mutex`]: A mutual exclusion primitive.
+//! - [`Mutex`]: A mutual exclusion primitive with priority inheritance.
+//! - [`RwLock`]: A readers-writer lock.
+//! - [`Condvar`]: A condition variable working with [`Mutex`].
+//! - [`Semaphore`]: A counting semaphore.
+//! - [`Barrier`]: A barrier to synchronize a group of tasks.
+//! - [`Once`] and [`OnceLock`]: One-time initialization.
+//! - [`Rcu`]: A read-copy-update protected pointer for read-mostly data.
 //! - mod [`spin`]: spinlocks imported from the [`kspin`] crate.
 //!
 //! # Cargo Features
@@ -10,19 +16,58 @@
 //! - `multitask`: For use in the multi-threaded environments. If the feature is
 //!   not enabled, [`Mutex`] will be an alias of [`spin::SpinNoIrq`]. This
 //!   feature is enabled by default.
+//! - `lockdep`: Validate the order of lock acquisitions of [`Mutex`], [`RwLock`]
+//!   and the spinlocks in mod [`spin`], and report possible deadlocks. It also
+//!   enables the `multitask` feature.
+//! - `irq`: Enable the timed waits, e.g., [`Condvar::wait_timeout`].
+//!
+//! All primitives except [`Mutex`] and [`Rcu`] are only available with the
+//! `multitask` feature.
 
 #![cfg_attr(not(test), no_std)]
 #![feature(doc_cfg)]
 
+extern crate alloc;
+
+#[cfg(not(feature = "lockdep"))]
 pub use kspin as spin;
 
+#[cfg(feature = "lockdep")]
+pub use axtask::lockdep::spin;
+
+#[cfg(feature = "multitask")]
+mod barrier;
+#[cfg(feature = "multitask")]
+mod condvar;
 #[cfg(feature = "multitask")]
 mod mutex;
+#[cfg(feature = "multitask")]
+mod once;
+mod rcu;
+#[cfg(feature = "multitask")]
+mod rwlock;
+#[cfg(feature = "multitask")]
+mod semaphore;
+
+#[cfg(test)]
+mod tests;
+
+pub use self::rcu::{synchronize_rcu, Rcu, RcuReadGuard};
 
 #[cfg(feature = "multitask")]
 #[doc(cfg(feature = "multitask"))]
 pub use self::mutex::{Mutex, MutexGuard};
 
+#[cfg(feature = "multitask")]
+#[doc(cfg(feature = "multitask"))]
+pub use self::{
+    barrier::{Barrier, BarrierWaitResult},
+    condvar::{Condvar, WaitTimeoutResult},
+    once::{Once, OnceLock},
+    rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+    semaphore::{Semaphore, SemaphoreGuard},
+};
+
 #[cfg(not(feature = "multitask"))]
 #[doc(cfg(not(feature = "multitask")))]
 pub use kspin::{SpinNoIrq as Mutex, SpinNoIrqGuard as MutexGuard};
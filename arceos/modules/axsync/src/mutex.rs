@@ -5,17 +5,30 @@ use core::fmt;
 use core::ops::{Deref, DerefMut};
 use core::sync::atomic::{AtomicU64, Ordering};
 
-use axtask::{current, WaitQueue};
+use axtask::{current, AxTaskRef, WaitQueue};
+
+use crate::spin::SpinNoIrq;
 
 /// A mutual exclusion primitive useful for protecting shared data, similar to
 /// [`std::sync::Mutex`](https://doc.rust-lang.org/std/sync/struct.Mutex.html).
 ///
 /// When the mutex is locked, the current task will block and be put into the
-/// wait queue. When the mutex is unlocked, all tasks waiting on the queue
-/// will be woken up.
+/// wait queue. When the mutex is unlocked, one task waiting on the queue will
+/// be woken up.
+///
+/// The mutex implements priority inheritance: while a task is blocked on it,
+/// the owner runs with the waiter's priority if that's higher, so that it
+/// cannot be starved by tasks of medium priority. The priority inherited
+/// through a mutex is dropped when the owner unlocks it, while those inherited
+/// through other mutexes it still holds are kept.
 pub struct Mutex<T: ?Sized> {
     wq: WaitQueue,
     owner_id: AtomicU64,
+    /// The owner task, guarded by a lock so that waiters cannot lend their
+    /// priority to it after it has unlocked the mutex.
+    owner: SpinNoIrq<Option<AxTaskRef>>,
+    #[cfg(feature = "lockdep")]
+    class: axtask::lockdep::LockClass,
     data: UnsafeCell<T>,
 }
 
@@ -23,7 +36,7 @@ pub struct Mutex<T: ?Sized> {
 ///
 /// When the guard falls out of scope it will release the lock.
 pub struct MutexGuard<'a, T: ?Sized + 'a> {
-    lock: &'a Mutex<T>,
+    pub(crate) lock: &'a Mutex<T>,
     data: *mut T,
 }
 
@@ -34,10 +47,14 @@ unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
 impl<T> Mutex<T> {
     /// Creates a new [`Mutex`] wrapping the supplied data.
     #[inline(always)]
+    #[cfg_attr(feature = "lockdep", track_caller)]
     pub const fn new(data: T) -> Self {
         Self {
             wq: WaitQueue::new(),
             owner_id: AtomicU64::new(0),
+            owner: SpinNoIrq::new(None),
+            #[cfg(feature = "lockdep")]
+            class: axtask::lockdep::LockClass::new(),
             data: UnsafeCell::new(data),
         }
     }
@@ -68,29 +85,46 @@ impl<T: ?Sized> Mutex<T> {
     ///
     /// The returned value may be dereferenced for data access
     /// and the lock will be dropped when the guard falls out of scope.
+    #[cfg_attr(feature = "lockdep", track_caller)]
     pub fn lock(&self) -> MutexGuard<T> {
-        let current_id = current().id().as_u64();
+        #[cfg(feature = "lockdep")]
+        self.class.acquire(axtask::lockdep::LockKind::Sleep);
+        let curr = current();
+        let current_id = curr.id().as_u64();
+        let key = self.key();
         loop {
-            // Can fail to lock even if the spinlock is not locked. May be more efficient than `try_lock`
-            // when called in a loop.
-            match self.owner_id.compare_exchange_weak(
-                0,
-                current_id,
-                Ordering::Acquire,
-                Ordering::Relaxed,
-            ) {
-                Ok(_) => break,
-                Err(owner_id) => {
+            let mut owner = self.owner.lock();
+            match owner.as_ref() {
+                None => {
+                    *owner = Some(curr.as_task_ref().clone());
+                    self.owner_id.store(current_id, Ordering::Release);
+                    drop(owner);
+                    // Inherit the priority of the tasks still waiting.
+                    if let Some(prio) = self.wq.top_priority() {
+                        axtask::inherit_priority(curr.as_task_ref(), key, prio);
+                    }
+                    break;
+                }
+                Some(owner_task) => {
                     assert_ne!(
-                        owner_id,
+                        owner_task.id().as_u64(),
                         current_id,
                         "{} tried to acquire mutex it already owns.",
-                        current().id_name()
+                        curr.id_name()
                     );
-                    // Wait until the lock looks unlocked before retrying
-                    self.wq.wait_until(|| !self.is_locked());
                 }
             }
+            drop(owner);
+            // Wait until the lock is unlocked before retrying, and lend our
+            // priority to whoever owns it in the meantime.
+            let prio = curr.priority();
+            self.wq.wait_until(|| match self.owner.lock().as_ref() {
+                None => true,
+                Some(owner_task) => {
+                    axtask::inherit_priority(owner_task, key, prio);
+                    false
+                }
+            });
         }
         MutexGuard {
             lock: self,
@@ -100,22 +134,21 @@ impl<T: ?Sized> Mutex<T> {
 
     /// Try to lock this [`Mutex`], returning a lock guard if successful.
     #[inline(always)]
+    #[cfg_attr(feature = "lockdep", track_caller)]
     pub fn try_lock(&self) -> Option<MutexGuard<T>> {
-        let current_id = current().id().as_u64();
-        // The reason for using a strong compare_exchange is explained here:
-        // https://github.com/Amanieu/parking_lot/pull/207#issuecomment-575869107
-        if self
-            .owner_id
-            .compare_exchange(0, current_id, Ordering::Acquire, Ordering::Relaxed)
-            .is_ok()
-        {
-            Some(MutexGuard {
-                lock: self,
-                data: unsafe { &mut *self.data.get() },
-            })
-        } else {
-            None
+        let mut owner = self.owner.lock();
+        if owner.is_some() {
+            return None;
         }
+        let curr = current();
+        self.owner_id.store(curr.id().as_u64(), Ordering::Release);
+        *owner = Some(curr.as_task_ref().clone());
+        #[cfg(feature = "lockdep")]
+        self.class.acquired(axtask::lockdep::LockKind::Sleep);
+        Some(MutexGuard {
+            lock: self,
+            data: unsafe { &mut *self.data.get() },
+        })
     }
 
     /// Force unlock the [`Mutex`].
@@ -126,6 +159,7 @@ impl<T: ?Sized> Mutex<T> {
     /// thread. However, this can be useful in some instances for exposing
     /// the lock to FFI that doesn’t know how to deal with RAII.
     pub unsafe fn force_unlock(&self) {
+        let mut owner = self.owner.lock();
         let owner_id = self.owner_id.swap(0, Ordering::Release);
         assert_eq!(
             owner_id,
@@ -133,9 +167,34 @@ impl<T: ?Sized> Mutex<T> {
             "{} tried to release mutex it doesn't own",
             current().id_name()
         );
+        *owner = None;
+        #[cfg(feature = "lockdep")]
+        self.class.release();
+        axtask::restore_current_priority(self.key());
+        drop(owner);
         self.wq.notify_one(true);
     }
 
+    /// Identifies the mutex when the owner inherits priorities through it.
+    #[inline(always)]
+    fn key(&self) -> usize {
+        self as *const Self as *const () as usize
+    }
+
+    /// Creates a guard for the [`Mutex`] without locking it.
+    ///
+    /// # Safety
+    ///
+    /// The lock must be held by the current thread, e.g., it's locked by a
+    /// guard that has been forgotten. This can be useful for exposing the lock
+    /// to FFI that doesn’t know how to deal with RAII.
+    pub unsafe fn make_guard_unchecked(&self) -> MutexGuard<T> {
+        MutexGuard {
+            lock: self,
+            data: self.data.get(),
+        }
+    }
+
     /// Returns a mutable reference to the underlying data.
     ///
     /// Since this call borrows the [`Mutex`] mutably, and a mutable reference is guaranteed to be exclusive in
@@ -151,6 +210,7 @@ impl<T: ?Sized> Mutex<T> {
 
 impl<T: ?Sized + Default> Default for Mutex<T> {
     #[inline(always)]
+    #[cfg_attr(feature = "lockdep", track_caller)]
     fn default() -> Self {
         Self::new(Default::default())
     }
@@ -199,11 +259,9 @@ impl<'a, T: ?Sized> Drop for MutexGuard<'a, T> {
 
 #[cfg(test)]
 mod tests {
+    use crate::tests::{INIT, SERIAL};
     use crate::Mutex;
     use axtask as thread;
-    use std::sync::Once;
-
-    static INIT: Once = Once::new();
 
     fn may_interrupt() {
         // simulate interrupts
@@ -214,6 +272,7 @@ mod tests {
 
     #[test]
     fn lots_and_lots() {
+        let _lock = SERIAL.lock();
         INIT.call_once(thread::init_scheduler);
 
         const NUM_TASKS: u32 = 10;
@@ -0,0 +1,171 @@
+//! Read-copy-update (RCU) protected pointers.
+
+use alloc::boxed::Box;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+use kernel_guard::NoPreempt;
+
+/// Waits for a grace period, i.e., until all the RCU read-side critical
+/// sections that were running when it's called have completed.
+///
+/// Without the `multitask` feature, there is only one task which cannot be
+/// preempted, so there are no readers to wait for.
+pub fn synchronize_rcu() {
+    #[cfg(feature = "multitask")]
+    axtask::rcu::synchronize();
+}
+
+/// A pointer protected by RCU (read-copy-update), for read-mostly data.
+///
+/// Readers access the current value by [`read`](Self::read) without taking
+/// any lock, they only disable preemption. Writers publish a new value with
+/// [`replace`](Self::replace) or [`update`](Self::update), and the old value
+/// is dropped after a grace period, when no reader can still see it.
+///
+/// Read-side critical sections (the lifetime of [`RcuReadGuard`]) must not
+/// block or yield, and writers block until the grace period elapses, so it's
+/// only suitable for data that are rarely updated.
+pub struct Rcu<T> {
+    ptr: AtomicPtr<T>,
+    _marker: PhantomData<Box<T>>,
+}
+
+// The old values are dropped by writers, which may run on other tasks.
+unsafe impl<T: Send + Sync> Sync for Rcu<T> {}
+unsafe impl<T: Send> Send for Rcu<T> {}
+
+/// A guard that provides access to the value of [`Rcu`].
+///
+/// Preemption is disabled while the guard is alive, so the current task must
+/// not block or yield before dropping it.
+pub struct RcuReadGuard<'a, T> {
+    data: &'a T,
+    _guard: NoPreempt,
+}
+
+impl<T> Rcu<T> {
+    /// Creates a new `Rcu` with the initial value.
+    pub fn new(value: T) -> Self {
+        Self {
+            ptr: AtomicPtr::new(Box::into_raw(Box::new(value))),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Enters a read-side critical section, and returns a guard to access the
+    /// current value.
+    ///
+    /// The value will not be dropped until the guard is dropped, even if it's
+    /// replaced by writers in the meantime.
+    #[inline]
+    pub fn read(&self) -> RcuReadGuard<'_, T> {
+        let _guard = NoPreempt::new();
+        // SAFETY: the value will not be dropped until a grace period elapses,
+        // which waits for this critical section.
+        let data = unsafe { &*self.ptr.load(Ordering::Acquire) };
+        RcuReadGuard { data, _guard }
+    }
+
+    /// Publishes a new value, waits for a grace period and then returns the
+    /// old value.
+    ///
+    /// Concurrent writers are allowed, but they don't see the values of each
+    /// other, consider [`update`](Self::update) or a lock to serialize them.
+    pub fn replace(&self, value: T) -> T {
+        let new = Box::into_raw(Box::new(value));
+        // `SeqCst` orders the publication before the grace period starts,
+        // i.e., readers that don't see the new value are waited for.
+        let old = self.ptr.swap(new, Ordering::SeqCst);
+        synchronize_rcu();
+        // SAFETY: no reader can still see the old value after the grace
+        // period, and only this writer has got it by `swap`.
+        *unsafe { Box::from_raw(old) }
+    }
+
+    /// Publishes a new value computed from the current one by `f`, and drops
+    /// the old value after a grace period.
+    ///
+    /// If other writers publish first, `f` is called again with their value,
+    /// so no update is lost. `f` runs in a read-side critical section, so it
+    /// must not block or yield.
+    pub fn update<F>(&self, mut f: F)
+    where
+        F: FnMut(&T) -> T,
+    {
+        loop {
+            let guard = self.read();
+            let current = guard.data as *const T as *mut T;
+            let new = Box::into_raw(Box::new(f(&guard)));
+            // keep `current` alive until the exchange, so its address cannot
+            // be reused by a new value.
+            let res = self
+                .ptr
+                .compare_exchange(current, new, Ordering::SeqCst, Ordering::Acquire);
+            drop(guard);
+            match res {
+                Ok(_) => {
+                    // see `replace` for the ordering.
+                    synchronize_rcu();
+                    // SAFETY: see `replace`.
+                    drop(unsafe { Box::from_raw(current) });
+                    return;
+                }
+                // SAFETY: `new` has not been published.
+                Err(_) => drop(unsafe { Box::from_raw(new) }),
+            }
+        }
+    }
+
+    /// Returns a mutable reference to the value.
+    ///
+    /// Since this call borrows the `Rcu` mutably, no readers can exist.
+    pub fn get_mut(&mut self) -> &mut T {
+        // SAFETY: the pointer is always valid, and we have exclusive access.
+        unsafe { &mut **self.ptr.get_mut() }
+    }
+
+    /// Consumes this `Rcu`, returning the value.
+    pub fn into_inner(self) -> T {
+        let ptr = self.ptr.load(Ordering::Relaxed);
+        core::mem::forget(self);
+        // SAFETY: the pointer is owned by this `Rcu`.
+        *unsafe { Box::from_raw(ptr) }
+    }
+}
+
+impl<T> Drop for Rcu<T> {
+    fn drop(&mut self) {
+        // SAFETY: no readers can exist, as we have exclusive access.
+        drop(unsafe { Box::from_raw(*self.ptr.get_mut()) });
+    }
+}
+
+impl<T: Default> Default for Rcu<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Rcu<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Rcu").field("data", &*self.read()).finish()
+    }
+}
+
+impl<T> Deref for RcuReadGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        self.data
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for RcuReadGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.data, f)
+    }
+}
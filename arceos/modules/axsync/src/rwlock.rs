@@ -0,0 +1,309 @@
+//! A sleeping readers-writer lock.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axtask::WaitQueue;
+
+/// The bit set in the state when the lock is held by a writer, the other bits
+/// count the readers.
+const WRITER: usize = 1 << (usize::BITS - 1);
+
+/// A readers-writer lock, similar to
+/// [`std::sync::RwLock`](https://doc.rust-lang.org/std/sync/struct.RwLock.html).
+///
+/// This lock allows a number of readers or at most one writer at any point in
+/// time. Tasks that cannot acquire the lock are blocked and put into the wait
+/// queue, and are woken up when the lock is released.
+///
+/// Readers are not blocked by waiting writers, so writers may be starved if
+/// there are always readers holding the lock.
+pub struct RwLock<T: ?Sized> {
+    wq: WaitQueue,
+    state: AtomicUsize,
+    #[cfg(feature = "lockdep")]
+    class: axtask::lockdep::LockClass,
+    data: UnsafeCell<T>,
+}
+
+/// A guard that provides immutable data access.
+///
+/// When the guard falls out of scope it will decrement the read count,
+/// potentially releasing the lock.
+pub struct RwLockReadGuard<'a, T: ?Sized + 'a> {
+    lock: &'a RwLock<T>,
+    data: *const T,
+}
+
+/// A guard that provides mutable data access.
+///
+/// When the guard falls out of scope it will release the lock.
+pub struct RwLockWriteGuard<'a, T: ?Sized + 'a> {
+    lock: &'a RwLock<T>,
+    data: *mut T,
+}
+
+// Same unsafe impls as `std::sync::RwLock`
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+unsafe impl<T: ?Sized + Sync> Sync for RwLockReadGuard<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for RwLockWriteGuard<'_, T> {}
+
+impl<T> RwLock<T> {
+    /// Creates a new [`RwLock`] wrapping the supplied data.
+    #[inline(always)]
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub const fn new(data: T) -> Self {
+        Self {
+            wq: WaitQueue::new(),
+            state: AtomicUsize::new(0),
+            #[cfg(feature = "lockdep")]
+            class: axtask::lockdep::LockClass::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Consumes this [`RwLock`] and unwraps the underlying data.
+    #[inline(always)]
+    pub fn into_inner(self) -> T {
+        // We know statically that there are no outstanding references to
+        // `self` so there's no need to lock.
+        let RwLock { data, .. } = self;
+        data.into_inner()
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Returns `true` if the lock is currently held by a writer.
+    ///
+    /// # Safety
+    ///
+    /// This function provides no synchronization guarantees and so its result should be considered 'out of date'
+    /// the instant it is called. Do not use it for synchronization purposes. However, it may be useful as a heuristic.
+    #[inline(always)]
+    pub fn is_write_locked(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & WRITER != 0
+    }
+
+    /// Returns the number of readers that currently hold the lock.
+    ///
+    /// # Safety
+    ///
+    /// This function provides no synchronization guarantees and so its result should be considered 'out of date'
+    /// the instant it is called. Do not use it for synchronization purposes. However, it may be useful as a heuristic.
+    #[inline(always)]
+    pub fn reader_count(&self) -> usize {
+        self.state.load(Ordering::Relaxed) & !WRITER
+    }
+
+    fn try_read_raw(&self) -> bool {
+        let mut state = self.state.load(Ordering::Relaxed);
+        while state & WRITER == 0 {
+            match self.state.compare_exchange_weak(
+                state,
+                state + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(s) => state = s,
+            }
+        }
+        false
+    }
+
+    fn try_write_raw(&self) -> bool {
+        self.state
+            .compare_exchange(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    /// Locks this [`RwLock`] with shared read access, blocking the current
+    /// task until it can be acquired.
+    ///
+    /// The lock will be released when the returned guard falls out of scope.
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub fn read(&self) -> RwLockReadGuard<T> {
+        #[cfg(feature = "lockdep")]
+        self.class.acquire_read(axtask::lockdep::LockKind::Sleep);
+        while !self.try_read_raw() {
+            self.wq.wait_until(|| !self.is_write_locked());
+        }
+        RwLockReadGuard {
+            lock: self,
+            data: self.data.get(),
+        }
+    }
+
+    /// Attempts to acquire this [`RwLock`] with shared read access, returning
+    /// a guard if successful.
+    #[inline(always)]
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub fn try_read(&self) -> Option<RwLockReadGuard<T>> {
+        if !self.try_read_raw() {
+            return None;
+        }
+        #[cfg(feature = "lockdep")]
+        self.class.acquired_read(axtask::lockdep::LockKind::Sleep);
+        Some(RwLockReadGuard {
+            lock: self,
+            data: self.data.get(),
+        })
+    }
+
+    /// Locks this [`RwLock`] with exclusive write access, blocking the current
+    /// task until it can be acquired.
+    ///
+    /// The lock will be released when the returned guard falls out of scope.
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub fn write(&self) -> RwLockWriteGuard<T> {
+        #[cfg(feature = "lockdep")]
+        self.class.acquire(axtask::lockdep::LockKind::Sleep);
+        while !self.try_write_raw() {
+            self.wq
+                .wait_until(|| self.state.load(Ordering::Relaxed) == 0);
+        }
+        RwLockWriteGuard {
+            lock: self,
+            data: self.data.get(),
+        }
+    }
+
+    /// Attempts to acquire this [`RwLock`] with exclusive write access,
+    /// returning a guard if successful.
+    #[inline(always)]
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<T>> {
+        if !self.try_write_raw() {
+            return None;
+        }
+        #[cfg(feature = "lockdep")]
+        self.class.acquired(axtask::lockdep::LockKind::Sleep);
+        Some(RwLockWriteGuard {
+            lock: self,
+            data: self.data.get(),
+        })
+    }
+
+    /// Force decrement the reader count of the [`RwLock`].
+    ///
+    /// # Safety
+    ///
+    /// This is *extremely* unsafe if the lock is not held by the current
+    /// thread as a reader. However, this can be useful in some instances for
+    /// exposing the lock to FFI that doesn’t know how to deal with RAII.
+    pub unsafe fn force_read_decrement(&self) {
+        #[cfg(feature = "lockdep")]
+        self.class.release();
+        let prev = self.state.fetch_sub(1, Ordering::Release);
+        debug_assert!(
+            prev & !WRITER != 0,
+            "read unlocking a RwLock not read locked"
+        );
+        if prev == 1 {
+            // the last reader wakes up the writers.
+            self.wq.notify_all(true);
+        }
+    }
+
+    /// Force unlock exclusive write access of the [`RwLock`].
+    ///
+    /// # Safety
+    ///
+    /// This is *extremely* unsafe if the lock is not held by the current
+    /// thread as a writer. However, this can be useful in some instances for
+    /// exposing the lock to FFI that doesn’t know how to deal with RAII.
+    pub unsafe fn force_write_unlock(&self) {
+        #[cfg(feature = "lockdep")]
+        self.class.release();
+        let prev = self.state.swap(0, Ordering::Release);
+        debug_assert_eq!(prev, WRITER, "write unlocking a RwLock not write locked");
+        self.wq.notify_all(true);
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// Since this call borrows the [`RwLock`] mutably, and a mutable reference is guaranteed to be exclusive in
+    /// Rust, no actual locking needs to take place -- the mutable borrow statically guarantees no locks exist. As
+    /// such, this is a 'zero-cost' operation.
+    #[inline(always)]
+    pub fn get_mut(&mut self) -> &mut T {
+        // We know statically that there are no other references to `self`, so
+        // there's no need to lock the inner lock.
+        unsafe { &mut *self.data.get() }
+    }
+}
+
+impl<T: ?Sized + Default> Default for RwLock<T> {
+    #[inline(always)]
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_read() {
+            Some(guard) => write!(f, "RwLock {{ data: ")
+                .and_then(|()| (*guard).fmt(f))
+                .and_then(|()| write!(f, "}}")),
+            None => write!(f, "RwLock {{ <locked> }}"),
+        }
+    }
+}
+
+impl<'a, T: ?Sized> Deref for RwLockReadGuard<'a, T> {
+    type Target = T;
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        // We know statically that only readers are referencing data
+        unsafe { &*self.data }
+    }
+}
+
+impl<'a, T: ?Sized> Deref for RwLockWriteGuard<'a, T> {
+    type Target = T;
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        // We know statically that only we are referencing data
+        unsafe { &*self.data }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for RwLockWriteGuard<'a, T> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut T {
+        // We know statically that only we are referencing data
+        unsafe { &mut *self.data }
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for RwLockReadGuard<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for RwLockWriteGuard<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RwLockReadGuard<'a, T> {
+    /// The dropping of the [`RwLockReadGuard`] will decrement the read count.
+    fn drop(&mut self) {
+        unsafe { self.lock.force_read_decrement() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RwLockWriteGuard<'a, T> {
+    /// The dropping of the [`RwLockWriteGuard`] will release the lock it was created from.
+    fn drop(&mut self) {
+        unsafe { self.lock.force_write_unlock() }
+    }
+}
@@ -0,0 +1,317 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex as StdMutex, Once as StdOnce};
+
+use axtask as thread;
+
+use crate::{Barrier, Condvar, Mutex, Once, OnceLock, Rcu, RwLock, Semaphore};
+
+pub(crate) static INIT: StdOnce = StdOnce::new();
+pub(crate) static SERIAL: StdMutex<()> = StdMutex::new(());
+
+fn wait_for(counter: &AtomicUsize, n: usize) {
+    while counter.load(Ordering::Acquire) < n {
+        thread::yield_now();
+    }
+}
+
+#[test]
+fn test_condvar() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(thread::init_scheduler);
+
+    const NUM_ITEMS: usize = 100;
+    static QUEUE: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+    static NOT_EMPTY: Condvar = Condvar::new();
+    static CONSUMED: AtomicUsize = AtomicUsize::new(0);
+
+    thread::spawn(|| {
+        for i in 0..NUM_ITEMS {
+            let mut queue = NOT_EMPTY.wait_while(QUEUE.lock(), |q| q.is_empty());
+            assert_eq!(queue.remove(0), i);
+            CONSUMED.fetch_add(1, Ordering::Release);
+        }
+    });
+
+    for i in 0..NUM_ITEMS {
+        QUEUE.lock().push(i);
+        NOT_EMPTY.notify_one();
+        if i % 3 == 0 {
+            thread::yield_now();
+        }
+    }
+    wait_for(&CONSUMED, NUM_ITEMS);
+    assert!(QUEUE.lock().is_empty());
+}
+
+#[test]
+fn test_rwlock() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(thread::init_scheduler);
+
+    const NUM_TASKS: usize = 10;
+    const NUM_ITERS: usize = 100;
+    static LOCK: RwLock<(usize, usize)> = RwLock::new((0, 0));
+    static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+    for i in 0..NUM_TASKS {
+        thread::spawn(move || {
+            for _ in 0..NUM_ITERS {
+                if i % 2 == 0 {
+                    let mut val = LOCK.write();
+                    val.0 += 1;
+                    thread::yield_now();
+                    val.1 += 1;
+                } else {
+                    let val = LOCK.read();
+                    thread::yield_now();
+                    assert_eq!(val.0, val.1);
+                }
+            }
+            FINISHED.fetch_add(1, Ordering::Release);
+        });
+    }
+
+    wait_for(&FINISHED, NUM_TASKS);
+    let val = LOCK.read();
+    assert!(LOCK.try_read().is_some());
+    assert!(LOCK.try_write().is_none());
+    assert_eq!(*val, (NUM_TASKS / 2 * NUM_ITERS, NUM_TASKS / 2 * NUM_ITERS));
+}
+
+#[test]
+fn test_semaphore_and_barrier() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(thread::init_scheduler);
+
+    const NUM_TASKS: usize = 8;
+    static SEM: Semaphore = Semaphore::new(2);
+    static BARRIER: Barrier = Barrier::new(NUM_TASKS + 1);
+    static HOLDERS: AtomicUsize = AtomicUsize::new(0);
+    static LEADERS: AtomicUsize = AtomicUsize::new(0);
+    static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+    for _ in 0..NUM_TASKS {
+        thread::spawn(|| {
+            {
+                let _permit = SEM.access();
+                assert!(HOLDERS.fetch_add(1, Ordering::Relaxed) < 2);
+                thread::yield_now();
+                HOLDERS.fetch_sub(1, Ordering::Relaxed);
+            }
+            if BARRIER.wait().is_leader() {
+                LEADERS.fetch_add(1, Ordering::Relaxed);
+            }
+            FINISHED.fetch_add(1, Ordering::Release);
+        });
+    }
+
+    if BARRIER.wait().is_leader() {
+        LEADERS.fetch_add(1, Ordering::Relaxed);
+    }
+    wait_for(&FINISHED, NUM_TASKS);
+    assert_eq!(LEADERS.load(Ordering::Relaxed), 1);
+    assert_eq!(SEM.available_permits(), 2);
+    assert!(SEM.try_acquire());
+}
+
+#[test]
+fn test_once() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(thread::init_scheduler);
+
+    const NUM_TASKS: usize = 10;
+    static ONCE: Once = Once::new();
+    static CELL: OnceLock<usize> = OnceLock::new();
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+    static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+    for i in 0..NUM_TASKS {
+        thread::spawn(move || {
+            ONCE.call_once(|| {
+                thread::yield_now();
+                CALLS.fetch_add(1, Ordering::Relaxed);
+            });
+            assert!(ONCE.is_completed());
+            assert_eq!(*CELL.get_or_init(|| 42), 42);
+            assert_eq!(CELL.set(i), Err(i));
+            FINISHED.fetch_add(1, Ordering::Release);
+        });
+    }
+
+    wait_for(&FINISHED, NUM_TASKS);
+    assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+    assert_eq!(CELL.get(), Some(&42));
+}
+
+#[test]
+fn test_once_poison() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let _lock = SERIAL.lock();
+    INIT.call_once(thread::init_scheduler);
+
+    static ONCE: Once = Once::new();
+    static CELL: OnceLock<usize> = OnceLock::new();
+
+    // a panicking initialization poisons the `Once`.
+    let res = catch_unwind(AssertUnwindSafe(|| {
+        ONCE.call_once(|| panic!("init failed"))
+    }));
+    assert!(res.is_err());
+    assert!(!ONCE.is_completed());
+    let res = catch_unwind(AssertUnwindSafe(|| ONCE.call_once(|| {})));
+    assert!(res.is_err());
+    assert!(!ONCE.is_completed());
+
+    // but leaves the `OnceLock` empty for the next caller.
+    let res = catch_unwind(AssertUnwindSafe(|| {
+        CELL.get_or_init(|| panic!("init failed"))
+    }));
+    assert!(res.is_err());
+    assert_eq!(CELL.get(), None);
+    assert_eq!(*CELL.get_or_init(|| 42), 42);
+    assert_eq!(CELL.set(0), Err(0));
+}
+
+#[test]
+#[cfg(feature = "lockdep")]
+fn test_rwlock_lockdep() {
+    use axtask::lockdep::{num_reports, LockClass, LockKind};
+
+    let _lock = SERIAL.lock();
+    INIT.call_once(thread::init_scheduler);
+
+    let lock = RwLock::new(0);
+    let reports = num_reports();
+    {
+        // readers don't block each other, so nested reads are fine.
+        let _r1 = lock.read();
+        let _r2 = lock.read();
+        let _r3 = lock.try_read().unwrap();
+        let _r4 = lock.read();
+    }
+    assert_eq!(num_reports(), reports);
+
+    // but reading or writing while holding the write lock is reported.
+    let class = LockClass::new();
+    class.acquired(LockKind::Sleep);
+    class.acquire_read(LockKind::Sleep);
+    assert_eq!(num_reports(), reports + 1);
+    class.acquire(LockKind::Sleep);
+    assert_eq!(num_reports(), reports + 2);
+    for _ in 0..3 {
+        class.release();
+    }
+}
+
+#[test]
+fn test_priority_inheritance() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(thread::init_scheduler);
+
+    const LOW: isize = 10;
+    const MEDIUM: isize = 0;
+    const HIGH: isize = -10;
+    static A: Mutex<()> = Mutex::new(());
+    static B: Mutex<()> = Mutex::new(());
+    static STAGE: AtomicUsize = AtomicUsize::new(0);
+
+    fn wait_for_priority(prio: isize) {
+        while thread::current().priority() != prio {
+            thread::yield_now();
+        }
+    }
+
+    let low = thread::spawn(|| {
+        if !thread::set_priority(LOW) {
+            // The scheduler doesn't support priorities.
+            STAGE.store(usize::MAX, Ordering::Release);
+            return;
+        }
+        let a = A.lock();
+        let b = B.lock();
+        STAGE.store(1, Ordering::Release);
+        // The medium-priority task is blocked on B.
+        wait_for_priority(MEDIUM);
+        STAGE.store(2, Ordering::Release);
+        // The high-priority task is blocked on A.
+        wait_for_priority(HIGH);
+
+        // Still boosted by the high-priority task after releasing B.
+        drop(b);
+        assert_eq!(thread::current().priority(), HIGH);
+        drop(a);
+        assert_eq!(thread::current().priority(), LOW);
+    });
+
+    wait_for(&STAGE, 1);
+    if STAGE.load(Ordering::Acquire) == usize::MAX {
+        assert_eq!(low.join(), Some(0));
+        return;
+    }
+    let medium = thread::spawn(|| {
+        assert!(thread::set_priority(MEDIUM));
+        let _b = B.lock();
+        assert_eq!(thread::current().priority(), MEDIUM);
+    });
+    wait_for(&STAGE, 2);
+    let high = thread::spawn(|| {
+        assert!(thread::set_priority(HIGH));
+        let _a = A.lock();
+        assert_eq!(thread::current().priority(), HIGH);
+    });
+
+    assert_eq!(low.join(), Some(0));
+    assert_eq!(medium.join(), Some(0));
+    assert_eq!(high.join(), Some(0));
+}
+
+#[test]
+fn test_rcu() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(thread::init_scheduler);
+
+    const NUM_READERS: usize = 8;
+    const NUM_ITERS: usize = 100;
+    static DONE: AtomicBool = AtomicBool::new(false);
+    static FINISHED: AtomicUsize = AtomicUsize::new(0);
+    static VERSIONS_SEEN: AtomicUsize = AtomicUsize::new(0);
+
+    let rcu = Arc::new(Rcu::new((0, 0)));
+    for _ in 0..NUM_READERS {
+        let rcu = rcu.clone();
+        thread::spawn(move || {
+            // read concurrently with the writer until it finishes.
+            let mut last = 0;
+            while !DONE.load(Ordering::Acquire) {
+                let val = rcu.read();
+                assert_eq!(val.0, val.1);
+                // the values are published in order.
+                assert!(val.0 >= last);
+                if val.0 > last {
+                    VERSIONS_SEEN.fetch_add(1, Ordering::Relaxed);
+                    last = val.0;
+                }
+                drop(val);
+                thread::yield_now();
+            }
+            FINISHED.fetch_add(1, Ordering::Release);
+        });
+    }
+
+    for i in 0..NUM_ITERS {
+        if i % 2 == 0 {
+            rcu.update(|&(a, b)| (a + 1, b + 1));
+        } else {
+            let (a, b) = *rcu.read();
+            assert_eq!(rcu.replace((a + 1, b + 1)), (a, b));
+        }
+        // let the readers see the new value.
+        thread::yield_now();
+    }
+    DONE.store(true, Ordering::Release);
+    wait_for(&FINISHED, NUM_READERS);
+    assert_eq!(*rcu.read(), (NUM_ITERS, NUM_ITERS));
+    // the readers have run between the updates.
+    assert!(VERSIONS_SEEN.load(Ordering::Relaxed) >= NUM_ITERS);
+}
@@ -2,12 +2,19 @@
 
 use alloc::{string::String, sync::Arc};
 
-pub(crate) use crate::run_queue::{AxRunQueue, RUN_QUEUE};
+use axerrno::{AxError, AxResult};
 
+pub(crate) use crate::run_queue::{current_run_queue, select_run_queue};
+
+#[doc(cfg(feature = "multitask"))]
+pub use crate::cpumask::AxCpuMask;
 #[doc(cfg(feature = "multitask"))]
-pub use crate::task::{CurrentTask, TaskId, TaskInner};
+pub use crate::registry::{find_task, ps, tasks, total_cpu_usage};
+pub use crate::task::{CpuUsage, CurrentTask, DeadlineParams, TaskId, TaskInner, TaskState};
+#[doc(hidden)]
+pub use crate::task_ext::__priv;
 #[doc(cfg(feature = "multitask"))]
-pub use crate::task_ext::{TaskExtMut, TaskExtRef};
+pub use crate::task_ext::{TaskExtKey, TaskExtMut, TaskExtRef};
 #[doc(cfg(feature = "multitask"))]
 pub use crate::wait_queue::WaitQueue;
 
@@ -19,6 +26,9 @@ cfg_if::cfg_if! {
         const MAX_TIME_SLICE: usize = 5;
         pub(crate) type AxTask = scheduler::RRTask<TaskInner, MAX_TIME_SLICE>;
         pub(crate) type Scheduler = scheduler::RRScheduler<TaskInner, MAX_TIME_SLICE>;
+    } else if #[cfg(feature = "sched_edf")] {
+        pub(crate) type AxTask = crate::sched_edf::EDFTask<TaskInner>;
+        pub(crate) type Scheduler = crate::sched_edf::EDFScheduler<TaskInner>;
     } else if #[cfg(feature = "sched_cfs")] {
         pub(crate) type AxTask = scheduler::CFSTask<TaskInner>;
         pub(crate) type Scheduler = scheduler::CFScheduler<TaskInner>;
@@ -70,6 +80,8 @@ pub fn init_scheduler() {
     crate::run_queue::init();
     #[cfg(feature = "irq")]
     crate::timers::init();
+    #[cfg(feature = "watchdog")]
+    crate::watchdog::init();
 
     info!("  use {} scheduler.", Scheduler::scheduler_name());
 }
@@ -77,6 +89,8 @@ pub fn init_scheduler() {
 /// Initializes the task scheduler for secondary CPUs.
 pub fn init_scheduler_secondary() {
     crate::run_queue::init_secondary();
+    #[cfg(feature = "irq")]
+    crate::timers::init();
 }
 
 /// Handles periodic timer ticks for the task manager.
@@ -85,14 +99,21 @@ pub fn init_scheduler_secondary() {
 #[cfg(feature = "irq")]
 #[doc(cfg(feature = "irq"))]
 pub fn on_timer_tick() {
+    let start = axhal::time::monotonic_time_nanos();
     crate::timers::check_events();
-    RUN_QUEUE.lock().scheduler_timer_tick();
+    current_run_queue().scheduler_timer_tick();
+    crate::current().account_system_time(axhal::time::monotonic_time_nanos() - start);
 }
 
 /// Adds the given task to the run queue, returns the task reference.
+///
+/// The task is placed on the least loaded CPU, and may be migrated to other
+/// CPUs later by load balancing.
 pub fn spawn_task(task: TaskInner) -> AxTaskRef {
     let task_ref = task.into_arc();
-    RUN_QUEUE.lock().add_task(task_ref.clone());
+    crate::registry::register(&task_ref);
+    let _guard = kernel_guard::NoPreemptIrqSave::new();
+    crate::run_queue::select_run_queue_for_spawn(&task_ref).add_task(task_ref.clone());
     task_ref
 }
 
@@ -123,24 +144,153 @@ where
 ///
 /// The range of the priority is dependent on the underlying scheduler. For
 /// example, in the [CFS] scheduler, the priority is the nice value, ranging from
-/// -20 to 19.
+/// -20 to 19. A smaller value means a higher priority.
 ///
 /// Returns `true` if the priority is set successfully.
 ///
 /// [CFS]: https://en.wikipedia.org/wiki/Completely_Fair_Scheduler
 pub fn set_priority(prio: isize) -> bool {
-    RUN_QUEUE.lock().set_current_priority(prio)
+    let _guard = kernel_guard::NoPreemptIrqSave::new();
+    current_run_queue().set_current_priority(prio)
+}
+
+/// Raises the priority of the given task, the owner of the lock identified by
+/// `lock` (e.g., its address), to `prio`, if `prio` is higher than its
+/// current priority.
+///
+/// It's used by blocking locks to implement priority inheritance: a task that
+/// is going to wait for a lock lends its priority to the lock owner, which is
+/// taken back by [`restore_current_priority`] when the owner releases the lock.
+/// It has no effect if the scheduler doesn't support priorities.
+pub fn inherit_priority(task: &AxTaskRef, lock: usize, prio: isize) {
+    let _guard = kernel_guard::NoPreemptIrqSave::new();
+    select_run_queue(task).inherit_priority(task, lock, prio);
+}
+
+/// Drops the priority that the current task inherits through the lock `lock`,
+/// when it releases the lock.
+///
+/// The priorities inherited through other locks it still holds are kept,
+/// otherwise the one set by [`set_priority`] is restored.
+///
+/// Returns `true` if the priority of the current task changes.
+pub fn restore_current_priority(lock: usize) -> bool {
+    let _guard = kernel_guard::NoPreemptIrqSave::new();
+    current_run_queue().restore_current_priority(lock)
+}
+
+/// Whether the scheduler is EDF (the `sched_edf` feature is enabled), which
+/// supports real-time tasks with deadline parameters.
+pub const fn deadline_supported() -> bool {
+    cfg!(feature = "sched_edf")
+}
+
+/// Adds the given task to the run queue as a real-time task with the given
+/// deadline parameters, returns the task reference.
+///
+/// The bandwidth is reserved on the first CPU in the task's CPU mask that can
+/// admit it, and the task is pinned to that CPU.
+///
+/// Returns `None` and drops the task if the parameters are invalid, no CPU
+/// can admit it, or the scheduler is not EDF (the `sched_edf` feature is not
+/// enabled).
+pub fn spawn_task_deadline(task: TaskInner, params: DeadlineParams) -> Option<AxTaskRef> {
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "sched_edf")] {
+            let task_ref = task.into_arc();
+            let cpumask = task_ref.cpu_mask();
+            let _guard = kernel_guard::NoPreemptIrqSave::new();
+            let (cpu_id, rq) = (0..axconfig::SMP)
+                .filter(|&cpu_id| cpumask.get(cpu_id))
+                .filter_map(|cpu_id| Some((cpu_id, crate::run_queue::run_queue_of(cpu_id)?)))
+                .find(|&(cpu_id, _)| task_ref.set_deadline_params(Some(params), cpu_id))?;
+            task_ref.pin_cpu(cpu_id);
+            crate::registry::register(&task_ref);
+            rq.add_task(task_ref.clone());
+            Some(task_ref)
+        } else {
+            let _ = (task, params);
+            None
+        }
+    }
+}
+
+/// Sets the deadline parameters of the current task, making it a real-time
+/// task under the EDF scheduler, or a normal task again if `params` is `None`.
+///
+/// The bandwidth is reserved on the current CPU, and the task is pinned to
+/// it until it exits or becomes a normal task again, which restores its
+/// previous CPU mask. It's not migrated to other CPUs to find more bandwidth.
+///
+/// Returns `false` if the parameters are invalid, the current CPU cannot
+/// admit the bandwidth they reserve, or the scheduler is not EDF (the
+/// `sched_edf` feature is not enabled).
+pub fn set_deadline(params: Option<DeadlineParams>) -> bool {
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "sched_edf")] {
+            let _guard = kernel_guard::NoPreemptIrqSave::new();
+            let curr = current();
+            let cpu_id = axhal::cpu::this_cpu_id();
+            if !curr.as_task_ref().set_deadline_params(params, cpu_id) {
+                return false;
+            }
+            if params.is_some() {
+                curr.pin_cpu(cpu_id);
+            } else {
+                curr.unpin_cpu();
+            }
+            true
+        } else {
+            params.is_none()
+        }
+    }
+}
+
+/// Sets the CPUs that the given task is allowed to run on.
+///
+/// If the task is the current task and the current CPU is not in `cpumask`,
+/// it is migrated immediately. Otherwise, the task is migrated the next time
+/// it's scheduled.
+///
+/// Returns `false` if `cpumask` contains no CPU, or the task is a deadline
+/// task that would leave the CPU reserving its bandwidth.
+pub fn set_affinity(task: &AxTaskRef, cpumask: AxCpuMask) -> bool {
+    if cpumask.is_empty() {
+        return false;
+    }
+    #[cfg(feature = "sched_edf")]
+    if task
+        .deadline_cpu()
+        .is_some_and(|cpu_id| cpumask != AxCpuMask::one_shot(cpu_id))
+    {
+        return false;
+    }
+    task.set_cpu_mask(cpumask);
+    let _guard = kernel_guard::NoPreemptIrqSave::new();
+    if crate::current().ptr_eq(task) {
+        current_run_queue().migrate_current();
+    }
+    true
+}
+
+/// Sets the CPUs that the current task is allowed to run on.
+///
+/// See [`set_affinity`] for details.
+pub fn set_current_affinity(cpumask: AxCpuMask) -> bool {
+    set_affinity(crate::current().as_task_ref(), cpumask)
 }
 
 /// Current task gives up the CPU time voluntarily, and switches to another
 /// ready task.
 pub fn yield_now() {
-    RUN_QUEUE.lock().yield_current();
+    let _guard = kernel_guard::NoPreemptIrqSave::new();
+    current_run_queue().yield_current();
 }
 
 /// Current task is going to sleep for the given duration.
 ///
 /// If the feature `irq` is not enabled, it uses busy-wait instead.
+#[cfg_attr(feature = "lockdep", track_caller)]
 pub fn sleep(dur: core::time::Duration) {
     sleep_until(axhal::time::wall_time() + dur);
 }
@@ -148,26 +298,107 @@ pub fn sleep(dur: core::time::Duration) {
 /// Current task is going to sleep, it will be woken up at the given deadline.
 ///
 /// If the feature `irq` is not enabled, it uses busy-wait instead.
+#[cfg_attr(feature = "lockdep", track_caller)]
 pub fn sleep_until(deadline: axhal::time::TimeValue) {
+    #[cfg(feature = "lockdep")]
+    crate::lockdep::might_sleep();
     #[cfg(feature = "irq")]
-    RUN_QUEUE.lock().sleep_until(deadline);
+    {
+        let _guard = kernel_guard::NoPreemptIrqSave::new();
+        current_run_queue().sleep_until(deadline);
+    }
     #[cfg(not(feature = "irq"))]
     axhal::time::busy_wait_until(deadline);
 }
 
+/// Current task is going to sleep for the given duration, unless it's
+/// cancelled.
+///
+/// Returns [`AxError::Interrupted`] if the task is cancelled before or during
+/// the sleep, see [`TaskInner::cancel`].
+#[cfg_attr(feature = "lockdep", track_caller)]
+pub fn sleep_interruptible(dur: core::time::Duration) -> AxResult {
+    sleep_until_interruptible(axhal::time::wall_time() + dur)
+}
+
+/// Current task is going to sleep until the given deadline, unless it's
+/// cancelled.
+///
+/// Returns [`AxError::Interrupted`] if the task is cancelled before or during
+/// the sleep, see [`TaskInner::cancel`].
+#[cfg_attr(feature = "lockdep", track_caller)]
+pub fn sleep_until_interruptible(deadline: axhal::time::TimeValue) -> AxResult {
+    #[cfg(feature = "lockdep")]
+    crate::lockdep::might_sleep();
+    let curr = current();
+    curr.set_interruptible(true);
+    #[cfg(feature = "irq")]
+    {
+        let _guard = kernel_guard::NoPreemptIrqSave::new();
+        current_run_queue().sleep_until(deadline);
+    }
+    #[cfg(not(feature = "irq"))]
+    while !curr.is_cancelled() && axhal::time::wall_time() < deadline {
+        core::hint::spin_loop();
+    }
+    curr.set_interruptible(false);
+    if curr.is_cancelled() {
+        Err(AxError::Interrupted)
+    } else {
+        Ok(())
+    }
+}
+
+/// Whether the current task has been cancelled by [`TaskInner::cancel`].
+///
+/// Long-running or polling loops should check it and return early.
+pub fn is_cancelled() -> bool {
+    current_may_uninit().is_some_and(|curr| curr.is_cancelled())
+}
+
+/// Wakes the given [`Waker`](core::task::Waker) when the wall time reaches
+/// the deadline.
+///
+/// It's the building block of asynchronous timers. The waker is called in
+/// the timer interrupt handler, so it must not block.
+#[cfg(feature = "irq")]
+#[doc(cfg(feature = "irq"))]
+pub fn wake_at(deadline: axhal::time::TimeValue, waker: core::task::Waker) {
+    crate::timers::set_alarm_waker(deadline, waker);
+}
+
 /// Exits the current task.
 pub fn exit(exit_code: i32) -> ! {
-    RUN_QUEUE.lock().exit_current(exit_code)
+    let _guard = kernel_guard::NoPreemptIrqSave::new();
+    current_run_queue().exit_current(exit_code)
 }
 
 /// The idle task routine.
 ///
-/// It runs an infinite loop that keeps calling [`yield_now()`].
+/// It runs an infinite loop that keeps calling [`yield_now()`]. If the
+/// `tickless` feature is enabled, the periodic tick is stopped while the CPU
+/// is waiting for IRQs.
 pub fn run_idle() -> ! {
     loop {
         yield_now();
         debug!("idle task: waiting for IRQs...");
-        #[cfg(feature = "irq")]
+        #[cfg(feature = "tickless")]
+        {
+            // IRQs are disabled until the CPU sleeps, so that a task woken up
+            // after the check, or a deadline before the timer is programmed,
+            // is not missed.
+            axhal::arch::disable_irqs();
+            if current_run_queue().has_ready_tasks() {
+                axhal::arch::enable_irqs();
+                continue;
+            }
+            crate::timers::stop_tick();
+            axhal::arch::enable_irqs_and_wait();
+            // the IRQ that woke up the CPU has been handled.
+            let _guard = kernel_guard::NoPreemptIrqSave::new();
+            crate::timers::restart_tick();
+        }
+        #[cfg(all(feature = "irq", not(feature = "tickless")))]
         axhal::arch::wait_for_irqs();
     }
 }
@@ -1,5 +1,7 @@
 //! Task APIs for single-task configuration.
 
+use axerrno::AxResult;
+
 /// For single-task situation, we just relax the CPU and wait for incoming
 /// interrupts.
 pub fn yield_now() {
@@ -20,3 +22,22 @@ pub fn sleep(dur: core::time::Duration) {
 pub fn sleep_until(deadline: axhal::time::TimeValue) {
     axhal::time::busy_wait_until(deadline);
 }
+
+/// For single-task situation, we just busy wait for the given duration, as
+/// the only task cannot be cancelled.
+pub fn sleep_interruptible(dur: core::time::Duration) -> AxResult {
+    axhal::time::busy_wait(dur);
+    Ok(())
+}
+
+/// For single-task situation, we just busy wait until reaching the given
+/// deadline, as the only task cannot be cancelled.
+pub fn sleep_until_interruptible(deadline: axhal::time::TimeValue) -> AxResult {
+    axhal::time::busy_wait_until(deadline);
+    Ok(())
+}
+
+/// For single-task situation, the only task is never cancelled.
+pub fn is_cancelled() -> bool {
+    false
+}
@@ -0,0 +1,103 @@
+//! CPU masks for task affinity.
+
+use core::fmt;
+
+const BITS_PER_WORD: usize = u64::BITS as usize;
+const NUM_WORDS: usize = axconfig::SMP.div_ceil(BITS_PER_WORD);
+
+/// A set of CPUs, used to specify which CPUs a task is allowed to run on.
+///
+/// Only the first [`axconfig::SMP`] bits are meaningful, bits of non-existent
+/// CPUs are always cleared.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct AxCpuMask {
+    bits: [u64; NUM_WORDS],
+}
+
+impl AxCpuMask {
+    /// Creates an empty mask that contains no CPU.
+    pub const fn new() -> Self {
+        Self {
+            bits: [0; NUM_WORDS],
+        }
+    }
+
+    /// Creates a mask that contains all CPUs.
+    pub fn full() -> Self {
+        let mut mask = Self::new();
+        for cpu_id in 0..axconfig::SMP {
+            mask.set(cpu_id, true);
+        }
+        mask
+    }
+
+    /// Creates a mask that only contains the given CPU.
+    pub fn one_shot(cpu_id: usize) -> Self {
+        let mut mask = Self::new();
+        mask.set(cpu_id, true);
+        mask
+    }
+
+    /// Creates a mask from raw bits, in which bit `i` of `bits[i / 64]`
+    /// represents CPU `i`.
+    ///
+    /// Bits beyond the number of CPUs are ignored.
+    pub fn from_raw_bits(bits: &[u64]) -> Self {
+        let mut mask = Self::new();
+        for cpu_id in 0..axconfig::SMP.min(bits.len() * BITS_PER_WORD) {
+            if bits[cpu_id / BITS_PER_WORD] & (1 << (cpu_id % BITS_PER_WORD)) != 0 {
+                mask.set(cpu_id, true);
+            }
+        }
+        mask
+    }
+
+    /// Returns the raw bits of the mask, see [`AxCpuMask::from_raw_bits`].
+    pub const fn as_raw_bits(&self) -> &[u64] {
+        &self.bits
+    }
+
+    /// Returns whether the given CPU is in the mask.
+    pub const fn get(&self, cpu_id: usize) -> bool {
+        cpu_id < axconfig::SMP
+            && self.bits[cpu_id / BITS_PER_WORD] & (1 << (cpu_id % BITS_PER_WORD)) != 0
+    }
+
+    /// Adds the given CPU to (if `value` is `true`) or removes it from the
+    /// mask.
+    ///
+    /// It does nothing if `cpu_id` exceeds the number of CPUs.
+    pub fn set(&mut self, cpu_id: usize, value: bool) {
+        if cpu_id >= axconfig::SMP {
+            return;
+        }
+        let bit = 1 << (cpu_id % BITS_PER_WORD);
+        if value {
+            self.bits[cpu_id / BITS_PER_WORD] |= bit;
+        } else {
+            self.bits[cpu_id / BITS_PER_WORD] &= !bit;
+        }
+    }
+
+    /// Returns `true` if the mask contains no CPU.
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|&w| w == 0)
+    }
+
+    /// Returns the number of CPUs in the mask.
+    pub fn len(&self) -> usize {
+        self.bits.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Returns an iterator over the IDs of CPUs in the mask, in ascending
+    /// order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..axconfig::SMP).filter(|&cpu_id| self.get(cpu_id))
+    }
+}
+
+impl fmt::Debug for AxCpuMask {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
@@ -10,9 +10,31 @@
 //!   management and scheduling is used, as well as more task-related APIs.
 //!   Otherwise, only a few APIs with naive implementation is available.
 //! - `irq`: Interrupts are enabled. If this feature is enabled, timer-based
-//!    APIs can be used, such as [`sleep`], [`sleep_until`], and
-//!    [`WaitQueue::wait_timeout`].
+//!    APIs can be used, such as [`sleep`], [`sleep_until`],
+//!    [`WaitQueue::wait_timeout`], and the callbacks in mod `timer`.
 //! - `preempt`: Enable preemptive scheduling.
+//! - `tickless`: Stop the periodic timer tick when a CPU is idle, it only wakes
+//!   up for the next timer event (or IRQs). It also enables the `irq` feature.
+//! - `paging`: Flush the TLBs for the shootdowns of other CPUs on reschedules
+//!   and timer ticks.
+//! - `stack_guard`: Map kernel stacks of tasks in the kernel address space,
+//!   each with an unmapped guard page below it, so that stack overflows are
+//!   reported on double faults instead of corrupting other memory. It's only
+//!   supported on x86_64, which handles the faults on a separate stack, and
+//!   refuses to build on other architectures. It also enables the
+//!   `multitask` and `paging` features.
+//! - `lockdep`: Validate the order of lock acquisitions at runtime, and report
+//!   possible deadlocks, recursive locking, and sleeping while holding a
+//!   spinlock. See mod `lockdep` for details. It also enables the
+//!   `multitask` feature.
+//! - `sched_trace`: Record scheduler events in per-CPU ring buffers, which
+//!   can be exported in the Chrome trace event format. See mod `trace`.
+//! - `watchdog`: Report CPUs that don't reschedule and tasks blocked for too
+//!   long. See mod `watchdog`. It also enables the
+//!   `multitask` and `irq` features.
+//! - `smp`: Enable multi-core support. Each CPU has its own run queue, new
+//!   tasks are placed on the least loaded CPU, and idle CPUs steal ready tasks
+//!   from busy ones.
 //! - `sched_fifo`: Use the [FIFO cooperative scheduler][1]. It also enables the
 //!   `multitask` feature if it is enabled. This feature is enabled by default,
 //!   and it can be overriden by other scheduler features.
@@ -20,6 +42,11 @@
 //!   the `multitask` and `preempt` features if it is enabled.
 //! - `sched_cfs`: Use the [Completely Fair Scheduler][3]. It also enables the
 //!   the `multitask` and `preempt` features if it is enabled.
+//! - `sched_edf`: Use the earliest-deadline-first real-time scheduler. Tasks
+//!   declare their runtime, deadline and period with [`set_deadline`] or
+//!   [`spawn_task_deadline`], and are pinned to the CPU that admits them.
+//!   Tasks without deadlines run only when no real-time task is ready. It
+//!   also enables the `multitask` and `preempt` features if it is enabled.
 //!
 //! [1]: scheduler::FifoScheduler
 //! [2]: scheduler::RRScheduler
@@ -33,6 +60,9 @@
 #![feature(const_ptr_is_null)]
 #![feature(const_unsafecell_get_mut)]
 
+#[cfg(all(feature = "stack_guard", not(target_arch = "x86_64")))]
+compile_error!("the `stack_guard` feature is only supported on x86_64");
+
 #[cfg(test)]
 mod tests;
 
@@ -42,20 +72,49 @@ cfg_if::cfg_if! {
         extern crate log;
         extern crate alloc;
 
+        mod cpumask;
+        mod registry;
         mod run_queue;
         mod task;
         mod task_ext;
         mod api;
         mod wait_queue;
 
+        pub mod rcu;
+
         #[cfg(feature = "irq")]
         mod timers;
+        #[cfg(feature = "irq")]
+        #[doc(cfg(feature = "irq"))]
+        pub mod timer;
+        #[cfg(feature = "sched_edf")]
+        mod sched_edf;
+        #[cfg(feature = "lockdep")]
+        #[doc(cfg(feature = "lockdep"))]
+        pub mod lockdep;
+        #[cfg(feature = "sched_trace")]
+        #[doc(cfg(feature = "sched_trace"))]
+        pub mod trace;
+        #[cfg(feature = "watchdog")]
+        #[doc(cfg(feature = "watchdog"))]
+        pub mod watchdog;
+
+        #[cfg(feature = "lockdep")]
+        use self::lockdep::spin;
+        #[cfg(not(feature = "lockdep"))]
+        use kspin as spin;
 
         #[doc(cfg(feature = "multitask"))]
         pub use self::api::*;
-        pub use self::api::{sleep, sleep_until, yield_now};
+        pub use self::api::{
+            is_cancelled, sleep, sleep_interruptible, sleep_until, sleep_until_interruptible,
+            yield_now,
+        };
     } else {
         mod api_s;
-        pub use self::api_s::{sleep, sleep_until, yield_now};
+        pub use self::api_s::{
+            is_cancelled, sleep, sleep_interruptible, sleep_until, sleep_until_interruptible,
+            yield_now,
+        };
     }
 }
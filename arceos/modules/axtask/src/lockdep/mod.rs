@@ -0,0 +1,346 @@
+//! Lock dependency validator.
+//!
+//! Locks are grouped into classes by the call sites of their constructors,
+//! like the static lock class keys in Linux: all locks created at the same
+//! site (e.g., the locks of different tasks) belong to the same class. For
+//! each task, the validator keeps the list of locks it's holding. When the
+//! task acquires a lock, the validator records that lock's class as acquired
+//! after every held class. It reports these problems through the log:
+//!
+//! - A circular dependency: acquiring lock `B` while holding lock `A` after
+//!   `A` has been acquired while holding `B` (maybe through other locks).
+//!   Both call sites of every dependency in the cycle are printed.
+//! - Recursive locking: acquiring a lock already held by the current task,
+//!   except for taking a read lock that is only held for reading.
+//! - Sleeping (e.g., waiting on a [`WaitQueue`](crate::WaitQueue)) while
+//!   holding a spinlock.
+//!
+//! Locks of the same class are not ordered against each other, so nesting
+//! them (e.g., locking two run queues) is not reported.
+//!
+//! Tracked locks are `axsync::Mutex`, `axsync::RwLock`, the spinlocks in mod
+//! [`spin`] (which are re-exported as `axsync::spin`), and the locks inside
+//! this crate. Spinlocks used directly from the [`kspin`] crate are not
+//! tracked.
+
+pub mod spin;
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::fmt;
+use core::panic::Location;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use kernel_guard::NoPreemptIrqSave;
+use kspin::SpinRaw;
+
+use crate::task::CurrentTask;
+
+/// The kind of a tracked lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LockKind {
+    /// A spinlock, the holder must not sleep.
+    Spin,
+    /// A sleeping lock, e.g., a mutex.
+    Sleep,
+}
+
+/// The key of a lock class in the dependency graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum ClassKey {
+    /// Locks created at the call site. The kind tells apart the locks created
+    /// together, e.g., a mutex and its internal spinlock.
+    Site(&'static Location<'static>, LockKind),
+    /// A lock without a known construction site (e.g., a `pthread_mutex_t`
+    /// initialized by C code), which is a class of its own.
+    Instance(usize),
+}
+
+impl fmt::Display for ClassKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Site(site, _) => write!(f, "lock created at {}", site),
+            Self::Instance(addr) => write!(f, "lock {:#x}", addr),
+        }
+    }
+}
+
+/// A lock held by a task.
+pub(crate) struct HeldLock {
+    class: ClassKey,
+    /// The address of the lock, to tell apart locks of the same class.
+    instance: usize,
+    kind: LockKind,
+    /// Whether the lock is held for shared reading.
+    read: bool,
+    location: &'static Location<'static>,
+}
+
+/// A recorded dependency: the call sites where the first lock and then the
+/// second lock were acquired.
+struct Dependency {
+    from: &'static Location<'static>,
+    to: &'static Location<'static>,
+}
+
+/// Dependencies between lock classes, accessed with IRQs disabled.
+static DEPENDENCIES: SpinRaw<BTreeMap<ClassKey, BTreeMap<ClassKey, Dependency>>> =
+    SpinRaw::new(BTreeMap::new());
+
+/// The number of problems reported so far.
+static NUM_REPORTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the number of problems reported so far.
+pub fn num_reports() -> usize {
+    NUM_REPORTS.load(Ordering::Relaxed)
+}
+
+fn report() {
+    NUM_REPORTS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// The identity of a tracked lock in the dependency graph.
+///
+/// It should be embedded in the lock, and created in the constructor of the
+/// lock, which should be `#[track_caller]` so that the class is keyed by the
+/// call site of the constructor.
+pub struct LockClass {
+    site: Option<&'static Location<'static>>,
+}
+
+impl LockClass {
+    /// Creates the lock class of the caller's call site.
+    #[track_caller]
+    pub const fn new() -> Self {
+        Self {
+            site: Some(Location::caller()),
+        }
+    }
+
+    fn key(&self, kind: LockKind) -> ClassKey {
+        match self.site {
+            Some(site) => ClassKey::Site(site, kind),
+            None => ClassKey::Instance(self.instance()),
+        }
+    }
+
+    fn instance(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    /// Validates and records that the current task is going to acquire the
+    /// lock.
+    ///
+    /// It must be called before spinning or blocking on the lock, so that
+    /// problems are reported before the deadlock happens.
+    #[track_caller]
+    pub fn acquire(&self, kind: LockKind) {
+        self.acquire_inner(kind, false, Location::caller());
+    }
+
+    /// Like [`acquire`](Self::acquire), but for the shared read access of a
+    /// readers-writer lock.
+    ///
+    /// Taking the read lock again while holding it for reading is not
+    /// reported, as readers don't block each other.
+    #[track_caller]
+    pub fn acquire_read(&self, kind: LockKind) {
+        self.acquire_inner(kind, true, Location::caller());
+    }
+
+    fn acquire_inner(&self, kind: LockKind, read: bool, location: &'static Location<'static>) {
+        let _guard = NoPreemptIrqSave::new();
+        let Some(curr) = CurrentTask::try_get() else {
+            return;
+        };
+        let class = self.key(kind);
+        let instance = self.instance();
+        // SAFETY: IRQs are disabled, and the list is only accessed by the
+        // current task.
+        let held = unsafe { curr.held_locks() };
+        let mut same = held.iter().filter(|h| h.instance == instance);
+        if let Some(prev) = same.clone().find(|h| !(read && h.read)) {
+            report();
+            error!("lockdep: recursive locking detected in {}", curr.id_name());
+            error!("  acquiring {} at {}", class, location);
+            error!("  which is already held since {}", prev.location);
+        } else if same.next().is_none() {
+            let mut deps = DEPENDENCIES.lock();
+            for prev in held.iter().filter(|h| h.class != class) {
+                add_dependency(&mut deps, prev, class, location, &curr);
+            }
+        }
+        held.push(HeldLock {
+            class,
+            instance,
+            kind,
+            read,
+            location,
+        });
+    }
+
+    /// Records that the current task has acquired the lock without waiting
+    /// (e.g., by `try_lock`), which cannot cause deadlocks by itself.
+    #[track_caller]
+    pub fn acquired(&self, kind: LockKind) {
+        self.acquired_inner(kind, false, Location::caller());
+    }
+
+    /// Like [`acquired`](Self::acquired), but for the shared read access of a
+    /// readers-writer lock.
+    #[track_caller]
+    pub fn acquired_read(&self, kind: LockKind) {
+        self.acquired_inner(kind, true, Location::caller());
+    }
+
+    fn acquired_inner(&self, kind: LockKind, read: bool, location: &'static Location<'static>) {
+        let _guard = NoPreemptIrqSave::new();
+        if let Some(curr) = CurrentTask::try_get() {
+            // SAFETY: IRQs are disabled, and the list is only accessed by the
+            // current task.
+            unsafe { curr.held_locks() }.push(HeldLock {
+                class: self.key(kind),
+                instance: self.instance(),
+                kind,
+                read,
+                location,
+            });
+        }
+    }
+
+    /// Records that the current task has released the lock.
+    pub fn release(&self) {
+        let _guard = NoPreemptIrqSave::new();
+        if let Some(curr) = CurrentTask::try_get() {
+            let instance = self.instance();
+            // SAFETY: IRQs are disabled, and the list is only accessed by the
+            // current task.
+            let held = unsafe { curr.held_locks() };
+            // locks are not always released in reverse order.
+            if let Some(pos) = held.iter().rposition(|h| h.instance == instance) {
+                held.remove(pos);
+            }
+        }
+    }
+}
+
+impl Default for LockClass {
+    #[track_caller]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for LockClass {
+    fn drop(&mut self) {
+        // only the classes of single locks die with the locks.
+        if self.site.is_some() {
+            return;
+        }
+        let class = ClassKey::Instance(self.instance());
+        let _guard = NoPreemptIrqSave::new();
+        let mut deps = DEPENDENCIES.lock();
+        deps.remove(&class);
+        for to in deps.values_mut() {
+            to.remove(&class);
+        }
+    }
+}
+
+/// Reports if the current task holds any spinlock, it must be called before
+/// the task sleeps.
+#[track_caller]
+pub fn might_sleep() {
+    let location = Location::caller();
+    let _guard = NoPreemptIrqSave::new();
+    let Some(curr) = CurrentTask::try_get() else {
+        return;
+    };
+    // SAFETY: IRQs are disabled, and the list is only accessed by the current
+    // task.
+    let held = unsafe { curr.held_locks() };
+    for lock in held.iter().filter(|h| h.kind == LockKind::Spin) {
+        report();
+        error!(
+            "lockdep: {} may sleep at {} while holding spin{} acquired at {}",
+            curr.id_name(),
+            location,
+            lock.class,
+            lock.location
+        );
+    }
+}
+
+/// Records that `class` is acquired after `prev`, and reports if it closes a
+/// cycle.
+fn add_dependency(
+    deps: &mut BTreeMap<ClassKey, BTreeMap<ClassKey, Dependency>>,
+    prev: &HeldLock,
+    class: ClassKey,
+    location: &'static Location<'static>,
+    curr: &CurrentTask,
+) {
+    if deps
+        .get(&prev.class)
+        .is_some_and(|to| to.contains_key(&class))
+    {
+        return; // already recorded
+    }
+    if let Some(path) = find_path(deps, class, prev.class) {
+        report();
+        error!(
+            "lockdep: possible circular locking dependency detected in {}",
+            curr.id_name()
+        );
+        error!("  acquiring {} at {}", class, location);
+        error!(
+            "  while holding {} acquired at {}",
+            prev.class, prev.location
+        );
+        error!("  but the reverse order has been recorded before:");
+        for (from, to) in path {
+            let dep = &deps[&from][&to];
+            error!("    {} acquired at {}, then", from, dep.from);
+            error!("    {} acquired at {}", to, dep.to);
+        }
+    }
+    deps.entry(prev.class).or_default().insert(
+        class,
+        Dependency {
+            from: prev.location,
+            to: location,
+        },
+    );
+}
+
+/// Finds a path of dependencies from `src` to `dst`, returns the edges on the
+/// path.
+fn find_path(
+    deps: &BTreeMap<ClassKey, BTreeMap<ClassKey, Dependency>>,
+    src: ClassKey,
+    dst: ClassKey,
+) -> Option<Vec<(ClassKey, ClassKey)>> {
+    let mut parent = BTreeMap::new();
+    let mut stack = alloc::vec![src];
+    parent.insert(src, src);
+    while let Some(node) = stack.pop() {
+        if node == dst {
+            let mut path = Vec::new();
+            let mut node = dst;
+            while node != src {
+                let prev = parent[&node];
+                path.push((prev, node));
+                node = prev;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        for &next in deps.get(&node).into_iter().flat_map(|to| to.keys()) {
+            if !parent.contains_key(&next) {
+                parent.insert(next, node);
+                stack.push(next);
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,85 @@
+//! Grace period detection for RCU (read-copy-update).
+//!
+//! RCU readers run with preemption disabled and must not block, so once a CPU
+//! has rescheduled, all the read-side critical sections that were running on
+//! it have finished, i.e., the CPU has passed a *quiescent state*. An idle CPU
+//! is always in a quiescent state. A grace period ends when every CPU has
+//! passed a quiescent state since it began.
+//!
+//! Quiescent states are reported by the scheduler on every reschedule, and on
+//! timer ticks that interrupt a preemptible task (with the `preempt` feature).
+//! Therefore, a CPU that runs a single task which never yields under a
+//! cooperative scheduler will delay the grace period until it yields.
+//!
+//! The RCU-protected pointer itself is provided by `axsync::Rcu`.
+
+use core::sync::atomic::{fence, AtomicUsize, Ordering};
+
+/// The low bit of the per-CPU state, set if the CPU is running its idle task.
+const IDLE: usize = 1;
+/// The amount added to the per-CPU state on each quiescent state.
+const QS_ONE: usize = 2;
+
+struct CpuQsState {
+    /// The number of quiescent states (times [`QS_ONE`]), with the [`IDLE`]
+    /// flag.
+    state: AtomicUsize,
+}
+
+impl CpuQsState {
+    /// CPUs that are not booted yet are considered idle.
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = Self {
+        state: AtomicUsize::new(IDLE),
+    };
+}
+
+static CPUS: [CpuQsState; axconfig::SMP] = [CpuQsState::INIT; axconfig::SMP];
+
+/// Reports a quiescent state of the given CPU, which is about to run the idle
+/// task if `idle` is true.
+///
+/// It must be called on the CPU `cpu_id` with IRQs disabled.
+pub(crate) fn report_qs(cpu_id: usize, idle: bool) {
+    let state = &CPUS[cpu_id].state;
+    // the accesses of the finished read-side critical sections must complete
+    // before the quiescent state is observed by `synchronize`.
+    fence(Ordering::SeqCst);
+    // only the CPU itself updates its state, no need to use RMW operations.
+    let seq = state.load(Ordering::Relaxed) & !IDLE;
+    state.store(seq.wrapping_add(QS_ONE) | idle as usize, Ordering::SeqCst);
+}
+
+/// Blocks the current task until a grace period has elapsed, i.e., all the
+/// RCU read-side critical sections that were running when it's called have
+/// completed.
+///
+/// It must not be called inside a read-side critical section, otherwise it
+/// will never return.
+pub fn synchronize() {
+    let this_cpu = {
+        let _guard = kernel_guard::NoPreemptIrqSave::new();
+        axhal::cpu::this_cpu_id()
+    };
+    // The current CPU is in a quiescent state, as the caller is not a reader.
+    // Even if we are migrated afterwards, the readers that were running on it
+    // must have finished before we ran.
+    //
+    // The fence orders the publication of the new value (before this call)
+    // before the snapshot, so the readers that started after the snapshot
+    // see the new value.
+    fence(Ordering::SeqCst);
+    let snapshot: [usize; axconfig::SMP] =
+        core::array::from_fn(|cpu_id| CPUS[cpu_id].state.load(Ordering::SeqCst));
+    for (cpu_id, &seq) in snapshot.iter().enumerate() {
+        if cpu_id == this_cpu || seq & IDLE != 0 {
+            continue;
+        }
+        while CPUS[cpu_id].state.load(Ordering::SeqCst) == seq {
+            crate::yield_now();
+        }
+    }
+    // pairs with the fence in `report_qs`, the readers have finished
+    // accessing the old value before the caller frees it.
+    fence(Ordering::SeqCst);
+}
@@ -0,0 +1,97 @@
+//! The registry of all live tasks, for task introspection.
+
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::fmt::Write;
+
+use crate::spin::SpinNoIrq;
+use crate::{AxTaskRef, CpuUsage};
+
+/// All tasks that have not exited, indexed by task IDs.
+static TASKS: SpinNoIrq<BTreeMap<u64, AxTaskRef>> = SpinNoIrq::new(BTreeMap::new());
+
+/// CPU usage of all tasks that have exited.
+static EXITED_USAGE: SpinNoIrq<CpuUsage> = SpinNoIrq::new(CpuUsage {
+    utime: core::time::Duration::ZERO,
+    stime: core::time::Duration::ZERO,
+    voluntary_switches: 0,
+    involuntary_switches: 0,
+});
+
+pub(crate) fn register(task: &AxTaskRef) {
+    TASKS.lock().insert(task.id().as_u64(), task.clone());
+}
+
+pub(crate) fn unregister(task: &AxTaskRef) {
+    if TASKS.lock().remove(&task.id().as_u64()).is_some() {
+        *EXITED_USAGE.lock() += task.cpu_usage();
+    }
+}
+
+/// Returns an iterator over a snapshot of all live tasks (including idle
+/// tasks), in the order of task IDs.
+pub fn tasks() -> impl Iterator<Item = AxTaskRef> {
+    TASKS
+        .lock()
+        .values()
+        .cloned()
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+/// Returns the live tasks that satisfy the predicate, which is called with
+/// the registry locked.
+#[cfg(feature = "watchdog")]
+pub(crate) fn filter_tasks<F>(mut pred: F) -> Vec<AxTaskRef>
+where
+    F: FnMut(&AxTaskRef) -> bool,
+{
+    TASKS
+        .lock()
+        .values()
+        .filter(|&task| pred(task))
+        .cloned()
+        .collect()
+}
+
+/// Finds the live task with the given ID.
+pub fn find_task(id: u64) -> Option<AxTaskRef> {
+    TASKS.lock().get(&id).cloned()
+}
+
+/// Returns the total CPU usage of all tasks except the idle tasks, including
+/// those that have exited.
+pub fn total_cpu_usage() -> CpuUsage {
+    let mut usage = *EXITED_USAGE.lock();
+    for task in tasks().filter(|t| !t.is_idle()) {
+        usage += task.cpu_usage();
+    }
+    usage
+}
+
+/// Returns a `ps`-style table of all live tasks.
+pub fn ps() -> String {
+    let mut buf = String::new();
+    writeln!(
+        buf,
+        "{:>5} {:>3} S {:>12} {:>12} {:>8} {:>8} NAME",
+        "TID", "CPU", "UTIME(us)", "STIME(us)", "NVCSW", "NIVCSW"
+    )
+    .ok();
+    for task in tasks() {
+        let usage = task.cpu_usage();
+        writeln!(
+            buf,
+            "{:>5} {:>3} {} {:>12} {:>12} {:>8} {:>8} {}",
+            task.id().as_u64(),
+            task.cpu_id(),
+            task.state().as_char(),
+            usage.utime.as_micros(),
+            usage.stime.as_micros(),
+            usage.voluntary_switches,
+            usage.involuntary_switches,
+            task.name(),
+        )
+        .ok();
+    }
+    buf
+}
@@ -1,72 +1,265 @@
 use alloc::collections::VecDeque;
 use alloc::sync::Arc;
-use kspin::SpinNoIrq;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
 use lazyinit::LazyInit;
 use scheduler::BaseScheduler;
 
+use crate::spin::{SpinNoIrq, SpinRaw};
 use crate::task::{CurrentTask, TaskState};
-use crate::{AxTaskRef, Scheduler, TaskInner, WaitQueue};
+use crate::wait_queue::WaitQueueGuard;
+use crate::{AxCpuMask, AxTaskRef, Scheduler, TaskInner, WaitQueue};
 
-// TODO: per-CPU
-pub(crate) static RUN_QUEUE: LazyInit<SpinNoIrq<AxRunQueue>> = LazyInit::new();
+/// The number of timer ticks between two periodic load balancing attempts.
+#[cfg(feature = "irq")]
+const BALANCE_INTERVAL_TICKS: usize = 10;
 
-// TODO: per-CPU
-static EXITED_TASKS: SpinNoIrq<VecDeque<AxTaskRef>> = SpinNoIrq::new(VecDeque::new());
+#[allow(clippy::declare_interior_mutable_const)]
+const RUN_QUEUE_INIT: LazyInit<AxRunQueue> = LazyInit::new();
 
-static WAIT_FOR_EXIT: WaitQueue = WaitQueue::new();
+/// Run queues of all CPUs, indexed by the CPU ID.
+static RUN_QUEUES: [LazyInit<AxRunQueue>; axconfig::SMP] = [RUN_QUEUE_INIT; axconfig::SMP];
 
 #[percpu::def_percpu]
 static IDLE_TASK: LazyInit<AxTaskRef> = LazyInit::new();
 
+/// The task that was switched out by the last context switch on this CPU,
+/// whose `on_cpu` flag must be cleared by the next task.
+#[cfg(feature = "smp")]
+#[percpu::def_percpu]
+static PREV_TASK_PTR: usize = 0;
+
+/// Returns the run queue of the current CPU.
+///
+/// IRQs and preemption must be disabled by the caller, otherwise the current
+/// task may be migrated to another CPU while operating on the queue.
+#[inline]
+pub(crate) fn current_run_queue() -> &'static AxRunQueue {
+    &RUN_QUEUES[axhal::cpu::this_cpu_id()]
+}
+
+/// Returns the run queue of the given CPU, if it has been initialized.
+#[cfg(feature = "sched_edf")]
+pub(crate) fn run_queue_of(cpu_id: usize) -> Option<&'static AxRunQueue> {
+    RUN_QUEUES
+        .get(cpu_id)
+        .filter(|rq| rq.is_inited())
+        .map(|rq| &**rq)
+}
+
+/// Returns the run queue that the given task should be put into.
+///
+/// It's the queue of the CPU it last ran on, unless that CPU is no longer
+/// allowed by the task's CPU mask.
+pub(crate) fn select_run_queue(task: &AxTaskRef) -> &'static AxRunQueue {
+    let cpumask = task.cpu_mask();
+    let cpu_id = task.cpu_id();
+    if cpumask.get(cpu_id) {
+        &RUN_QUEUES[cpu_id]
+    } else {
+        least_loaded_run_queue(&cpumask)
+    }
+}
+
+/// Chooses the run queue for a newly spawned task.
+pub(crate) fn select_run_queue_for_spawn(task: &AxTaskRef) -> &'static AxRunQueue {
+    least_loaded_run_queue(&task.cpu_mask())
+}
+
+/// Returns the least loaded run queue among the CPUs in `cpumask`.
+///
+/// Ties are broken in a round-robin manner, so that tasks spawned in a burst
+/// spread over all CPUs. Falls back to the current CPU if no CPU in the mask
+/// has been initialized.
+fn least_loaded_run_queue(cpumask: &AxCpuMask) -> &'static AxRunQueue {
+    static NEXT_CPU: AtomicUsize = AtomicUsize::new(0);
+    let start = NEXT_CPU.fetch_add(1, Ordering::Relaxed);
+    (0..axconfig::SMP)
+        .map(|i| (start + i) % axconfig::SMP)
+        .filter(|&cpu_id| cpumask.get(cpu_id) && RUN_QUEUES[cpu_id].is_inited())
+        .map(|cpu_id| &*RUN_QUEUES[cpu_id])
+        .min_by_key(|rq| rq.nr_ready())
+        .unwrap_or_else(current_run_queue)
+}
+
+/// Finds the run queue with the most ready tasks other than `this`.
+fn busiest_run_queue(this: &AxRunQueue) -> Option<&'static AxRunQueue> {
+    RUN_QUEUES
+        .iter()
+        .filter(|rq| rq.is_inited() && rq.cpu_id != this.cpu_id)
+        .map(|rq| &**rq)
+        .max_by_key(|rq| rq.nr_ready())
+}
+
+/// The per-CPU run queue.
+///
+/// All methods must be called with IRQs and preemption disabled.
 pub(crate) struct AxRunQueue {
-    scheduler: Scheduler,
+    cpu_id: usize,
+    scheduler: SpinRaw<Scheduler>,
+    /// Number of tasks in `scheduler`, used for task placement and load
+    /// balancing.
+    nr_ready: AtomicUsize,
+    /// Exited tasks of this CPU, waiting to be dropped by the `gc` task.
+    exited_tasks: SpinNoIrq<VecDeque<AxTaskRef>>,
+    wait_for_exit: WaitQueue,
+    #[cfg(feature = "irq")]
+    ticks: AtomicUsize,
 }
 
 impl AxRunQueue {
-    pub fn new() -> SpinNoIrq<Self> {
-        let gc_task = TaskInner::new(gc_entry, "gc".into(), axconfig::TASK_STACK_SIZE).into_arc();
+    pub(crate) fn new(cpu_id: usize) -> Self {
+        let gc_task = TaskInner::new(
+            move || gc_entry(cpu_id),
+            alloc::format!("gc/{}", cpu_id),
+            axconfig::TASK_STACK_SIZE,
+        )
+        .into_arc();
+        gc_task.set_cpu_id(cpu_id);
+        // it waits for exited tasks, maybe forever.
+        #[cfg(feature = "watchdog")]
+        gc_task.set_hung_check(false);
+        crate::registry::register(&gc_task);
         let mut scheduler = Scheduler::new();
         scheduler.add_task(gc_task);
-        SpinNoIrq::new(Self { scheduler })
+        Self {
+            cpu_id,
+            scheduler: SpinRaw::new(scheduler),
+            nr_ready: AtomicUsize::new(1),
+            exited_tasks: SpinNoIrq::new(VecDeque::new()),
+            wait_for_exit: WaitQueue::new(),
+            #[cfg(feature = "irq")]
+            ticks: AtomicUsize::new(0),
+        }
     }
 
-    pub fn add_task(&mut self, task: AxTaskRef) {
-        debug!("task spawn: {}", task.id_name());
+    #[inline]
+    pub(crate) fn nr_ready(&self) -> usize {
+        self.nr_ready.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn enqueue(&self, task: AxTaskRef) {
+        task.set_cpu_id(self.cpu_id);
+        self.scheduler.lock().add_task(task);
+        self.nr_ready.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_task(&self, task: AxTaskRef) {
+        debug!("task spawn: {} on CPU {}", task.id_name(), self.cpu_id);
         assert!(task.is_ready());
-        self.scheduler.add_task(task);
+        #[cfg(feature = "sched_trace")]
+        crate::trace::record(
+            crate::trace::EventKind::Spawn,
+            task.id().as_u64(),
+            self.cpu_id as u64,
+        );
+        self.enqueue(task);
     }
 
     #[cfg(feature = "irq")]
-    pub fn scheduler_timer_tick(&mut self) {
+    pub fn scheduler_timer_tick(&self) {
         let curr = crate::current();
-        if !curr.is_idle() && self.scheduler.task_tick(curr.as_task_ref()) {
+        if !curr.is_idle() && self.scheduler.lock().task_tick(curr.as_task_ref()) {
             #[cfg(feature = "preempt")]
             curr.set_preempt_pending(true);
         }
+        // The IRQ handler has disabled preemption once, the interrupted task
+        // is not an RCU reader if it has not disabled preemption itself.
+        #[cfg(feature = "preempt")]
+        if curr.can_preempt(1) {
+            crate::rcu::report_qs(self.cpu_id, curr.is_idle());
+        }
+        #[cfg(feature = "paging")]
+        axmm::handle_tlb_shootdown(self.cpu_id, curr.is_idle());
+        if self.ticks.fetch_add(1, Ordering::Relaxed) % BALANCE_INTERVAL_TICKS == 0 {
+            self.balance();
+        }
+        #[cfg(feature = "watchdog")]
+        crate::watchdog::on_timer_tick(self.cpu_id, self.nr_ready());
+    }
+
+    /// Returns when the earliest throttled deadline task on this CPU gets its
+    /// budget back, in monotonic nanoseconds.
+    #[cfg(all(feature = "tickless", feature = "sched_edf"))]
+    pub fn next_replenish_time(&self) -> Option<u64> {
+        self.scheduler.lock().next_replenish_time()
+    }
+
+    /// Whether there are tasks ready to run on this CPU, which the idle task
+    /// must not sleep through. Throttled deadline tasks are not ready, the
+    /// timer is programmed for their replenishment instead.
+    #[cfg(feature = "tickless")]
+    pub fn has_ready_tasks(&self) -> bool {
+        #[cfg(feature = "sched_edf")]
+        let nr_throttled = self.scheduler.lock().nr_throttled();
+        #[cfg(not(feature = "sched_edf"))]
+        let nr_throttled = 0;
+        self.nr_ready() > nr_throttled
+    }
+
+    /// Migrates the current task to another CPU if it's no longer allowed to
+    /// run on this CPU.
+    pub fn migrate_current(&self) {
+        let curr = crate::current();
+        if !curr.cpu_mask().get(self.cpu_id) {
+            debug!("task migrate: {} from CPU {}", curr.id_name(), self.cpu_id);
+            self.resched(false);
+        }
     }
 
-    pub fn yield_current(&mut self) {
+    pub fn yield_current(&self) {
         let curr = crate::current();
         trace!("task yield: {}", curr.id_name());
         assert!(curr.is_running());
         self.resched(false);
     }
 
-    pub fn set_current_priority(&mut self, prio: isize) -> bool {
+    pub fn set_current_priority(&self, prio: isize) -> bool {
+        let curr = crate::current();
+        let mut scheduler = self.scheduler.lock();
+        if !scheduler.set_priority(curr.as_task_ref(), prio) {
+            return false;
+        }
+        curr.set_base_priority(prio);
+        // Keep the priorities inherited through the locks it holds.
+        let effective = curr.inherited_priority();
+        if effective != prio {
+            scheduler.set_priority(curr.as_task_ref(), effective);
+        }
+        curr.set_effective_priority(effective);
+        true
+    }
+
+    pub fn inherit_priority(&self, task: &AxTaskRef, lock: usize, prio: isize) {
+        task.add_pi_boost(lock, prio);
+        if prio < task.priority() && self.scheduler.lock().set_priority(task, prio) {
+            debug!("task inherits priority {}: {}", prio, task.id_name());
+            task.set_effective_priority(prio);
+        }
+    }
+
+    pub fn restore_current_priority(&self, lock: usize) -> bool {
+        let curr = crate::current();
+        curr.remove_pi_boost(lock);
+        let effective = curr.inherited_priority();
+        if curr.priority() == effective {
+            return false;
+        }
         self.scheduler
-            .set_priority(crate::current().as_task_ref(), prio)
+            .lock()
+            .set_priority(curr.as_task_ref(), effective);
+        curr.set_effective_priority(effective);
+        true
     }
 
     #[cfg(feature = "preempt")]
-    pub fn preempt_resched(&mut self) {
+    pub fn preempt_resched(&self) {
         let curr = crate::current();
         assert!(curr.is_running());
 
-        // When we get the mutable reference of the run queue, we must
-        // have held the `SpinNoIrq` lock with both IRQs and preemption
-        // disabled. So we need to set `current_disable_count` to 1 in
-        // `can_preempt()` to obtain the preemption permission before
-        //  locking the run queue.
+        // When we operate on the run queue, we must have disabled both IRQs
+        // and preemption. So we need to set `current_disable_count` to 1 in
+        // `can_preempt()` to obtain the preemption permission.
         let can_preempt = curr.can_preempt(1);
 
         debug!(
@@ -81,28 +274,44 @@ impl AxRunQueue {
         }
     }
 
-    pub fn exit_current(&mut self, exit_code: i32) -> ! {
+    pub fn exit_current(&self, exit_code: i32) -> ! {
         let curr = crate::current();
         debug!("task exit: {}, exit_code={}", curr.id_name(), exit_code);
         assert!(curr.is_running());
         assert!(!curr.is_idle());
         if curr.is_init() {
-            EXITED_TASKS.lock().clear();
+            self.exited_tasks.lock().clear();
             axhal::misc::terminate();
         } else {
+            // Unregister it while it's still running, so that the CPU time of
+            // its last time slice is counted.
+            crate::registry::unregister(curr.as_task_ref());
+            curr.set_exit_code(exit_code);
             curr.set_state(TaskState::Exited);
-            curr.notify_exit(exit_code, self);
-            EXITED_TASKS.lock().push_back(curr.clone());
-            WAIT_FOR_EXIT.notify_one_locked(false, self);
+            // release the reserved bandwidth.
+            #[cfg(feature = "sched_edf")]
+            curr.as_task_ref().set_deadline_params(None, self.cpu_id);
+            curr.notify_exit();
+            #[cfg(feature = "sched_trace")]
+            crate::trace::record(
+                crate::trace::EventKind::Exit,
+                curr.id().as_u64(),
+                exit_code as u64,
+            );
+            self.exited_tasks.lock().push_back(curr.clone());
+            self.wait_for_exit.notify_one(false);
             self.resched(false);
         }
         unreachable!("task exited!");
     }
 
-    pub fn block_current<F>(&mut self, wait_queue_push: F)
-    where
-        F: FnOnce(AxTaskRef),
-    {
+    /// Blocks the current task and puts it into the wait queue guarded by
+    /// `wq_guard`.
+    ///
+    /// The wait queue lock is released only after the task state is set to
+    /// [`TaskState::Blocked`], so that a concurrent notifier can never miss
+    /// the task.
+    pub fn blocked_resched(&self, mut wq_guard: WaitQueueGuard) {
         let curr = crate::current();
         debug!("task block: {}", curr.id_name());
         assert!(curr.is_running());
@@ -113,16 +322,38 @@ impl AxRunQueue {
         assert!(curr.can_preempt(1));
 
         curr.set_state(TaskState::Blocked);
-        wait_queue_push(curr.clone());
+        if curr.interrupt_pending() {
+            // cancelled before blocking, return early if no one has woken it
+            // up yet.
+            drop(wq_guard);
+            if !curr.transition_state(TaskState::Blocked, TaskState::Running) {
+                self.resched(false);
+            }
+            return;
+        }
+        curr.set_in_wait_queue(true);
+        #[cfg(feature = "watchdog")]
+        curr.set_blocked_since(axhal::time::monotonic_time_nanos());
+        #[cfg(feature = "sched_trace")]
+        crate::trace::record(crate::trace::EventKind::Block, curr.id().as_u64(), 0);
+        wq_guard.push_back(curr.clone());
+        drop(wq_guard);
         self.resched(false);
     }
 
-    pub fn unblock_task(&mut self, task: AxTaskRef, resched: bool) {
-        debug!("task unblock: {}", task.id_name());
-        if task.is_blocked() {
-            task.set_state(TaskState::Ready);
-            self.scheduler.add_task(task); // TODO: priority
-            if resched {
+    pub fn unblock_task(&self, task: AxTaskRef, resched: bool) {
+        debug!("task unblock: {} on CPU {}", task.id_name(), self.cpu_id);
+        // Use a CAS so that the task is enqueued only once if it is woken up
+        // by multiple events (e.g., timer and `notify()`) on different CPUs.
+        if task.transition_state(TaskState::Blocked, TaskState::Ready) {
+            #[cfg(feature = "sched_trace")]
+            crate::trace::record(
+                crate::trace::EventKind::Unblock,
+                task.id().as_u64(),
+                self.cpu_id as u64,
+            );
+            self.enqueue(task);
+            if resched && self.cpu_id == axhal::cpu::this_cpu_id() {
                 #[cfg(feature = "preempt")]
                 crate::current().set_preempt_pending(true);
             }
@@ -130,7 +361,7 @@ impl AxRunQueue {
     }
 
     #[cfg(feature = "irq")]
-    pub fn sleep_until(&mut self, deadline: axhal::time::TimeValue) {
+    pub fn sleep_until(&self, deadline: axhal::time::TimeValue) {
         let curr = crate::current();
         debug!("task sleep: {}, deadline={:?}", curr.id_name(), deadline);
         assert!(curr.is_running());
@@ -140,7 +371,17 @@ impl AxRunQueue {
         if now < deadline {
             crate::timers::set_alarm_wakeup(deadline, curr.clone());
             curr.set_state(TaskState::Blocked);
-            self.resched(false);
+            if !(curr.interrupt_pending()
+                && curr.transition_state(TaskState::Blocked, TaskState::Running))
+            {
+                #[cfg(feature = "sched_trace")]
+                crate::trace::record(crate::trace::EventKind::Block, curr.id().as_u64(), 0);
+                self.resched(false);
+            }
+            if curr.in_timer_list() {
+                // woken up early by `cancel()`.
+                crate::timers::cancel_alarm(curr.as_task_ref());
+            }
         }
     }
 }
@@ -148,22 +389,104 @@ impl AxRunQueue {
 impl AxRunQueue {
     /// Common reschedule subroutine. If `preempt`, keep current task's time
     /// slice, otherwise reset it.
-    fn resched(&mut self, preempt: bool) {
+    fn resched(&self, preempt: bool) {
         let prev = crate::current();
+        #[cfg(feature = "watchdog")]
+        crate::watchdog::touch(self.cpu_id, axhal::time::monotonic_time_nanos());
         if prev.is_running() {
             prev.set_state(TaskState::Ready);
             if !prev.is_idle() {
-                self.scheduler.put_prev_task(prev.clone(), preempt);
+                if prev.cpu_mask().get(self.cpu_id) {
+                    self.scheduler.lock().put_prev_task(prev.clone(), preempt);
+                    self.nr_ready.fetch_add(1, Ordering::Relaxed);
+                } else {
+                    // the CPU mask was changed, move it to an allowed CPU.
+                    select_run_queue(prev.as_task_ref()).enqueue(prev.clone());
+                }
             }
         }
-        let next = self.scheduler.pick_next_task().unwrap_or_else(|| unsafe {
-            // Safety: IRQs must be disabled at this time.
-            IDLE_TASK.current_ref_raw().get_unchecked().clone()
-        });
+        let next = self
+            .pick_next_task()
+            .or_else(|| self.steal_task())
+            .unwrap_or_else(|| unsafe {
+                // Safety: IRQs must be disabled at this time.
+                IDLE_TASK.current_ref_raw().get_unchecked().clone()
+            });
+        crate::rcu::report_qs(self.cpu_id, next.is_idle());
+        #[cfg(feature = "paging")]
+        axmm::handle_tlb_shootdown(self.cpu_id, next.is_idle());
         self.switch_to(prev, next);
     }
 
-    fn switch_to(&mut self, prev_task: CurrentTask, next_task: AxTaskRef) {
+    /// Picks the next task to run from this queue.
+    ///
+    /// Tasks that are no longer allowed to run on this CPU are moved to
+    /// other run queues.
+    fn pick_next_task(&self) -> Option<AxTaskRef> {
+        loop {
+            let task = self.scheduler.lock().pick_next_task()?;
+            self.nr_ready.fetch_sub(1, Ordering::Relaxed);
+            if task.cpu_mask().get(self.cpu_id) {
+                return Some(task);
+            }
+            select_run_queue(&task).enqueue(task);
+        }
+    }
+
+    /// Takes one ready task from the busiest run queue, used when this CPU is
+    /// about to go idle.
+    fn steal_task(&self) -> Option<AxTaskRef> {
+        let busiest = busiest_run_queue(self)?;
+        if busiest.nr_ready() == 0 {
+            return None;
+        }
+        let task = busiest.take_task_for(self)?;
+        debug!(
+            "task steal: {} from CPU {} to CPU {}",
+            task.id_name(),
+            busiest.cpu_id,
+            self.cpu_id
+        );
+        task.set_cpu_id(self.cpu_id);
+        Some(task)
+    }
+
+    /// Takes the next ready task from this queue if it's allowed to run on
+    /// the CPU of `target`, otherwise puts it back.
+    pub(crate) fn take_task_for(&self, target: &AxRunQueue) -> Option<AxTaskRef> {
+        let mut scheduler = self.scheduler.lock();
+        let task = scheduler.pick_next_task()?;
+        if task.cpu_mask().get(target.cpu_id) {
+            self.nr_ready.fetch_sub(1, Ordering::Relaxed);
+            Some(task)
+        } else {
+            // keep its position in the queue as much as possible.
+            scheduler.put_prev_task(task, true);
+            None
+        }
+    }
+
+    /// Pulls one ready task from the busiest run queue if the load is
+    /// unbalanced.
+    #[cfg(feature = "irq")]
+    fn balance(&self) {
+        let Some(busiest) = busiest_run_queue(self) else {
+            return;
+        };
+        if busiest.nr_ready() > self.nr_ready() + 1 {
+            if let Some(task) = busiest.take_task_for(self) {
+                debug!(
+                    "task migrate: {} from CPU {} to CPU {}",
+                    task.id_name(),
+                    busiest.cpu_id,
+                    self.cpu_id
+                );
+                self.enqueue(task);
+            }
+        }
+    }
+
+    fn switch_to(&self, prev_task: CurrentTask, next_task: AxTaskRef) {
         trace!(
             "context switch: {} -> {}",
             prev_task.id_name(),
@@ -176,6 +499,24 @@ impl AxRunQueue {
             return;
         }
 
+        let now = axhal::time::monotonic_time_nanos();
+        prev_task.account_switch_out(now);
+        next_task.account_switch_in(now);
+        #[cfg(feature = "sched_trace")]
+        crate::trace::record(
+            crate::trace::EventKind::Switch,
+            prev_task.id().as_u64(),
+            next_task.id().as_u64(),
+        );
+
+        // The next task may be still switching out on another CPU (e.g., it
+        // was stolen or woken up right after it was put back to the queue),
+        // wait until its context is completely saved.
+        #[cfg(feature = "smp")]
+        while next_task.on_cpu() {
+            core::hint::spin_loop();
+        }
+
         unsafe {
             let prev_ctx_ptr = prev_task.ctx_mut_ptr();
             let next_ctx_ptr = next_task.ctx_mut_ptr();
@@ -185,56 +526,118 @@ impl AxRunQueue {
             assert!(Arc::strong_count(prev_task.as_task_ref()) > 1);
             assert!(Arc::strong_count(&next_task) >= 1);
 
+            #[cfg(feature = "smp")]
+            {
+                next_task.set_on_cpu(true);
+                PREV_TASK_PTR.write_current_raw(Arc::as_ptr(prev_task.as_task_ref()) as usize);
+            }
+
             CurrentTask::set_current(prev_task, next_task);
             (*prev_ctx_ptr).switch_to(&*next_ctx_ptr);
+
+            #[cfg(feature = "smp")]
+            clear_prev_task_on_cpu();
         }
     }
 }
 
-fn gc_entry() {
+/// Clears the `on_cpu` flag of the task that was switched out by the last
+/// context switch on this CPU, so that other CPUs can run it.
+///
+/// # Safety
+///
+/// It must be called right after the context switch, with IRQs disabled.
+#[cfg(feature = "smp")]
+pub(crate) unsafe fn clear_prev_task_on_cpu() {
+    let ptr = PREV_TASK_PTR.read_current_raw() as *const crate::AxTask;
+    if !ptr.is_null() {
+        (*ptr).set_on_cpu(false);
+    }
+}
+
+/// Wakes up the GC task of the given CPU to check the exited tasks again.
+pub(crate) fn wake_gc(cpu_id: usize) {
+    RUN_QUEUES[cpu_id].wait_for_exit.notify_one(false);
+}
+
+fn gc_entry(cpu_id: usize) {
+    let rq = &RUN_QUEUES[cpu_id];
     loop {
         // Drop all exited tasks and recycle resources.
-        let n = EXITED_TASKS.lock().len();
+        let n = rq.exited_tasks.lock().len();
+        let mut switching = false;
         for _ in 0..n {
             // Do not do the slow drops in the critical section.
-            let task = EXITED_TASKS.lock().pop_front();
+            let task = rq.exited_tasks.lock().pop_front();
             if let Some(task) = task {
-                if Arc::strong_count(&task) == 1 {
-                    // If I'm the last holder of the task, drop it immediately.
+                if task.on_cpu() {
+                    // `switch_to` is not completed, try again soon.
+                    switching = true;
+                    rq.exited_tasks.lock().push_back(task);
+                } else if Arc::strong_count(&task) == 1 || task.is_detached() {
+                    // If I'm the last holder of the task, or no one is going
+                    // to join it, drop it immediately.
                     drop(task);
                 } else {
-                    // Otherwise (e.g, `switch_to` is not compeleted, held by the
-                    // joiner, etc), push it back and wait for them to drop first.
-                    EXITED_TASKS.lock().push_back(task);
+                    // Otherwise (e.g., held by the joiner), push it back and
+                    // wait for them to drop first.
+                    rq.exited_tasks.lock().push_back(task);
                 }
             }
         }
-        WAIT_FOR_EXIT.wait();
+        if switching {
+            crate::yield_now();
+        } else {
+            rq.wait_for_exit.wait();
+        }
     }
 }
 
+fn init_run_queue(cpu_id: usize) {
+    RUN_QUEUES[cpu_id].init_once(AxRunQueue::new(cpu_id));
+}
+
 pub(crate) fn init() {
+    let cpu_id = axhal::cpu::this_cpu_id();
+
     // Create the `idle` task (not current task).
     const IDLE_TASK_STACK_SIZE: usize = 4096;
-    let idle_task = TaskInner::new(|| crate::run_idle(), "idle".into(), IDLE_TASK_STACK_SIZE);
+    let idle_task =
+        TaskInner::new(|| crate::run_idle(), "idle".into(), IDLE_TASK_STACK_SIZE).into_arc();
+    idle_task.set_cpu_id(cpu_id);
+    crate::registry::register(&idle_task);
     IDLE_TASK.with_current(|i| {
-        i.init_once(idle_task.into_arc());
+        i.init_once(idle_task);
     });
 
     // Put the subsequent execution into the `main` task.
     let main_task = TaskInner::new_init("main".into()).into_arc();
+    main_task.set_cpu_id(cpu_id);
     main_task.set_state(TaskState::Running);
+    crate::registry::register(&main_task);
     unsafe { CurrentTask::init_current(main_task) };
 
-    RUN_QUEUE.init_once(AxRunQueue::new());
+    init_run_queue(cpu_id);
+    crate::rcu::report_qs(cpu_id, false);
+    #[cfg(feature = "paging")]
+    axmm::handle_tlb_shootdown(cpu_id, false);
 }
 
 pub(crate) fn init_secondary() {
+    let cpu_id = axhal::cpu::this_cpu_id();
+
     // Put the subsequent execution into the `idle` task.
     let idle_task = TaskInner::new_init("idle".into()).into_arc();
+    idle_task.set_cpu_id(cpu_id);
     idle_task.set_state(TaskState::Running);
+    crate::registry::register(&idle_task);
     IDLE_TASK.with_current(|i| {
         i.init_once(idle_task.clone());
     });
     unsafe { CurrentTask::init_current(idle_task) }
+
+    init_run_queue(cpu_id);
+    crate::rcu::report_qs(cpu_id, true);
+    #[cfg(feature = "paging")]
+    axmm::handle_tlb_shootdown(cpu_id, true);
 }
@@ -0,0 +1,384 @@
+//! Earliest-deadline-first (EDF) scheduler.
+//!
+//! Tasks with [`DeadlineParams`] are scheduled by their absolute deadlines,
+//! and each of them is limited to its `runtime` in every `period` by a
+//! constant bandwidth server (CBS), so that an overrunning task cannot break
+//! the guarantees of others. Tasks without deadline parameters run in FIFO
+//! order only when no deadline task is ready.
+//!
+//! Admission control is done per CPU: a deadline task is pinned to the CPU
+//! that reserves its bandwidth, so that the guarantees hold without relying
+//! on migration.
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use core::ops::Deref;
+
+use axhal::time::monotonic_time_nanos;
+use scheduler::BaseScheduler;
+
+use crate::spin::SpinNoIrq;
+use crate::DeadlineParams;
+
+/// Bandwidths (`runtime / period`) are fixed-point numbers with `BW_SHIFT`
+/// fractional bits.
+const BW_SHIFT: u32 = 20;
+
+/// The maximum bandwidth that deadline tasks can reserve on each CPU (95%),
+/// the rest is left for normal tasks.
+const MAX_BW_PER_CPU: u64 = (95 << BW_SHIFT) / 100;
+
+/// The bandwidth reserved by deadline tasks on each CPU.
+static CPU_BW: SpinNoIrq<[u64; axconfig::SMP]> = SpinNoIrq::new([0; axconfig::SMP]);
+
+fn bandwidth(runtime: u64, period: u64) -> u64 {
+    (((runtime as u128) << BW_SHIFT) / period as u128) as u64
+}
+
+/// Run-time state of a deadline task, all in nanoseconds.
+struct DeadlineState {
+    runtime: u64,
+    deadline: u64,
+    period: u64,
+    /// Start of the current period.
+    activation: u64,
+    /// Absolute deadline of the current job.
+    abs_deadline: u64,
+    /// The runtime left for the current job.
+    budget: u64,
+    /// When the task last started running or was last charged.
+    exec_start: u64,
+    /// The CPU whose bandwidth is reserved.
+    cpu_id: usize,
+}
+
+impl DeadlineState {
+    fn new(params: &DeadlineParams, cpu_id: usize, now: u64) -> Self {
+        let mut state = Self {
+            runtime: params.runtime.as_nanos() as u64,
+            deadline: params.deadline.as_nanos() as u64,
+            period: params.period.as_nanos() as u64,
+            activation: 0,
+            abs_deadline: 0,
+            budget: 0,
+            exec_start: now,
+            cpu_id,
+        };
+        state.start_job(now);
+        state
+    }
+
+    fn bandwidth(&self) -> u64 {
+        bandwidth(self.runtime, self.period)
+    }
+
+    fn start_job(&mut self, now: u64) {
+        self.activation = now;
+        self.abs_deadline = now + self.deadline;
+        self.budget = self.runtime;
+    }
+
+    /// The CBS wake-up rule: whether running the remaining budget before the
+    /// current deadline would exceed the reserved bandwidth.
+    fn overflows(&self, now: u64) -> bool {
+        now >= self.abs_deadline
+            || self.budget as u128 * self.period as u128
+                > (self.abs_deadline - now) as u128 * self.runtime as u128
+    }
+
+    /// Charges the time since the last charge, returns `true` if the budget
+    /// of the current job is used up.
+    fn charge(&mut self, now: u64) -> bool {
+        self.budget = self
+            .budget
+            .saturating_sub(now.saturating_sub(self.exec_start));
+        self.exec_start = now;
+        self.budget == 0
+    }
+
+    /// Moves to the next period when the current job is completed or runs
+    /// out of budget.
+    fn next_period(&mut self, now: u64) {
+        let mut activation = self.activation + self.period;
+        if activation + self.deadline <= now {
+            // far behind the schedule, restart from now.
+            activation = now;
+        }
+        self.activation = activation;
+        self.abs_deadline = activation + self.deadline;
+        self.budget = self.runtime;
+    }
+}
+
+/// A task wrapper for the [`EDFScheduler`].
+pub struct EDFTask<T> {
+    inner: T,
+    dl: SpinNoIrq<Option<DeadlineState>>,
+}
+
+impl<T> EDFTask<T> {
+    /// Creates a new [`EDFTask`] from the inner task struct, without deadline
+    /// parameters.
+    pub const fn new(inner: T) -> Self {
+        Self {
+            inner,
+            dl: SpinNoIrq::new(None),
+        }
+    }
+
+    /// Returns a reference to the inner task struct.
+    pub const fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns the deadline parameters of the task, if any.
+    pub fn deadline_params(&self) -> Option<DeadlineParams> {
+        use core::time::Duration;
+        self.dl.lock().as_ref().map(|dl| DeadlineParams {
+            runtime: Duration::from_nanos(dl.runtime),
+            deadline: Duration::from_nanos(dl.deadline),
+            period: Duration::from_nanos(dl.period),
+        })
+    }
+
+    /// Returns the CPU that reserves the bandwidth of the task, if it has
+    /// deadline parameters.
+    pub(crate) fn deadline_cpu(&self) -> Option<usize> {
+        self.dl.lock().as_ref().map(|dl| dl.cpu_id)
+    }
+
+    /// Sets or clears the deadline parameters of the task, and starts a new
+    /// job immediately. The bandwidth is reserved on CPU `cpu_id`, which the
+    /// task must be pinned to.
+    ///
+    /// Returns `false` if the parameters are invalid, or the bandwidth of the
+    /// CPU would exceed the limit (admission control).
+    ///
+    /// It must be called on the current task or a task not spawned yet, which
+    /// is not in any scheduler.
+    pub(crate) fn set_deadline_params(
+        &self,
+        params: Option<DeadlineParams>,
+        cpu_id: usize,
+    ) -> bool {
+        if params.is_some_and(|p| !p.is_valid()) {
+            return false;
+        }
+        let now = monotonic_time_nanos();
+        let new_state = params.map(|p| DeadlineState::new(&p, cpu_id, now));
+        let new_bw = new_state.as_ref().map_or(0, DeadlineState::bandwidth);
+
+        let mut dl = self.dl.lock();
+        let mut cpu_bw = CPU_BW.lock();
+        let old_bw = match dl.as_ref() {
+            Some(old) if old.cpu_id == cpu_id => old.bandwidth(),
+            _ => 0,
+        };
+        if new_bw > old_bw && cpu_bw[cpu_id] - old_bw + new_bw > MAX_BW_PER_CPU {
+            return false;
+        }
+        if let Some(old) = dl.as_ref() {
+            cpu_bw[old.cpu_id] -= old.bandwidth();
+        }
+        cpu_bw[cpu_id] += new_bw;
+        *dl = new_state;
+        true
+    }
+}
+
+impl<T> Deref for EDFTask<T> {
+    type Target = T;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+/// Where a task should be put into the [`EDFScheduler`].
+enum Placement {
+    /// Ready to run, with the absolute deadline.
+    Ready(u64),
+    /// Waiting for the replenishment at the given time.
+    Throttled(u64),
+    /// A task without deadline parameters.
+    Normal,
+}
+
+/// An earliest-deadline-first scheduler with bandwidth reservation.
+///
+/// Deadline tasks that yield the CPU are considered to have completed their
+/// current job, and sleep until the next period. So do the tasks that run
+/// out of their budget.
+pub struct EDFScheduler<T> {
+    /// Ready deadline tasks, ordered by absolute deadlines.
+    ready: BTreeMap<(u64, usize), Arc<EDFTask<T>>>,
+    /// Deadline tasks waiting for the next period, ordered by the start of
+    /// the period.
+    throttled: BTreeMap<(u64, usize), Arc<EDFTask<T>>>,
+    /// Tasks without deadline parameters.
+    normal: VecDeque<Arc<EDFTask<T>>>,
+}
+
+impl<T> EDFScheduler<T> {
+    /// Creates a new empty [`EDFScheduler`].
+    pub const fn new() -> Self {
+        Self {
+            ready: BTreeMap::new(),
+            throttled: BTreeMap::new(),
+            normal: VecDeque::new(),
+        }
+    }
+
+    /// get the name of scheduler
+    pub fn scheduler_name() -> &'static str {
+        "EDF"
+    }
+
+    fn key(task: &Arc<EDFTask<T>>, time: u64) -> (u64, usize) {
+        // tasks with the same time are ordered by their addresses.
+        (time, Arc::as_ptr(task) as usize)
+    }
+
+    fn place(&mut self, task: Arc<EDFTask<T>>, placement: Placement, preempt: bool) {
+        match placement {
+            Placement::Ready(deadline) => {
+                self.ready.insert(Self::key(&task, deadline), task);
+            }
+            Placement::Throttled(activation) => {
+                self.throttled.insert(Self::key(&task, activation), task);
+            }
+            Placement::Normal if preempt => self.normal.push_front(task),
+            Placement::Normal => self.normal.push_back(task),
+        }
+    }
+
+    /// Moves throttled tasks whose next period has started to the ready set.
+    fn replenish(&mut self, now: u64) {
+        while let Some(entry) = self.throttled.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+            let task = entry.remove();
+            let placement = match task.dl.lock().as_ref() {
+                Some(dl) => Placement::Ready(dl.abs_deadline),
+                None => Placement::Normal,
+            };
+            self.place(task, placement, false);
+        }
+    }
+
+    /// Returns when the earliest throttled task gets its budget back, in
+    /// monotonic nanoseconds.
+    ///
+    /// An idle CPU must wake up by then if the periodic tick is stopped.
+    pub fn next_replenish_time(&self) -> Option<u64> {
+        self.throttled
+            .first_key_value()
+            .map(|(&(activation, _), _)| activation)
+    }
+
+    /// Returns the number of throttled tasks, which are not ready to run until
+    /// their next periods.
+    pub fn nr_throttled(&self) -> usize {
+        self.throttled.len()
+    }
+
+    fn earliest_deadline(&self) -> Option<u64> {
+        self.ready
+            .first_key_value()
+            .map(|(&(deadline, _), _)| deadline)
+    }
+}
+
+impl<T> BaseScheduler for EDFScheduler<T> {
+    type SchedItem = Arc<EDFTask<T>>;
+
+    fn init(&mut self) {}
+
+    fn add_task(&mut self, task: Self::SchedItem) {
+        let now = monotonic_time_nanos();
+        let placement = match task.dl.lock().as_mut() {
+            Some(dl) => {
+                if dl.overflows(now) {
+                    dl.start_job(now);
+                }
+                Placement::Ready(dl.abs_deadline)
+            }
+            None => Placement::Normal,
+        };
+        self.place(task, placement, false);
+    }
+
+    fn remove_task(&mut self, task: &Self::SchedItem) -> Option<Self::SchedItem> {
+        if let Some(pos) = self.normal.iter().position(|t| Arc::ptr_eq(t, task)) {
+            return self.normal.remove(pos);
+        }
+        let key = self
+            .ready
+            .iter()
+            .chain(self.throttled.iter())
+            .find(|(_, t)| Arc::ptr_eq(t, task))
+            .map(|(&key, _)| key)?;
+        self.ready
+            .remove(&key)
+            .or_else(|| self.throttled.remove(&key))
+    }
+
+    fn pick_next_task(&mut self) -> Option<Self::SchedItem> {
+        let now = monotonic_time_nanos();
+        self.replenish(now);
+        let task = match self.ready.pop_first() {
+            Some((_, task)) => task,
+            None => self.normal.pop_front()?,
+        };
+        if let Some(dl) = task.dl.lock().as_mut() {
+            dl.exec_start = now;
+        }
+        Some(task)
+    }
+
+    fn put_prev_task(&mut self, prev: Self::SchedItem, preempt: bool) {
+        let now = monotonic_time_nanos();
+        let placement = match prev.dl.lock().as_mut() {
+            Some(dl) => {
+                if dl.charge(now) || !preempt {
+                    dl.next_period(now);
+                }
+                if dl.activation > now {
+                    Placement::Throttled(dl.activation)
+                } else {
+                    Placement::Ready(dl.abs_deadline)
+                }
+            }
+            None => Placement::Normal,
+        };
+        self.place(prev, placement, preempt);
+    }
+
+    fn task_tick(&mut self, current: &Self::SchedItem) -> bool {
+        let now = monotonic_time_nanos();
+        self.replenish(now);
+        match current.dl.lock().as_mut() {
+            // preempt it if its budget is used up, or a task with an earlier
+            // deadline is ready.
+            Some(dl) => {
+                dl.charge(now)
+                    || self
+                        .earliest_deadline()
+                        .is_some_and(|deadline| deadline < dl.abs_deadline)
+            }
+            // normal tasks are always preempted by deadline tasks.
+            None => !self.ready.is_empty(),
+        }
+    }
+
+    fn set_priority(&mut self, _task: &Self::SchedItem, _prio: isize) -> bool {
+        false
+    }
+}
+
+impl<T> Default for EDFScheduler<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,10 +1,9 @@
-use alloc::{boxed::Box, string::String, sync::Arc};
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
 use core::ops::Deref;
-use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, AtomicU8, Ordering};
-use core::{alloc::Layout, cell::UnsafeCell, fmt, ptr::NonNull};
-
-#[cfg(feature = "preempt")]
-use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::{
+    fence, AtomicBool, AtomicI32, AtomicIsize, AtomicU64, AtomicU8, AtomicUsize, Ordering,
+};
+use core::{alloc::Layout, cell::UnsafeCell, fmt, ptr::NonNull, time::Duration};
 
 #[cfg(feature = "tls")]
 use axhal::tls::TlsArea;
@@ -12,8 +11,9 @@ use axhal::tls::TlsArea;
 use axhal::arch::TaskContext;
 use memory_addr::{align_up_4k, VirtAddr};
 
-use crate::task_ext::AxTaskExt;
-use crate::{AxRunQueue, AxTask, AxTaskRef, WaitQueue};
+use crate::spin::SpinNoIrq;
+use crate::task_ext::{AxTaskExt, TaskExtSlots};
+use crate::{AxCpuMask, AxTask, AxTaskRef, WaitQueue};
 
 /// A unique identifier for a thread.
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -22,13 +22,74 @@ pub struct TaskId(u64);
 /// The possible states of a task.
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
-pub(crate) enum TaskState {
+pub enum TaskState {
+    /// The task is running on a CPU.
     Running = 1,
+    /// The task is in a run queue, waiting to be scheduled.
     Ready = 2,
+    /// The task is waiting for an event, e.g., in a wait queue or sleeping.
     Blocked = 3,
+    /// The task has exited, but not yet been dropped.
     Exited = 4,
 }
 
+impl TaskState {
+    /// Returns the one-letter state code used by `ps` and `/proc/<pid>/stat`.
+    pub const fn as_char(&self) -> char {
+        match self {
+            Self::Running | Self::Ready => 'R',
+            Self::Blocked => 'S',
+            Self::Exited => 'Z',
+        }
+    }
+}
+
+/// CPU time and context switches consumed by a task (or a set of tasks).
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct CpuUsage {
+    /// Time spent on running the task's own code.
+    pub utime: Duration,
+    /// Time spent on handling timer interrupts while the task is running.
+    pub stime: Duration,
+    /// Number of times that the task gave up the CPU because it was blocked
+    /// or exited.
+    pub voluntary_switches: u64,
+    /// Number of times that the task was switched out while still runnable,
+    /// e.g., preempted or yielding.
+    pub involuntary_switches: u64,
+}
+
+impl core::ops::AddAssign for CpuUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.utime += other.utime;
+        self.stime += other.stime;
+        self.voluntary_switches += other.voluntary_switches;
+        self.involuntary_switches += other.involuntary_switches;
+    }
+}
+
+/// Parameters of a real-time task under the EDF scheduler, similar to
+/// `SCHED_DEADLINE` in Linux.
+///
+/// The task is guaranteed to run for `runtime` within `deadline` from the
+/// start of every `period`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct DeadlineParams {
+    /// The execution time reserved in each period.
+    pub runtime: Duration,
+    /// The relative deadline of each job, from the start of its period.
+    pub deadline: Duration,
+    /// The length of a period.
+    pub period: Duration,
+}
+
+impl DeadlineParams {
+    /// Returns `true` if `0 < runtime <= deadline <= period`.
+    pub fn is_valid(&self) -> bool {
+        !self.runtime.is_zero() && self.runtime <= self.deadline && self.deadline <= self.period
+    }
+}
+
 /// The inner task structure.
 pub struct TaskInner {
     id: TaskId,
@@ -39,9 +100,64 @@ pub struct TaskInner {
     entry: Option<*mut dyn FnOnce()>,
     state: AtomicU8,
 
+    /// The CPU whose run queue the task belongs to.
+    cpu_id: AtomicUsize,
+    /// CPUs that the task is allowed to run on.
+    cpumask: SpinNoIrq<AxCpuMask>,
+    /// The CPU mask before the task was pinned to the CPU reserving its
+    /// deadline bandwidth, restored when it's no longer a deadline task.
+    #[cfg(feature = "sched_edf")]
+    unpinned_cpumask: SpinNoIrq<Option<AxCpuMask>>,
+    /// The priority that the task is scheduled with, may be raised above
+    /// `base_priority` by priority inheritance.
+    priority: AtomicIsize,
+    /// The priority set by [`crate::set_priority`].
+    base_priority: AtomicIsize,
+    /// The priorities inherited through each lock the task holds, keyed by
+    /// the lock address, see [`crate::inherit_priority`].
+    pi_boosts: SpinNoIrq<Vec<(usize, isize)>>,
+    /// Whether the task is running on a CPU, including the period of being
+    /// switched in or out.
+    #[cfg(feature = "smp")]
+    on_cpu: AtomicBool,
+
+    /// Total time on CPU, in nanoseconds, excluding the current run.
+    run_time_ns: AtomicU64,
+    /// Part of `run_time_ns` spent on timer interrupts.
+    stime_ns: AtomicU64,
+    /// When the task was last switched in, in nanoseconds.
+    switch_in_ns: AtomicU64,
+    nvcsw: AtomicU64,
+    nivcsw: AtomicU64,
+
     in_wait_queue: AtomicBool,
     #[cfg(feature = "irq")]
     in_timer_list: AtomicBool,
+    /// Whether the task has been cancelled by [`TaskInner::cancel`].
+    interrupted: AtomicBool,
+    /// Whether no one is going to join the task, see [`TaskInner::detach`].
+    detached: AtomicBool,
+    /// When the task was last blocked, in nanoseconds.
+    #[cfg(feature = "watchdog")]
+    blocked_since_ns: AtomicU64,
+    /// Whether the task is checked by the hung task watchdog.
+    #[cfg(feature = "watchdog")]
+    hung_check: AtomicBool,
+    /// Whether the task has been reported as hung since it was last blocked.
+    #[cfg(feature = "watchdog")]
+    hung_reported: AtomicBool,
+    /// Whether the task is in an idle wait, see [`WaitQueue::wait_idle_until`].
+    #[cfg(feature = "watchdog")]
+    idle_wait: AtomicBool,
+    /// Whether the task is in an interruptible wait, which is ended early
+    /// when the task is cancelled.
+    interruptible: AtomicBool,
+    /// Identifies the latest alarm of the task, bumped on every set or cancel.
+    #[cfg(feature = "irq")]
+    timer_ticket_id: AtomicU64,
+    /// The CPU whose timer list holds the latest alarm of the task.
+    #[cfg(feature = "irq")]
+    timer_cpu_id: AtomicUsize,
 
     #[cfg(feature = "preempt")]
     need_resched: AtomicBool,
@@ -51,9 +167,14 @@ pub struct TaskInner {
     exit_code: AtomicI32,
     wait_for_exit: WaitQueue,
 
+    /// Locks held by the task, only accessed by itself with IRQs disabled.
+    #[cfg(feature = "lockdep")]
+    held_locks: UnsafeCell<alloc::vec::Vec<crate::lockdep::HeldLock>>,
+
     kstack: Option<TaskStack>,
     ctx: UnsafeCell<TaskContext>,
     task_ext: AxTaskExt,
+    ext_slots: TaskExtSlots,
 
     #[cfg(feature = "tls")]
     tls: TlsArea,
@@ -121,20 +242,136 @@ impl TaskInner {
         self.name.as_str()
     }
 
+    /// Gets the current state of the task.
+    #[inline]
+    pub fn state(&self) -> TaskState {
+        self.state.load(Ordering::Acquire).into()
+    }
+
+    /// Gets the ID of the CPU that the task is running on, or last ran on.
+    #[inline]
+    pub fn cpu_id(&self) -> usize {
+        self.cpu_id.load(Ordering::Acquire)
+    }
+
+    /// Gets the CPU time and context switches consumed by the task so far.
+    pub fn cpu_usage(&self) -> CpuUsage {
+        let mut run_time = self.run_time_ns.load(Ordering::Acquire);
+        if self.is_running() {
+            let now = axhal::time::monotonic_time_nanos();
+            run_time += now.saturating_sub(self.switch_in_ns.load(Ordering::Acquire));
+        }
+        let stime = self.stime_ns.load(Ordering::Acquire);
+        CpuUsage {
+            utime: Duration::from_nanos(run_time.saturating_sub(stime)),
+            stime: Duration::from_nanos(stime),
+            voluntary_switches: self.nvcsw.load(Ordering::Relaxed),
+            involuntary_switches: self.nivcsw.load(Ordering::Relaxed),
+        }
+    }
+
     /// Get a combined string of the task ID and name.
     pub fn id_name(&self) -> alloc::string::String {
         alloc::format!("Task({}, {:?})", self.id.as_u64(), self.name)
     }
 
+    /// Gets the set of CPUs that the task is allowed to run on.
+    pub fn cpu_mask(&self) -> AxCpuMask {
+        *self.cpumask.lock()
+    }
+
+    /// Gets the priority that the task is currently scheduled with, including
+    /// the one it inherits from tasks blocked on it.
+    ///
+    /// A smaller value means a higher priority, see [`crate::set_priority`].
+    pub fn priority(&self) -> isize {
+        self.priority.load(Ordering::Acquire)
+    }
+
     /// Wait for the task to exit, and return the exit code.
     ///
     /// It will return immediately if the task has already exited (but not dropped).
     pub fn join(&self) -> Option<i32> {
         self.wait_for_exit
-            .wait_until(|| self.state() == TaskState::Exited);
+            .wait_idle_until(|| self.state() == TaskState::Exited);
         Some(self.exit_code.load(Ordering::Acquire))
     }
 
+    /// Wait for the task to exit for at most `dur`, and return the exit code.
+    ///
+    /// Returns [`None`] if the task is still running after `dur` has elapsed.
+    /// If the feature `irq` is not enabled, it polls the task state with
+    /// [`crate::yield_now`] instead.
+    pub fn join_timeout(&self, dur: Duration) -> Option<i32> {
+        #[cfg(feature = "irq")]
+        self.wait_for_exit
+            .wait_timeout_until(dur, || self.state() == TaskState::Exited);
+        #[cfg(not(feature = "irq"))]
+        {
+            let deadline = axhal::time::wall_time() + dur;
+            while self.state() != TaskState::Exited && axhal::time::wall_time() < deadline {
+                crate::yield_now();
+            }
+        }
+        self.try_join()
+    }
+
+    /// Returns the exit code if the task has exited, or [`None`] if it's
+    /// still running, without blocking.
+    pub fn try_join(&self) -> Option<i32> {
+        if self.state() == TaskState::Exited {
+            Some(self.exit_code.load(Ordering::Acquire))
+        } else {
+            None
+        }
+    }
+
+    /// Marks the task as detached, i.e., no one is going to join it.
+    ///
+    /// The GC task releases its reference to a detached task as soon as it
+    /// exits, rather than waiting for the joiner to drop the other references
+    /// first. The task is freed when the last reference (usually the one
+    /// returned by [`crate::spawn`]) is dropped, or right away if there is
+    /// none.
+    pub fn detach(&self) {
+        if !self.detached.swap(true, Ordering::SeqCst) && self.state() == TaskState::Exited {
+            // the GC task may have skipped it, as it was not detached.
+            crate::run_queue::wake_gc(self.cpu_id());
+        }
+    }
+
+    /// Whether the task has been detached by [`TaskInner::detach`].
+    pub fn is_detached(&self) -> bool {
+        self.detached.load(Ordering::Acquire)
+    }
+
+    /// Cancels the task.
+    ///
+    /// The task is marked as interrupted, and woken up if it's in an
+    /// interruptible wait (e.g., [`WaitQueue::wait_interruptible`] or
+    /// [`crate::sleep_interruptible`]), which then returns
+    /// [`AxError::Interrupted`](axerrno::AxError::Interrupted). All later
+    /// interruptible waits of the task also return immediately. Other waits
+    /// are not affected, the task can check [`TaskInner::is_cancelled`] to
+    /// stop itself.
+    pub fn cancel(&self) {
+        self.interrupted.store(true, Ordering::SeqCst);
+        // pairs with the fence in `interrupt_pending()`, so that either we
+        // see the task blocked, or it sees the flag.
+        fence(Ordering::SeqCst);
+        if self.interruptible.load(Ordering::SeqCst) && self.state() == TaskState::Blocked {
+            if let Some(task) = crate::find_task(self.id.as_u64()) {
+                let _guard = kernel_guard::NoPreemptIrqSave::new();
+                crate::select_run_queue(&task).unblock_task(task, true);
+            }
+        }
+    }
+
+    /// Whether the task has been cancelled by [`TaskInner::cancel`].
+    pub fn is_cancelled(&self) -> bool {
+        self.interrupted.load(Ordering::Acquire)
+    }
+
     /// Returns the pointer to the user-defined task extended data.
     ///
     /// # Safety
@@ -148,6 +385,12 @@ impl TaskInner {
         self.task_ext.as_ptr()
     }
 
+    /// Returns the values of the task extension slots, see
+    /// [`TaskExtKey`](crate::TaskExtKey).
+    pub(crate) fn task_ext_slots(&self) -> &TaskExtSlots {
+        &self.ext_slots
+    }
+
     /// Initialize the user-defined task extended data.
     ///
     /// Returns a reference to the task extended data if it has not been
@@ -159,6 +402,18 @@ impl TaskInner {
             None
         }
     }
+
+    /// Sets the CPUs that the task is allowed to run on, before it's spawned.
+    ///
+    /// Returns `false` if `cpumask` contains no CPU. Use
+    /// [`set_affinity`](crate::set_affinity) for spawned tasks.
+    pub fn init_cpu_mask(&mut self, cpumask: AxCpuMask) -> bool {
+        if cpumask.is_empty() {
+            return false;
+        }
+        *self.cpumask.get_mut() = cpumask;
+        true
+    }
 }
 
 // private methods
@@ -171,18 +426,50 @@ impl TaskInner {
             is_init: false,
             entry: None,
             state: AtomicU8::new(TaskState::Ready as u8),
+            cpu_id: AtomicUsize::new(0),
+            cpumask: SpinNoIrq::new(AxCpuMask::full()),
+            #[cfg(feature = "sched_edf")]
+            unpinned_cpumask: SpinNoIrq::new(None),
+            priority: AtomicIsize::new(0),
+            base_priority: AtomicIsize::new(0),
+            pi_boosts: SpinNoIrq::new(Vec::new()),
+            #[cfg(feature = "smp")]
+            on_cpu: AtomicBool::new(false),
+            run_time_ns: AtomicU64::new(0),
+            stime_ns: AtomicU64::new(0),
+            switch_in_ns: AtomicU64::new(axhal::time::monotonic_time_nanos()),
+            nvcsw: AtomicU64::new(0),
+            nivcsw: AtomicU64::new(0),
             in_wait_queue: AtomicBool::new(false),
             #[cfg(feature = "irq")]
             in_timer_list: AtomicBool::new(false),
+            interrupted: AtomicBool::new(false),
+            detached: AtomicBool::new(false),
+            #[cfg(feature = "watchdog")]
+            blocked_since_ns: AtomicU64::new(0),
+            #[cfg(feature = "watchdog")]
+            hung_check: AtomicBool::new(true),
+            #[cfg(feature = "watchdog")]
+            hung_reported: AtomicBool::new(false),
+            #[cfg(feature = "watchdog")]
+            idle_wait: AtomicBool::new(false),
+            interruptible: AtomicBool::new(false),
+            #[cfg(feature = "irq")]
+            timer_ticket_id: AtomicU64::new(0),
+            #[cfg(feature = "irq")]
+            timer_cpu_id: AtomicUsize::new(0),
             #[cfg(feature = "preempt")]
             need_resched: AtomicBool::new(false),
             #[cfg(feature = "preempt")]
             preempt_disable_count: AtomicUsize::new(0),
             exit_code: AtomicI32::new(0),
             wait_for_exit: WaitQueue::new(),
+            #[cfg(feature = "lockdep")]
+            held_locks: UnsafeCell::new(alloc::vec::Vec::new()),
             kstack: None,
             ctx: UnsafeCell::new(TaskContext::new()),
             task_ext: AxTaskExt::empty(),
+            ext_slots: TaskExtSlots::new(),
             #[cfg(feature = "tls")]
             tls: TlsArea::alloc(),
         }
@@ -199,6 +486,8 @@ impl TaskInner {
     pub(crate) fn new_init(name: String) -> Self {
         let mut t = Self::new_common(TaskId::new(), name);
         t.is_init = true;
+        #[cfg(feature = "smp")]
+        t.set_on_cpu(true);
         if t.name == "idle" {
             t.is_idle = true;
         }
@@ -210,13 +499,22 @@ impl TaskInner {
     }
 
     #[inline]
-    pub(crate) fn state(&self) -> TaskState {
-        self.state.load(Ordering::Acquire).into()
+    pub(crate) fn set_state(&self, state: TaskState) {
+        self.state.store(state as u8, Ordering::Release)
     }
 
+    /// Transitions the task state from `current_state` to `new_state`,
+    /// returns `false` if the current state is not `current_state`.
     #[inline]
-    pub(crate) fn set_state(&self, state: TaskState) {
-        self.state.store(state as u8, Ordering::Release)
+    pub(crate) fn transition_state(&self, current_state: TaskState, new_state: TaskState) -> bool {
+        self.state
+            .compare_exchange(
+                current_state as u8,
+                new_state as u8,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_ok()
     }
 
     #[inline]
@@ -229,11 +527,6 @@ impl TaskInner {
         matches!(self.state(), TaskState::Ready)
     }
 
-    #[inline]
-    pub(crate) fn is_blocked(&self) -> bool {
-        matches!(self.state(), TaskState::Blocked)
-    }
-
     #[inline]
     pub(crate) const fn is_init(&self) -> bool {
         self.is_init
@@ -244,6 +537,74 @@ impl TaskInner {
         self.is_idle
     }
 
+    #[inline]
+    pub(crate) fn set_cpu_id(&self, cpu_id: usize) {
+        self.cpu_id.store(cpu_id, Ordering::Release);
+    }
+
+    /// Updates the accounting when the task is switched in at `now`.
+    #[inline]
+    pub(crate) fn account_switch_in(&self, now: u64) {
+        self.switch_in_ns.store(now, Ordering::Release);
+    }
+
+    /// Updates the accounting when the task is switched out at `now`.
+    pub(crate) fn account_switch_out(&self, now: u64) {
+        let delta = now.saturating_sub(self.switch_in_ns.load(Ordering::Acquire));
+        self.run_time_ns.fetch_add(delta, Ordering::AcqRel);
+        if self.is_ready() {
+            self.nivcsw.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.nvcsw.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn account_system_time(&self, ns: u64) {
+        self.stime_ns.fetch_add(ns, Ordering::AcqRel);
+    }
+
+    #[inline]
+    pub(crate) fn set_cpu_mask(&self, cpumask: AxCpuMask) {
+        *self.cpumask.lock() = cpumask;
+    }
+
+    /// Pins the task to CPU `cpu_id`, saving the CPU mask to be restored by
+    /// [`TaskInner::unpin_cpu`] if it's not pinned yet.
+    #[cfg(feature = "sched_edf")]
+    pub(crate) fn pin_cpu(&self, cpu_id: usize) {
+        let mut cpumask = self.cpumask.lock();
+        self.unpinned_cpumask.lock().get_or_insert(*cpumask);
+        *cpumask = AxCpuMask::one_shot(cpu_id);
+    }
+
+    /// Restores the CPU mask saved by [`TaskInner::pin_cpu`], if any.
+    #[cfg(feature = "sched_edf")]
+    pub(crate) fn unpin_cpu(&self) {
+        let mut cpumask = self.cpumask.lock();
+        if let Some(unpinned) = self.unpinned_cpumask.lock().take() {
+            *cpumask = unpinned;
+        }
+    }
+
+    #[inline]
+    #[cfg(feature = "smp")]
+    pub(crate) fn on_cpu(&self) -> bool {
+        self.on_cpu.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    #[cfg(not(feature = "smp"))]
+    pub(crate) const fn on_cpu(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    #[cfg(feature = "smp")]
+    pub(crate) fn set_on_cpu(&self, on_cpu: bool) {
+        self.on_cpu.store(on_cpu, Ordering::Release);
+    }
+
     #[inline]
     pub(crate) fn in_wait_queue(&self) -> bool {
         self.in_wait_queue.load(Ordering::Acquire)
@@ -254,6 +615,72 @@ impl TaskInner {
         self.in_wait_queue.store(in_wait_queue, Ordering::Release);
     }
 
+    #[inline]
+    pub(crate) fn set_interruptible(&self, interruptible: bool) {
+        self.interruptible.store(interruptible, Ordering::SeqCst);
+    }
+
+    /// Whether the task is in an interruptible wait and has been cancelled,
+    /// so that it should not block.
+    #[inline]
+    pub(crate) fn interrupt_pending(&self) -> bool {
+        // pairs with the fence in `cancel()`, it must be called after the
+        // task state is set to blocked.
+        fence(Ordering::SeqCst);
+        self.interruptible.load(Ordering::SeqCst) && self.interrupted.load(Ordering::SeqCst)
+    }
+
+    #[inline]
+    pub(crate) fn is_interruptible(&self) -> bool {
+        self.interruptible.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    #[cfg(feature = "watchdog")]
+    pub(crate) fn blocked_since(&self) -> u64 {
+        self.blocked_since_ns.load(Ordering::Acquire)
+    }
+
+    /// Records that the task is blocked at `now`.
+    #[inline]
+    #[cfg(feature = "watchdog")]
+    pub(crate) fn set_blocked_since(&self, now: u64) {
+        self.blocked_since_ns.store(now, Ordering::Release);
+        self.hung_reported.store(false, Ordering::Release);
+    }
+
+    /// Marks the task as reported as hung, returns whether it was reported
+    /// before.
+    #[inline]
+    #[cfg(feature = "watchdog")]
+    pub(crate) fn set_hung_reported(&self) -> bool {
+        self.hung_reported.swap(true, Ordering::AcqRel)
+    }
+
+    #[inline]
+    #[cfg(feature = "watchdog")]
+    pub(crate) fn hung_check_enabled(&self) -> bool {
+        self.hung_check.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    #[cfg(feature = "watchdog")]
+    pub(crate) fn set_hung_check(&self, enabled: bool) {
+        self.hung_check.store(enabled, Ordering::Release);
+    }
+
+    #[inline]
+    #[cfg(feature = "watchdog")]
+    pub(crate) fn in_idle_wait(&self) -> bool {
+        self.idle_wait.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    #[cfg(feature = "watchdog")]
+    pub(crate) fn set_idle_wait(&self, idle: bool) {
+        self.idle_wait.store(idle, Ordering::Release);
+    }
+
     #[inline]
     #[cfg(feature = "irq")]
     pub(crate) fn in_timer_list(&self) -> bool {
@@ -266,6 +693,71 @@ impl TaskInner {
         self.in_timer_list.store(in_timer_list, Ordering::Release);
     }
 
+    #[inline]
+    #[cfg(feature = "irq")]
+    pub(crate) fn timer_ticket(&self) -> u64 {
+        self.timer_ticket_id.load(Ordering::Acquire)
+    }
+
+    /// Invalidates the previous alarm and returns the ticket of the new one.
+    #[inline]
+    #[cfg(feature = "irq")]
+    pub(crate) fn next_timer_ticket(&self) -> u64 {
+        self.timer_ticket_id.fetch_add(1, Ordering::AcqRel) + 1
+    }
+
+    #[inline]
+    #[cfg(feature = "irq")]
+    pub(crate) fn timer_cpu_id(&self) -> usize {
+        self.timer_cpu_id.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    #[cfg(feature = "irq")]
+    pub(crate) fn set_timer_cpu_id(&self, cpu_id: usize) {
+        self.timer_cpu_id.store(cpu_id, Ordering::Release);
+    }
+
+    #[inline]
+    pub(crate) fn set_effective_priority(&self, prio: isize) {
+        self.priority.store(prio, Ordering::Release);
+    }
+
+    #[inline]
+    pub(crate) fn base_priority(&self) -> isize {
+        self.base_priority.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub(crate) fn set_base_priority(&self, prio: isize) {
+        self.base_priority.store(prio, Ordering::Release);
+    }
+
+    /// Records that the task inherits `prio` through the lock `lock`.
+    pub(crate) fn add_pi_boost(&self, lock: usize, prio: isize) {
+        let mut boosts = self.pi_boosts.lock();
+        match boosts.iter_mut().find(|(l, _)| *l == lock) {
+            Some((_, p)) => *p = (*p).min(prio),
+            None => boosts.push((lock, prio)),
+        }
+    }
+
+    /// Forgets the priority inherited through the lock `lock`.
+    pub(crate) fn remove_pi_boost(&self, lock: usize) {
+        self.pi_boosts.lock().retain(|(l, _)| *l != lock);
+    }
+
+    /// Returns the priority the task should be scheduled with, i.e., the
+    /// highest of the base priority and those inherited through the locks.
+    pub(crate) fn inherited_priority(&self) -> isize {
+        let base = self.base_priority();
+        self.pi_boosts
+            .lock()
+            .iter()
+            .map(|(_, p)| *p)
+            .fold(base, isize::min)
+    }
+
     #[inline]
     #[cfg(feature = "preempt")]
     pub(crate) fn set_preempt_pending(&self, pending: bool) {
@@ -297,16 +789,33 @@ impl TaskInner {
     fn current_check_preempt_pending() {
         let curr = crate::current();
         if curr.need_resched.load(Ordering::Acquire) && curr.can_preempt(0) {
-            let mut rq = crate::RUN_QUEUE.lock();
+            let _guard = kernel_guard::NoPreemptIrqSave::new();
             if curr.need_resched.load(Ordering::Acquire) {
-                rq.preempt_resched();
+                crate::current_run_queue().preempt_resched();
             }
         }
     }
 
-    pub(crate) fn notify_exit(&self, exit_code: i32, rq: &mut AxRunQueue) {
+    /// Sets the exit code, it must be called before the state is set to
+    /// [`TaskState::Exited`], so that joiners see the code once they see the
+    /// state.
+    pub(crate) fn set_exit_code(&self, exit_code: i32) {
         self.exit_code.store(exit_code, Ordering::Release);
-        self.wait_for_exit.notify_all_locked(false, rq);
+    }
+
+    pub(crate) fn notify_exit(&self) {
+        self.wait_for_exit.notify_all(false);
+    }
+
+    /// Returns the locks held by the task.
+    ///
+    /// # Safety
+    ///
+    /// It must be called by the task itself with IRQs disabled.
+    #[cfg(feature = "lockdep")]
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) unsafe fn held_locks(&self) -> &mut alloc::vec::Vec<crate::lockdep::HeldLock> {
+        &mut *self.held_locks.get()
     }
 
     #[inline]
@@ -352,6 +861,7 @@ struct TaskStack {
 }
 
 impl TaskStack {
+    #[cfg(not(feature = "stack_guard"))]
     pub fn alloc(size: usize) -> Self {
         let layout = Layout::from_size_align(size, 16).unwrap();
         Self {
@@ -360,15 +870,66 @@ impl TaskStack {
         }
     }
 
+    /// Allocates the stack in the kernel address space, with a guard page
+    /// below it to catch stack overflows.
+    #[cfg(feature = "stack_guard")]
+    pub fn alloc(size: usize) -> Self {
+        let layout = Layout::from_size_align(size, memory_addr::PAGE_SIZE_4K).unwrap();
+        let bottom = axmm::alloc_kernel_stack(size).expect("failed to allocate kernel stack");
+        Self {
+            ptr: NonNull::new(bottom.as_mut_ptr()).unwrap(),
+            layout,
+        }
+    }
+
     pub const fn top(&self) -> VirtAddr {
         unsafe { core::mem::transmute(self.ptr.as_ptr().add(self.layout.size())) }
     }
+
+    /// Whether the address is in the guard page below the stack.
+    #[cfg(feature = "stack_guard")]
+    pub fn guard_contains(&self, vaddr: VirtAddr) -> bool {
+        let bottom = VirtAddr::from_mut_ptr_of(self.ptr.as_ptr());
+        (bottom - axmm::KERNEL_STACK_GUARD_SIZE..bottom).contains(&vaddr)
+    }
 }
 
 impl Drop for TaskStack {
+    #[cfg(not(feature = "stack_guard"))]
     fn drop(&mut self) {
         unsafe { alloc::alloc::dealloc(self.ptr.as_ptr(), self.layout) }
     }
+
+    #[cfg(feature = "stack_guard")]
+    fn drop(&mut self) {
+        let bottom = VirtAddr::from_mut_ptr_of(self.ptr.as_ptr());
+        if let Err(e) = axmm::dealloc_kernel_stack(bottom, self.layout.size()) {
+            warn!(
+                "failed to deallocate kernel stack at {:#x}: {:?}",
+                bottom, e
+            );
+        }
+    }
+}
+
+/// Returns the ID and name of the current task if the address is in the guard
+/// page of its stack, which is used to report kernel stack overflows on double
+/// faults.
+#[cfg(feature = "stack_guard")]
+#[axhal::trap::register_trap_handler(axhal::trap::STACK_GUARD)]
+fn stack_guard_owner(vaddr: VirtAddr) -> Option<(u64, &'static str)> {
+    let curr = CurrentTask::try_get()?;
+    if !curr
+        .kstack
+        .as_ref()
+        .is_some_and(|s| s.guard_contains(vaddr))
+    {
+        return None;
+    }
+    // SAFETY: the current task is not dropped while it's running, and the CPU
+    // panics right after the overflow is reported.
+    let name = unsafe { &*(curr.name() as *const str) };
+    Some((curr.id().as_u64(), name))
 }
 
 use core::mem::ManuallyDrop;
@@ -429,8 +990,12 @@ impl Deref for CurrentTask {
 }
 
 extern "C" fn task_entry() -> ! {
-    // release the lock that was implicitly held across the reschedule
-    unsafe { crate::RUN_QUEUE.force_unlock() };
+    // the previous task has been completely switched out, let other CPUs run it
+    #[cfg(feature = "smp")]
+    unsafe {
+        crate::run_queue::clear_prev_task_on_cpu()
+    };
+    // IRQs were disabled by the previous task before the reschedule
     #[cfg(feature = "irq")]
     axhal::arch::enable_irqs();
     let task = crate::current();
@@ -1,7 +1,25 @@
 //! User-defined task extended data.
+//!
+//! There are two ways to attach data to tasks:
+//!
+//! - [`def_task_ext!`](crate::def_task_ext) defines a single extension type
+//!   for the whole image, which must be initialized by
+//!   [`TaskInner::init_task_ext`](crate::TaskInner::init_task_ext) before the
+//!   task is spawned.
+//! - [`def_task_ext_slot!`](crate::def_task_ext_slot) defines a keyed slot.
+//!   Any number of crates can define their own slots, and each slot of a task
+//!   is initialized lazily on first access, and dropped with the task.
 
+use alloc::boxed::Box;
 use core::alloc::Layout;
+use core::marker::PhantomData;
 use core::mem::{align_of, size_of};
+use core::ptr::null_mut;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+use linkme::distributed_slice;
+
+use crate::TaskInner;
 
 #[no_mangle]
 #[linkage = "weak"]
@@ -185,3 +203,184 @@ macro_rules! def_task_ext {
         }
     };
 }
+
+/// The descriptor of a task extension slot, defined by
+/// [`def_task_ext_slot!`](crate::def_task_ext_slot).
+#[doc(hidden)]
+pub struct TaskExtSlot {
+    /// Creates the initial value, and returns the pointer to it in a box.
+    pub init: fn() -> *mut u8,
+    /// Drops the value and frees the box returned by `init`.
+    pub drop: unsafe fn(*mut u8),
+}
+
+/// All task extension slots in the image.
+#[doc(hidden)]
+#[distributed_slice]
+pub static TASK_EXT_SLOTS: [TaskExtSlot];
+
+impl TaskExtSlot {
+    fn index(&'static self) -> usize {
+        let offset = self as *const Self as usize - TASK_EXT_SLOTS.as_ptr() as usize;
+        offset / size_of::<Self>()
+    }
+}
+
+/// Boxes the value of a slot, used by `TaskExtSlot::init`.
+#[doc(hidden)]
+pub fn into_slot_ptr<T>(value: T) -> *mut u8 {
+    Box::into_raw(Box::new(value)) as *mut u8
+}
+
+/// Drops the value of a slot, used as `TaskExtSlot::drop`.
+#[doc(hidden)]
+pub unsafe fn drop_slot_ptr<T>(ptr: *mut u8) {
+    drop(unsafe { Box::from_raw(ptr as *mut T) });
+}
+
+/// The values of all task extension slots of a task, null if not initialized.
+pub(crate) struct TaskExtSlots {
+    ptrs: Box<[AtomicPtr<u8>]>,
+}
+
+impl TaskExtSlots {
+    pub fn new() -> Self {
+        Self {
+            ptrs: TASK_EXT_SLOTS
+                .iter()
+                .map(|_| AtomicPtr::new(null_mut()))
+                .collect(),
+        }
+    }
+
+    fn get_or_init(&self, slot: &'static TaskExtSlot) -> *mut u8 {
+        let ptr = &self.ptrs[slot.index()];
+        let value = ptr.load(Ordering::Acquire);
+        if !value.is_null() {
+            return value;
+        }
+        let new = (slot.init)();
+        match ptr.compare_exchange(null_mut(), new, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => new,
+            Err(value) => {
+                // initialized by others at the same time.
+                unsafe { (slot.drop)(new) };
+                value
+            }
+        }
+    }
+
+    fn get(&self, slot: &'static TaskExtSlot) -> *mut u8 {
+        self.ptrs[slot.index()].load(Ordering::Acquire)
+    }
+}
+
+impl Drop for TaskExtSlots {
+    fn drop(&mut self) {
+        for (slot, ptr) in TASK_EXT_SLOTS.iter().zip(self.ptrs.iter_mut()) {
+            let value = *ptr.get_mut();
+            if !value.is_null() {
+                unsafe { (slot.drop)(value) };
+            }
+        }
+    }
+}
+
+/// The key to access a task extension slot of type `T`, defined by
+/// [`def_task_ext_slot!`](crate::def_task_ext_slot).
+pub struct TaskExtKey<T: 'static> {
+    slot: &'static TaskExtSlot,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Send + Sync + 'static> TaskExtKey<T> {
+    /// Creates a key of the slot.
+    ///
+    /// # Safety
+    ///
+    /// The slot must create and drop values of type `T`.
+    #[doc(hidden)]
+    pub const unsafe fn new(slot: &'static TaskExtSlot) -> Self {
+        Self {
+            slot,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Gets the value of the slot in the given task, initializes it first if
+    /// it's not initialized.
+    ///
+    /// The initializer is run in the context of the caller, and may be run
+    /// more than once if several tasks access the slot at the same time, with
+    /// only one of the values kept.
+    pub fn get<'a>(&self, task: &'a TaskInner) -> &'a T {
+        // SAFETY: the value lives as long as the task, and is of type `T`.
+        unsafe { &*(task.task_ext_slots().get_or_init(self.slot) as *const T) }
+    }
+
+    /// Gets the value of the slot in the given task, or returns [`None`] if
+    /// it's not initialized.
+    pub fn try_get<'a>(&self, task: &'a TaskInner) -> Option<&'a T> {
+        let ptr = task.task_ext_slots().get(self.slot) as *const T;
+        // SAFETY: the value lives as long as the task, and is of type `T`.
+        unsafe { ptr.as_ref() }
+    }
+
+    /// Gets the value of the slot in the current task, initializes it first
+    /// if it's not initialized.
+    pub fn with_current<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(self.get(&crate::current()))
+    }
+}
+
+/// Define a task extension slot.
+///
+/// Every task has its own value of the slot, of type `T` that must be
+/// `Send + Sync` (as the value of a task can be accessed by others), so it
+/// usually uses interior mutability. The value is initialized by the given
+/// expression on first access, and dropped when the task is dropped.
+///
+/// Unlike [`def_task_ext!`], several crates can define their own slots in the
+/// same image.
+///
+/// # Example
+///
+/// ```
+/// use axtask::def_task_ext_slot;
+/// use core::sync::atomic::{AtomicUsize, Ordering};
+///
+/// def_task_ext_slot! {
+///     /// The number of system calls made by the task.
+///     static SYSCALL_COUNT: AtomicUsize = AtomicUsize::new(0);
+/// }
+///
+/// axtask::init_scheduler();
+///
+/// SYSCALL_COUNT.with_current(|n| n.fetch_add(1, Ordering::Relaxed));
+/// let task = axtask::spawn(|| {});
+/// assert_eq!(SYSCALL_COUNT.try_get(&task).map(|n| n.load(Ordering::Relaxed)), None);
+/// assert_eq!(SYSCALL_COUNT.get(&task).load(Ordering::Relaxed), 0);
+/// assert_eq!(SYSCALL_COUNT.get(&axtask::current()).load(Ordering::Relaxed), 1);
+/// ```
+#[macro_export]
+macro_rules! def_task_ext_slot {
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $ty:ty = $init:expr;) => {
+        $(#[$attr])*
+        $vis static $name: $crate::TaskExtKey<$ty> = {
+            #[$crate::__priv::linkme::distributed_slice($crate::__priv::TASK_EXT_SLOTS)]
+            #[linkme(crate = $crate::__priv::linkme)]
+            static SLOT: $crate::__priv::TaskExtSlot = $crate::__priv::TaskExtSlot {
+                init: || $crate::__priv::into_slot_ptr::<$ty>($init),
+                drop: $crate::__priv::drop_slot_ptr::<$ty>,
+            };
+            // SAFETY: the slot creates and drops values of `$ty`.
+            unsafe { $crate::TaskExtKey::new(&SLOT) }
+        };
+    };
+}
+
+#[doc(hidden)]
+pub mod __priv {
+    pub use super::{drop_slot_ptr, into_slot_ptr, TaskExtSlot, TASK_EXT_SLOTS};
+    pub use linkme;
+}
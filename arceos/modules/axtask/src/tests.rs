@@ -1,7 +1,9 @@
 use core::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{Mutex, Once};
+use std::sync::{Arc, Mutex, Once};
 
-use crate::{api as axtask, current, WaitQueue};
+use axerrno::AxError;
+
+use crate::{api as axtask, current, AxCpuMask, TaskInner, TaskState, WaitQueue};
 
 static INIT: Once = Once::new();
 static SERIAL: Mutex<()> = Mutex::new(());
@@ -128,3 +130,648 @@ fn test_task_join() {
         assert_eq!(tasks[i].join(), Some(i as _));
     }
 }
+
+#[test]
+fn test_join_timeout() {
+    use core::sync::atomic::AtomicBool;
+    use core::time::Duration;
+
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    static DONE: AtomicBool = AtomicBool::new(false);
+
+    let task = axtask::spawn(|| {
+        while !DONE.load(Ordering::Acquire) {
+            axtask::yield_now();
+        }
+        axtask::exit(7);
+    });
+    assert_eq!(task.try_join(), None);
+    assert_eq!(task.join_timeout(Duration::from_millis(10)), None);
+    DONE.store(true, Ordering::Release);
+    assert_eq!(task.join_timeout(Duration::from_secs(10)), Some(7));
+    assert_eq!(task.try_join(), Some(7));
+
+    let task = axtask::spawn(|| {});
+    task.detach();
+    assert!(task.is_detached());
+    assert_eq!(task.join(), Some(0));
+
+    // The GC task does not keep a detached task after it exits, so it's freed
+    // once the handle is dropped, even before it runs.
+    let task = axtask::spawn(|| axtask::exit(3));
+    task.detach();
+    let weak = Arc::downgrade(&task);
+    drop(task);
+    for _ in 0..1000 {
+        if weak.strong_count() == 0 {
+            break;
+        }
+        axtask::yield_now();
+    }
+    assert_eq!(weak.strong_count(), 0);
+}
+
+#[test]
+fn test_task_cancel() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    static WQ: WaitQueue = WaitQueue::new();
+
+    let task = axtask::spawn(|| {
+        assert_eq!(WQ.wait_interruptible(), Err(AxError::Interrupted));
+        assert!(axtask::is_cancelled());
+        // later interruptible waits return immediately.
+        assert_eq!(
+            WQ.wait_until_interruptible(|| false),
+            Err(AxError::Interrupted)
+        );
+        assert!(!current().in_wait_queue());
+    });
+    while task.state() != TaskState::Blocked {
+        axtask::yield_now();
+    }
+    assert!(!task.is_cancelled());
+    task.cancel();
+    assert!(task.is_cancelled());
+    assert_eq!(task.join(), Some(0));
+    assert!(!WQ.notify_one(false));
+}
+
+struct DropCounter(AtomicUsize);
+
+static SLOT_DROPS: AtomicUsize = AtomicUsize::new(0);
+
+impl Drop for DropCounter {
+    fn drop(&mut self) {
+        SLOT_DROPS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+crate::def_task_ext_slot! {
+    static COUNTER_SLOT: DropCounter = DropCounter(AtomicUsize::new(1));
+}
+
+crate::def_task_ext_slot! {
+    static NAME_SLOT: Mutex<String> = Mutex::new(String::from("none"));
+}
+
+#[test]
+fn test_task_ext_slots() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    let task = axtask::spawn(|| {
+        COUNTER_SLOT.with_current(|c| c.0.fetch_add(1, Ordering::Relaxed));
+        *NAME_SLOT.get(&current()).lock().unwrap() = String::from("worker");
+    });
+    task.join();
+    assert_eq!(COUNTER_SLOT.get(&task).0.load(Ordering::Relaxed), 2);
+    assert_eq!(*NAME_SLOT.try_get(&task).unwrap().lock().unwrap(), "worker");
+
+    // slots are initialized lazily, and dropped with the task.
+    let inner = TaskInner::new(|| {}, "".into(), 0x1000);
+    assert!(COUNTER_SLOT.try_get(&inner).is_none());
+    assert_eq!(COUNTER_SLOT.get(&inner).0.load(Ordering::Relaxed), 1);
+    assert!(NAME_SLOT.try_get(&inner).is_none());
+    let drops = SLOT_DROPS.load(Ordering::Relaxed);
+    drop(inner);
+    assert_eq!(SLOT_DROPS.load(Ordering::Relaxed), drops + 1);
+}
+
+#[test]
+fn test_cpu_affinity() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    assert_eq!(current().cpu_mask(), AxCpuMask::full());
+    assert!(!axtask::set_current_affinity(AxCpuMask::new()));
+
+    let cpu_id = axhal::cpu::this_cpu_id();
+    let task = axtask::spawn(move || {
+        assert!(axtask::set_current_affinity(AxCpuMask::one_shot(cpu_id)));
+        assert_eq!(current().cpu_mask(), AxCpuMask::one_shot(cpu_id));
+        axtask::yield_now();
+        assert_eq!(axhal::cpu::this_cpu_id(), cpu_id);
+    });
+    assert_eq!(task.join(), Some(0));
+    assert_eq!(task.cpu_mask(), AxCpuMask::one_shot(cpu_id));
+}
+
+#[test]
+fn test_cpu_accounting() {
+    use core::time::Duration;
+
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    const BUSY_TIME: Duration = Duration::from_millis(10);
+    let before = axtask::total_cpu_usage();
+    let task = axtask::spawn(|| {
+        axtask::yield_now();
+        // Spend the time in the last time slice before exiting.
+        let start = axhal::time::monotonic_time();
+        while axhal::time::monotonic_time() - start < BUSY_TIME {}
+    });
+    let id = task.id().as_u64();
+    assert!(axtask::find_task(id).is_some());
+    assert_eq!(task.join(), Some(0));
+
+    // The last time slice is counted once the task is unregistered.
+    assert!(axtask::find_task(id).is_none());
+    let after = axtask::total_cpu_usage();
+    assert!(after.utime - before.utime >= BUSY_TIME);
+}
+
+#[test]
+fn test_run_queue_migration() {
+    use crate::run_queue::AxRunQueue;
+
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    // Two queues of the same CPU, as there is only one CPU in tests.
+    let src = AxRunQueue::new(0);
+    let dst = AxRunQueue::new(0);
+    assert_eq!(src.nr_ready(), 1); // the `gc` task
+    let gc = src.take_task_for(&dst).unwrap();
+    dst.enqueue(gc);
+    assert_eq!((src.nr_ready(), dst.nr_ready()), (0, 2));
+
+    // A task not allowed to run on the target CPU is kept in its queue.
+    let task = TaskInner::new(|| {}, "migrant".into(), 0x1000).into_arc();
+    task.set_cpu_mask(AxCpuMask::new());
+    src.enqueue(task.clone());
+    assert!(src.take_task_for(&dst).is_none());
+    assert_eq!((src.nr_ready(), dst.nr_ready()), (1, 2));
+
+    task.set_cpu_mask(AxCpuMask::full());
+    let migrant = src.take_task_for(&dst).unwrap();
+    assert!(Arc::ptr_eq(&migrant, &task));
+    dst.enqueue(migrant);
+    assert_eq!((src.nr_ready(), dst.nr_ready()), (0, 3));
+    assert_eq!(task.cpu_id(), 0);
+}
+
+#[test]
+#[cfg(feature = "irq")]
+fn test_timer_cancel() {
+    use crate::timers::{cancel_alarm, next_deadline, set_alarm_wakeup};
+    use core::time::Duration;
+
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    let task = TaskInner::new(|| {}, "sleeper".into(), 0x1000).into_arc();
+    let deadline = axhal::time::wall_time() + Duration::from_secs(3600);
+    set_alarm_wakeup(deadline, task.clone());
+    assert_eq!(Arc::strong_count(&task), 2);
+
+    // A reset alarm replaces the previous one.
+    set_alarm_wakeup(deadline + Duration::from_secs(1), task.clone());
+    assert_eq!(Arc::strong_count(&task), 2);
+    assert_ne!(next_deadline(), Some(deadline));
+
+    // A cancelled alarm releases the task and no longer wakes up the CPU.
+    cancel_alarm(&task);
+    assert_eq!(Arc::strong_count(&task), 1);
+    assert_ne!(next_deadline(), Some(deadline + Duration::from_secs(1)));
+}
+
+#[test]
+#[cfg(feature = "irq")]
+fn test_timer_oneshot() {
+    use crate::{timer, timers::check_events};
+
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    let fired = Arc::new(AtomicUsize::new(0));
+    let their_fired = fired.clone();
+    let handle = timer::at(axhal::time::wall_time(), move || {
+        their_fired.fetch_add(1, Ordering::Relaxed);
+    });
+    check_events();
+    assert_eq!(fired.load(Ordering::Relaxed), 1);
+
+    // fired only once, and the callback is released after firing.
+    check_events();
+    assert_eq!(fired.load(Ordering::Relaxed), 1);
+    assert_eq!(Arc::strong_count(&fired), 1);
+    assert!(!handle.is_cancelled());
+}
+
+#[test]
+#[cfg(feature = "irq")]
+fn test_timer_periodic() {
+    use crate::{timer, timers::check_events, timers::next_deadline};
+    use core::time::Duration;
+
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    let period = Duration::from_secs(60);
+    let now = axhal::time::wall_time();
+    let fired = Arc::new(AtomicUsize::new(0));
+    let their_fired = fired.clone();
+    let handle = timer::set(
+        now,
+        Some(period),
+        Box::new(move || {
+            their_fired.fetch_add(1, Ordering::Relaxed);
+        }),
+    );
+    check_events();
+    assert_eq!(fired.load(Ordering::Relaxed), 1);
+
+    // re-armed one period later, and not fired again before that.
+    assert_eq!(next_deadline(), Some(now + period));
+    check_events();
+    assert_eq!(fired.load(Ordering::Relaxed), 1);
+
+    handle.cancel();
+    assert_ne!(next_deadline(), Some(now + period));
+    drop(handle);
+    assert_eq!(Arc::strong_count(&fired), 1);
+}
+
+#[test]
+#[cfg(feature = "irq")]
+fn test_timer_handle_cancel() {
+    use crate::{timer, timers::check_events, timers::next_deadline};
+    use core::time::Duration;
+
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    let deadline = axhal::time::wall_time() + Duration::from_secs(7200);
+    let fired = Arc::new(AtomicUsize::new(0));
+    let their_fired = fired.clone();
+    let handle = timer::at(deadline, move || {
+        their_fired.fetch_add(1, Ordering::Relaxed);
+    });
+    assert_eq!(Arc::strong_count(&fired), 2);
+    assert_eq!(next_deadline(), Some(deadline));
+
+    // The cancelled timer is removed from the timer list, so the callback is
+    // released once the handle is dropped.
+    handle.cancel();
+    assert!(handle.is_cancelled());
+    assert_ne!(next_deadline(), Some(deadline));
+    let cloned = handle.clone();
+    drop(handle);
+    assert_eq!(Arc::strong_count(&fired), 2);
+    drop(cloned);
+    assert_eq!(Arc::strong_count(&fired), 1);
+
+    // A cancelled timer never fires.
+    let their_fired = fired.clone();
+    let handle = timer::at(axhal::time::wall_time(), move || {
+        their_fired.fetch_add(1, Ordering::Relaxed);
+    });
+    handle.cancel();
+    check_events();
+    assert_eq!(fired.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+#[cfg(feature = "lockdep")]
+fn test_lockdep_inversion() {
+    use crate::lockdep::{num_reports, spin::SpinNoIrq};
+
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    static A: SpinNoIrq<()> = SpinNoIrq::new(());
+    static B: SpinNoIrq<()> = SpinNoIrq::new(());
+    // locks created at the same site are of the same class.
+    let per_task: Vec<_> = (0..2).map(|_| SpinNoIrq::new(())).collect();
+
+    let reports = num_reports();
+    {
+        let _a = A.lock();
+        let _b = B.lock();
+    }
+    {
+        let _b = B.lock();
+        let _a = A.lock();
+    }
+    assert_eq!(num_reports(), reports + 1);
+
+    // the inversion is recorded once, and nesting locks of the same class is
+    // not reported.
+    {
+        let _b = B.lock();
+        let _a = A.lock();
+        let _first = per_task[0].lock();
+        let _second = per_task[1].lock();
+    }
+    {
+        let _second = per_task[1].lock();
+        let _first = per_task[0].lock();
+    }
+    assert_eq!(num_reports(), reports + 1);
+}
+
+#[test]
+#[cfg(feature = "watchdog")]
+fn test_watchdog_hung_task() {
+    use crate::watchdog::{self, check_hung_tasks, set_hung_task_threshold};
+    use core::sync::atomic::AtomicBool;
+    use core::time::Duration;
+
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    static WQ: WaitQueue = WaitQueue::new();
+    static DONE: AtomicBool = AtomicBool::new(false);
+
+    let threshold = Duration::from_secs(1);
+    set_hung_task_threshold(threshold);
+    // the clock is fixed at 0 on the host, pretend that time has passed.
+    let now = threshold.as_nanos() as u64;
+
+    let blocked = axtask::spawn(|| WQ.wait_until(|| DONE.load(Ordering::Acquire)));
+    let idle = axtask::spawn(|| WQ.wait_idle_until(|| DONE.load(Ordering::Acquire)));
+    let interruptible = axtask::spawn(|| {
+        WQ.wait_until_interruptible(|| DONE.load(Ordering::Acquire))
+            .unwrap()
+    });
+    let exempted = axtask::spawn(|| WQ.wait_until(|| DONE.load(Ordering::Acquire)));
+    watchdog::set_hung_check(&exempted, false);
+    for task in [&blocked, &idle, &interruptible, &exempted] {
+        while task.state() != TaskState::Blocked {
+            axtask::yield_now();
+        }
+    }
+
+    let reported = check_hung_tasks(now);
+    assert!(reported.iter().any(|t| Arc::ptr_eq(t, &blocked)));
+    for task in [&idle, &interruptible, &exempted] {
+        assert!(!reported.iter().any(|t| Arc::ptr_eq(t, task)));
+    }
+    // a hung task is reported once until it's woken up.
+    assert!(!check_hung_tasks(now)
+        .iter()
+        .any(|t| Arc::ptr_eq(t, &blocked)));
+
+    DONE.store(true, Ordering::Release);
+    WQ.notify_all(false);
+    for task in [blocked, idle, interruptible, exempted] {
+        assert_eq!(task.join(), Some(0));
+    }
+    set_hung_task_threshold(Duration::from_secs(watchdog::DEFAULT_HUNG_TASK_SECS));
+}
+
+#[test]
+#[cfg(feature = "watchdog")]
+fn test_watchdog_soft_lockup() {
+    use crate::watchdog::{self, check_soft_lockup, set_soft_lockup_threshold, touch};
+    use core::time::Duration;
+
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    let threshold = Duration::from_secs(1);
+    set_soft_lockup_threshold(threshold);
+    let threshold = threshold.as_nanos() as u64;
+
+    touch(0, 0);
+    // not reported before the threshold, or if no other tasks are waiting.
+    assert!(!check_soft_lockup(0, 1, threshold - 1));
+    assert!(!check_soft_lockup(0, 0, threshold));
+    assert!(check_soft_lockup(0, 1, threshold));
+    // reported once until the CPU reschedules.
+    assert!(!check_soft_lockup(0, 1, threshold * 2));
+    touch(0, threshold * 2);
+    assert!(!check_soft_lockup(0, 1, threshold * 2));
+    assert!(check_soft_lockup(0, 1, threshold * 3));
+
+    touch(0, 0);
+    set_soft_lockup_threshold(Duration::from_secs(watchdog::DEFAULT_SOFT_LOCKUP_SECS));
+}
+
+#[test]
+#[cfg(feature = "sched_trace")]
+fn test_trace_buffer() {
+    use crate::trace::{EventKind, TraceBuffer, BUFFER_LEN};
+
+    let buf = Box::new(TraceBuffer::EMPTY);
+    for i in 0..10 {
+        buf.push(i, EventKind::Switch, 1, i + 100);
+    }
+    let events = buf.snapshot(2);
+    assert_eq!(events.len(), 10);
+    for (i, ev) in events.iter().enumerate() {
+        assert_eq!(ev.timestamp, i as u64);
+        assert_eq!((ev.cpu, ev.kind, ev.task_id), (2, EventKind::Switch, 1));
+        assert_eq!(ev.arg, i as u64 + 100);
+    }
+
+    // when full, the oldest events are overwritten.
+    let total = BUFFER_LEN as u64 + 100;
+    for i in 10..total {
+        buf.push(i, EventKind::Block, 1, 0);
+    }
+    let events = buf.snapshot(0);
+    assert_eq!(events.len(), BUFFER_LEN);
+    assert_eq!(events[0].timestamp, total - BUFFER_LEN as u64);
+    assert_eq!(events[BUFFER_LEN - 1].timestamp, total - 1);
+    assert!(events
+        .windows(2)
+        .all(|w| w[0].timestamp + 1 == w[1].timestamp));
+
+    // only the events after clearing are kept.
+    buf.clear();
+    assert!(buf.snapshot(0).is_empty());
+    buf.push(total, EventKind::Exit, 1, 0);
+    let events = buf.snapshot(0);
+    assert_eq!(events.len(), 1);
+    assert_eq!(
+        (events[0].timestamp, events[0].kind),
+        (total, EventKind::Exit)
+    );
+}
+
+#[test]
+#[cfg(feature = "sched_trace")]
+fn test_trace_dump() {
+    use crate::trace::{dump, JsonStr};
+    use core::sync::atomic::AtomicBool;
+
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    assert_eq!(
+        format!("{}", JsonStr("a\"b\\c\nd\u{1}\u{e9}")),
+        "a\\\"b\\\\c\\u000ad\\u0001\u{e9}"
+    );
+
+    // names of live tasks are escaped in the dump.
+    static DONE: AtomicBool = AtomicBool::new(false);
+    let task = axtask::spawn_raw(
+        || {
+            while !DONE.load(Ordering::Acquire) {
+                axtask::yield_now();
+            }
+        },
+        "say \"hi\"\n".into(),
+        0x1000,
+    );
+    let mut json = String::new();
+    dump(&mut json).unwrap();
+    DONE.store(true, Ordering::Release);
+    task.join();
+    assert!(json.starts_with("{\"traceEvents\":["));
+    assert!(json.ends_with("],\"displayTimeUnit\":\"ns\"}\n"));
+    assert_eq!(json.matches('\n').count(), 1);
+    assert!(json.contains("say \\\"hi\\\"\\u000a"));
+}
+
+#[test]
+#[cfg(feature = "sched_cfs")]
+fn test_priority_inheritance() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    // Lock keys, as `axsync::Mutex` uses its address.
+    const LOCK_A: usize = 0x1000;
+    const LOCK_B: usize = 0x2000;
+
+    let low = axtask::spawn(|| {
+        assert!(axtask::set_priority(10));
+        let curr = current();
+
+        // Waiters on both locks lend their priorities, the highest wins.
+        axtask::inherit_priority(curr.as_task_ref(), LOCK_A, -10);
+        axtask::inherit_priority(curr.as_task_ref(), LOCK_B, 0);
+        assert_eq!(curr.priority(), -10);
+
+        // Changing the base priority keeps the inherited one.
+        assert!(axtask::set_priority(5));
+        assert_eq!(curr.priority(), -10);
+
+        // Releasing B keeps the priority inherited through A.
+        assert!(!axtask::restore_current_priority(LOCK_B));
+        assert_eq!(curr.priority(), -10);
+
+        // Releasing A falls back to the base priority.
+        axtask::inherit_priority(curr.as_task_ref(), LOCK_B, 0);
+        assert!(axtask::restore_current_priority(LOCK_A));
+        assert_eq!(curr.priority(), 0);
+        assert!(axtask::restore_current_priority(LOCK_B));
+        assert_eq!(curr.priority(), 5);
+    });
+
+    assert_eq!(low.join(), Some(0));
+    assert_eq!(low.priority(), 5);
+}
+
+#[cfg(feature = "sched_edf")]
+fn deadline_params(runtime_ms: u64, deadline_ms: u64, period_ms: u64) -> crate::DeadlineParams {
+    use core::time::Duration;
+    crate::DeadlineParams {
+        runtime: Duration::from_millis(runtime_ms),
+        deadline: Duration::from_millis(deadline_ms),
+        period: Duration::from_millis(period_ms),
+    }
+}
+
+#[test]
+#[cfg(feature = "sched_edf")]
+fn test_edf_admission() {
+    use crate::sched_edf::EDFTask;
+
+    let _lock = SERIAL.lock();
+
+    let a = EDFTask::new(());
+    let b = EDFTask::new(());
+    assert!(!a.set_deadline_params(Some(deadline_params(20, 10, 10)), 0));
+    assert!(a.set_deadline_params(Some(deadline_params(6, 10, 10)), 0));
+    assert_eq!(a.deadline_cpu(), Some(0));
+
+    // The CPU cannot reserve more than 95% of its time.
+    assert!(!b.set_deadline_params(Some(deadline_params(4, 10, 10)), 0));
+    assert!(b.set_deadline_params(Some(deadline_params(3, 10, 10)), 0));
+    assert!(!a.set_deadline_params(Some(deadline_params(7, 10, 10)), 0));
+    assert_eq!(a.deadline_params(), Some(deadline_params(6, 10, 10)));
+    assert!(a.set_deadline_params(Some(deadline_params(5, 10, 10)), 0));
+
+    // The bandwidth is given back when the parameters are cleared.
+    assert!(a.set_deadline_params(None, 0));
+    assert_eq!(a.deadline_cpu(), None);
+    assert!(b.set_deadline_params(Some(deadline_params(9, 10, 10)), 0));
+    assert!(b.set_deadline_params(None, 0));
+}
+
+#[test]
+#[cfg(feature = "sched_edf")]
+fn test_edf_scheduler() {
+    use crate::sched_edf::{EDFScheduler, EDFTask};
+    use scheduler::BaseScheduler;
+
+    let _lock = SERIAL.lock();
+
+    let normal = Arc::new(EDFTask::new(0));
+    let late = Arc::new(EDFTask::new(1));
+    let early = Arc::new(EDFTask::new(2));
+    assert!(late.set_deadline_params(Some(deadline_params(1, 100, 100)), 0));
+    assert!(early.set_deadline_params(Some(deadline_params(1, 10, 1000)), 0));
+
+    let mut sched = EDFScheduler::new();
+    sched.add_task(normal.clone());
+    sched.add_task(late.clone());
+    sched.add_task(early.clone());
+
+    // Deadline tasks run first, in the order of their deadlines.
+    for expected in [2, 1, 0] {
+        assert_eq!(*sched.pick_next_task().unwrap().inner(), expected);
+    }
+    assert!(sched.next_replenish_time().is_none());
+
+    // A deadline task that yields has completed its job, and is throttled
+    // until the next period.
+    let now = axhal::time::monotonic_time_nanos();
+    sched.put_prev_task(early.clone(), false);
+    assert!(sched.pick_next_task().is_none());
+    assert!(sched.next_replenish_time().is_some_and(|t| t > now));
+
+    // A normal task is preempted once a deadline task is ready.
+    sched.add_task(late.clone());
+    assert!(sched.task_tick(&normal));
+
+    assert!(late.set_deadline_params(None, 0));
+    assert!(early.set_deadline_params(None, 0));
+}
+
+#[test]
+#[cfg(feature = "sched_edf")]
+fn test_edf_cpu_mask() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    // An empty mask tells the saved one from the pinned one, as there is only
+    // one CPU in tests.
+    let task = TaskInner::new(|| {}, "edf".into(), 0x1000).into_arc();
+    task.set_cpu_mask(AxCpuMask::new());
+
+    // A deadline task is pinned to the CPU reserving its bandwidth, and gets
+    // its previous CPU mask back when it's a normal task again.
+    task.pin_cpu(0);
+    assert_eq!(task.cpu_mask(), AxCpuMask::one_shot(0));
+    task.pin_cpu(0);
+    task.unpin_cpu();
+    assert_eq!(task.cpu_mask(), AxCpuMask::new());
+    task.unpin_cpu();
+    assert_eq!(task.cpu_mask(), AxCpuMask::new());
+
+    let task = axtask::spawn(|| {
+        let cpumask = current().cpu_mask();
+        assert!(axtask::set_deadline(Some(deadline_params(1, 10, 10))));
+        assert!(axtask::set_deadline(None));
+        assert_eq!(current().cpu_mask(), cpumask);
+    });
+    assert_eq!(task.join(), Some(0));
+}
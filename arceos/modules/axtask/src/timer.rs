@@ -0,0 +1,139 @@
+//! Kernel timers that run callbacks at a given time, once or periodically.
+//!
+//! Callbacks are run in the timer interrupt handler of the CPU on which the
+//! timer is set, with IRQs disabled, so they must be short and must not
+//! block. Blocking work should be handed over to a task instead, e.g. by
+//! notifying a [`WaitQueue`](crate::WaitQueue).
+
+use alloc::{boxed::Box, sync::Arc};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::time::Duration;
+
+use axhal::time::{wall_time, TimeValue};
+
+use crate::spin::SpinNoIrq;
+
+type TimerFn = Box<dyn FnMut() + Send + 'static>;
+
+/// A callback in the timer list, shared with its [`TimerHandle`].
+pub(crate) struct TimerCallback {
+    func: SpinNoIrq<TimerFn>,
+    period: Option<Duration>,
+    cancelled: AtomicBool,
+    /// The CPU whose timer list holds the callback.
+    cpu_id: AtomicUsize,
+}
+
+impl TimerCallback {
+    /// Runs the callback that expires at `deadline`, and returns the next
+    /// deadline if it's periodic.
+    pub(crate) fn fire(&self, deadline: TimeValue, now: TimeValue) -> Option<TimeValue> {
+        if self.is_cancelled() {
+            return None;
+        }
+        (self.func.lock())();
+        let period = self.period?;
+        if self.is_cancelled() {
+            return None;
+        }
+        // skip the missed periods if the timer is late.
+        let next = deadline + period;
+        if next > now {
+            Some(next)
+        } else {
+            let late = (now - deadline).as_nanos() % period.as_nanos();
+            Some(now + period - Duration::from_nanos(late as u64))
+        }
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn cpu_id(&self) -> usize {
+        self.cpu_id.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn set_cpu_id(&self, cpu_id: usize) {
+        self.cpu_id.store(cpu_id, Ordering::Release);
+    }
+}
+
+/// A handle to cancel a timer set by [`after`], [`at`] or [`every`].
+///
+/// Dropping the handle does not cancel the timer.
+#[derive(Clone)]
+pub struct TimerHandle {
+    inner: Arc<TimerCallback>,
+}
+
+impl TimerHandle {
+    /// Cancels the timer, and removes it from the timer list so that the
+    /// callback (and everything it captures) is released immediately.
+    ///
+    /// The callback is not called after this function returns, unless it's
+    /// running on another CPU at the same time.
+    pub fn cancel(&self) {
+        if !self.inner.cancelled.swap(true, Ordering::AcqRel) {
+            crate::timers::cancel_alarm_callback(&self.inner);
+        }
+    }
+
+    /// Whether the timer has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.is_cancelled()
+    }
+}
+
+pub(crate) fn set(deadline: TimeValue, period: Option<Duration>, f: TimerFn) -> TimerHandle {
+    let inner = Arc::new(TimerCallback {
+        func: SpinNoIrq::new(f),
+        period,
+        cancelled: AtomicBool::new(false),
+        cpu_id: AtomicUsize::new(0),
+    });
+    crate::timers::set_alarm_callback(deadline, inner.clone());
+    TimerHandle { inner }
+}
+
+/// Runs the callback once when the wall time reaches `deadline`.
+pub fn at<F>(deadline: TimeValue, f: F) -> TimerHandle
+where
+    F: FnOnce() + Send + 'static,
+{
+    let mut f = Some(f);
+    set(
+        deadline,
+        None,
+        Box::new(move || {
+            if let Some(f) = f.take() {
+                f()
+            }
+        }),
+    )
+}
+
+/// Runs the callback once after `dur` has elapsed.
+pub fn after<F>(dur: Duration, f: F) -> TimerHandle
+where
+    F: FnOnce() + Send + 'static,
+{
+    at(wall_time() + dur, f)
+}
+
+/// Runs the callback every `period`, starting one period from now, until the
+/// timer is cancelled.
+///
+/// If the timer interrupt is late by more than one period, the missed
+/// callbacks are skipped rather than run in a burst.
+///
+/// # Panics
+///
+/// Panics if `period` is zero.
+pub fn every<F>(period: Duration, f: F) -> TimerHandle
+where
+    F: FnMut() + Send + 'static,
+{
+    assert!(!period.is_zero(), "timer period must be non-zero");
+    set(wall_time() + period, Some(period), Box::new(f))
+}
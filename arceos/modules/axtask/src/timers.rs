@@ -1,40 +1,129 @@
 use alloc::sync::Arc;
+use core::task::Waker;
+
 use axhal::time::wall_time;
-use kspin::SpinNoIrq;
 use lazyinit::LazyInit;
 use timer_list::{TimeValue, TimerEvent, TimerList};
 
-use crate::{AxTaskRef, RUN_QUEUE};
+use crate::spin::SpinNoIrq;
+use crate::{select_run_queue, timer::TimerCallback, AxTaskRef};
 
-// TODO: per-CPU
-static TIMER_LIST: LazyInit<SpinNoIrq<TimerList<TaskWakeupEvent>>> = LazyInit::new();
+/// The per-CPU timer lists, which are locked so that the events can be
+/// cancelled from other CPUs.
+#[percpu::def_percpu]
+static TIMER_LIST: LazyInit<SpinNoIrq<TimerList<AxTimerEvent>>> = LazyInit::new();
 
-struct TaskWakeupEvent(AxTaskRef);
+enum AxTimerEvent {
+    /// Wakes up a task that is sleeping or waiting with a timeout.
+    TaskWakeup { ticket: u64, task: AxTaskRef },
+    /// Wakes up an asynchronous task.
+    Waker(Waker),
+    /// Runs a callback set by the [`timer`](crate::timer) APIs.
+    Callback {
+        deadline: TimeValue,
+        callback: Arc<TimerCallback>,
+    },
+}
 
-impl TimerEvent for TaskWakeupEvent {
-    fn callback(self, _now: TimeValue) {
-        let mut rq = RUN_QUEUE.lock();
-        self.0.set_in_timer_list(false);
-        rq.unblock_task(self.0, true);
+impl TimerEvent for AxTimerEvent {
+    fn callback(self, now: TimeValue) {
+        match self {
+            Self::TaskWakeup { ticket, task } => {
+                // The alarm has been reset, the new one is in charge.
+                if task.timer_ticket() != ticket {
+                    return;
+                }
+                task.set_in_timer_list(false);
+                select_run_queue(&task).unblock_task(task, true);
+            }
+            Self::Waker(waker) => waker.wake(),
+            Self::Callback { deadline, callback } => {
+                if let Some(next) = callback.fire(deadline, now) {
+                    set_alarm_callback(next, callback);
+                }
+            }
+        }
+    }
+}
+
+impl AxTimerEvent {
+    /// Whether the event has been cancelled and needn't fire.
+    fn is_cancelled(&self) -> bool {
+        match self {
+            Self::TaskWakeup { ticket, task } => task.timer_ticket() != *ticket,
+            Self::Callback { callback, .. } => callback.is_cancelled(),
+            Self::Waker(_) => false,
+        }
     }
 }
 
+/// Operates on the timer list of the given CPU.
+fn with_timer_list<F, R>(cpu_id: usize, f: F) -> R
+where
+    F: FnOnce(&mut TimerList<AxTimerEvent>) -> R,
+{
+    // Safety: the timer lists are initialized before any event is set, and
+    // are only accessed with the locks held.
+    f(&mut unsafe { TIMER_LIST.remote_ref_raw(cpu_id) }.lock())
+}
+
 pub fn set_alarm_wakeup(deadline: TimeValue, task: AxTaskRef) {
-    let mut timers = TIMER_LIST.lock();
+    // remove the previous alarm, if any.
+    if task.in_timer_list() {
+        cancel_alarm(&task);
+    }
+    let cpu_id = axhal::cpu::this_cpu_id();
+    let ticket = task.next_timer_ticket();
+    task.set_timer_cpu_id(cpu_id);
     task.set_in_timer_list(true);
-    timers.set(deadline, TaskWakeupEvent(task));
+    with_timer_list(cpu_id, |timers| {
+        timers.set(deadline, AxTimerEvent::TaskWakeup { ticket, task })
+    });
+}
+
+pub fn set_alarm_waker(deadline: TimeValue, waker: Waker) {
+    with_timer_list(axhal::cpu::this_cpu_id(), |timers| {
+        timers.set(deadline, AxTimerEvent::Waker(waker))
+    });
+}
+
+pub fn set_alarm_callback(deadline: TimeValue, callback: Arc<TimerCallback>) {
+    let cpu_id = axhal::cpu::this_cpu_id();
+    callback.set_cpu_id(cpu_id);
+    with_timer_list(cpu_id, |timers| {
+        timers.set(deadline, AxTimerEvent::Callback { deadline, callback })
+    });
 }
 
+/// Removes the (cancelled) callback from the timer list.
+///
+/// A periodic callback that is being re-armed concurrently on another CPU may
+/// still be left in the list, it's dropped without firing when it expires.
+pub fn cancel_alarm_callback(callback: &Arc<TimerCallback>) {
+    with_timer_list(callback.cpu_id(), |timers| {
+        timers.cancel(|event| {
+            matches!(event, AxTimerEvent::Callback { callback: c, .. } if Arc::ptr_eq(c, callback))
+        })
+    });
+}
+
+/// Cancels the alarm of the task, and removes it from the timer list so that
+/// the task reference is released immediately.
 pub fn cancel_alarm(task: &AxTaskRef) {
-    let mut timers = TIMER_LIST.lock();
+    task.next_timer_ticket();
     task.set_in_timer_list(false);
-    timers.cancel(|t| Arc::ptr_eq(&t.0, task));
+    with_timer_list(task.timer_cpu_id(), |timers| {
+        timers.cancel(|event| {
+            matches!(event, AxTimerEvent::TaskWakeup { task: t, .. } if Arc::ptr_eq(t, task))
+        })
+    });
 }
 
 pub fn check_events() {
+    let cpu_id = axhal::cpu::this_cpu_id();
     loop {
         let now = wall_time();
-        let event = TIMER_LIST.lock().expire_one(now);
+        let event = with_timer_list(cpu_id, |timers| timers.expire_one(now));
         if let Some((_deadline, event)) = event {
             event.callback(now);
         } else {
@@ -43,6 +132,62 @@ pub fn check_events() {
     }
 }
 
+/// Returns the earliest deadline of pending events on the current CPU.
+///
+/// Cancelled events are dropped first, so that they do not wake up the CPU
+/// for nothing.
+#[cfg(any(feature = "tickless", test))]
+pub fn next_deadline() -> Option<TimeValue> {
+    with_timer_list(axhal::cpu::this_cpu_id(), |timers| {
+        timers.cancel(AxTimerEvent::is_cancelled);
+        timers.next_deadline()
+    })
+}
+
+#[cfg(feature = "tickless")]
+mod tickless {
+    use axhal::time::{epochoffset_nanos, monotonic_time_nanos, NANOS_PER_SEC};
+
+    const TICK_NANOS: u64 = NANOS_PER_SEC / axconfig::TICKS_PER_SEC as u64;
+
+    /// The maximum time that an idle CPU sleeps without ticks.
+    ///
+    /// Other CPUs have no way (e.g., IPIs) to wake up an idle CPU after they
+    /// put tasks into its run queue, so it still needs to wake up regularly.
+    const MAX_IDLE_NANOS: u64 = if cfg!(feature = "smp") {
+        TICK_NANOS * 10
+    } else {
+        NANOS_PER_SEC
+    };
+
+    /// Stops the periodic tick before the idle CPU goes to sleep, the timer is
+    /// programmed for the earliest pending deadline instead.
+    pub fn stop_tick() {
+        let now_ns = monotonic_time_nanos();
+        let deadline_ns = match super::next_deadline() {
+            // deadlines in the timer list are in wall time.
+            Some(deadline) => (deadline.as_nanos() as u64).saturating_sub(epochoffset_nanos()),
+            None => u64::MAX,
+        };
+        // Throttled deadline tasks must be replenished in time.
+        #[cfg(feature = "sched_edf")]
+        let deadline_ns = crate::current_run_queue()
+            .next_replenish_time()
+            .map_or(deadline_ns, |ns| ns.min(deadline_ns));
+        axhal::time::set_oneshot_timer(deadline_ns.clamp(now_ns, now_ns + MAX_IDLE_NANOS));
+    }
+
+    /// Restarts the periodic tick after the idle CPU is woken up by an IRQ.
+    pub fn restart_tick() {
+        axhal::time::set_oneshot_timer(monotonic_time_nanos() + TICK_NANOS);
+    }
+}
+
+#[cfg(feature = "tickless")]
+pub use tickless::{restart_tick, stop_tick};
+
 pub fn init() {
-    TIMER_LIST.init_once(SpinNoIrq::new(TimerList::new()));
+    TIMER_LIST.with_current(|timer_list| {
+        timer_list.init_once(SpinNoIrq::new(TimerList::new()));
+    });
 }
@@ -0,0 +1,345 @@
+//! Scheduler event tracing.
+//!
+//! Scheduler events (spawn, switch, block, unblock, exit, IRQ enter/exit) are
+//! recorded with timestamps in per-CPU ring buffers. Each buffer is written
+//! only by its own CPU with IRQs disabled, and readers detect slots being
+//! overwritten with sequence numbers, so no locks are needed. When a buffer
+//! is full, the oldest events are overwritten.
+//!
+//! The events can be exported by [`dump`] in the [Chrome trace event
+//! format][1], and opened in [Perfetto](https://ui.perfetto.dev) or
+//! `chrome://tracing`. Each CPU is shown as a thread, with the running tasks
+//! as slices on it.
+//!
+//! [1]: https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU
+
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::fmt::{self, Write};
+use core::sync::atomic::{fence, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+
+use axhal::trap::{register_trap_handler, IRQ_ENTER, IRQ_EXIT};
+
+/// The number of events kept for each CPU.
+pub(crate) const BUFFER_LEN: usize = 4096;
+
+/// The kind of a scheduler event.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    /// A task is spawned, `arg` is the CPU whose run queue it's put into.
+    Spawn = 1,
+    /// The CPU switches from the task to another one, `arg` is the ID of the
+    /// next task.
+    Switch,
+    /// The task is blocked.
+    Block,
+    /// The task is woken up, `arg` is the CPU whose run queue it's put into.
+    Unblock,
+    /// The task exits, `arg` is the exit code.
+    Exit,
+    /// An IRQ handler is entered while the task is running, `arg` is the IRQ
+    /// number.
+    IrqEnter,
+    /// An IRQ handler returns, `arg` is the IRQ number.
+    IrqExit,
+}
+
+impl EventKind {
+    fn from_u8(kind: u8) -> Option<Self> {
+        Some(match kind {
+            1 => Self::Spawn,
+            2 => Self::Switch,
+            3 => Self::Block,
+            4 => Self::Unblock,
+            5 => Self::Exit,
+            6 => Self::IrqEnter,
+            7 => Self::IrqExit,
+            _ => return None,
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Spawn => "spawn",
+            Self::Switch => "switch",
+            Self::Block => "block",
+            Self::Unblock => "unblock",
+            Self::Exit => "exit",
+            Self::IrqEnter => "irq_enter",
+            Self::IrqExit => "irq_exit",
+        }
+    }
+}
+
+/// A recorded scheduler event.
+#[derive(Debug, Clone, Copy)]
+pub struct Event {
+    /// The monotonic time when the event happens, in nanoseconds.
+    pub timestamp: u64,
+    /// The CPU on which the event is recorded.
+    pub cpu: usize,
+    /// The kind of the event.
+    pub kind: EventKind,
+    /// The ID of the task that the event is about.
+    pub task_id: u64,
+    /// An argument depending on the kind of the event.
+    pub arg: u64,
+}
+
+struct Slot {
+    /// The index of the event plus one, or zero if it's being written.
+    seq: AtomicUsize,
+    timestamp: AtomicU64,
+    kind: AtomicU8,
+    task_id: AtomicU64,
+    arg: AtomicU64,
+}
+
+impl Slot {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY: Self = Self {
+        seq: AtomicUsize::new(0),
+        timestamp: AtomicU64::new(0),
+        kind: AtomicU8::new(0),
+        task_id: AtomicU64::new(0),
+        arg: AtomicU64::new(0),
+    };
+}
+
+pub(crate) struct TraceBuffer {
+    /// The index of the next event to write.
+    head: AtomicUsize,
+    /// The index of the first event to read, moved forward by [`clear`].
+    tail: AtomicUsize,
+    slots: [Slot; BUFFER_LEN],
+}
+
+impl TraceBuffer {
+    #[allow(clippy::declare_interior_mutable_const)]
+    pub(crate) const EMPTY: Self = Self {
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+        slots: [Slot::EMPTY; BUFFER_LEN],
+    };
+
+    /// Appends an event, it must be called on the owner CPU with IRQs
+    /// disabled.
+    pub(crate) fn push(&self, timestamp: u64, kind: EventKind, task_id: u64, arg: u64) {
+        let idx = self.head.load(Ordering::Relaxed);
+        let slot = &self.slots[idx % BUFFER_LEN];
+        slot.seq.store(0, Ordering::Relaxed);
+        fence(Ordering::Release);
+        slot.timestamp.store(timestamp, Ordering::Relaxed);
+        slot.kind.store(kind as u8, Ordering::Relaxed);
+        slot.task_id.store(task_id, Ordering::Relaxed);
+        slot.arg.store(arg, Ordering::Relaxed);
+        slot.seq.store(idx + 1, Ordering::Release);
+        self.head.store(idx + 1, Ordering::Release);
+    }
+
+    /// Reads the events in the buffer, skipping the ones being overwritten.
+    pub(crate) fn snapshot(&self, cpu: usize) -> Vec<Event> {
+        let head = self.head.load(Ordering::Acquire);
+        let start = self
+            .tail
+            .load(Ordering::Relaxed)
+            .max(head.saturating_sub(BUFFER_LEN));
+        let mut events = Vec::with_capacity(head - start.min(head));
+        for idx in start..head {
+            let slot = &self.slots[idx % BUFFER_LEN];
+            if slot.seq.load(Ordering::Acquire) != idx + 1 {
+                continue;
+            }
+            let timestamp = slot.timestamp.load(Ordering::Relaxed);
+            let kind = slot.kind.load(Ordering::Relaxed);
+            let task_id = slot.task_id.load(Ordering::Relaxed);
+            let arg = slot.arg.load(Ordering::Relaxed);
+            fence(Ordering::Acquire);
+            if slot.seq.load(Ordering::Relaxed) != idx + 1 {
+                continue; // overwritten while reading
+            }
+            if let Some(kind) = EventKind::from_u8(kind) {
+                events.push(Event {
+                    timestamp,
+                    cpu,
+                    kind,
+                    task_id,
+                    arg,
+                });
+            }
+        }
+        events
+    }
+
+    /// Discards the events in the buffer.
+    pub(crate) fn clear(&self) {
+        self.tail
+            .store(self.head.load(Ordering::Acquire), Ordering::Relaxed);
+    }
+}
+
+static BUFFERS: [TraceBuffer; axconfig::SMP] = [TraceBuffer::EMPTY; axconfig::SMP];
+
+/// Records an event about the task on the current CPU.
+pub(crate) fn record(kind: EventKind, task_id: u64, arg: u64) {
+    let _guard = kernel_guard::IrqSave::new();
+    let timestamp = axhal::time::monotonic_time_nanos();
+    BUFFERS[axhal::cpu::this_cpu_id()].push(timestamp, kind, task_id, arg);
+}
+
+#[register_trap_handler(IRQ_ENTER)]
+fn trace_irq_enter(irq_num: usize) {
+    if let Some(curr) = crate::current_may_uninit() {
+        record(EventKind::IrqEnter, curr.id().as_u64(), irq_num as u64);
+    }
+}
+
+#[register_trap_handler(IRQ_EXIT)]
+fn trace_irq_exit(irq_num: usize) {
+    if let Some(curr) = crate::current_may_uninit() {
+        record(EventKind::IrqExit, curr.id().as_u64(), irq_num as u64);
+    }
+}
+
+/// Returns the recorded events of all CPUs, sorted by CPU and then by time.
+pub fn events() -> Vec<Event> {
+    BUFFERS
+        .iter()
+        .enumerate()
+        .flat_map(|(cpu, buf)| buf.snapshot(cpu))
+        .collect()
+}
+
+/// Discards the recorded events.
+pub fn clear() {
+    for buf in BUFFERS.iter() {
+        buf.clear();
+    }
+}
+
+/// Writes the recorded events in the Chrome trace event format (JSON).
+pub fn dump<W: Write>(w: &mut W) -> fmt::Result {
+    // task names are looked up once, exited tasks are shown by their IDs.
+    let names: BTreeMap<u64, String> = crate::tasks()
+        .map(|task| (task.id().as_u64(), task.id_name()))
+        .collect();
+    let mut out = JsonWriter {
+        w,
+        first: true,
+        names: &names,
+    };
+    out.w.write_str("{\"traceEvents\":[")?;
+    for (cpu, buf) in BUFFERS.iter().enumerate() {
+        out.event(format_args!(
+            "\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":0,\"tid\":{},\
+             \"args\":{{\"name\":\"CPU {}\"}}",
+            cpu, cpu
+        ))?;
+
+        let events = buf.snapshot(cpu);
+        // the task running on the CPU, and since when.
+        let mut running: Option<(u64, u64)> = None;
+        for ev in events.iter() {
+            let ts = Micros(ev.timestamp);
+            match ev.kind {
+                EventKind::Switch => {
+                    if let Some((task_id, start)) = running {
+                        out.slice(cpu, task_id, start, ev.timestamp)?;
+                    }
+                    running = Some((ev.arg, ev.timestamp));
+                }
+                EventKind::IrqEnter | EventKind::IrqExit => {
+                    let ph = if ev.kind == EventKind::IrqEnter {
+                        "B"
+                    } else {
+                        "E"
+                    };
+                    out.event(format_args!(
+                        "\"name\":\"irq {}\",\"cat\":\"irq\",\"ph\":\"{}\",\
+                         \"pid\":0,\"tid\":{},\"ts\":{}",
+                        ev.arg, ph, cpu, ts
+                    ))?;
+                }
+                _ => {
+                    out.event(format_args!(
+                        "\"name\":\"{}\",\"cat\":\"sched\",\"ph\":\"i\",\"s\":\"t\",\
+                         \"pid\":0,\"tid\":{},\"ts\":{},\
+                         \"args\":{{\"task\":\"{}\",\"arg\":{}}}",
+                        ev.kind.name(),
+                        cpu,
+                        ts,
+                        JsonStr(&out.task_name(ev.task_id)),
+                        ev.arg
+                    ))?;
+                }
+            }
+        }
+        if let Some((task_id, start)) = running {
+            let end = axhal::time::monotonic_time_nanos().max(start);
+            out.slice(cpu, task_id, start, end)?;
+        }
+    }
+    out.w.write_str("],\"displayTimeUnit\":\"ns\"}\n")
+}
+
+struct JsonWriter<'a, W: Write> {
+    w: &'a mut W,
+    first: bool,
+    names: &'a BTreeMap<u64, String>,
+}
+
+impl<W: Write> JsonWriter<'_, W> {
+    fn task_name(&self, task_id: u64) -> String {
+        match self.names.get(&task_id) {
+            Some(name) => name.clone(),
+            None => alloc::format!("Task({})", task_id),
+        }
+    }
+
+    /// Writes an event object with the given fields.
+    fn event(&mut self, fields: fmt::Arguments) -> fmt::Result {
+        if !self.first {
+            self.w.write_char(',')?;
+        }
+        self.first = false;
+        write!(self.w, "{{{}}}", fields)
+    }
+
+    /// Writes a complete event of a task running from `start` to `end`.
+    fn slice(&mut self, cpu: usize, task_id: u64, start: u64, end: u64) -> fmt::Result {
+        self.event(format_args!(
+            "\"name\":\"{}\",\"cat\":\"task\",\"ph\":\"X\",\"pid\":0,\"tid\":{},\
+             \"ts\":{},\"dur\":{}",
+            JsonStr(&self.task_name(task_id)),
+            cpu,
+            Micros(start),
+            Micros(end - start)
+        ))
+    }
+}
+
+/// Formats nanoseconds as microseconds.
+struct Micros(u64);
+
+impl fmt::Display for Micros {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{:03}", self.0 / 1000, self.0 % 1000)
+    }
+}
+
+/// Formats a string with JSON escapes.
+pub(crate) struct JsonStr<'a>(pub(crate) &'a str);
+
+impl fmt::Display for JsonStr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
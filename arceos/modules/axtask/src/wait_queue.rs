@@ -1,8 +1,13 @@
 use alloc::collections::VecDeque;
 use alloc::sync::Arc;
-use kspin::SpinRaw;
+use axerrno::{AxError, AxResult};
+use kernel_guard::NoPreemptIrqSave;
 
-use crate::{AxRunQueue, AxTaskRef, CurrentTask, RUN_QUEUE};
+use crate::spin::{SpinRaw, SpinRawGuard};
+use crate::{current_run_queue, select_run_queue, AxTaskRef, CurrentTask};
+
+/// A guard of the locked wait queue, passed to `AxRunQueue::blocked_resched`.
+pub(crate) type WaitQueueGuard<'a> = SpinRawGuard<'a, VecDeque<AxTaskRef>>;
 
 /// A queue to store sleeping tasks.
 ///
@@ -27,7 +32,7 @@ use crate::{AxRunQueue, AxTaskRef, CurrentTask, RUN_QUEUE};
 /// assert_eq!(VALUE.load(Ordering::Relaxed), 1);
 /// ```
 pub struct WaitQueue {
-    queue: SpinRaw<VecDeque<AxTaskRef>>, // we already disabled IRQs when lock the `RUN_QUEUE`
+    queue: SpinRaw<VecDeque<AxTaskRef>>, // always locked with IRQs and preemption disabled
 }
 
 impl WaitQueue {
@@ -50,8 +55,7 @@ impl WaitQueue {
         // the event from another queue.
         if curr.in_wait_queue() {
             // wake up by timer (timeout).
-            // `RUN_QUEUE` is not locked here, so disable IRQs.
-            let _guard = kernel_guard::IrqSave::new();
+            let _guard = NoPreemptIrqSave::new();
             self.queue.lock().retain(|t| !curr.ptr_eq(t));
             curr.set_in_wait_queue(false);
         }
@@ -64,11 +68,13 @@ impl WaitQueue {
 
     /// Blocks the current task and put it into the wait queue, until other task
     /// notifies it.
+    #[cfg_attr(feature = "lockdep", track_caller)]
     pub fn wait(&self) {
-        RUN_QUEUE.lock().block_current(|task| {
-            task.set_in_wait_queue(true);
-            self.queue.lock().push_back(task)
-        });
+        #[cfg(feature = "lockdep")]
+        crate::lockdep::might_sleep();
+        let guard = NoPreemptIrqSave::new();
+        current_run_queue().blocked_resched(self.queue.lock());
+        drop(guard);
         self.cancel_events(crate::current());
     }
 
@@ -77,27 +83,111 @@ impl WaitQueue {
     ///
     /// Note that even other tasks notify this task, it will not wake up until
     /// the condition becomes true.
+    #[cfg_attr(feature = "lockdep", track_caller)]
     pub fn wait_until<F>(&self, condition: F)
     where
         F: Fn() -> bool,
     {
+        #[cfg(feature = "lockdep")]
+        crate::lockdep::might_sleep();
+        let guard = NoPreemptIrqSave::new();
         loop {
-            let mut rq = RUN_QUEUE.lock();
+            // Check the condition with the wait queue locked, so that the
+            // notifier cannot wake up nobody between the check and the block.
+            let wq = self.queue.lock();
             if condition() {
                 break;
             }
-            rq.block_current(|task| {
-                task.set_in_wait_queue(true);
-                self.queue.lock().push_back(task);
-            });
+            current_run_queue().blocked_resched(wq);
         }
+        drop(guard);
         self.cancel_events(crate::current());
     }
 
+    /// Blocks the current task and put it into the wait queue, until the given
+    /// `condition` becomes true, like [`wait_until`](Self::wait_until).
+    ///
+    /// The wait is idle, i.e., the task is waiting for work rather than for a
+    /// resource (e.g., a worker waiting for jobs), so it may last forever and
+    /// is not reported by the hung task watchdog.
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub fn wait_idle_until<F>(&self, condition: F)
+    where
+        F: Fn() -> bool,
+    {
+        #[cfg(feature = "watchdog")]
+        let curr = crate::current();
+        #[cfg(feature = "watchdog")]
+        curr.set_idle_wait(true);
+        self.wait_until(condition);
+        #[cfg(feature = "watchdog")]
+        curr.set_idle_wait(false);
+    }
+
+    /// Blocks the current task and put it into the wait queue, until other task
+    /// notifies it, or the current task is cancelled.
+    ///
+    /// Returns [`AxError::Interrupted`] if the task is cancelled, see
+    /// [`TaskInner::cancel`](crate::TaskInner::cancel).
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub fn wait_interruptible(&self) -> AxResult {
+        #[cfg(feature = "lockdep")]
+        crate::lockdep::might_sleep();
+        let curr = crate::current();
+        curr.set_interruptible(true);
+        let guard = NoPreemptIrqSave::new();
+        current_run_queue().blocked_resched(self.queue.lock());
+        drop(guard);
+        curr.set_interruptible(false);
+        let cancelled = curr.is_cancelled();
+        self.cancel_events(curr);
+        if cancelled {
+            Err(AxError::Interrupted)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Blocks the current task and put it into the wait queue, until the given
+    /// `condition` becomes true, or the current task is cancelled.
+    ///
+    /// Returns [`AxError::Interrupted`] if the task is cancelled before the
+    /// condition becomes true, see
+    /// [`TaskInner::cancel`](crate::TaskInner::cancel).
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub fn wait_until_interruptible<F>(&self, condition: F) -> AxResult
+    where
+        F: Fn() -> bool,
+    {
+        #[cfg(feature = "lockdep")]
+        crate::lockdep::might_sleep();
+        let curr = crate::current();
+        curr.set_interruptible(true);
+        let guard = NoPreemptIrqSave::new();
+        let res = loop {
+            let wq = self.queue.lock();
+            if condition() {
+                break Ok(());
+            }
+            if curr.is_cancelled() {
+                break Err(AxError::Interrupted);
+            }
+            current_run_queue().blocked_resched(wq);
+        };
+        drop(guard);
+        curr.set_interruptible(false);
+        self.cancel_events(curr);
+        res
+    }
+
     /// Blocks the current task and put it into the wait queue, until other tasks
     /// notify it, or the given duration has elapsed.
     #[cfg(feature = "irq")]
+    #[cfg_attr(feature = "lockdep", track_caller)]
     pub fn wait_timeout(&self, dur: core::time::Duration) -> bool {
+        #[cfg(feature = "lockdep")]
+        crate::lockdep::might_sleep();
+        let guard = NoPreemptIrqSave::new();
         let curr = crate::current();
         let deadline = axhal::time::wall_time() + dur;
         debug!(
@@ -107,10 +197,8 @@ impl WaitQueue {
         );
         crate::timers::set_alarm_wakeup(deadline, curr.clone());
 
-        RUN_QUEUE.lock().block_current(|task| {
-            task.set_in_wait_queue(true);
-            self.queue.lock().push_back(task)
-        });
+        current_run_queue().blocked_resched(self.queue.lock());
+        drop(guard);
         let timeout = curr.in_wait_queue(); // still in the wait queue, must have timed out
         self.cancel_events(curr);
         timeout
@@ -122,10 +210,14 @@ impl WaitQueue {
     /// Note that even other tasks notify this task, it will not wake up until
     /// the above conditions are met.
     #[cfg(feature = "irq")]
+    #[cfg_attr(feature = "lockdep", track_caller)]
     pub fn wait_timeout_until<F>(&self, dur: core::time::Duration, condition: F) -> bool
     where
         F: Fn() -> bool,
     {
+        #[cfg(feature = "lockdep")]
+        crate::lockdep::might_sleep();
+        let guard = NoPreemptIrqSave::new();
         let curr = crate::current();
         let deadline = axhal::time::wall_time() + dur;
         debug!(
@@ -137,16 +229,14 @@ impl WaitQueue {
 
         let mut timeout = true;
         while axhal::time::wall_time() < deadline {
-            let mut rq = RUN_QUEUE.lock();
+            let wq = self.queue.lock();
             if condition() {
                 timeout = false;
                 break;
             }
-            rq.block_current(|task| {
-                task.set_in_wait_queue(true);
-                self.queue.lock().push_back(task);
-            });
+            current_run_queue().blocked_resched(wq);
         }
+        drop(guard);
         self.cancel_events(curr);
         timeout
     }
@@ -156,28 +246,37 @@ impl WaitQueue {
     /// If `resched` is true, the current task will be preempted when the
     /// preemption is enabled.
     pub fn notify_one(&self, resched: bool) -> bool {
-        let mut rq = RUN_QUEUE.lock();
-        if !self.queue.lock().is_empty() {
-            self.notify_one_locked(resched, &mut rq)
+        let _guard = NoPreemptIrqSave::new();
+        let task = self.queue.lock().pop_front();
+        if let Some(task) = task {
+            unblock_one_task(task, resched);
+            true
         } else {
             false
         }
     }
 
+    /// Returns the highest priority (i.e., the smallest value) of the tasks
+    /// in the wait queue, or [`None`] if it's empty.
+    pub fn top_priority(&self) -> Option<isize> {
+        let _guard = NoPreemptIrqSave::new();
+        self.queue.lock().iter().map(|task| task.priority()).min()
+    }
+
     /// Wakes all tasks in the wait queue.
     ///
     /// If `resched` is true, the current task will be preempted when the
     /// preemption is enabled.
     pub fn notify_all(&self, resched: bool) {
+        let _guard = NoPreemptIrqSave::new();
         loop {
-            let mut rq = RUN_QUEUE.lock();
-            if let Some(task) = self.queue.lock().pop_front() {
-                task.set_in_wait_queue(false);
-                rq.unblock_task(task, resched);
+            // we must unlock `self.queue` before unblocking the task.
+            let task = self.queue.lock().pop_front();
+            if let Some(task) = task {
+                unblock_one_task(task, resched);
             } else {
                 break;
             }
-            drop(rq); // we must unlock `RUN_QUEUE` after unlocking `self.queue`.
         }
     }
 
@@ -186,31 +285,24 @@ impl WaitQueue {
     /// If `resched` is true, the current task will be preempted when the
     /// preemption is enabled.
     pub fn notify_task(&mut self, resched: bool, task: &AxTaskRef) -> bool {
-        let mut rq = RUN_QUEUE.lock();
+        let _guard = NoPreemptIrqSave::new();
         let mut wq = self.queue.lock();
         if let Some(index) = wq.iter().position(|t| Arc::ptr_eq(t, task)) {
-            task.set_in_wait_queue(false);
-            rq.unblock_task(wq.remove(index).unwrap(), resched);
-            true
-        } else {
-            false
-        }
-    }
-
-    pub(crate) fn notify_one_locked(&self, resched: bool, rq: &mut AxRunQueue) -> bool {
-        if let Some(task) = self.queue.lock().pop_front() {
-            task.set_in_wait_queue(false);
-            rq.unblock_task(task, resched);
+            let task = wq.remove(index).unwrap();
+            drop(wq);
+            unblock_one_task(task, resched);
             true
         } else {
             false
         }
     }
+}
 
-    pub(crate) fn notify_all_locked(&self, resched: bool, rq: &mut AxRunQueue) {
-        while let Some(task) = self.queue.lock().pop_front() {
-            task.set_in_wait_queue(false);
-            rq.unblock_task(task, resched);
-        }
-    }
+/// Wakes up a task that was just removed from a wait queue, and puts it into
+/// the run queue of the CPU it last ran on.
+///
+/// IRQs and preemption must be disabled by the caller.
+fn unblock_one_task(task: AxTaskRef, resched: bool) {
+    task.set_in_wait_queue(false);
+    select_run_queue(&task).unblock_task(task, resched);
 }
@@ -0,0 +1,192 @@
+//! Soft-lockup and hung-task detection.
+//!
+//! The watchdog reports two kinds of problems through the log:
+//!
+//! - Soft lockup: a CPU has not rescheduled for longer than the soft lockup
+//!   threshold while other tasks are ready to run on it, e.g., a task is
+//!   spinning with preemption disabled, or never yields under a cooperative
+//!   scheduler.
+//! - Hung task: a task has been blocked in a [`WaitQueue`](crate::WaitQueue)
+//!   for longer than the hung task threshold. Interruptible waits (e.g.,
+//!   [`WaitQueue::wait_interruptible`](crate::WaitQueue::wait_interruptible)),
+//!   idle waits (e.g., [`WaitQueue::wait_idle_until`](crate::WaitQueue::wait_idle_until))
+//!   and sleeps are not checked, neither are the tasks exempted by
+//!   [`set_hung_check`].
+//!
+//! Soft lockups are checked on each timer tick. Walking all tasks is too slow
+//! for the interrupt context, so the timer tick only wakes up the `watchdog`
+//! task once per second, which checks the blocked tasks.
+//!
+//! Each problem is reported once, until the CPU reschedules or the task is
+//! woken up. The report includes the ID, name and state of the task, but not
+//! its backtrace, as there is no unwinder in the kernel yet.
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::time::Duration;
+
+use crate::{AxTaskRef, TaskInner, TaskState, WaitQueue};
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+/// How often blocked tasks are checked, in nanoseconds.
+const HUNG_CHECK_INTERVAL_NS: u64 = NANOS_PER_SEC;
+
+/// The default soft lockup threshold, in seconds.
+pub const DEFAULT_SOFT_LOCKUP_SECS: u64 = 20;
+/// The default hung task threshold, in seconds.
+pub const DEFAULT_HUNG_TASK_SECS: u64 = 120;
+
+static SOFT_LOCKUP_THRESHOLD_NS: AtomicU64 =
+    AtomicU64::new(DEFAULT_SOFT_LOCKUP_SECS * NANOS_PER_SEC);
+static HUNG_TASK_THRESHOLD_NS: AtomicU64 = AtomicU64::new(DEFAULT_HUNG_TASK_SECS * NANOS_PER_SEC);
+
+static NEXT_HUNG_CHECK_NS: AtomicU64 = AtomicU64::new(0);
+static HUNG_CHECK_PENDING: AtomicBool = AtomicBool::new(false);
+static WATCHDOG_WQ: WaitQueue = WaitQueue::new();
+
+struct CpuWatchdog {
+    /// When the CPU last rescheduled, in nanoseconds.
+    last_resched_ns: AtomicU64,
+    /// Whether the current lockup has been reported.
+    reported: AtomicBool,
+}
+
+impl CpuWatchdog {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = Self {
+        last_resched_ns: AtomicU64::new(0),
+        reported: AtomicBool::new(false),
+    };
+}
+
+static CPUS: [CpuWatchdog; axconfig::SMP] = [CpuWatchdog::INIT; axconfig::SMP];
+
+/// Sets how long a CPU can go without rescheduling before it's reported,
+/// zero disables the check.
+pub fn set_soft_lockup_threshold(threshold: Duration) {
+    SOFT_LOCKUP_THRESHOLD_NS.store(threshold.as_nanos() as u64, Ordering::Relaxed);
+}
+
+/// Sets how long a task can be blocked in a wait queue before it's reported,
+/// zero disables the check.
+pub fn set_hung_task_threshold(threshold: Duration) {
+    HUNG_TASK_THRESHOLD_NS.store(threshold.as_nanos() as u64, Ordering::Relaxed);
+}
+
+/// Enables or disables the hung task check for the given task.
+///
+/// It's useful for daemon tasks that wait for requests for a long time.
+pub fn set_hung_check(task: &AxTaskRef, enabled: bool) {
+    task.set_hung_check(enabled);
+}
+
+/// Spawns the `watchdog` task, which checks blocked tasks when requested by
+/// the timer tick.
+pub(crate) fn init() {
+    let task = TaskInner::new(watchdog_entry, "watchdog".into(), axconfig::TASK_STACK_SIZE);
+    crate::spawn_task(task);
+}
+
+fn watchdog_entry() {
+    loop {
+        WATCHDOG_WQ.wait_idle_until(|| HUNG_CHECK_PENDING.swap(false, Ordering::Acquire));
+        check_hung_tasks(axhal::time::monotonic_time_nanos());
+    }
+}
+
+/// Records that the current CPU reschedules.
+pub(crate) fn touch(cpu_id: usize, now: u64) {
+    let cpu = &CPUS[cpu_id];
+    cpu.last_resched_ns.store(now, Ordering::Relaxed);
+    cpu.reported.store(false, Ordering::Relaxed);
+}
+
+/// Runs the checks on a timer tick, `nr_ready` is the number of ready tasks
+/// in the run queue of the current CPU.
+pub(crate) fn on_timer_tick(cpu_id: usize, nr_ready: usize) {
+    let now = axhal::time::monotonic_time_nanos();
+    check_soft_lockup(cpu_id, nr_ready, now);
+
+    // only one CPU checks blocked tasks in each interval.
+    let next = NEXT_HUNG_CHECK_NS.load(Ordering::Relaxed);
+    if now >= next
+        && NEXT_HUNG_CHECK_NS
+            .compare_exchange(
+                next,
+                now + HUNG_CHECK_INTERVAL_NS,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+    {
+        HUNG_CHECK_PENDING.store(true, Ordering::Release);
+        WATCHDOG_WQ.notify_one(false);
+    }
+}
+
+/// Checks whether the given CPU is locked up, returns `true` if it's newly
+/// reported.
+pub(crate) fn check_soft_lockup(cpu_id: usize, nr_ready: usize, now: u64) -> bool {
+    let threshold = SOFT_LOCKUP_THRESHOLD_NS.load(Ordering::Relaxed);
+    let cpu = &CPUS[cpu_id];
+    let last = cpu.last_resched_ns.load(Ordering::Relaxed);
+    if threshold == 0 || nr_ready == 0 || now.saturating_sub(last) < threshold {
+        return false;
+    }
+    if cpu.reported.swap(true, Ordering::Relaxed) {
+        return false;
+    }
+    let curr = crate::current();
+    error!(
+        "watchdog: soft lockup on CPU {} for {:?}, {} ready tasks are waiting",
+        cpu_id,
+        Duration::from_nanos(now - last),
+        nr_ready
+    );
+    report_task(curr.as_task_ref());
+    true
+}
+
+/// Checks the blocked tasks, returns the newly reported ones.
+pub(crate) fn check_hung_tasks(now: u64) -> Vec<AxTaskRef> {
+    let threshold = HUNG_TASK_THRESHOLD_NS.load(Ordering::Relaxed);
+    if threshold == 0 {
+        return Vec::new();
+    }
+    let hung: Vec<AxTaskRef> = crate::registry::filter_tasks(|task| {
+        task.state() == TaskState::Blocked
+            && task.in_wait_queue()
+            && !task.is_interruptible()
+            && !task.in_idle_wait()
+            && task.hung_check_enabled()
+            && now.saturating_sub(task.blocked_since()) >= threshold
+    });
+    let mut reported = Vec::new();
+    for task in hung {
+        if task.set_hung_reported() {
+            continue;
+        }
+        error!(
+            "watchdog: task blocked for more than {:?}",
+            Duration::from_nanos(now.saturating_sub(task.blocked_since()))
+        );
+        report_task(&task);
+        reported.push(task);
+    }
+    reported
+}
+
+fn report_task(task: &AxTaskRef) {
+    error!(
+        "  {} on CPU {}, state: {:?}",
+        task.id_name(),
+        task.cpu_id(),
+        task.state()
+    );
+    let usage = task.cpu_usage();
+    error!(
+        "  utime: {:?}, stime: {:?}, nvcsw: {}, nivcsw: {}",
+        usage.utime, usage.stime, usage.voluntary_switches, usage.involuntary_switches
+    );
+}
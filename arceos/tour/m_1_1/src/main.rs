@@ -22,6 +22,7 @@ use axmm::AddrSpace;
 use loader::load_user_app;
 
 const USER_STACK_SIZE: usize = 0x10000;
+const USER_STACK_MAX_SIZE: usize = 0x80_0000; // 8 MiB, the default RLIMIT_STACK
 const KERNEL_STACK_SIZE: usize = 0x40000; // 256 KiB
 const APP_ENTRY: usize = 0x1000;
 
@@ -57,9 +58,10 @@ fn init_user_stack(uspace: &mut AddrSpace, populating: bool) -> io::Result<VirtA
         "Mapping user stack: {:#x?} -> {:#x?}",
         ustack_vaddr, ustack_top
     );
-    uspace.map_alloc(
-        ustack_vaddr,
+    uspace.map_stack(
+        ustack_top,
         crate::USER_STACK_SIZE,
+        crate::USER_STACK_MAX_SIZE,
         MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER,
         populating,
     ).unwrap();
@@ -24,6 +24,7 @@ use axtask::TaskExtRef;
 use axhal::trap::{register_trap_handler, PAGE_FAULT};
 
 const USER_STACK_SIZE: usize = 0x10000;
+const USER_STACK_MAX_SIZE: usize = 0x80_0000; // 8 MiB, the default RLIMIT_STACK
 const KERNEL_STACK_SIZE: usize = 0x40000; // 256 KiB
 const APP_ENTRY: usize = 0x1000;
 
@@ -59,9 +60,10 @@ fn init_user_stack(uspace: &mut AddrSpace, populating: bool) -> io::Result<VirtA
         "Mapping user stack: {:#x?} -> {:#x?}",
         ustack_vaddr, ustack_top
     );
-    uspace.map_alloc(
-        ustack_vaddr,
+    uspace.map_stack(
+        ustack_top,
         crate::USER_STACK_SIZE,
+        crate::USER_STACK_MAX_SIZE,
         MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER,
         populating,
     ).unwrap();
@@ -6,32 +6,6 @@
 #include <stdio.h>
 #include <unistd.h>
 
-int pthread_setcancelstate(int new, int *old)
-{
-    unimplemented();
-    return 0;
-}
-
-int pthread_setcanceltype(int new, int *old)
-{
-    unimplemented();
-    return 0;
-}
-
-// TODO
-void pthread_testcancel(void)
-{
-    unimplemented();
-    return;
-}
-
-// TODO
-int pthread_cancel(pthread_t t)
-{
-    unimplemented();
-    return 0;
-}
-
 // TODO
 int pthread_mutex_trylock(pthread_mutex_t *m)
 {
@@ -46,38 +20,6 @@ int pthread_setname_np(pthread_t thread, const char *name)
     return 0;
 }
 
-int pthread_cond_init(pthread_cond_t *restrict c, const pthread_condattr_t *restrict a)
-{
-    *c = (pthread_cond_t){0};
-    if (a) {
-        c->_c_clock = a->__attr & 0x7fffffff;
-        if (a->__attr >> 31)
-            c->_c_shared = (void *)-1;
-    }
-    return 0;
-}
-
-// TODO
-int pthread_cond_signal(pthread_cond_t *__cond)
-{
-    unimplemented();
-    return 0;
-}
-
-// TODO
-int pthread_cond_wait(pthread_cond_t *__restrict__ __cond, pthread_mutex_t *__restrict__ __mutex)
-{
-    unimplemented();
-    return 0;
-}
-
-// TODO
-int pthread_cond_broadcast(pthread_cond_t *c)
-{
-    unimplemented();
-    return 0;
-}
-
 #define DEFAULT_STACK_SIZE 131072
 #define DEFAULT_GUARD_SIZE 8192
 
@@ -103,6 +103,8 @@ int fchown(int fd, uid_t owner, gid_t group)
     return 0;
 }
 
+#ifndef AX_CONFIG_FS
+
 // TODO:
 int ftruncate(int fd, off_t length)
 {
@@ -110,6 +112,8 @@ int ftruncate(int fd, off_t length)
     return 0;
 }
 
+#endif // AX_CONFIG_FS
+
 // TODO
 int chdir(const char *__path)
 {
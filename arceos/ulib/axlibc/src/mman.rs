@@ -0,0 +1,42 @@
+use core::ffi::{c_int, c_void};
+
+use arceos_posix_api::{sys_mmap, sys_msync, sys_munmap};
+
+use crate::{ctypes, utils::e};
+
+/// Map a file or anonymous memory into the address space.
+///
+/// Return the start address of the mapping, or `MAP_FAILED` on error.
+#[no_mangle]
+pub unsafe extern "C" fn mmap(
+    addr: *mut c_void,
+    len: ctypes::size_t,
+    prot: c_int,
+    flags: c_int,
+    fd: c_int,
+    off: ctypes::off_t,
+) -> *mut c_void {
+    let ret = sys_mmap(addr, len, prot, flags, fd, off);
+    if ret < 0 {
+        crate::errno::set_errno(-ret as _);
+        usize::MAX as *mut c_void // MAP_FAILED
+    } else {
+        ret as _
+    }
+}
+
+/// Remove the mappings in the specified address range.
+///
+/// Return 0 if succeed
+#[no_mangle]
+pub unsafe extern "C" fn munmap(addr: *mut c_void, len: ctypes::size_t) -> c_int {
+    e(sys_munmap(addr, len))
+}
+
+/// Write the changes of the shared file mappings back to the files.
+///
+/// Return 0 if succeed
+#[no_mangle]
+pub unsafe extern "C" fn msync(addr: *mut c_void, len: ctypes::size_t, flags: c_int) -> c_int {
+    e(sys_msync(addr, len, flags))
+}
@@ -0,0 +1,35 @@
+use crate::{ctypes, utils::e};
+use arceos_posix_api::{sys_sched_getaffinity, sys_sched_setaffinity, sys_sched_setattr};
+use core::ffi::{c_int, c_uint};
+
+/// Set the CPU affinity mask of the thread `pid` (0 means the calling thread).
+#[no_mangle]
+pub unsafe extern "C" fn sched_setaffinity(
+    pid: ctypes::pid_t,
+    cpusetsize: usize,
+    mask: *const ctypes::cpu_set_t,
+) -> c_int {
+    e(sys_sched_setaffinity(pid, cpusetsize, mask))
+}
+
+/// Get the CPU affinity mask of the thread `pid` (0 means the calling thread).
+#[no_mangle]
+pub unsafe extern "C" fn sched_getaffinity(
+    pid: ctypes::pid_t,
+    cpusetsize: usize,
+    mask: *mut ctypes::cpu_set_t,
+) -> c_int {
+    // glibc returns 0 on success, rather than the size of the mask.
+    e(sys_sched_getaffinity(pid, cpusetsize, mask).min(0))
+}
+
+/// Set the scheduling policy and attributes of the thread `pid` (0 means the
+/// calling thread).
+#[no_mangle]
+pub unsafe extern "C" fn sched_setattr(
+    pid: ctypes::pid_t,
+    attr: *mut ctypes::sched_attr,
+    flags: c_uint,
+) -> c_int {
+    e(sys_sched_setattr(pid, attr, flags))
+}
@@ -0,0 +1,65 @@
+use core::ffi::{c_char, c_int, c_void};
+
+use arceos_posix_api::{
+    sys_shm_open, sys_shm_unlink, sys_shmat, sys_shmctl, sys_shmdt, sys_shmget,
+};
+
+use crate::{ctypes, utils::e};
+
+/// Get or create a System V shared memory segment associated with `key`.
+///
+/// Return the segment identifier.
+#[no_mangle]
+pub unsafe extern "C" fn shmget(key: ctypes::key_t, size: ctypes::size_t, shmflg: c_int) -> c_int {
+    e(sys_shmget(key, size, shmflg))
+}
+
+/// Attach the shared memory segment `shmid` to the address space.
+///
+/// Return the address of the attached segment, or `(void *)-1` on error.
+#[no_mangle]
+pub unsafe extern "C" fn shmat(shmid: c_int, shmaddr: *const c_void, shmflg: c_int) -> *mut c_void {
+    let ret = sys_shmat(shmid, shmaddr, shmflg);
+    if ret < 0 {
+        crate::errno::set_errno(-ret as _);
+        usize::MAX as *mut c_void
+    } else {
+        ret as _
+    }
+}
+
+/// Detach the shared memory segment attached at `shmaddr`.
+///
+/// Return 0 if succeed
+#[no_mangle]
+pub unsafe extern "C" fn shmdt(shmaddr: *const c_void) -> c_int {
+    e(sys_shmdt(shmaddr))
+}
+
+/// Control the shared memory segment `shmid`.
+///
+/// Return 0 if succeed
+#[no_mangle]
+pub unsafe extern "C" fn shmctl(shmid: c_int, cmd: c_int, buf: *mut ctypes::shmid_ds) -> c_int {
+    e(sys_shmctl(shmid, cmd, buf))
+}
+
+/// Open or create the POSIX shared memory object `name`.
+///
+/// Return the file descriptor.
+#[no_mangle]
+pub unsafe extern "C" fn shm_open(
+    name: *const c_char,
+    oflag: c_int,
+    mode: ctypes::mode_t,
+) -> c_int {
+    e(sys_shm_open(name, oflag, mode))
+}
+
+/// Remove the POSIX shared memory object `name`.
+///
+/// Return 0 if succeed
+#[no_mangle]
+pub unsafe extern "C" fn shm_unlink(name: *const c_char) -> c_int {
+    e(sys_shm_unlink(name))
+}
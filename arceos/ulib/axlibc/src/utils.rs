@@ -8,3 +8,13 @@ pub fn e(ret: c_int) -> c_int {
         ret as _
     }
 }
+
+/// Converts the result for functions that return the error number instead of
+/// setting `errno`, e.g., `pthread_cond_timedwait`.
+pub fn en(ret: c_int) -> c_int {
+    if ret < 0 {
+        -ret
+    } else {
+        ret
+    }
+}
@@ -15,6 +15,7 @@
 //!     - `fp_simd`: Enable floating point and SIMD support.
 //! - Interrupts:
 //!     - `irq`: Enable interrupt handling support.
+//!     - `tickless`: Stop the periodic timer tick on idle CPUs.
 //! - Memory
 //!     - `alloc`: Enable dynamic memory allocation.
 //!     - `alloc-tlsf`: Use the TLSF allocator.
@@ -22,14 +23,27 @@
 //!     - `alloc-buddy`: Use the buddy system allocator.
 //!     - `paging`: Enable page table manipulation.
 //!     - `tls`: Enable thread-local storage.
+//!     - `stack_guard`: Put a guard page below each kernel stack to report
+//!       stack overflows (x86_64 only).
 //! - Task management
 //!     - `multitask`: Enable multi-threading support.
 //!     - `sched_fifo`: Use the FIFO cooperative scheduler.
 //!     - `sched_rr`: Use the Round-robin preemptive scheduler.
 //!     - `sched_cfs`: Use the Completely Fair Scheduler (CFS) preemptive scheduler.
+//!     - `sched_edf`: Use the earliest-deadline-first (EDF) real-time scheduler.
+//!     - `lockdep`: Validate the lock acquisition order at runtime and report
+//!       possible deadlocks.
+//!     - `sched_trace`: Record scheduler events, which can be exported in the
+//!       Chrome trace event format (also at `/proc/sched_trace` if `fs` is enabled).
+//!     - `watchdog`: Report soft lockups (CPUs that don't reschedule) and hung
+//!       tasks (tasks blocked for too long).
+//!     - `async`: Enable the asynchronous runtime, which is available at
+//!       `os::arceos::modules::axasync`.
 //! - Upperlayer stacks
 //!     - `fs`: Enable file system support.
 //!     - `myfs`: Allow users to define their custom filesystems to override the default.
+//!     - `swap`: Enable swapping of lazily allocated pages to the swap area
+//!       given by `SWAP` when building (a block device or a swap file).
 //!     - `net`: Enable networking support.
 //!     - `dns`: Enable DNS lookup support.
 //!     - `display`: Enable graphics support.
@@ -75,3 +89,6 @@ pub mod time;
 pub mod fs;
 #[cfg(feature = "net")]
 pub mod net;
+
+#[cfg(test)]
+mod tests;
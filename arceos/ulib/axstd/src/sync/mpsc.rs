@@ -0,0 +1,563 @@
+//! Multi-producer, single-consumer FIFO queue communication primitives,
+//! similar to [`std::sync::mpsc`](https://doc.rust-lang.org/std/sync/mpsc/index.html).
+//!
+//! A channel is created by [`channel`] (unbounded) or [`sync_channel`]
+//! (bounded), which returns a sender and a [`Receiver`]. The senders can be
+//! cloned to send to the same channel multiple times, but only one receiver is
+//! supported.
+//!
+//! # Disconnection
+//!
+//! The send and receive operations on channels will all return a [`Result`]
+//! indicating whether the operation succeeded or not. An unsuccessful operation
+//! is normally indicative of the other half of a channel having "hung up" by
+//! being dropped in its corresponding thread.
+//!
+//! Once half of a channel has been deallocated, most operations can no longer
+//! continue to make progress, so [`Err`] will be returned. Messages that are
+//! already in the channel can still be received after all senders are
+//! dropped.
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::fmt;
+use core::time::Duration;
+
+use arceos_api::modules::axsync::spin::SpinNoIrq;
+use arceos_api::task::{self as api, AxWaitQueueHandle};
+
+use crate::time::Instant;
+
+struct State<T> {
+    queue: VecDeque<T>,
+    senders: usize,
+    receiver_alive: bool,
+    /// Whether the receiver is blocked waiting for a message.
+    receiver_waiting: bool,
+    /// Total number of messages that have been sent.
+    sent: usize,
+    /// Total number of messages that have been received.
+    received: usize,
+}
+
+struct Shared<T> {
+    state: SpinNoIrq<State<T>>,
+    /// The receiver waiting for messages.
+    recv_wq: AxWaitQueueHandle,
+    /// Senders waiting for free space, or for their messages to be received on
+    /// a zero-capacity channel.
+    send_wq: AxWaitQueueHandle,
+    /// `None` for unbounded channels.
+    bound: Option<usize>,
+}
+
+impl<T> Shared<T> {
+    fn new(bound: Option<usize>) -> Arc<Self> {
+        Arc::new(Self {
+            state: SpinNoIrq::new(State {
+                queue: VecDeque::new(),
+                senders: 1,
+                receiver_alive: true,
+                receiver_waiting: false,
+                sent: 0,
+                received: 0,
+            }),
+            recv_wq: AxWaitQueueHandle::new(),
+            send_wq: AxWaitQueueHandle::new(),
+            bound,
+        })
+    }
+
+    /// The number of messages that can be buffered, a zero-capacity channel
+    /// holds one message until it's received.
+    fn capacity(&self) -> usize {
+        self.bound.map_or(usize::MAX, |b| b.max(1))
+    }
+
+    /// Pushes the message without blocking, returns the sequence number of
+    /// the message.
+    fn try_push(&self, t: T) -> Result<usize, TrySendError<T>> {
+        let mut state = self.state.lock();
+        if !state.receiver_alive {
+            return Err(TrySendError::Disconnected(t));
+        }
+        if state.queue.len() >= self.capacity() {
+            return Err(TrySendError::Full(t));
+        }
+        state.queue.push_back(t);
+        state.sent += 1;
+        let seq = state.sent;
+        drop(state);
+        api::ax_wait_queue_wake(&self.recv_wq, 1);
+        Ok(seq)
+    }
+
+    fn send(&self, mut t: T) -> Result<(), SendError<T>> {
+        let seq = loop {
+            match self.try_push(t) {
+                Ok(seq) => break seq,
+                Err(TrySendError::Disconnected(t)) => return Err(SendError(t)),
+                Err(TrySendError::Full(v)) => t = v,
+            }
+            api::ax_wait_queue_wait(
+                &self.send_wq,
+                || {
+                    let state = self.state.lock();
+                    !state.receiver_alive || state.queue.len() < self.capacity()
+                },
+                None,
+            );
+        };
+        if self.bound == Some(0) {
+            // rendezvous: wait for the message to be received.
+            api::ax_wait_queue_wait(
+                &self.send_wq,
+                || {
+                    let state = self.state.lock();
+                    !state.receiver_alive || state.received >= seq
+                },
+                None,
+            );
+            let mut state = self.state.lock();
+            if state.received < seq {
+                // the receiver has hung up, take the message back. It must be
+                // the only one in the queue.
+                return Err(SendError(state.queue.pop_back().unwrap()));
+            }
+        }
+        Ok(())
+    }
+
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut state = self.state.lock();
+        match state.queue.pop_front() {
+            Some(t) => {
+                state.received += 1;
+                drop(state);
+                if self.bound.is_some() {
+                    api::ax_wait_queue_wake(&self.send_wq, u32::MAX);
+                }
+                Ok(t)
+            }
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    fn recv_timeout(&self, dur: Option<Duration>) -> Result<T, RecvTimeoutError> {
+        let start = Instant::now();
+        loop {
+            match self.try_recv() {
+                Ok(t) => return Ok(t),
+                Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
+                Err(TryRecvError::Empty) => {}
+            }
+            let timeout = match dur {
+                Some(dur) => {
+                    let elapsed = start.elapsed();
+                    if elapsed >= dur {
+                        return Err(RecvTimeoutError::Timeout);
+                    }
+                    Some(dur - elapsed)
+                }
+                None => None,
+            };
+            self.state.lock().receiver_waiting = true;
+            api::ax_wait_queue_wait(
+                &self.recv_wq,
+                || {
+                    let state = self.state.lock();
+                    !state.queue.is_empty() || state.senders == 0
+                },
+                timeout,
+            );
+            self.state.lock().receiver_waiting = false;
+        }
+    }
+}
+
+/// Creates a new asynchronous channel, returning the sender/receiver halves.
+///
+/// All data sent on the [`Sender`] will become available on the [`Receiver`]
+/// in the same order as it was sent, and no [`send`](Sender::send) will block
+/// the calling thread (this channel has an "infinite buffer").
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let shared = Shared::new(None);
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+/// Creates a new synchronous, bounded channel.
+///
+/// All data sent on the [`SyncSender`] will become available on the
+/// [`Receiver`] in the same order as it was sent. The channel has an internal
+/// buffer on which messages will be queued, and [`send`](SyncSender::send)
+/// will block if the buffer is full.
+///
+/// `bound` specifies the buffer size. When it's 0, the channel becomes a
+/// rendezvous channel, where each send will not return until the message is
+/// received.
+pub fn sync_channel<T>(bound: usize) -> (SyncSender<T>, Receiver<T>) {
+    let shared = Shared::new(Some(bound));
+    (
+        SyncSender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+/// The sending-half of the unbounded channel created by [`channel`].
+///
+/// Messages can be sent through this channel with [`send`](Self::send). It
+/// can be cloned to send to the same channel from multiple threads.
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// The sending-half of the bounded channel created by [`sync_channel`].
+///
+/// Messages can be sent through this channel with [`send`](Self::send) or
+/// [`try_send`](Self::try_send). It can be cloned to send to the same
+/// channel from multiple threads.
+pub struct SyncSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// The receiving half of the channel created by [`channel`] or
+/// [`sync_channel`].
+///
+/// Messages sent to the channel can be retrieved using [`recv`](Self::recv).
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Attempts to send a value on this channel, returning it back if it could
+    /// not be sent.
+    ///
+    /// A successful send occurs when it is determined that the other end of
+    /// the channel has not hung up already. This method never blocks.
+    pub fn send(&self, t: T) -> Result<(), SendError<T>> {
+        self.shared.send(t)
+    }
+}
+
+impl<T> SyncSender<T> {
+    /// Sends a value on this synchronous channel.
+    ///
+    /// This function will block until space in the internal buffer becomes
+    /// available, or a receiver is available to hand off the message to.
+    ///
+    /// An error is returned if the receiver has hung up.
+    pub fn send(&self, t: T) -> Result<(), SendError<T>> {
+        self.shared.send(t)
+    }
+
+    /// Attempts to send a value on this channel without blocking.
+    ///
+    /// Returns [`TrySendError::Full`] if the buffer is full, or the channel
+    /// has zero capacity and the receiver is not waiting for a message.
+    pub fn try_send(&self, t: T) -> Result<(), TrySendError<T>> {
+        if self.shared.bound == Some(0) {
+            let state = self.shared.state.lock();
+            if state.receiver_alive && !state.receiver_waiting {
+                return Err(TrySendError::Full(t));
+            }
+        }
+        self.shared.try_push(t).map(|_| ())
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Attempts to return a pending value on this receiver without blocking.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.shared.try_recv()
+    }
+
+    /// Attempts to wait for a value on this receiver, returning an error if
+    /// the corresponding channel has hung up.
+    ///
+    /// This function will always block the current thread if there is no data
+    /// available and it's possible for more data to be sent.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        self.shared.recv_timeout(None).map_err(|_| RecvError)
+    }
+
+    /// Attempts to wait for a value on this receiver, returning an error if
+    /// the corresponding channel has hung up, or if it waits more than
+    /// `timeout`.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        self.shared.recv_timeout(Some(timeout))
+    }
+
+    /// Returns an iterator that will block waiting for messages, but never
+    /// [`panic!`]. It will return [`None`] when the channel has hung up.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { rx: self }
+    }
+
+    /// Returns an iterator that will attempt to yield all pending values for
+    /// a receiver, never blocking.
+    pub fn try_iter(&self) -> TryIter<'_, T> {
+        TryIter { rx: self }
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.state.lock().senders += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Clone for SyncSender<T> {
+    fn clone(&self) -> Self {
+        self.shared.state.lock().senders += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+fn drop_sender<T>(shared: &Shared<T>) {
+    let mut state = shared.state.lock();
+    state.senders -= 1;
+    if state.senders == 0 {
+        drop(state);
+        api::ax_wait_queue_wake(&shared.recv_wq, u32::MAX);
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        drop_sender(&self.shared);
+    }
+}
+
+impl<T> Drop for SyncSender<T> {
+    fn drop(&mut self) {
+        drop_sender(&self.shared);
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.state.lock().receiver_alive = false;
+        api::ax_wait_queue_wake(&self.shared.send_wq, u32::MAX);
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Debug for SyncSender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SyncSender").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver").finish_non_exhaustive()
+    }
+}
+
+/// An iterator over messages on a [`Receiver`], created by
+/// [`iter`](Receiver::iter).
+#[derive(Debug)]
+pub struct Iter<'a, T: 'a> {
+    rx: &'a Receiver<T>,
+}
+
+/// An iterator that attempts to yield all pending values for a [`Receiver`],
+/// created by [`try_iter`](Receiver::try_iter).
+#[derive(Debug)]
+pub struct TryIter<'a, T: 'a> {
+    rx: &'a Receiver<T>,
+}
+
+/// An owning iterator over messages on a [`Receiver`], created by
+/// [`into_iter`](Receiver::into_iter).
+#[derive(Debug)]
+pub struct IntoIter<T> {
+    rx: Receiver<T>,
+}
+
+impl<T> Iterator for Iter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.rx.recv().ok()
+    }
+}
+
+impl<T> Iterator for TryIter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.rx.try_recv().ok()
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.rx.recv().ok()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Receiver<T> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T> IntoIterator for Receiver<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { rx: self }
+    }
+}
+
+/// An error returned from the [`Sender::send`] or [`SyncSender::send`]
+/// function on channels.
+///
+/// It is returned when the receiver has hung up, and contains the message
+/// that could not be sent.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct SendError<T>(pub T);
+
+/// An error returned from the [`recv`](Receiver::recv) function on a
+/// [`Receiver`], when all senders have hung up.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct RecvError;
+
+/// This enumeration is the list of the possible reasons that
+/// [`try_recv`](Receiver::try_recv) could not return data when called.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum TryRecvError {
+    /// This channel is currently empty, but the senders have not yet
+    /// disconnected, so data may yet become available.
+    Empty,
+    /// The channel's sending half has become disconnected, and there will
+    /// never be any more data received on it.
+    Disconnected,
+}
+
+/// This enumeration is the list of possible errors that made
+/// [`recv_timeout`](Receiver::recv_timeout) unable to return data when
+/// called.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum RecvTimeoutError {
+    /// This channel is currently empty, but the senders have not yet
+    /// disconnected, so data may yet become available.
+    Timeout,
+    /// The channel's sending half has become disconnected, and there will
+    /// never be any more data received on it.
+    Disconnected,
+}
+
+/// This enumeration is the list of the possible error outcomes for the
+/// [`try_send`](SyncSender::try_send) method.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum TrySendError<T> {
+    /// The data could not be sent on the channel because it would require
+    /// the sender to block.
+    Full(T),
+    /// The receiver has hung up, so the data could never be received.
+    Disconnected(T),
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SendError").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "sending on a closed channel".fmt(f)
+    }
+}
+
+impl<T> fmt::Debug for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            TrySendError::Full(..) => "Full(..)".fmt(f),
+            TrySendError::Disconnected(..) => "Disconnected(..)".fmt(f),
+        }
+    }
+}
+
+impl<T> fmt::Display for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            TrySendError::Full(..) => "sending on a full channel".fmt(f),
+            TrySendError::Disconnected(..) => "sending on a closed channel".fmt(f),
+        }
+    }
+}
+
+impl<T> From<SendError<T>> for TrySendError<T> {
+    fn from(err: SendError<T>) -> TrySendError<T> {
+        match err {
+            SendError(t) => TrySendError::Disconnected(t),
+        }
+    }
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "receiving on a closed channel".fmt(f)
+    }
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            TryRecvError::Empty => "receiving on an empty channel".fmt(f),
+            TryRecvError::Disconnected => "receiving on a closed channel".fmt(f),
+        }
+    }
+}
+
+impl From<RecvError> for TryRecvError {
+    fn from(err: RecvError) -> TryRecvError {
+        match err {
+            RecvError => TryRecvError::Disconnected,
+        }
+    }
+}
+
+impl fmt::Display for RecvTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RecvTimeoutError::Timeout => "timed out waiting on channel".fmt(f),
+            RecvTimeoutError::Disconnected => "channel is empty and sending half is closed".fmt(f),
+        }
+    }
+}
+
+impl From<RecvError> for RecvTimeoutError {
+    fn from(err: RecvError) -> RecvTimeoutError {
+        match err {
+            RecvError => RecvTimeoutError::Disconnected,
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+use std::sync::{Mutex as StdMutex, Once as StdOnce};
+
+use arceos_api::modules::axtask;
+
+use crate::sync::mpsc::{
+    channel, sync_channel, RecvError, RecvTimeoutError, SendError, TryRecvError, TrySendError,
+};
+use crate::thread;
+
+static INIT: StdOnce = StdOnce::new();
+static SERIAL: StdMutex<()> = StdMutex::new(());
+
+/// Yields until the flag is set, or gives up after `n` yields.
+fn yield_until(flag: &AtomicBool, n: usize) -> bool {
+    for _ in 0..n {
+        if flag.load(Ordering::Acquire) {
+            return true;
+        }
+        thread::yield_now();
+    }
+    flag.load(Ordering::Acquire)
+}
+
+#[test]
+fn test_mpsc_channel() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    const NUM_SENDERS: usize = 4;
+    const NUM_MESSAGES: usize = 10;
+
+    let (tx, rx) = channel();
+    let handles: Vec<_> = (0..NUM_SENDERS)
+        .map(|i| {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                for j in 0..NUM_MESSAGES {
+                    tx.send((i, j)).unwrap();
+                    thread::yield_now();
+                }
+            })
+        })
+        .collect();
+    drop(tx);
+
+    // messages from the same sender are received in order.
+    let mut next = [0; NUM_SENDERS];
+    for (i, j) in rx.iter() {
+        assert_eq!(next[i], j);
+        next[i] += 1;
+    }
+    assert_eq!(next, [NUM_MESSAGES; NUM_SENDERS]);
+    for h in handles {
+        h.join().unwrap();
+    }
+}
+
+#[test]
+fn test_mpsc_rendezvous() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    static SENT: AtomicBool = AtomicBool::new(false);
+
+    let (tx, rx) = sync_channel(0);
+    // no receiver is waiting for the message.
+    assert_eq!(tx.try_send(1), Err(TrySendError::Full(1)));
+
+    let sender = thread::spawn(move || {
+        tx.send(2).unwrap();
+        SENT.store(true, Ordering::Release);
+        tx
+    });
+    // the send does not return until the message is received.
+    assert!(!yield_until(&SENT, 100));
+    assert_eq!(rx.recv(), Ok(2));
+    assert!(yield_until(&SENT, usize::MAX));
+    let tx = sender.join().unwrap();
+
+    // the receiver hangs up without receiving the message, which is returned
+    // to the waiting sender.
+    let sender = thread::spawn(move || tx.send(3));
+    thread::yield_now();
+    drop(rx);
+    assert_eq!(sender.join().unwrap(), Err(SendError(3)));
+}
+
+#[test]
+fn test_mpsc_disconnect() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    // all senders hang up, buffered messages can still be received.
+    let (tx, rx) = sync_channel(2);
+    let tx2 = tx.clone();
+    tx.send(1).unwrap();
+    tx2.try_send(2).unwrap();
+    assert_eq!(tx2.try_send(3), Err(TrySendError::Full(3)));
+    drop(tx);
+    drop(tx2);
+    assert_eq!(rx.recv(), Ok(1));
+    assert_eq!(rx.try_recv(), Ok(2));
+    assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    assert_eq!(rx.recv(), Err(RecvError));
+
+    // a blocked receiver is woken up when the last sender hangs up.
+    let (tx, rx) = channel::<i32>();
+    let receiver = thread::spawn(move || rx.recv());
+    thread::yield_now();
+    drop(tx);
+    assert_eq!(receiver.join().unwrap(), Err(RecvError));
+
+    // the receiver hangs up, the message is returned.
+    let (tx, rx) = channel();
+    drop(rx);
+    assert_eq!(tx.send(1), Err(SendError(1)));
+
+    // a blocked sender is woken up when the receiver hangs up.
+    let (tx, rx) = sync_channel(1);
+    tx.send(1).unwrap();
+    let sender = thread::spawn(move || tx.send(2));
+    thread::yield_now();
+    drop(rx);
+    assert_eq!(sender.join().unwrap(), Err(SendError(2)));
+}
+
+#[test]
+fn test_mpsc_recv_timeout() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    let (tx, rx) = channel();
+    assert_eq!(
+        rx.recv_timeout(Duration::ZERO),
+        Err(RecvTimeoutError::Timeout)
+    );
+    tx.send(1).unwrap();
+    assert_eq!(rx.recv_timeout(Duration::ZERO), Ok(1));
+
+    // woken up by the sender before the timeout.
+    let sender = thread::spawn(move || {
+        thread::yield_now();
+        tx.send(2).unwrap();
+    });
+    assert_eq!(rx.recv_timeout(Duration::from_secs(60)), Ok(2));
+    sender.join().unwrap();
+    assert_eq!(
+        rx.recv_timeout(Duration::from_secs(60)),
+        Err(RecvTimeoutError::Disconnected)
+    );
+}
@@ -4,11 +4,13 @@ extern crate alloc;
 
 use crate::io;
 use alloc::{string::String, sync::Arc};
-use core::{cell::UnsafeCell, num::NonZeroU64};
+use core::{cell::UnsafeCell, num::NonZeroU64, time::Duration};
 
 use arceos_api::task::{self as api, AxTaskHandle};
 use axerrno::ax_err_type;
 
+pub use arceos_api::task::{AxCpuMask, AxDeadlineParams};
+
 /// A unique identifier for a running thread.
 #[derive(Eq, PartialEq, Clone, Copy, Debug)]
 pub struct ThreadId(NonZeroU64);
@@ -49,6 +51,10 @@ pub struct Builder {
     name: Option<String>,
     // The size of the stack for the spawned thread in bytes
     stack_size: Option<usize>,
+    // The CPUs that the spawned thread is allowed to run on
+    cpu_affinity: Option<AxCpuMask>,
+    // The deadline parameters of the spawned thread
+    deadline: Option<AxDeadlineParams>,
 }
 
 impl Builder {
@@ -58,6 +64,8 @@ impl Builder {
         Builder {
             name: None,
             stack_size: None,
+            cpu_affinity: None,
+            deadline: None,
         }
     }
 
@@ -73,6 +81,26 @@ impl Builder {
         self
     }
 
+    /// Sets the CPUs that the new thread is allowed to run on.
+    ///
+    /// By default, the thread can run on all CPUs.
+    pub fn cpu_affinity(mut self, cpumask: AxCpuMask) -> Builder {
+        self.cpu_affinity = Some(cpumask);
+        self
+    }
+
+    /// Makes the new thread a real-time thread with the given runtime, deadline
+    /// and period, which requires the `sched_edf` feature.
+    ///
+    /// The thread is pinned to the first CPU allowed by
+    /// [`cpu_affinity`](Builder::cpu_affinity) that can reserve the CPU
+    /// bandwidth it needs, and [`spawn`](Builder::spawn) fails if there is
+    /// none.
+    pub fn deadline(mut self, params: AxDeadlineParams) -> Builder {
+        self.deadline = Some(params);
+        self
+    }
+
     /// Spawns a new thread by taking ownership of the `Builder`, and returns an
     /// [`io::Result`] to its [`JoinHandle`].
     ///
@@ -115,7 +143,14 @@ impl Builder {
             drop(their_packet);
         };
 
-        let task = api::ax_spawn(main, name, stack_size);
+        let task = match (self.cpu_affinity, self.deadline) {
+            (cpumask, Some(params)) => {
+                let cpumask = cpumask.unwrap_or_else(AxCpuMask::full);
+                api::ax_spawn_deadline(main, name, stack_size, cpumask, params)?
+            }
+            (Some(cpumask), None) => api::ax_spawn_on(main, name, stack_size, cpumask)?,
+            (None, None) => api::ax_spawn(main, name, stack_size),
+        };
         Ok(JoinHandle {
             thread: Thread::from_id(task.id()),
             native: task,
@@ -179,7 +214,46 @@ impl<T> JoinHandle<T> {
     /// already finished.
     pub fn join(mut self) -> io::Result<T> {
         api::ax_wait_for_exit(self.native).ok_or_else(|| ax_err_type!(BadState))?;
-        Arc::get_mut(&mut self.packet)
+        Self::take_result(&mut self.packet)
+    }
+
+    /// Waits for the associated thread to finish for at most `dur`.
+    ///
+    /// Returns the handle back in `Err` if the thread is still running after
+    /// `dur` has elapsed, so that the caller can wait again or [`detach`]
+    /// it.
+    ///
+    /// [`detach`]: JoinHandle::detach
+    pub fn join_timeout(mut self, dur: Duration) -> Result<io::Result<T>, Self> {
+        match api::ax_wait_for_exit_timeout(&self.native, dur) {
+            Some(_) => Ok(Self::take_result(&mut self.packet)),
+            None => Err(self),
+        }
+    }
+
+    /// Returns the result of the associated thread if it has finished, or the
+    /// handle back in `Err` if it's still running, without blocking.
+    pub fn try_join(mut self) -> Result<io::Result<T>, Self> {
+        match api::ax_try_wait_for_exit(&self.native) {
+            Some(_) => Ok(Self::take_result(&mut self.packet)),
+            None => Err(self),
+        }
+    }
+
+    /// Checks if the associated thread has finished running its main
+    /// function.
+    pub fn is_finished(&self) -> bool {
+        api::ax_try_wait_for_exit(&self.native).is_some()
+    }
+
+    /// Detaches the associated thread, which is reclaimed as soon as it
+    /// finishes, and its return value is dropped.
+    pub fn detach(self) {
+        api::ax_detach(self.native);
+    }
+
+    fn take_result(packet: &mut Arc<Packet<T>>) -> io::Result<T> {
+        Arc::get_mut(packet)
             .unwrap()
             .result
             .get_mut()